@@ -16,7 +16,7 @@ import "./TrafficMap.css";
 import io from "socket.io-client";
 import { useAuth } from "../contexts/AuthContext";
 import { api } from "../utils/api";
-import { parseSumoNetXml } from "../utils/sumoNetParserWasm";
+import { parseSumoNetXmlStream } from "../utils/sumoNetParserWasm";
 import TrafficLightModal from "./TrafficLightModal";
 import { TrafficLightPhasePreview } from "./TrafficLightPhaseViz";
 // Optional clustering: keep footprint tiny without extra deps by grouping by grid
@@ -809,7 +809,7 @@ const TrafficMap = () => {
       }
 
       // If no cache, parse from file
-      const data = await parseSumoNetXml(path);
+      const data = await parseSumoNetXmlStream(path);
 
       // Cache the parsed data
       setCachedNetworkData(data);
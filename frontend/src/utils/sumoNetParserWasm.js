@@ -160,5 +160,46 @@ export async function parseSumoNetXml(url) {
   }
 }
 
+// Streaming variant: parses top-level elements while the file downloads.
+// Falls back to the buffered path when streaming or WASM is unavailable.
+export async function parseSumoNetXmlStream(url) {
+  let wasm;
+  try {
+    wasm = await loadWasmModule();
+  } catch (error) {
+    return parseSumoNetXml(url);
+  }
+
+  const startTime = performance.now();
+  const res = await fetch(url, { cache: "no-store", credentials: "same-origin" });
+  if (!res.ok || !res.body) {
+    return parseSumoNetXml(url);
+  }
+
+  const parser = new wasm.NetParser();
+  const reader = res.body.getReader();
+  // finish() takes ownership of the parser, so it is only freed before that
+  let finishing = false;
+  try {
+    for (;;) {
+      const { done, value } = await reader.read();
+      if (done) break;
+      parser.feed(value);
+    }
+    finishing = true;
+    const result = parser.finish();
+    const elapsed = performance.now() - startTime;
+    console.log(`✅ WASM streaming parse complete in ${elapsed.toFixed(2)}ms`);
+    return result;
+  } catch (error) {
+    if (!finishing) {
+      parser.free();
+      reader.cancel().catch(() => {});
+    }
+    console.warn("⚠️ WASM streaming parse failed, falling back to buffered parse:", error);
+    return parseSumoNetXml(url);
+  }
+}
+
 // Export for direct access if needed
 export { loadWasmModule, parseWithJavaScript };
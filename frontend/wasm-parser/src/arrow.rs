@@ -0,0 +1,215 @@
+// Apache Arrow IPC streams (feature "arrow") of the parsed network and of
+// simulation outputs, for DuckDB-WASM and Observable notebooks: one record
+// batch per table, snake_case columns for SQL, geometry as WKT in network
+// coordinates.
+use arrow_array::{ArrayRef, BooleanArray, Float64Array, RecordBatch, StringArray, UInt32Array};
+use arrow_ipc::writer::StreamWriter;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+use wasm_bindgen::prelude::*;
+
+use crate::tripinfo::{read_trips, TripEmissions, TripInfo};
+use crate::{attr_f64, parse_xml, NetAccumulator};
+
+fn ipc_stream(columns: Vec<(&str, ArrayRef)>) -> Result<Vec<u8>, JsValue> {
+    let error = |e: arrow_schema::ArrowError| JsValue::from_str(&format!("Arrow export failed: {}", e));
+    let batch = RecordBatch::try_from_iter(columns).map_err(error)?;
+    let mut out = Vec::new();
+    let mut writer = StreamWriter::try_new(&mut out, &batch.schema()).map_err(error)?;
+    writer.write(&batch).map_err(error)?;
+    writer.finish().map_err(error)?;
+    drop(writer);
+    Ok(out)
+}
+
+fn strings<'a>(values: impl Iterator<Item = Option<&'a str>>) -> ArrayRef {
+    Arc::new(StringArray::from_iter(values))
+}
+
+fn floats(values: impl Iterator<Item = Option<f64>>) -> ArrayRef {
+    Arc::new(Float64Array::from_iter(values))
+}
+
+fn bools(values: impl Iterator<Item = bool>) -> ArrayRef {
+    Arc::new(BooleanArray::from_iter(values.map(Some)))
+}
+
+// Render points are [lat, lng] = [y, x]
+fn wkt_coords(out: &mut String, points: &[Vec<f64>]) {
+    for (i, p) in points.iter().enumerate() {
+        let _ = write!(out, "{}{} {}", if i > 0 { ", " } else { "" }, p[1], p[0]);
+    }
+}
+
+fn wkt_line(points: &[Vec<f64>]) -> String {
+    let mut out = String::from("LINESTRING (");
+    wkt_coords(&mut out, points);
+    out.push(')');
+    out
+}
+
+fn wkt_polygon(ring: &[Vec<f64>]) -> String {
+    let mut out = String::from("POLYGON ((");
+    wkt_coords(&mut out, ring);
+    // WKT rings repeat their first point
+    if let (Some(first), Some(last)) = (ring.first(), ring.last()) {
+        if ring.len() > 1 && first != last {
+            let _ = write!(out, ", {} {}", first[1], first[0]);
+        }
+    }
+    out.push_str("))");
+    out
+}
+
+// One layer of the render output as a table: "lanes", "junctions" or "tls"
+#[wasm_bindgen]
+pub fn network_to_arrow(xml_text: &str, layer: &str) -> Result<Vec<u8>, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let mut acc = NetAccumulator::new();
+    acc.add_document(doc.root_element());
+    let net = acc.finish();
+
+    match layer {
+        "lanes" => {
+            let lanes = &net.lanes;
+            let geometry: Vec<String> = lanes.iter().map(|l| wkt_line(&l.points)).collect();
+            ipc_stream(vec![
+                ("id", strings(lanes.iter().map(|l| Some(l.id.as_str())))),
+                ("edge_id", strings(lanes.iter().map(|l| l.edge_id.as_deref()))),
+                ("is_internal", bools(lanes.iter().map(|l| l.is_internal))),
+                ("is_roundabout", bools(lanes.iter().map(|l| l.is_roundabout))),
+                ("speed", floats(lanes.iter().map(|l| l.speed))),
+                ("length", floats(lanes.iter().map(|l| l.length))),
+                ("edge_length", floats(lanes.iter().map(|l| l.edge_length))),
+                ("allow", strings(lanes.iter().map(|l| l.allow.as_deref()))),
+                ("disallow", strings(lanes.iter().map(|l| l.disallow.as_deref()))),
+                ("geometry", strings(geometry.iter().map(|g| Some(g.as_str())))),
+            ])
+        }
+        "junctions" => {
+            let junctions = &net.junctions;
+            let geometry: Vec<String> = junctions.iter().map(|j| wkt_polygon(&j.polygon)).collect();
+            ipc_stream(vec![
+                ("id", strings(junctions.iter().map(|j| Some(j.id.as_str())))),
+                ("type", strings(junctions.iter().map(|j| Some(j.junction_type.as_str())))),
+                ("is_roundabout", bools(junctions.iter().map(|j| j.is_roundabout))),
+                ("geometry", strings(geometry.iter().map(|g| Some(g.as_str())))),
+            ])
+        }
+        "tls" => {
+            let tls = &net.tls;
+            ipc_stream(vec![
+                ("id", strings(tls.iter().map(|t| Some(t.id.as_str())))),
+                ("cluster_id", strings(tls.iter().map(|t| Some(t.cluster_id.as_str())))),
+                ("x", floats(tls.iter().map(|t| Some(t.lng)))),
+                ("y", floats(tls.iter().map(|t| Some(t.lat)))),
+            ])
+        }
+        _ => Err(JsValue::from_str(&format!("Unknown layer '{}'; use lanes, junctions or tls", layer))),
+    }
+}
+
+// One row per trip of a --tripinfo-output file; emission columns are null
+// for trips without an <emissions> child
+#[wasm_bindgen]
+pub fn tripinfo_to_arrow(xml_text: &str) -> Result<Vec<u8>, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let trips = read_trips(doc.root_element());
+    let column = |f: fn(&TripInfo) -> f64| floats(trips.iter().map(|t| Some(f(t))));
+    let emission = |f: fn(&TripEmissions) -> f64| {
+        floats(trips.iter().map(|t| t.emissions.as_ref().map(f)))
+    };
+    ipc_stream(vec![
+        ("id", strings(trips.iter().map(|t| Some(t.id.as_str())))),
+        ("v_type", strings(trips.iter().map(|t| t.v_type.as_deref()))),
+        ("depart", column(|t| t.depart)),
+        ("arrival", column(|t| t.arrival)),
+        ("duration", column(|t| t.duration)),
+        ("depart_lane", strings(trips.iter().map(|t| t.depart_lane.as_deref()))),
+        ("arrival_lane", strings(trips.iter().map(|t| t.arrival_lane.as_deref()))),
+        ("route_length", column(|t| t.route_length)),
+        ("waiting_time", column(|t| t.waiting_time)),
+        ("time_loss", column(|t| t.time_loss)),
+        ("co", emission(|e| e.co)),
+        ("co2", emission(|e| e.co2)),
+        ("hc", emission(|e| e.hc)),
+        ("pmx", emission(|e| e.pmx)),
+        ("nox", emission(|e| e.nox)),
+        ("fuel", emission(|e| e.fuel)),
+        ("electricity", emission(|e| e.electricity)),
+    ])
+}
+
+struct MeanRow<'a> {
+    begin: f64,
+    end: f64,
+    kind: &'a str,
+    id: &'a str,
+    values: HashMap<&'a str, f64>,
+}
+
+// edgeData / laneData (meandata) in long form: one row per edge or lane per
+// interval, one column per numeric attribute in order of first appearance
+#[wasm_bindgen]
+pub fn meandata_to_arrow(xml_text: &str) -> Result<Vec<u8>, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let mut names: Vec<&str> = Vec::new();
+    let mut rows: Vec<MeanRow> = Vec::new();
+    for interval in doc.root_element().children().filter(|n| n.has_tag_name("interval")) {
+        let begin = attr_f64(interval, "begin").unwrap_or(0.0);
+        let end = attr_f64(interval, "end").unwrap_or(0.0);
+        for node in interval.descendants().filter(|n| n.has_tag_name("edge") || n.has_tag_name("lane")) {
+            let Some(id) = node.attribute("id") else { continue };
+            let values: HashMap<&str, f64> = node
+                .attributes()
+                .filter(|a| a.name() != "id")
+                .filter_map(|a| Some((a.name(), attr_f64(node, a.name())?)))
+                .collect();
+            // laneData edges only group their lanes
+            if values.is_empty() {
+                continue;
+            }
+            for a in node.attributes() {
+                if values.contains_key(a.name()) && !names.contains(&a.name()) {
+                    names.push(a.name());
+                }
+            }
+            rows.push(MeanRow {
+                begin,
+                end,
+                kind: node.tag_name().name(),
+                id,
+                values,
+            });
+        }
+    }
+
+    let mut columns = vec![
+        ("begin", floats(rows.iter().map(|r| Some(r.begin)))),
+        ("end", floats(rows.iter().map(|r| Some(r.end)))),
+        ("kind", strings(rows.iter().map(|r| Some(r.kind)))),
+        ("id", strings(rows.iter().map(|r| Some(r.id)))),
+    ];
+    for name in names {
+        columns.push((name, floats(rows.iter().map(|r| r.values.get(name).copied()))));
+    }
+    ipc_stream(columns)
+}
+
+// EdgeSpeedGrid in long form, intervals with records only
+pub(crate) fn edge_speed_table(
+    edge_ids: &[String],
+    begins: &[f64],
+    mean_speed: &[f32],
+    count: &[u32],
+) -> Result<Vec<u8>, JsValue> {
+    let cells: Vec<usize> = (0..count.len()).filter(|i| count[*i] > 0).collect();
+    let edges = edge_ids.len().max(1);
+    ipc_stream(vec![
+        ("begin", floats(cells.iter().map(|i| Some(begins[i / edges])))),
+        ("edge_id", strings(cells.iter().map(|i| Some(edge_ids[i % edges].as_str())))),
+        ("mean_speed", floats(cells.iter().map(|i| Some(mean_speed[*i] as f64)))),
+        ("count", Arc::new(UInt32Array::from_iter_values(cells.iter().map(|i| count[*i]))) as ArrayRef),
+    ])
+}
@@ -0,0 +1,219 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::heatmap::lane_edge;
+use crate::{attr_f64, parse_xml, to_js};
+
+// One electric vehicle's battery over time as parallel arrays, a value per
+// timestep it was in the simulation. Energies are in Wh.
+#[wasm_bindgen]
+#[derive(Clone, Default)]
+pub struct BatterySeries {
+    time: Vec<f64>,
+    soc: Vec<f64>,
+    charge: Vec<f64>,
+    energy_consumed: Vec<f64>,
+    energy_charged: Vec<f64>,
+    speed: Vec<f64>,
+    // Full capacity, from the last step that had one
+    capacity: f64,
+}
+
+#[wasm_bindgen]
+impl BatterySeries {
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.time.len()
+    }
+
+    // Seconds
+    #[wasm_bindgen(getter)]
+    pub fn time(&self) -> Vec<f64> {
+        self.time.clone()
+    }
+
+    // State of charge, 0 to 1; NaN without a maximum capacity
+    #[wasm_bindgen(getter)]
+    pub fn soc(&self) -> Vec<f64> {
+        self.soc.clone()
+    }
+
+    // Charge left (actualBatteryCapacity)
+    #[wasm_bindgen(getter)]
+    pub fn charge(&self) -> Vec<f64> {
+        self.charge.clone()
+    }
+
+    // Energy used in the step; negative while recuperating
+    #[wasm_bindgen(getter, js_name = energyConsumed)]
+    pub fn energy_consumed(&self) -> Vec<f64> {
+        self.energy_consumed.clone()
+    }
+
+    // Energy taken from a charging station in the step
+    #[wasm_bindgen(getter, js_name = energyCharged)]
+    pub fn energy_charged(&self) -> Vec<f64> {
+        self.energy_charged.clone()
+    }
+
+    // m/s
+    #[wasm_bindgen(getter)]
+    pub fn speed(&self) -> Vec<f64> {
+        self.speed.clone()
+    }
+
+    // maximumBatteryCapacity
+    #[wasm_bindgen(getter)]
+    pub fn capacity(&self) -> f64 {
+        self.capacity
+    }
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct BatterySummary {
+    pub id: String,
+    #[serde(rename = "initialSoc")]
+    pub initial_soc: f64,
+    #[serde(rename = "finalSoc")]
+    pub final_soc: f64,
+    #[serde(rename = "minSoc")]
+    pub min_soc: f64,
+    // Wh, positive steps only
+    pub consumed: f64,
+    // Wh recuperated (negative consumption)
+    pub regenerated: f64,
+    // Wh from charging stations
+    pub charged: f64,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct EdgeEnergy {
+    pub edge: String,
+    // Wh, positive steps only
+    pub consumed: f64,
+    pub regenerated: f64,
+    pub charged: f64,
+    // Distinct vehicles seen on the edge
+    pub vehicles: usize,
+    // Vehicle-steps on the edge
+    pub steps: usize,
+}
+
+// Every vehicle with a battery device in a battery output (--battery-output)
+#[wasm_bindgen]
+pub struct BatteryOutput {
+    series: BTreeMap<String, BatterySeries>,
+    edges: Vec<EdgeEnergy>,
+}
+
+#[wasm_bindgen]
+impl BatteryOutput {
+    pub fn ids(&self) -> Vec<String> {
+        self.series.keys().cloned().collect()
+    }
+
+    pub fn series(&self, vehicle_id: &str) -> Option<BatterySeries> {
+        self.series.get(vehicle_id).cloned()
+    }
+
+    // Per-vehicle state of charge and energy totals, lowest minimum SOC first
+    #[wasm_bindgen(unchecked_return_type = "BatterySummary[]")]
+    pub fn summary(&self) -> Result<JsValue, JsValue> {
+        to_js(&summarize_batteries(&self.series))
+    }
+
+    // Energy per edge (from the lane of each record), most consumed first
+    #[wasm_bindgen(unchecked_return_type = "EdgeEnergy[]")]
+    pub fn edges(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.edges)
+    }
+}
+
+#[derive(Default)]
+struct EdgeTotals<'a> {
+    consumed: f64,
+    regenerated: f64,
+    charged: f64,
+    vehicles: BTreeSet<&'a str>,
+    steps: usize,
+}
+
+pub(crate) fn read_battery_steps(root: roxmltree::Node) -> (BTreeMap<String, BatterySeries>, Vec<EdgeEnergy>) {
+    let mut series: BTreeMap<String, BatterySeries> = BTreeMap::new();
+    let mut by_edge: BTreeMap<&str, EdgeTotals> = BTreeMap::new();
+    for step in root.children().filter(|n| n.has_tag_name("timestep")) {
+        let Some(time) = attr_f64(step, "time") else { continue };
+        for vehicle in step.children().filter(|n| n.has_tag_name("vehicle")) {
+            let Some(id) = vehicle.attribute("id") else { continue };
+            let charge = attr_f64(vehicle, "actualBatteryCapacity").unwrap_or(f64::NAN);
+            let capacity = attr_f64(vehicle, "maximumBatteryCapacity").filter(|c| *c > 0.0);
+            let consumed = attr_f64(vehicle, "energyConsumed").unwrap_or(0.0);
+            let charged = attr_f64(vehicle, "energyCharged").unwrap_or(0.0);
+
+            let s = series.entry(id.to_string()).or_default();
+            s.time.push(time);
+            s.soc.push(capacity.map_or(f64::NAN, |c| charge / c));
+            s.charge.push(charge);
+            s.energy_consumed.push(consumed);
+            s.energy_charged.push(charged);
+            s.speed.push(attr_f64(vehicle, "speed").unwrap_or(0.0));
+            if let Some(c) = capacity {
+                s.capacity = c;
+            }
+
+            let Some(lane) = vehicle.attribute("lane").filter(|l| !l.is_empty()) else { continue };
+            let totals = by_edge.entry(lane_edge(lane)).or_default();
+            totals.consumed += consumed.max(0.0);
+            totals.regenerated += (-consumed).max(0.0);
+            totals.charged += charged;
+            totals.vehicles.insert(id);
+            totals.steps += 1;
+        }
+    }
+
+    let mut edges: Vec<EdgeEnergy> = by_edge
+        .into_iter()
+        .map(|(edge, t)| EdgeEnergy {
+            edge: edge.to_string(),
+            consumed: t.consumed,
+            regenerated: t.regenerated,
+            charged: t.charged,
+            vehicles: t.vehicles.len(),
+            steps: t.steps,
+        })
+        .collect();
+    edges.sort_by(|a, b| b.consumed.total_cmp(&a.consumed));
+    (series, edges)
+}
+
+fn summarize_batteries(series: &BTreeMap<String, BatterySeries>) -> Vec<BatterySummary> {
+    let mut summaries: Vec<BatterySummary> = series
+        .iter()
+        .map(|(id, s)| {
+            let soc: Vec<f64> = s.soc.iter().copied().filter(|v| !v.is_nan()).collect();
+            BatterySummary {
+                id: id.clone(),
+                initial_soc: soc.first().copied().unwrap_or(f64::NAN),
+                final_soc: soc.last().copied().unwrap_or(f64::NAN),
+                min_soc: soc.iter().copied().reduce(f64::min).unwrap_or(f64::NAN),
+                consumed: s.energy_consumed.iter().map(|e| e.max(0.0)).sum(),
+                regenerated: s.energy_consumed.iter().map(|e| (-e).max(0.0)).sum(),
+                charged: s.energy_charged.iter().sum(),
+            }
+        })
+        .collect();
+    summaries.sort_by(|a, b| a.min_soc.total_cmp(&b.min_soc));
+    summaries
+}
+
+#[wasm_bindgen]
+pub fn parse_battery_output(xml_text: &str) -> Result<BatteryOutput, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let (series, edges) = read_battery_steps(doc.root_element());
+
+    console_log!("Parsed battery output for {} vehicles on {} edges", series.len(), edges.len());
+
+    Ok(BatteryOutput { series, edges })
+}
@@ -0,0 +1,59 @@
+// Compact binary encodings of ParsedNetwork, for caching in IndexedDB and
+// handing a parsed network to another worker without a JSON round trip:
+// MessagePack (feature "msgpack") and CBOR (feature "cbor"). Both keep the
+// serde field names, so decoding gives the object parse_sumo_net_xml returns.
+use wasm_bindgen::prelude::*;
+
+use crate::{to_js, ParsedNetwork};
+
+fn from_js(network: JsValue) -> Result<ParsedNetwork, JsValue> {
+    serde_wasm_bindgen::from_value(network).map_err(|e| JsValue::from_str(&format!("Invalid network: {}", e)))
+}
+
+#[cfg(feature = "msgpack")]
+pub(crate) fn to_msgpack(network: &ParsedNetwork) -> Result<Vec<u8>, JsValue> {
+    rmp_serde::to_vec_named(network).map_err(|e| JsValue::from_str(&format!("MessagePack encoding failed: {}", e)))
+}
+
+#[cfg(feature = "cbor")]
+pub(crate) fn to_cbor(network: &ParsedNetwork) -> Result<Vec<u8>, JsValue> {
+    let mut out = Vec::new();
+    ciborium::into_writer(network, &mut out).map_err(|e| JsValue::from_str(&format!("CBOR encoding failed: {}", e)))?;
+    Ok(out)
+}
+
+// A network from parse_sumo_net_xml as MessagePack
+#[cfg(feature = "msgpack")]
+#[wasm_bindgen]
+pub fn network_to_msgpack(
+    #[wasm_bindgen(unchecked_param_type = "ParsedNetwork")] network: JsValue,
+) -> Result<Vec<u8>, JsValue> {
+    to_msgpack(&from_js(network)?)
+}
+
+#[cfg(feature = "msgpack")]
+#[wasm_bindgen(unchecked_return_type = "ParsedNetwork")]
+pub fn network_from_msgpack(bytes: &[u8]) -> Result<JsValue, JsValue> {
+    let network: ParsedNetwork = rmp_serde::from_slice(bytes)
+        .map_err(|e| JsValue::from_str(&format!("Invalid MessagePack network: {}", e)))?;
+    console_log!("Decoded {} lanes from {} bytes of MessagePack", network.lanes.len(), bytes.len());
+    to_js(&network)
+}
+
+// A network from parse_sumo_net_xml as CBOR
+#[cfg(feature = "cbor")]
+#[wasm_bindgen]
+pub fn network_to_cbor(
+    #[wasm_bindgen(unchecked_param_type = "ParsedNetwork")] network: JsValue,
+) -> Result<Vec<u8>, JsValue> {
+    to_cbor(&from_js(network)?)
+}
+
+#[cfg(feature = "cbor")]
+#[wasm_bindgen(unchecked_return_type = "ParsedNetwork")]
+pub fn network_from_cbor(bytes: &[u8]) -> Result<JsValue, JsValue> {
+    let network: ParsedNetwork =
+        ciborium::from_reader(bytes).map_err(|e| JsValue::from_str(&format!("Invalid CBOR network: {}", e)))?;
+    console_log!("Decoded {} lanes from {} bytes of CBOR", network.lanes.len(), bytes.len());
+    to_js(&network)
+}
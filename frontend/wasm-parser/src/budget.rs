@@ -0,0 +1,69 @@
+// Frame-time feedback loop for the Network handle: JS reports how long its
+// frames take and the level of detail is raised or lowered to stay within a
+// target, so slow devices coarsen geometry automatically.
+
+const DEFAULT_TARGET_MS: f64 = 1000.0 / 60.0;
+pub(crate) const MAX_LOD: u32 = 5;
+// Smoothing of reported frame times (exponential moving average weight)
+const SMOOTHING: f64 = 0.2;
+// Frames to wait after a change so its effect shows up before the next one
+const SETTLE_FRAMES: u32 = 10;
+
+pub(crate) struct FrameBudget {
+    target_ms: f64,
+    smoothed_ms: Option<f64>,
+    lod: u32,
+    frames_since_change: u32,
+}
+
+impl FrameBudget {
+    pub fn new() -> FrameBudget {
+        FrameBudget {
+            target_ms: DEFAULT_TARGET_MS,
+            smoothed_ms: None,
+            lod: 0,
+            frames_since_change: 0,
+        }
+    }
+
+    pub fn lod(&self) -> u32 {
+        self.lod
+    }
+
+    pub fn set_target(&mut self, target_ms: f64) {
+        if target_ms.is_finite() && target_ms > 0.0 {
+            self.target_ms = target_ms;
+        }
+    }
+
+    // Feed one frame time; returns the (possibly changed) level of detail.
+    // Coarsen above the target, refine only well below it to avoid flapping.
+    pub fn report(&mut self, frame_ms: f64) -> u32 {
+        if !frame_ms.is_finite() || frame_ms < 0.0 {
+            return self.lod;
+        }
+        let smoothed = match self.smoothed_ms {
+            Some(s) => s + SMOOTHING * (frame_ms - s),
+            None => frame_ms,
+        };
+        self.smoothed_ms = Some(smoothed);
+        self.frames_since_change += 1;
+        if self.frames_since_change < SETTLE_FRAMES {
+            return self.lod;
+        }
+
+        let next = if smoothed > self.target_ms * 1.1 {
+            (self.lod + 1).min(MAX_LOD)
+        } else if smoothed < self.target_ms * 0.6 {
+            self.lod.saturating_sub(1)
+        } else {
+            self.lod
+        };
+        if next != self.lod {
+            console_debug!("Frame time {:.1} ms (target {:.1}): lod {} -> {}", smoothed, self.target_ms, self.lod, next);
+            self.lod = next;
+            self.frames_since_change = 0;
+        }
+        self.lod
+    }
+}
@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::geometry;
+use crate::net::{EdgeModel, LaneModel, NetModel};
+use crate::{parse_xml, to_js};
+
+const DEFAULT_MAX_GAP: f64 = 150.0;
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct CorridorGap {
+    #[serde(rename = "edgeId")]
+    pub edge_id: String,
+    pub length: f64,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct BusCorridor {
+    // Edge ids in driving order, including gap edges
+    pub edges: Vec<String>,
+    #[serde(rename = "busLaneIds")]
+    pub bus_lane_ids: Vec<String>,
+    // Meters covered by a dedicated bus lane
+    #[serde(rename = "busLaneLength")]
+    pub bus_lane_length: f64,
+    // Meters where buses share lanes with general traffic
+    #[serde(rename = "gapLength")]
+    pub gap_length: f64,
+    pub gaps: Vec<CorridorGap>,
+}
+
+// A lane counts as a bus lane when buses may use it but private cars may not,
+// which covers both allow="bus ..." and long disallow lists
+fn bus_lane(edge: &EdgeModel) -> Option<&LaneModel> {
+    edge.lanes.iter().find(|l| l.permits("bus") && !l.permits("passenger"))
+}
+
+// Straight-on successors first, so corridors follow the main road
+fn successors(net: &NetModel) -> HashMap<&str, Vec<&str>> {
+    let mut next: HashMap<&str, Vec<(&str, bool)>> = HashMap::new();
+    for c in &net.connections {
+        if c.from.starts_with(':') || c.to.starts_with(':') || c.dir == "t" {
+            continue;
+        }
+        let list = next.entry(c.from.as_str()).or_default();
+        if !list.iter().any(|(to, _)| *to == c.to) {
+            list.push((c.to.as_str(), c.dir == "s"));
+        }
+    }
+    next.into_iter()
+        .map(|(from, mut list)| {
+            list.sort_by_key(|(_, straight)| !straight);
+            (from, list.into_iter().map(|(to, _)| to).collect())
+        })
+        .collect()
+}
+
+pub(crate) fn bus_corridors(net: &NetModel, max_gap: f64) -> Vec<BusCorridor> {
+    let bus_edges: HashSet<&str> = net
+        .edges
+        .iter()
+        .filter(|e| !e.is_internal() && bus_lane(e).is_some())
+        .map(|e| e.id.as_str())
+        .collect();
+    let succ = successors(net);
+    let after = |id: &str| succ.get(id).map(|v| v.as_slice()).unwrap_or(&[]).to_vec();
+
+    // Next bus edge along the corridor, possibly across one short gap edge
+    let mut next: HashMap<&str, (&str, Option<&str>)> = HashMap::new();
+    for &id in &bus_edges {
+        let candidates = after(id);
+        let link = candidates
+            .iter()
+            .find(|to| bus_edges.contains(*to))
+            .map(|to| (*to, None))
+            .or_else(|| {
+                candidates.iter().find_map(|gap| {
+                    let gap_edge = net.edge(gap)?;
+                    if gap_edge.length() > max_gap {
+                        return None;
+                    }
+                    after(gap)
+                        .into_iter()
+                        .find(|to| bus_edges.contains(to) && *to != id)
+                        .map(|to| (to, Some(*gap)))
+                })
+            });
+        if let Some(link) = link {
+            next.insert(id, link);
+        }
+    }
+
+    let has_predecessor: HashSet<&str> = next.values().map(|(to, _)| *to).collect();
+    let mut starts: Vec<&str> = bus_edges.iter().copied().filter(|id| !has_predecessor.contains(id)).collect();
+    starts.sort_unstable();
+    // Closed loops have no natural start; pick their smallest id
+    let mut rest: Vec<&str> = bus_edges.iter().copied().collect();
+    rest.sort_unstable();
+    starts.extend(rest);
+
+    let mut visited: HashSet<&str> = HashSet::new();
+    let mut corridors = Vec::new();
+    for start in starts {
+        if visited.contains(start) {
+            continue;
+        }
+        let mut corridor = BusCorridor {
+            edges: Vec::new(),
+            bus_lane_ids: Vec::new(),
+            bus_lane_length: 0.0,
+            gap_length: 0.0,
+            gaps: Vec::new(),
+        };
+        let mut current = Some(start);
+        while let Some(id) = current.filter(|id| visited.insert(*id)) {
+            let Some(edge) = net.edge(id) else { break };
+            if let Some(lane) = bus_lane(edge) {
+                corridor.bus_lane_ids.push(lane.id.clone());
+                corridor.bus_lane_length += geometry::polyline_length(&lane.shape);
+            }
+            corridor.edges.push(id.to_string());
+
+            current = match next.get(id) {
+                Some((to, Some(gap))) if !visited.contains(to) => {
+                    let length = net.edge(gap).map(|e| e.length()).unwrap_or(0.0);
+                    corridor.edges.push(gap.to_string());
+                    corridor.gap_length += length;
+                    corridor.gaps.push(CorridorGap {
+                        edge_id: gap.to_string(),
+                        length,
+                    });
+                    Some(*to)
+                }
+                Some((to, None)) => Some(*to),
+                _ => None,
+            };
+        }
+        corridors.push(corridor);
+    }
+
+    corridors.sort_by(|a, b| b.bus_lane_length.total_cmp(&a.bus_lane_length));
+    corridors
+}
+
+// Contiguous bus-lane corridors, bridging general-traffic edges up to
+// `max_gap` meters long (default 150) that interrupt a corridor
+#[wasm_bindgen(unchecked_return_type = "BusCorridor[]")]
+pub fn extract_bus_corridors(xml_text: &str, max_gap: Option<f64>) -> Result<JsValue, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let net = NetModel::from_root(doc.root_element());
+    let corridors = bus_corridors(&net, max_gap.unwrap_or(DEFAULT_MAX_GAP));
+
+    console_log!("Found {} bus-lane corridors", corridors.len());
+
+    to_js(&corridors)
+}
@@ -0,0 +1,126 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::net::NetModel;
+use crate::units::{speed_limit_kmh, SpeedClass};
+use crate::{parse_options, parse_xml, to_js};
+
+// Lookup tables for the planning-grade estimate. Every table is optional;
+// entries override the defaults below.
+#[derive(Deserialize, Default, Tsify)]
+#[serde(default)]
+pub struct CapacityOptions {
+    // Vehicle class whose lanes are counted (default "passenger")
+    #[serde(rename = "vClass")]
+    pub v_class: Option<String>,
+    // Per-lane capacity in pcu/h by speed class ("urban", "arterial", ...)
+    #[serde(rename = "bySpeedClass")]
+    pub by_speed_class: HashMap<String, f64>,
+    // Per-lane capacity in pcu/h by edge type id; takes precedence over speed
+    #[serde(rename = "byEdgeType")]
+    pub by_edge_type: HashMap<String, f64>,
+    // Passenger car equivalents by vClass
+    pub pce: HashMap<String, f64>,
+    // Observed or simulated volumes (veh/h) by edge id, for v/c ratios
+    pub volumes: HashMap<String, f64>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct EdgeCapacity {
+    #[serde(rename = "edgeId")]
+    pub edge_id: String,
+    // Lanes usable by the requested vClass
+    pub lanes: usize,
+    #[serde(rename = "capacityPerLane")]
+    pub capacity_per_lane: f64,
+    // veh/h of the requested vClass
+    pub capacity: f64,
+    pub volume: Option<f64>,
+    #[serde(rename = "vcRatio")]
+    pub vc_ratio: Option<f64>,
+}
+
+// Typical saturation flows per lane (pcu/h) after intersection losses
+fn default_lane_capacity(class: SpeedClass) -> f64 {
+    match class {
+        SpeedClass::Walking => 600.0,
+        SpeedClass::Residential => 800.0,
+        SpeedClass::Urban => 1000.0,
+        SpeedClass::Arterial => 1400.0,
+        SpeedClass::Expressway => 1800.0,
+        SpeedClass::Motorway => 2000.0,
+    }
+}
+
+fn default_pce(v_class: &str) -> f64 {
+    match v_class {
+        "bus" | "coach" | "trolleybus" => 2.0,
+        "truck" | "delivery" => 2.0,
+        "trailer" => 3.0,
+        "motorcycle" | "moped" => 0.5,
+        "bicycle" => 0.2,
+        _ => 1.0,
+    }
+}
+
+pub(crate) fn estimate(net: &NetModel, options: &CapacityOptions) -> Vec<EdgeCapacity> {
+    let v_class = options.v_class.as_deref().unwrap_or("passenger");
+    let pce = options
+        .pce
+        .get(v_class)
+        .copied()
+        .unwrap_or_else(|| default_pce(v_class))
+        .max(0.01);
+
+    net.edges
+        .iter()
+        .filter(|e| e.is_normal())
+        .map(|edge| {
+            let usable: Vec<_> = edge.lanes.iter().filter(|l| l.permits(v_class)).collect();
+            let speed = usable
+                .iter()
+                .filter_map(|l| l.speed)
+                .fold(0.0_f64, f64::max);
+            let class = SpeedClass::from_kmh(speed_limit_kmh(speed));
+
+            let per_lane_pcu = options
+                .by_edge_type
+                .get(&edge.edge_type)
+                .or_else(|| options.by_speed_class.get(class.name()))
+                .copied()
+                .unwrap_or_else(|| default_lane_capacity(class));
+            let capacity_per_lane = per_lane_pcu / pce;
+            let capacity = capacity_per_lane * usable.len() as f64;
+
+            let volume = options.volumes.get(&edge.id).copied();
+            let vc_ratio = volume.filter(|_| capacity > 0.0).map(|v| v / capacity);
+
+            EdgeCapacity {
+                edge_id: edge.id.clone(),
+                lanes: usable.len(),
+                capacity_per_lane,
+                capacity,
+                volume,
+                vc_ratio,
+            }
+        })
+        .collect()
+}
+
+#[wasm_bindgen(unchecked_return_type = "EdgeCapacity[]")]
+pub fn estimate_edge_capacity(
+    xml_text: &str,
+    #[wasm_bindgen(unchecked_param_type = "CapacityOptions | undefined")] options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let options: CapacityOptions = parse_options(options)?;
+
+    let doc = parse_xml(xml_text)?;
+    let net = NetModel::from_root(doc.root_element());
+    let capacities = estimate(&net, &options);
+
+    console_log!("Estimated capacity for {} edges", capacities.len());
+
+    to_js(&capacities)
+}
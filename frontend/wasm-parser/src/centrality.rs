@@ -0,0 +1,189 @@
+// Edge centrality for resilience screening: how many shortest paths (free-flow
+// travel time) run over each edge. Plain betweenness counts every
+// origin-destination pair of edges alike; the demand-weighted variant routes
+// an OD matrix between TAZ instead, so an edge's score is the number of trips
+// that would lose their best route if it failed.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::graph::EdgeGraph;
+use crate::net::NetModel;
+use crate::od::OdInterval;
+use crate::taz::read_tazs;
+use crate::{parse_options, parse_xml, to_js};
+
+// Plain betweenness runs one search per source edge; beyond this many edges
+// a regular sample of sources stands in for all of them
+const DEFAULT_SAMPLES: usize = 256;
+
+#[derive(Deserialize, Default, Tsify)]
+#[serde(default)]
+pub struct CentralityOptions {
+    // Vehicle class whose lanes form the graph (default "passenger")
+    #[serde(rename = "vClass")]
+    pub v_class: Option<String>,
+    // Source edges searched for plain betweenness (default 256); 0 = all
+    pub samples: Option<usize>,
+    // Only the highest-scoring edges
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct EdgeCentrality {
+    pub edge: String,
+    // Betweenness: share of shortest paths between edge pairs through this
+    // edge (0-1). Demand-weighted: trips routed over this edge.
+    pub score: f64,
+    // score relative to the highest-scoring edge (0-1)
+    pub relative: f64,
+}
+
+fn ranked(graph: &EdgeGraph, scores: Vec<f64>, limit: Option<usize>) -> Vec<EdgeCentrality> {
+    let max = scores.iter().copied().fold(0.0, f64::max);
+    let mut ranked: Vec<EdgeCentrality> = scores
+        .into_iter()
+        .enumerate()
+        .filter(|(_, s)| *s > 0.0)
+        .map(|(i, score)| EdgeCentrality {
+            edge: graph.ids[i].to_string(),
+            score,
+            relative: score / max,
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.score.total_cmp(&a.score));
+    if let Some(limit) = limit {
+        ranked.truncate(limit);
+    }
+    ranked
+}
+
+// Brandes' algorithm on the edge graph, from every edge or a regular sample
+fn betweenness(graph: &EdgeGraph, samples: usize) -> Vec<f64> {
+    let n = graph.len();
+    let step = if samples == 0 || samples >= n { 1 } else { n.div_ceil(samples) };
+    let sources: Vec<usize> = (0..n).step_by(step).collect();
+
+    let mut scores = vec![0.0; n];
+    let mut delta = vec![0.0; n];
+    for &s in &sources {
+        let search = graph.search(&[s]);
+        delta.iter_mut().for_each(|d| *d = 0.0);
+        for &w in search.order.iter().rev() {
+            for &v in &search.preds[w] {
+                delta[v] += search.sigma[v] / search.sigma[w] * (1.0 + delta[w]);
+            }
+            if w != s {
+                scores[w] += delta[w];
+            }
+        }
+    }
+    // Share of the ordered pairs between other edges, scaled up from the sample
+    let pairs = (n.saturating_sub(1) * n.saturating_sub(2)) as f64;
+    let scale = n as f64 / sources.len().max(1) as f64;
+    if pairs > 0.0 {
+        scores.iter_mut().for_each(|s| *s *= scale / pairs);
+    }
+    scores
+}
+
+// Trips of the OD matrix over each edge, each relation routed from its
+// origin TAZ's edges to the nearest edge of its destination TAZ. Ties split
+// the trips evenly between the tied paths.
+fn demand_load(graph: &EdgeGraph, intervals: &[OdInterval], tazs: &HashMap<String, Vec<usize>>) -> (Vec<f64>, f64) {
+    let mut by_origin: HashMap<&str, HashMap<&str, f64>> = HashMap::new();
+    for r in intervals.iter().flat_map(|i| &i.relations) {
+        if r.from != r.to && r.count > 0.0 {
+            *by_origin.entry(r.from.as_str()).or_default().entry(r.to.as_str()).or_default() += r.count;
+        }
+    }
+
+    let mut load = vec![0.0; graph.len()];
+    let mut unrouted = 0.0;
+    for (origin, destinations) in by_origin {
+        let Some(sources) = tazs.get(origin).filter(|s| !s.is_empty()) else {
+            unrouted += destinations.values().sum::<f64>();
+            continue;
+        };
+        let search = graph.search(sources);
+        // Trips ending at each edge, pushed back along the predecessors
+        let mut flow = vec![0.0; graph.len()];
+        for (destination, count) in destinations {
+            let target = tazs
+                .get(destination)
+                .into_iter()
+                .flatten()
+                .copied()
+                .filter(|t| search.dist[*t].is_finite())
+                .min_by(|a, b| search.dist[*a].total_cmp(&search.dist[*b]));
+            match target {
+                Some(t) => flow[t] += count,
+                None => unrouted += count,
+            }
+        }
+        for &w in search.order.iter().rev() {
+            if flow[w] == 0.0 {
+                continue;
+            }
+            load[w] += flow[w];
+            for &v in &search.preds[w] {
+                flow[v] += flow[w] * search.sigma[v] / search.sigma[w];
+            }
+        }
+    }
+    (load, unrouted)
+}
+
+// Betweenness of every edge: the share of shortest paths (free-flow time)
+// between other edges that use it
+#[wasm_bindgen(unchecked_return_type = "EdgeCentrality[]")]
+pub fn edge_centrality(
+    net_xml: &str,
+    #[wasm_bindgen(unchecked_param_type = "CentralityOptions | undefined")] options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let options: CentralityOptions = parse_options(options)?;
+    let doc = parse_xml(net_xml)?;
+    let net = NetModel::from_root(doc.root_element());
+    let graph = EdgeGraph::new(&net, options.v_class.as_deref().unwrap_or("passenger"));
+    let scores = betweenness(&graph, options.samples.unwrap_or(DEFAULT_SAMPLES));
+
+    console_log!("Computed betweenness for {} edges", graph.len());
+
+    to_js(&ranked(&graph, scores, options.limit))
+}
+
+// Trips of a parsed OD matrix (see parse_od_matrix) routed between TAZ and
+// counted per edge: the edges whose failure would affect the most trips
+#[wasm_bindgen(unchecked_return_type = "EdgeCentrality[]")]
+pub fn demand_weighted_centrality(
+    net_xml: &str,
+    #[wasm_bindgen(unchecked_param_type = "OdInterval[]")] matrix: JsValue,
+    taz_xml: &str,
+    #[wasm_bindgen(unchecked_param_type = "CentralityOptions | undefined")] options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let intervals: Vec<OdInterval> = serde_wasm_bindgen::from_value(matrix)
+        .map_err(|e| JsValue::from_str(&format!("Invalid OD matrix: {}", e)))?;
+    let options: CentralityOptions = parse_options(options)?;
+    let doc = parse_xml(net_xml)?;
+    let taz_doc = parse_xml(taz_xml)?;
+    let net = NetModel::from_root(doc.root_element());
+    let graph = EdgeGraph::new(&net, options.v_class.as_deref().unwrap_or("passenger"));
+
+    let tazs: HashMap<String, Vec<usize>> = read_tazs(taz_doc.root_element())
+        .into_iter()
+        .map(|t| {
+            let edges = t.edges.iter().filter_map(|e| graph.index.get(e.as_str()).copied()).collect();
+            (t.id, edges)
+        })
+        .collect();
+    let (load, unrouted) = demand_load(&graph, &intervals, &tazs);
+
+    console_log!(
+        "Routed OD demand over {} edges ({:.0} trips unroutable)",
+        load.iter().filter(|l| **l > 0.0).count(),
+        unrouted
+    );
+
+    to_js(&ranked(&graph, load, options.limit))
+}
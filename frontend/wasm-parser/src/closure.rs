@@ -0,0 +1,153 @@
+// What-if link closures: shortest free-flow travel times between a sample of
+// edge pairs, with and without the closed edges, as a quick robustness check
+// before committing to a full simulation run.
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::graph::EdgeGraph;
+use crate::net::NetModel;
+use crate::{parse_options, parse_xml, to_js};
+
+// Origins and destinations sampled (the pairs are their cross product)
+const DEFAULT_SAMPLES: usize = 50;
+const DEFAULT_WORST_PAIRS: usize = 20;
+// Seconds; smaller changes are floating-point noise
+const MIN_DELTA: f64 = 0.01;
+
+#[derive(Deserialize, Default, Tsify)]
+#[serde(default)]
+pub struct ClosureOptions {
+    // Vehicle class whose lanes form the graph (default "passenger")
+    #[serde(rename = "vClass")]
+    pub v_class: Option<String>,
+    // Edges sampled as origins and as destinations (default 50)
+    pub samples: Option<usize>,
+    // Most affected pairs listed (default 20)
+    #[serde(rename = "worstPairs")]
+    pub worst_pairs: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct PairImpact {
+    pub from: String,
+    pub to: String,
+    // Free-flow seconds
+    pub before: f64,
+    // None when the closure disconnects the pair
+    pub after: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct ClosureImpact {
+    // Requested edges found in the graph
+    pub closed: Vec<String>,
+    // Sampled pairs connected before the closure
+    pub pairs: usize,
+    // Pairs the closure disconnects
+    pub disconnected: usize,
+    // Pairs still connected but slower
+    pub slower: usize,
+    // Seconds over the connected pairs (unaffected pairs count as 0)
+    #[serde(rename = "meanDelta")]
+    pub mean_delta: f64,
+    #[serde(rename = "maxDelta")]
+    pub max_delta: f64,
+    // Relative increase of the summed travel time of the connected pairs
+    #[serde(rename = "relativeDelta")]
+    pub relative_delta: f64,
+    // Disconnected pairs first, then by added time
+    #[serde(rename = "worstPairs")]
+    pub worst_pairs: Vec<PairImpact>,
+}
+
+pub(crate) fn closure_impact(net: &NetModel, edge_ids: &[String], options: &ClosureOptions) -> ClosureImpact {
+    let v_class = options.v_class.as_deref().unwrap_or("passenger");
+    let open = EdgeGraph::new(net, v_class);
+    let closed: Vec<usize> = edge_ids.iter().filter_map(|id| open.index.get(id.as_str()).copied()).collect();
+    let mut blocked = EdgeGraph::new(net, v_class);
+    blocked.close(&closed);
+
+    // Regular sample of the edges that stay open
+    let candidates: Vec<usize> = (0..open.len()).filter(|i| !closed.contains(i)).collect();
+    let samples = options.samples.filter(|s| *s > 0).unwrap_or(DEFAULT_SAMPLES);
+    let step = candidates.len().div_ceil(samples).max(1);
+    let sampled: Vec<usize> = candidates.iter().step_by(step).copied().collect();
+
+    let mut impacts = Vec::new();
+    let (mut pairs, mut slower, mut disconnected) = (0, 0, 0);
+    let (mut total_before, mut total_after, mut max_delta) = (0.0, 0.0, 0.0f64);
+    for &origin in &sampled {
+        let before = open.search(&[origin]);
+        let after = blocked.search(&[origin]);
+        for &target in sampled.iter().filter(|t| **t != origin) {
+            let time = before.dist[target];
+            if !time.is_finite() {
+                continue;
+            }
+            pairs += 1;
+            let detour = Some(after.dist[target]).filter(|t| t.is_finite());
+            match detour {
+                None => disconnected += 1,
+                Some(t) => {
+                    total_before += time;
+                    total_after += t;
+                    if t - time > MIN_DELTA {
+                        slower += 1;
+                        max_delta = max_delta.max(t - time);
+                    } else {
+                        continue;
+                    }
+                }
+            }
+            impacts.push(PairImpact {
+                from: open.ids[origin].to_string(),
+                to: open.ids[target].to_string(),
+                before: time,
+                after: detour,
+            });
+        }
+    }
+
+    let connected = pairs - disconnected;
+    impacts.sort_by(|a, b| {
+        let delta = |p: &PairImpact| p.after.map_or(f64::INFINITY, |t| t - p.before);
+        delta(b).total_cmp(&delta(a))
+    });
+    impacts.truncate(options.worst_pairs.unwrap_or(DEFAULT_WORST_PAIRS));
+
+    ClosureImpact {
+        closed: closed.iter().map(|i| open.ids[*i].to_string()).collect(),
+        pairs,
+        disconnected,
+        slower,
+        mean_delta: if connected > 0 { (total_after - total_before) / connected as f64 } else { 0.0 },
+        max_delta,
+        relative_delta: if total_before > 0.0 { total_after / total_before - 1.0 } else { 0.0 },
+        worst_pairs: impacts,
+    }
+}
+
+// Reachability and free-flow travel-time changes between sampled edge pairs
+// if `edge_ids` were closed
+#[wasm_bindgen(unchecked_return_type = "ClosureImpact")]
+pub fn simulate_closure(
+    net_xml: &str,
+    edge_ids: Vec<String>,
+    #[wasm_bindgen(unchecked_param_type = "ClosureOptions | undefined")] options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let options: ClosureOptions = parse_options(options)?;
+    let doc = parse_xml(net_xml)?;
+    let net = NetModel::from_root(doc.root_element());
+    let impact = closure_impact(&net, &edge_ids, &options);
+
+    console_log!(
+        "Closing {} edges disconnects {} and slows {} of {} sampled pairs",
+        impact.closed.len(),
+        impact.disconnected,
+        impact.slower,
+        impact.pairs
+    );
+
+    to_js(&impact)
+}
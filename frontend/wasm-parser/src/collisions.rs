@@ -0,0 +1,68 @@
+// Collisions from a collision output (--collision-output), each placed on the
+// map at its lane position for a marker layer.
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::network::Network;
+use crate::{attr_f64, parse_xml, to_js};
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct Collision {
+    pub time: f64,
+    // collision, frontal, side, junction, ... as written by SUMO
+    #[serde(rename = "type")]
+    pub collision_type: Option<String>,
+    pub lane: String,
+    // Meters along the lane
+    pub pos: f64,
+    // The vehicle (or person) that ran into `victim`
+    pub collider: String,
+    pub victim: String,
+    #[serde(rename = "colliderType")]
+    pub collider_type: Option<String>,
+    #[serde(rename = "victimType")]
+    pub victim_type: Option<String>,
+    // m/s
+    #[serde(rename = "colliderSpeed")]
+    pub collider_speed: Option<f64>,
+    #[serde(rename = "victimSpeed")]
+    pub victim_speed: Option<f64>,
+    // [lat, lng] of the lane position; None when the lane is not in the network
+    pub position: Option<Vec<f64>>,
+}
+
+pub(crate) fn read_collisions(root: roxmltree::Node, network: &Network) -> Vec<Collision> {
+    root.children()
+        .filter(|n| n.has_tag_name("collision"))
+        .filter_map(|c| {
+            let lane = c.attribute("lane")?;
+            let pos = attr_f64(c, "pos")?;
+            Some(Collision {
+                time: attr_f64(c, "time")?,
+                collision_type: c.attribute("type").map(String::from),
+                lane: lane.to_string(),
+                pos,
+                collider: c.attribute("collider")?.to_string(),
+                victim: c.attribute("victim")?.to_string(),
+                collider_type: c.attribute("colliderType").map(String::from),
+                victim_type: c.attribute("victimType").map(String::from),
+                collider_speed: attr_f64(c, "colliderSpeed"),
+                victim_speed: attr_f64(c, "victimSpeed"),
+                position: network.lane_point_at(lane, pos),
+            })
+        })
+        .collect()
+}
+
+// Collisions in file order, positioned on `network`
+#[wasm_bindgen(unchecked_return_type = "Collision[]")]
+pub fn parse_collision_output(xml_text: &str, network: &Network) -> Result<JsValue, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let collisions = read_collisions(doc.root_element(), network);
+
+    let unplaced = collisions.iter().filter(|c| c.position.is_none()).count();
+    console_log!("Parsed {} collisions ({} on lanes not in the network)", collisions.len(), unplaced);
+
+    to_js(&collisions)
+}
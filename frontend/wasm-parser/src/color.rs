@@ -0,0 +1,116 @@
+use wasm_bindgen::prelude::*;
+
+use crate::hash::Fnv64;
+
+// SUMO color attributes: "r,g,b[,a]" as 0-255 integers or 0-1 floats, or a
+// named color
+pub(crate) fn parse_sumo_color(value: &str) -> Option<[u8; 4]> {
+    let named = match value.trim() {
+        "red" => Some([255, 0, 0, 255]),
+        "green" => Some([0, 255, 0, 255]),
+        "blue" => Some([0, 0, 255, 255]),
+        "yellow" => Some([255, 255, 0, 255]),
+        "cyan" => Some([0, 255, 255, 255]),
+        "magenta" => Some([255, 0, 255, 255]),
+        "orange" => Some([255, 128, 0, 255]),
+        "white" => Some([255, 255, 255, 255]),
+        "black" => Some([0, 0, 0, 255]),
+        "grey" | "gray" => Some([128, 128, 128, 255]),
+        _ => None,
+    };
+    if named.is_some() {
+        return named;
+    }
+
+    let parts: Vec<f64> = value
+        .split(',')
+        .map(|p| p.trim().parse::<f64>())
+        .collect::<Result<_, _>>()
+        .ok()?;
+    if parts.len() != 3 && parts.len() != 4 {
+        return None;
+    }
+    // Like SUMO, all components within 0-1 means the color is 0-1 scaled
+    let fractional = parts.iter().all(|v| *v <= 1.0);
+    let scale = if fractional { 255.0 } else { 1.0 };
+    let channel = |v: f64| (v * scale).round().clamp(0.0, 255.0) as u8;
+
+    Some([
+        channel(parts[0]),
+        channel(parts[1]),
+        channel(parts[2]),
+        parts.get(3).map(|a| channel(*a)).unwrap_or(255),
+    ])
+}
+
+fn hsl_to_rgb(h: f64, s: f64, l: f64) -> [u8; 3] {
+    let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    let hp = h / 60.0;
+    let x = c * (1.0 - (hp % 2.0 - 1.0).abs());
+    let (r, g, b) = match hp as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = l - c / 2.0;
+    let channel = |v: f64| ((v + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+    [channel(r), channel(g), channel(b)]
+}
+
+// Stable color for an id: same id, same color in every session, on the map
+// and in charts. Hue spans the full circle; saturation and lightness stay in
+// a band that reads on both light and dark basemaps.
+pub(crate) fn id_color(id: &str) -> [u8; 4] {
+    let mut h = Fnv64::new();
+    h.write(id.as_bytes());
+    // Finalizer from SplitMix64 so similar ids ("e1", "e2") spread apart
+    let mut x = h.finish();
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^= x >> 31;
+
+    let hue = (x % 3600) as f64 / 10.0;
+    let saturation = 0.55 + ((x >> 16) % 26) as f64 / 100.0;
+    let lightness = 0.45 + ((x >> 32) % 16) as f64 / 100.0;
+    let [r, g, b] = hsl_to_rgb(hue, saturation, lightness);
+    [r, g, b, 255]
+}
+
+// [r, g, b, a] for "color by id" modes
+#[wasm_bindgen]
+pub fn color_for_id(id: &str) -> Vec<u8> {
+    id_color(id).to_vec()
+}
+
+// CSS "#rrggbb" of the same color, for charts
+#[wasm_bindgen]
+pub fn css_color_for_id(id: &str) -> String {
+    let [r, g, b, _] = id_color(id);
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+// Flat RGBA for many ids at once (4 bytes per id), ready for a deck.gl color attribute
+#[wasm_bindgen]
+pub fn colors_for_ids(ids: Vec<String>) -> Vec<u8> {
+    ids.iter().flat_map(|id| id_color(id)).collect()
+}
+
+// Sequential ramp for heatmaps, t in 0-1: dark blue through teal and green to
+// yellow (viridis stops), readable on light and dark basemaps
+pub(crate) fn ramp_color(t: f64) -> [u8; 4] {
+    const STOPS: [[f64; 3]; 5] = [
+        [68.0, 1.0, 84.0],
+        [59.0, 82.0, 139.0],
+        [33.0, 145.0, 140.0],
+        [94.0, 201.0, 98.0],
+        [253.0, 231.0, 37.0],
+    ];
+    let scaled = if t.is_finite() { t.clamp(0.0, 1.0) } else { 0.0 } * (STOPS.len() - 1) as f64;
+    let i = (scaled as usize).min(STOPS.len() - 2);
+    let f = scaled - i as f64;
+    let channel = |c: usize| (STOPS[i][c] + f * (STOPS[i + 1][c] - STOPS[i][c])).round() as u8;
+    [channel(0), channel(1), channel(2), 255]
+}
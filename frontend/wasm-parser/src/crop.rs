@@ -0,0 +1,183 @@
+// Cutting a corridor or district out of a network: the edges inside or
+// crossing a region, the junctions they connect, the connections (with their
+// internal edges) between kept edges and the traffic light programs of those
+// connections. Edges crossing the border are kept whole.
+use serde::Deserialize;
+use std::collections::HashSet;
+use tsify::Tsify;
+
+use crate::geometry;
+use crate::heatmap::lane_edge;
+use crate::net::NetModel;
+use crate::network::to_xy;
+use crate::{Bounds, ParsedNetwork};
+
+// In the frame of the render output, like Network.slice_bbox
+#[derive(Deserialize, Default, Tsify)]
+#[serde(default)]
+pub struct CropRegion {
+    // x = lng, y = lat
+    pub bbox: Option<Bounds>,
+    // [lat, lng] ring; used instead of bbox when both are given
+    pub polygon: Option<Vec<Vec<f64>>>,
+}
+
+pub(crate) enum Region {
+    Box((f64, f64), (f64, f64)),
+    Polygon(Vec<(f64, f64)>),
+}
+
+impl Region {
+    pub(crate) fn from_options(region: &CropRegion) -> Result<Region, String> {
+        if let Some(polygon) = &region.polygon {
+            if polygon.len() < 3 || polygon.iter().any(|p| p.len() < 2) {
+                return Err("Crop polygon needs at least 3 [lat, lng] points".to_string());
+            }
+            return Ok(Region::Polygon(to_xy(polygon)));
+        }
+        match &region.bbox {
+            Some(b) => Ok(Region::Box(
+                (b.min_x.min(b.max_x), b.min_y.min(b.max_y)),
+                (b.min_x.max(b.max_x), b.min_y.max(b.max_y)),
+            )),
+            None => Err("Crop region needs a bbox or a polygon".to_string()),
+        }
+    }
+
+    fn contains(&self, p: (f64, f64)) -> bool {
+        match self {
+            Region::Box(min, max) => p.0 >= min.0 && p.0 <= max.0 && p.1 >= min.1 && p.1 <= max.1,
+            Region::Polygon(ring) => geometry::point_in_polygon(p, ring),
+        }
+    }
+
+    // Whether a polyline lies in or crosses the region
+    fn touches(&self, line: &[(f64, f64)]) -> bool {
+        if line.iter().any(|p| self.contains(*p)) {
+            return true;
+        }
+        line.windows(2).any(|w| match self {
+            Region::Box(min, max) => geometry::segment_intersects_box(w[0], w[1], *min, *max),
+            Region::Polygon(ring) => ring
+                .iter()
+                .zip(ring.iter().cycle().skip(1))
+                .any(|(a, b)| geometry::segment_intersection(w[0], w[1], *a, *b).is_some()),
+        })
+    }
+}
+
+// The part of `net` in the region. Signal programs keep all their link
+// indices, so phase states still cover links that were cut off.
+pub(crate) fn crop_model(net: &NetModel, region: &Region) -> NetModel {
+    let mut edges: HashSet<&str> = net
+        .edges
+        .iter()
+        .filter(|e| !e.is_internal() && e.lanes.iter().any(|l| region.touches(&l.shape)))
+        .map(|e| e.id.as_str())
+        .collect();
+
+    // Connections between kept edges bring their internal edges, which in
+    // turn keep the connections leaving them (split internal lanes chain)
+    let mut kept = vec![false; net.connections.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (i, c) in net.connections.iter().enumerate() {
+            if kept[i] || !edges.contains(c.from.as_str()) || !edges.contains(c.to.as_str()) {
+                continue;
+            }
+            kept[i] = true;
+            if let Some(via) = &c.via {
+                changed |= edges.insert(lane_edge(via));
+            }
+        }
+    }
+
+    let mut junctions: HashSet<&str> = net
+        .edges
+        .iter()
+        .filter(|e| edges.contains(e.id.as_str()))
+        .flat_map(|e| e.from.iter().chain(&e.to))
+        .map(|j| j.as_str())
+        .collect();
+    for j in &net.junctions {
+        // Internal junctions are named after the internal lane they start
+        let keep = if j.junction_type == "internal" {
+            edges.contains(lane_edge(&j.id))
+        } else {
+            region.contains((j.x, j.y))
+        };
+        if keep {
+            junctions.insert(&j.id);
+        }
+    }
+
+    let connections: Vec<_> = net.connections.iter().zip(&kept).filter(|(_, k)| **k).map(|(c, _)| c.clone()).collect();
+    let programs: HashSet<&str> = connections.iter().filter_map(|c| c.tl.as_deref()).collect();
+    let tl_logics = net.tl_logics.iter().filter(|t| programs.contains(t.id.as_str())).cloned().collect();
+    NetModel::new(
+        net.lefthand,
+        net.location.clone(),
+        net.edges.iter().filter(|e| edges.contains(e.id.as_str())).cloned().collect(),
+        net.junctions.iter().filter(|j| junctions.contains(j.id.as_str())).cloned().collect(),
+        connections,
+        tl_logics,
+    )
+}
+
+// Render output restricted to what `cropped` (from crop_model) kept, with
+// bounds around the remaining lanes and junctions
+pub(crate) fn crop_parsed(parsed: &ParsedNetwork, cropped: &NetModel) -> ParsedNetwork {
+    let lanes: Vec<_> = parsed
+        .lanes
+        .iter()
+        .filter(|l| match &l.edge_id {
+            Some(edge) => cropped.edge(edge).is_some(),
+            // Links drawn for networks without internal lanes: "<from lane>-><to lane>"
+            None => l
+                .id
+                .split_once("->")
+                .is_some_and(|(from, to)| cropped.lane(from).is_some() && cropped.lane(to).is_some()),
+        })
+        .cloned()
+        .collect();
+    let lane_ids: HashSet<&str> = lanes.iter().map(|l| l.id.as_str()).collect();
+
+    let junctions: Vec<_> = parsed
+        .junctions
+        .iter()
+        .filter(|j| cropped.junction(&j.id).is_some())
+        .map(|j| {
+            let mut junction = j.clone();
+            junction.inc_lanes.retain(|l| lane_ids.contains(l.as_str()));
+            junction.int_lanes.retain(|l| lane_ids.contains(l.as_str()));
+            junction
+        })
+        .collect();
+    let junction_points: Vec<_> =
+        parsed.junction_points.iter().filter(|j| cropped.junction(&j.id).is_some()).cloned().collect();
+
+    let bounds = Bounds::of_points(
+        lanes
+            .iter()
+            .flat_map(|l| to_xy(&l.points))
+            .chain(junction_points.iter().map(|j| (j.lng, j.lat))),
+    );
+
+    ParsedNetwork {
+        lanes,
+        bounds,
+        tls: parsed.tls.iter().filter(|t| cropped.junction(&t.id).is_some()).cloned().collect(),
+        junctions,
+        junction_points,
+        roundabouts: parsed
+            .roundabouts
+            .iter()
+            .filter(|r| r.edges.iter().all(|e| cropped.edge(e).is_some()))
+            .cloned()
+            .collect(),
+        crs: parsed.crs,
+        quantization: parsed.quantization,
+        ids: None,
+    }
+}
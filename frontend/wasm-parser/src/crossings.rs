@@ -0,0 +1,121 @@
+// Grade-separated crossings: edges whose shapes cross in plan view without
+// sharing a junction, i.e. flyovers and underpasses. SUMO has no element for
+// them, so without this list they look like broken topology (two roads
+// crossing with no intersection).
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::geometry;
+use crate::net::{EdgeModel, NetModel};
+use crate::spatial::SegmentGrid;
+use crate::{parse_xml, to_js};
+
+const INDEX_CELL_SIZE: f64 = 100.0;
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct GradeCrossing {
+    #[serde(rename = "edgeA")]
+    pub edge_a: String,
+    #[serde(rename = "edgeB")]
+    pub edge_b: String,
+    // Crossing point in plan view
+    pub lat: f64,
+    pub lng: f64,
+    // Edge heights at the crossing (meters), for 3D shapes
+    #[serde(rename = "elevationA")]
+    pub elevation_a: Option<f64>,
+    #[serde(rename = "elevationB")]
+    pub elevation_b: Option<f64>,
+    // The edge passing over the other, when both heights are known
+    pub upper: Option<String>,
+}
+
+// The middle lane stands in for the edge centerline
+struct Centerline<'a> {
+    edge: &'a EdgeModel,
+    shape: &'a [(f64, f64)],
+    // z per shape point, for 3D shapes
+    elevation: Option<&'a [f64]>,
+}
+
+fn centerline(edge: &EdgeModel) -> Option<Centerline<'_>> {
+    let lane = edge.lanes.get(edge.lanes.len() / 2)?;
+    let elevation = lane.elevation.as_deref().filter(|z| z.len() == lane.shape.len());
+    (lane.shape.len() >= 2).then_some(Centerline {
+        edge,
+        shape: &lane.shape,
+        elevation,
+    })
+}
+
+fn shares_junction(a: &EdgeModel, b: &EdgeModel) -> bool {
+    let b_ends = [b.from.as_deref(), b.to.as_deref()];
+    [a.from.as_deref(), a.to.as_deref()]
+        .into_iter()
+        .flatten()
+        .any(|j| b_ends.contains(&Some(j)))
+}
+
+pub(crate) fn find_crossings(net: &NetModel) -> Vec<GradeCrossing> {
+    let edges: Vec<Centerline> = net.edges.iter().filter(|e| e.is_normal()).filter_map(centerline).collect();
+
+    let mut index = SegmentGrid::new(INDEX_CELL_SIZE);
+    for (i, line) in edges.iter().enumerate() {
+        index.insert_polyline(i, line.shape);
+    }
+
+    let mut crossings = Vec::new();
+    for (i, a) in edges.iter().enumerate() {
+        let (lo, hi) = a.shape.iter().fold(
+            ((f64::INFINITY, f64::INFINITY), (f64::NEG_INFINITY, f64::NEG_INFINITY)),
+            |(lo, hi), p| ((lo.0.min(p.0), lo.1.min(p.1)), (hi.0.max(p.0), hi.1.max(p.1))),
+        );
+        // Each pair once
+        for j in index.owners_in_box(lo, hi).into_iter().filter(|j| *j > i) {
+            let b = &edges[j];
+            if shares_junction(a.edge, b.edge) {
+                continue;
+            }
+            for (sa, wa) in a.shape.windows(2).enumerate() {
+                for (sb, wb) in b.shape.windows(2).enumerate() {
+                    let Some((t, u)) = geometry::segment_intersection(wa[0], wa[1], wb[0], wb[1]) else {
+                        continue;
+                    };
+                    let x = wa[0].0 + t * (wa[1].0 - wa[0].0);
+                    let y = wa[0].1 + t * (wa[1].1 - wa[0].1);
+                    let height = |z: Option<&[f64]>, s: usize, f: f64| z.map(|z| z[s] + f * (z[s + 1] - z[s]));
+                    let (elevation_a, elevation_b) = (height(a.elevation, sa, t), height(b.elevation, sb, u));
+                    let upper = match (elevation_a, elevation_b) {
+                        (Some(za), Some(zb)) if za > zb => Some(a.edge.id.clone()),
+                        (Some(za), Some(zb)) if zb > za => Some(b.edge.id.clone()),
+                        _ => None,
+                    };
+                    crossings.push(GradeCrossing {
+                        edge_a: a.edge.id.clone(),
+                        edge_b: b.edge.id.clone(),
+                        lat: y,
+                        lng: x,
+                        elevation_a,
+                        elevation_b,
+                        upper,
+                    });
+                }
+            }
+        }
+    }
+    crossings
+}
+
+// Edge pairs that cross without a junction: render them as passing over one
+// another and do not flag them as missing intersections
+#[wasm_bindgen(unchecked_return_type = "GradeCrossing[]")]
+pub fn find_grade_crossings(xml_text: &str) -> Result<JsValue, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let net = NetModel::from_root(doc.root_element());
+    let crossings = find_crossings(&net);
+
+    console_log!("Found {} grade-separated crossings", crossings.len());
+
+    to_js(&crossings)
+}
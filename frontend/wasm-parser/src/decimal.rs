@@ -0,0 +1,67 @@
+// Float parsing for the hot paths (shape strings, numeric attributes). SUMO
+// writes plain decimals like "-1234.56"; those whose digits fit a float
+// mantissa are converted exactly with a single multiply or divide (Clinger's
+// fast path). Everything else, including "inf" and long mantissas, goes to std.
+
+// Powers of ten that are exact in f64
+const POW10: [f64; 23] = [
+    1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10, 1e11, 1e12, 1e13, 1e14, 1e15, 1e16, 1e17, 1e18,
+    1e19, 1e20, 1e21, 1e22,
+];
+const MAX_MANTISSA: u64 = 1 << 53;
+
+pub(crate) fn parse_f64(s: &str) -> Option<f64> {
+    fast_path(s.as_bytes()).or_else(|| s.parse().ok())
+}
+
+fn fast_path(s: &[u8]) -> Option<f64> {
+    let (negative, s) = match s.first()? {
+        b'-' => (true, &s[1..]),
+        b'+' => (false, &s[1..]),
+        _ => (false, s),
+    };
+
+    let mut i = 0;
+    let mut mantissa: u64 = 0;
+    let mut exponent: i32 = 0;
+    let mut any_digit = false;
+    let mut fraction = false;
+    while i < s.len() {
+        match s[i] {
+            d @ b'0'..=b'9' => {
+                mantissa = mantissa * 10 + (d - b'0') as u64;
+                if mantissa > MAX_MANTISSA {
+                    return None;
+                }
+                exponent -= i32::from(fraction);
+                any_digit = true;
+            }
+            b'.' if !fraction => fraction = true,
+            _ => break,
+        }
+        i += 1;
+    }
+    if !any_digit {
+        return None;
+    }
+
+    if i < s.len() {
+        if !matches!(s[i], b'e' | b'E') {
+            return None;
+        }
+        let (sign, digits) = match s.get(i + 1)? {
+            b'-' => (-1, &s[i + 2..]),
+            b'+' => (1, &s[i + 2..]),
+            _ => (1, &s[i + 1..]),
+        };
+        // Longer exponents are out of the fast path's range anyway
+        if digits.is_empty() || digits.len() > 3 || !digits.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        exponent += sign * digits.iter().fold(0, |acc, d| acc * 10 + (d - b'0') as i32);
+    }
+
+    let power = *POW10.get(exponent.unsigned_abs() as usize)?;
+    let value = if exponent < 0 { mantissa as f64 / power } else { mantissa as f64 * power };
+    Some(if negative { -value } else { value })
+}
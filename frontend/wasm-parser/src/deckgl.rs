@@ -0,0 +1,143 @@
+use wasm_bindgen::prelude::*;
+
+use crate::color::id_color;
+use crate::units::SpeedClass;
+use crate::Lane;
+
+// SUMO's default lane width, and a narrower stroke for junction internals
+const LANE_WIDTH: f32 = 3.2;
+const INTERNAL_LANE_WIDTH: f32 = 1.6;
+
+const INTERNAL_COLOR: [u8; 4] = [150, 150, 150, 255];
+const UNKNOWN_COLOR: [u8; 4] = [120, 120, 120, 255];
+// Lanes closed by a live update, in every color mode
+const CLOSED_COLOR: [u8; 4] = [40, 40, 40, 255];
+
+fn speed_class_color(class: SpeedClass) -> [u8; 4] {
+    match class {
+        SpeedClass::Walking => [166, 166, 166, 255],
+        SpeedClass::Residential => [69, 117, 180, 255],
+        SpeedClass::Urban => [116, 196, 118, 255],
+        SpeedClass::Arterial => [254, 224, 76, 255],
+        SpeedClass::Expressway => [253, 141, 60, 255],
+        SpeedClass::Motorway => [215, 48, 39, 255],
+    }
+}
+
+// Lanes packed for deck.gl's PathLayer binary data mode. Positions are
+// float32 offsets from `origin` (float32 cannot hold absolute coordinates
+// precisely); colors and widths are per vertex, as binary paths require.
+#[wasm_bindgen]
+pub struct PathBuffers {
+    ids: Vec<String>,
+    origin: (f64, f64),
+    start_indices: Vec<u32>,
+    positions: Vec<f32>,
+    colors: Vec<u8>,
+    widths: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl PathBuffers {
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.ids.len()
+    }
+
+    // Lane id per path, for picking
+    #[wasm_bindgen(getter)]
+    pub fn ids(&self) -> Vec<String> {
+        self.ids.clone()
+    }
+
+    // [x, y] subtracted from every position (lng, lat order)
+    #[wasm_bindgen(getter)]
+    pub fn origin(&self) -> Vec<f64> {
+        vec![self.origin.0, self.origin.1]
+    }
+
+    // Index of each path's first vertex, plus a final entry for the total
+    #[wasm_bindgen(getter, js_name = startIndices)]
+    pub fn start_indices(&self) -> Vec<u32> {
+        self.start_indices.clone()
+    }
+
+    // x, y per vertex
+    #[wasm_bindgen(getter)]
+    pub fn positions(&self) -> Vec<f32> {
+        self.positions.clone()
+    }
+
+    // r, g, b, a per vertex
+    #[wasm_bindgen(getter)]
+    pub fn colors(&self) -> Vec<u8> {
+        self.colors.clone()
+    }
+
+    // Width in meters per vertex
+    #[wasm_bindgen(getter)]
+    pub fn widths(&self) -> Vec<f32> {
+        self.widths.clone()
+    }
+}
+
+// `color_by`: "speed" (speed class palette, default), "id" (stable per lane
+// id) or "type" (internal vs. regular lanes)
+pub(crate) fn lane_color(color_by: &str) -> Result<impl Fn(&Lane) -> [u8; 4], String> {
+    let color_of: fn(&Lane) -> [u8; 4] = match color_by {
+        "speed" => |l| match (l.is_internal, l.speed_class) {
+            (true, _) => INTERNAL_COLOR,
+            (false, Some(class)) => speed_class_color(class),
+            (false, None) => UNKNOWN_COLOR,
+        },
+        "id" => |l| id_color(&l.id),
+        "type" => |l| if l.is_internal { INTERNAL_COLOR } else { UNKNOWN_COLOR },
+        other => return Err(format!("Unknown colorBy '{}'", other)),
+    };
+    Ok(move |l: &Lane| if l.is_closed { CLOSED_COLOR } else { color_of(l) })
+}
+
+// Only lanes with a drawable shape become paths
+pub(crate) fn is_path(lane: &Lane) -> bool {
+    lane.points.len() >= 2
+}
+
+pub(crate) fn pack_paths<'a>(
+    lanes: impl Iterator<Item = &'a Lane>,
+    origin: (f64, f64),
+    color_by: &str,
+) -> Result<PathBuffers, String> {
+    let color_of = lane_color(color_by)?;
+
+    let mut buffers = PathBuffers {
+        ids: Vec::new(),
+        origin,
+        start_indices: Vec::new(),
+        positions: Vec::new(),
+        colors: Vec::new(),
+        widths: Vec::new(),
+    };
+    let mut vertex_count = 0u32;
+    for lane in lanes.filter(|l| is_path(l)) {
+        let color = color_of(lane);
+        let width = if lane.is_internal { INTERNAL_LANE_WIDTH } else { LANE_WIDTH };
+
+        buffers.ids.push(lane.id.clone());
+        buffers.start_indices.push(vertex_count);
+        // Output points are [lat, lng] = [y, x]
+        for p in &lane.points {
+            buffers.positions.push((p[1] - origin.0) as f32);
+            buffers.positions.push((p[0] - origin.1) as f32);
+            buffers.colors.extend_from_slice(&color);
+            buffers.widths.push(width);
+        }
+        vertex_count += lane.points.len() as u32;
+    }
+    buffers.start_indices.push(vertex_count);
+    buffers.ids.shrink_to_fit();
+    buffers.start_indices.shrink_to_fit();
+    buffers.positions.shrink_to_fit();
+    buffers.colors.shrink_to_fit();
+    buffers.widths.shrink_to_fit();
+    Ok(buffers)
+}
@@ -0,0 +1,175 @@
+// Where delay happens: each vehicle's tripinfo timeLoss spread over the
+// edges of its route in proportion to the time it spent there beyond free
+// flow (from vehroute exit times), then summed per edge and per junction.
+// Time queued on an edge is charged to the junction at its end, where the
+// queue discharges.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::net::NetModel;
+use crate::tripinfo::read_trips;
+use crate::vehroutes::read_realized_routes;
+use crate::{parse_xml, to_js};
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct JunctionDelay {
+    pub junction: String,
+    // Seconds of timeLoss over all vehicles
+    #[serde(rename = "totalDelay")]
+    pub total_delay: f64,
+    // Vehicles that crossed the junction
+    pub vehicles: usize,
+    // Seconds per crossing vehicle
+    #[serde(rename = "meanDelay")]
+    pub mean_delay: f64,
+    // [lat, lng]
+    pub position: Option<Vec<f64>>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct EdgeDelay {
+    pub edge: String,
+    #[serde(rename = "totalDelay")]
+    pub total_delay: f64,
+    pub vehicles: usize,
+    #[serde(rename = "meanDelay")]
+    pub mean_delay: f64,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct DelayAttribution {
+    // Most delay first
+    pub junctions: Vec<JunctionDelay>,
+    pub edges: Vec<EdgeDelay>,
+    // Seconds of timeLoss placed on the network
+    pub attributed: f64,
+    // Seconds of timeLoss of vehicles without a usable vehroute record
+    pub unattributed: f64,
+}
+
+#[derive(Default)]
+struct DelaySum {
+    delay: f64,
+    vehicles: usize,
+}
+
+impl DelaySum {
+    fn mean(&self) -> f64 {
+        if self.vehicles > 0 {
+            self.delay / self.vehicles as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+pub(crate) fn attribute_delay(
+    net: &NetModel,
+    tripinfo_root: roxmltree::Node,
+    vehroute_root: roxmltree::Node,
+) -> DelayAttribution {
+    // Driven routes with an exit time for every edge
+    let realized = read_realized_routes(vehroute_root);
+    let routes: HashMap<&str, (f64, &[String], &[f64])> = realized
+        .iter()
+        .filter_map(|r| {
+            let exits = r.exit_times.as_deref().filter(|t| t.len() == r.edges.len() && !t.is_empty())?;
+            Some((r.id.as_str(), (r.depart?, r.edges.as_slice(), exits)))
+        })
+        .collect();
+    let mut by_edge: HashMap<&str, DelaySum> = HashMap::new();
+    let mut by_junction: HashMap<&str, DelaySum> = HashMap::new();
+    let (mut attributed, mut unattributed) = (0.0, 0.0);
+
+    for trip in read_trips(tripinfo_root) {
+        let Some(&(depart, edges, exits)) = routes.get(trip.id.as_str()) else {
+            unattributed += trip.time_loss;
+            continue;
+        };
+        // Time beyond free flow on each edge
+        let mut entry = depart;
+        let excess: Vec<f64> = edges
+            .iter()
+            .zip(exits)
+            .map(|(id, &exit)| {
+                let spent = exit - entry;
+                entry = exit;
+                net.edge(id).map_or(0.0, |e| (spent - e.free_flow_time(None)).max(0.0))
+            })
+            .collect();
+        let total: f64 = excess.iter().sum();
+        if total <= 0.0 {
+            unattributed += trip.time_loss;
+            continue;
+        }
+
+        let last = edges.len() - 1;
+        for (i, (id, share)) in edges.iter().zip(&excess).enumerate() {
+            let delay = trip.time_loss * share / total;
+            let sum = by_edge.entry(id).or_default();
+            sum.delay += delay;
+            sum.vehicles += 1;
+            // The vehicle never crosses the junction at the end of its last edge
+            if i == last {
+                continue;
+            }
+            if let Some(junction) = net.edge(id).and_then(|e| e.to.as_deref()) {
+                let sum = by_junction.entry(junction).or_default();
+                sum.delay += delay;
+                sum.vehicles += 1;
+            }
+        }
+        attributed += trip.time_loss;
+    }
+
+    let mut junctions: Vec<JunctionDelay> = by_junction
+        .into_iter()
+        .map(|(id, sum)| JunctionDelay {
+            junction: id.to_string(),
+            total_delay: sum.delay,
+            vehicles: sum.vehicles,
+            mean_delay: sum.mean(),
+            position: net.junction(id).map(|j| vec![j.y, j.x]),
+        })
+        .collect();
+    junctions.sort_by(|a, b| b.total_delay.total_cmp(&a.total_delay));
+    let mut edges: Vec<EdgeDelay> = by_edge
+        .into_iter()
+        .map(|(id, sum)| EdgeDelay {
+            edge: id.to_string(),
+            total_delay: sum.delay,
+            vehicles: sum.vehicles,
+            mean_delay: sum.mean(),
+        })
+        .collect();
+    edges.sort_by(|a, b| b.total_delay.total_cmp(&a.total_delay));
+
+    DelayAttribution {
+        junctions,
+        edges,
+        attributed,
+        unattributed,
+    }
+}
+
+// Ranks junctions and edges by the tripinfo timeLoss that accrued there.
+// `vehroute_xml` must be written with --vehroute-output.exit-times.
+#[wasm_bindgen(unchecked_return_type = "DelayAttribution")]
+pub fn attribute_junction_delay(net_xml: &str, tripinfo_xml: &str, vehroute_xml: &str) -> Result<JsValue, JsValue> {
+    let net_doc = parse_xml(net_xml)?;
+    let tripinfo_doc = parse_xml(tripinfo_xml)?;
+    let vehroute_doc = parse_xml(vehroute_xml)?;
+    let net = NetModel::from_root(net_doc.root_element());
+    let attribution = attribute_delay(&net, tripinfo_doc.root_element(), vehroute_doc.root_element());
+
+    console_log!(
+        "Attributed {:.0} s of time loss to {} junctions ({:.0} s unattributed)",
+        attribution.attributed,
+        attribution.junctions.len(),
+        attribution.unattributed
+    );
+
+    to_js(&attribution)
+}
@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+use wasm_bindgen::prelude::*;
+
+use crate::{attr_f64, parse_xml};
+
+// One detector's intervals as parallel arrays, in file order. Values SUMO
+// leaves undefined (-1, or not written by this detector type) are NaN.
+#[wasm_bindgen]
+#[derive(Clone, Default)]
+pub struct DetectorSeries {
+    begin: Vec<f64>,
+    end: Vec<f64>,
+    flow: Vec<f64>,
+    occupancy: Vec<f64>,
+    mean_speed: Vec<f64>,
+    jam_length: Vec<f64>,
+}
+
+#[wasm_bindgen]
+impl DetectorSeries {
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.begin.len()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn begin(&self) -> Vec<f64> {
+        self.begin.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn end(&self) -> Vec<f64> {
+        self.end.clone()
+    }
+
+    // veh/h
+    #[wasm_bindgen(getter)]
+    pub fn flow(&self) -> Vec<f64> {
+        self.flow.clone()
+    }
+
+    // Percent of time occupied
+    #[wasm_bindgen(getter)]
+    pub fn occupancy(&self) -> Vec<f64> {
+        self.occupancy.clone()
+    }
+
+    // m/s
+    #[wasm_bindgen(getter, js_name = meanSpeed)]
+    pub fn mean_speed(&self) -> Vec<f64> {
+        self.mean_speed.clone()
+    }
+
+    // Maximum jam length in meters (E2 only)
+    #[wasm_bindgen(getter, js_name = jamLength)]
+    pub fn jam_length(&self) -> Vec<f64> {
+        self.jam_length.clone()
+    }
+}
+
+// Every detector found in an E1 (induction loop) or E2 (lane area) output file
+#[wasm_bindgen]
+pub struct DetectorOutput {
+    series: BTreeMap<String, DetectorSeries>,
+}
+
+#[wasm_bindgen]
+impl DetectorOutput {
+    pub fn ids(&self) -> Vec<String> {
+        self.series.keys().cloned().collect()
+    }
+
+    pub fn series(&self, id: &str) -> Option<DetectorSeries> {
+        self.series.get(id).cloned()
+    }
+}
+
+pub(crate) fn read_detector_intervals(root: roxmltree::Node) -> BTreeMap<String, DetectorSeries> {
+    let mut series: BTreeMap<String, DetectorSeries> = BTreeMap::new();
+    let defined = |n: roxmltree::Node, name: &str| attr_f64(n, name).filter(|v| *v >= 0.0).unwrap_or(f64::NAN);
+
+    for interval in root.children().filter(|n| n.tag_name().name() == "interval") {
+        let (Some(id), Some(begin), Some(end)) = (
+            interval.attribute("id"),
+            attr_f64(interval, "begin"),
+            attr_f64(interval, "end"),
+        ) else {
+            continue;
+        };
+
+        // E2 intervals carry sampledSeconds/meanOccupancy and no flow
+        let lane_area = interval.has_attribute("meanOccupancy");
+        let (flow, occupancy, mean_speed, jam_length) = if lane_area {
+            let duration = end - begin;
+            let flow = attr_f64(interval, "nVehEntered")
+                .filter(|_| duration > 0.0)
+                .map(|n| n * 3600.0 / duration)
+                .unwrap_or(f64::NAN);
+            (
+                flow,
+                defined(interval, "meanOccupancy"),
+                defined(interval, "meanSpeed"),
+                defined(interval, "maxJamLengthInMeters"),
+            )
+        } else {
+            (
+                defined(interval, "flow"),
+                defined(interval, "occupancy"),
+                defined(interval, "speed"),
+                f64::NAN,
+            )
+        };
+
+        let s = series.entry(id.to_string()).or_default();
+        s.begin.push(begin);
+        s.end.push(end);
+        s.flow.push(flow);
+        s.occupancy.push(occupancy);
+        s.mean_speed.push(mean_speed);
+        s.jam_length.push(jam_length);
+    }
+    series
+}
+
+#[wasm_bindgen]
+pub fn parse_detector_output(xml_text: &str) -> Result<DetectorOutput, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let series = read_detector_intervals(doc.root_element());
+
+    console_log!("Parsed detector output for {} detectors", series.len());
+
+    Ok(DetectorOutput { series })
+}
@@ -0,0 +1,269 @@
+// Differences between two builds of a network (e.g. before and after changing
+// netconvert options): edges, lanes, junctions and signal programs matched by
+// id, with what changed for the ones present in both.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::geometry;
+use crate::net::{EdgeModel, JunctionModel, LaneModel, NetModel};
+use crate::{attr_f64, parse_options, parse_xml, to_js};
+
+// Shape points may move this much (meters) before counting as a change;
+// netconvert re-runs jitter coordinates by millimeters
+const DEFAULT_TOLERANCE: f64 = 0.05;
+
+#[derive(Deserialize, Default, Tsify)]
+#[serde(default)]
+pub struct DiffOptions {
+    // Meters (default 0.05)
+    pub tolerance: Option<f64>,
+    // Also compare internal edges and lanes, which netconvert regenerates
+    // on most option changes
+    #[serde(rename = "includeInternal")]
+    pub include_internal: bool,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct Modification {
+    pub id: String,
+    // What differs, e.g. "shape", "speed", "permissions", "type", "phases"
+    pub changes: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Tsify, Default)]
+pub struct ElementDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<Modification>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct NetworkDiff {
+    pub edges: ElementDiff,
+    pub lanes: ElementDiff,
+    pub junctions: ElementDiff,
+    // By traffic light id, over all its programs
+    pub tls: ElementDiff,
+    // Whether the new network was shifted into the old one's frame (the
+    // netOffset changed but the projection did not)
+    pub realigned: bool,
+}
+
+// Matches elements by id, in the order of the new network (removed ones in
+// the order of the old)
+fn diff_by_id<'a, T>(
+    old: impl Iterator<Item = (&'a str, T)>,
+    new: impl Iterator<Item = (&'a str, T)>,
+    changes: impl Fn(&T, &T) -> Vec<&'static str>,
+) -> ElementDiff {
+    let old: Vec<(&str, T)> = old.collect();
+    let old_index: HashMap<&str, usize> = old.iter().enumerate().map(|(i, (id, _))| (*id, i)).collect();
+    let mut seen = vec![false; old.len()];
+    let mut diff = ElementDiff::default();
+
+    for (id, item) in new {
+        match old_index.get(id) {
+            Some(&i) => {
+                seen[i] = true;
+                let changed = changes(&old[i].1, &item);
+                if !changed.is_empty() {
+                    diff.modified.push(Modification {
+                        id: id.to_string(),
+                        changes: changed.into_iter().map(String::from).collect(),
+                    });
+                }
+            }
+            None => diff.added.push(id.to_string()),
+        }
+    }
+    diff.removed = old.iter().zip(&seen).filter(|(_, s)| !**s).map(|((id, _), _)| id.to_string()).collect();
+    diff
+}
+
+struct Compare {
+    tolerance: f64,
+    // Added to new coordinates to bring them into the old frame
+    shift: (f64, f64),
+}
+
+impl Compare {
+    fn moved(&self, a: (f64, f64), b: (f64, f64)) -> bool {
+        geometry::distance(a, (b.0 + self.shift.0, b.1 + self.shift.1)) > self.tolerance
+    }
+
+    fn shape_changed(&self, a: &[(f64, f64)], b: &[(f64, f64)]) -> bool {
+        a.len() != b.len() || a.iter().zip(b).any(|(p, q)| self.moved(*p, *q))
+    }
+
+    fn lane(&self, a: &LaneModel, b: &LaneModel) -> Vec<&'static str> {
+        let mut changes = Vec::new();
+        if self.shape_changed(&a.shape, &b.shape) {
+            changes.push("shape");
+        }
+        if a.speed != b.speed {
+            changes.push("speed");
+        }
+        if a.allow != b.allow || a.disallow != b.disallow {
+            changes.push("permissions");
+        }
+        changes
+    }
+
+    fn edge(&self, a: &EdgeModel, b: &EdgeModel) -> Vec<&'static str> {
+        let mut changes = Vec::new();
+        if a.from != b.from || a.to != b.to {
+            changes.push("endpoints");
+        }
+        if a.lanes.len() != b.lanes.len() {
+            changes.push("lanes");
+        }
+        if a.function != b.function || a.edge_type != b.edge_type {
+            changes.push("type");
+        }
+        let centerline = |e: &EdgeModel| e.lane(0).or_else(|| e.lanes.first()).map(|l| l.shape.clone()).unwrap_or_default();
+        if self.shape_changed(&centerline(a), &centerline(b)) {
+            changes.push("shape");
+        }
+        changes
+    }
+}
+
+// A <tlLogic> reduced to what a viewer compares
+#[derive(PartialEq)]
+struct TlsProgram {
+    program_id: String,
+    logic_type: String,
+    offset: Option<f64>,
+    phases: Vec<(Option<f64>, String)>,
+}
+
+fn read_tls_programs<'a>(root: roxmltree::Node<'a, '_>) -> Vec<(&'a str, Vec<TlsProgram>)> {
+    let mut programs: Vec<(&str, Vec<TlsProgram>)> = Vec::new();
+    for logic in root.children().filter(|n| n.tag_name().name() == "tlLogic") {
+        let Some(id) = logic.attribute("id") else { continue };
+        let program = TlsProgram {
+            program_id: logic.attribute("programID").unwrap_or("").to_string(),
+            logic_type: logic.attribute("type").unwrap_or("").to_string(),
+            offset: attr_f64(logic, "offset"),
+            phases: logic
+                .children()
+                .filter(|n| n.tag_name().name() == "phase")
+                .map(|p| (attr_f64(p, "duration"), p.attribute("state").unwrap_or("").to_string()))
+                .collect(),
+        };
+        match programs.iter_mut().find(|(tl, _)| *tl == id) {
+            Some((_, list)) => list.push(program),
+            None => programs.push((id, vec![program])),
+        }
+    }
+    programs
+}
+
+fn tls_changes(a: &[TlsProgram], b: &[TlsProgram]) -> Vec<&'static str> {
+    if !a.iter().map(|p| &p.program_id).eq(b.iter().map(|p| &p.program_id)) {
+        return vec!["programs"];
+    }
+    let mut changes = Vec::new();
+    for (p, q) in a.iter().zip(b) {
+        if p.logic_type != q.logic_type && !changes.contains(&"type") {
+            changes.push("type");
+        }
+        if p.offset != q.offset && !changes.contains(&"offset") {
+            changes.push("offset");
+        }
+        if p.phases != q.phases && !changes.contains(&"phases") {
+            changes.push("phases");
+        }
+    }
+    changes
+}
+
+fn edges(net: &NetModel, internal: bool) -> impl Iterator<Item = (&str, &EdgeModel)> {
+    net.edges.iter().filter(move |e| internal || !e.is_internal()).map(|e| (e.id.as_str(), e))
+}
+
+fn lanes(edge: &EdgeModel) -> impl Iterator<Item = (&str, &LaneModel)> {
+    edge.lanes.iter().map(|l| (l.id.as_str(), l))
+}
+
+fn junctions(net: &NetModel, internal: bool) -> impl Iterator<Item = (&str, &JunctionModel)> {
+    net.junctions
+        .iter()
+        .filter(move |j| internal || j.junction_type != "internal")
+        .map(|j| (j.id.as_str(), j))
+}
+
+fn diff_models(
+    old: &NetModel,
+    new: &NetModel,
+    old_tls: Vec<(&str, Vec<TlsProgram>)>,
+    new_tls: Vec<(&str, Vec<TlsProgram>)>,
+    options: &DiffOptions,
+) -> NetworkDiff {
+    let shift = match (&old.location, &new.location) {
+        (Some(a), Some(b)) => b.offset_to(a),
+        _ => None,
+    };
+    let compare = Compare {
+        tolerance: options.tolerance.filter(|t| *t >= 0.0).unwrap_or(DEFAULT_TOLERANCE),
+        shift: shift.unwrap_or((0.0, 0.0)),
+    };
+    let internal = options.include_internal;
+    NetworkDiff {
+        edges: diff_by_id(edges(old, internal), edges(new, internal), |a, b| compare.edge(a, b)),
+        lanes: diff_by_id(
+            edges(old, internal).flat_map(|(_, e)| lanes(e)),
+            edges(new, internal).flat_map(|(_, e)| lanes(e)),
+            |a, b| compare.lane(a, b),
+        ),
+        junctions: diff_by_id(junctions(old, internal), junctions(new, internal), |a, b| {
+            let mut changes = Vec::new();
+            if compare.moved((a.x, a.y), (b.x, b.y)) {
+                changes.push("position");
+            }
+            if a.junction_type != b.junction_type {
+                changes.push("type");
+            }
+            changes
+        }),
+        tls: diff_by_id(old_tls.into_iter(), new_tls.into_iter(), |a, b| tls_changes(a, b)),
+        realigned: shift.is_some_and(|s| s != (0.0, 0.0)),
+    }
+}
+
+// Added, removed and modified edges, lanes, junctions and traffic lights
+// between two versions of a network, matched by id. Removed ids refer to the
+// old network, the others to the new one.
+#[wasm_bindgen(unchecked_return_type = "NetworkDiff")]
+pub fn diff_networks(
+    old_xml: &str,
+    new_xml: &str,
+    #[wasm_bindgen(unchecked_param_type = "DiffOptions | undefined")] options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let options: DiffOptions = parse_options(options)?;
+    let old_doc = parse_xml(old_xml)?;
+    let new_doc = parse_xml(new_xml)?;
+    let old = NetModel::from_root(old_doc.root_element());
+    let new = NetModel::from_root(new_doc.root_element());
+
+    let diff = diff_models(
+        &old,
+        &new,
+        read_tls_programs(old_doc.root_element()),
+        read_tls_programs(new_doc.root_element()),
+        &options,
+    );
+
+    let count = |d: &ElementDiff| d.added.len() + d.removed.len() + d.modified.len();
+    console_log!(
+        "Network diff: {} edge, {} lane, {} junction and {} traffic light changes",
+        count(&diff.edges),
+        count(&diff.lanes),
+        count(&diff.junctions),
+        count(&diff.tls)
+    );
+
+    to_js(&diff)
+}
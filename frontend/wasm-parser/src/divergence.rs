@@ -0,0 +1,189 @@
+// Planned vs realized routes: each vehicle's route from the route files
+// against the one it drove (vehroute output), with the edges it gained and
+// dropped and what that cost in free-flow distance and time, then summed per
+// origin-destination edge pair.
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::net::NetModel;
+use crate::routes::{read_planned_vehicles, PlannedVehicle};
+use crate::vehroutes::{read_realized_routes, RealizedRoute};
+use crate::{parse_xml, to_js};
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct RouteDivergence {
+    pub id: String,
+    pub from: String,
+    pub to: String,
+    // Edges driven but not planned, in driving order
+    pub added: Vec<String>,
+    // Edges planned but not driven, in planned order
+    pub removed: Vec<String>,
+    // Meters of realized minus planned route
+    #[serde(rename = "extraDistance")]
+    pub extra_distance: f64,
+    // Seconds of free-flow travel time, realized minus planned
+    #[serde(rename = "extraTime")]
+    pub extra_time: f64,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct OdDivergence {
+    pub from: String,
+    pub to: String,
+    pub vehicles: usize,
+    // Vehicles that drove a different route than planned
+    pub diverged: usize,
+    #[serde(rename = "meanExtraDistance")]
+    pub mean_extra_distance: f64,
+    #[serde(rename = "meanExtraTime")]
+    pub mean_extra_time: f64,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct PlannedRouteComparison {
+    // Vehicles whose route changed, largest extra time first
+    pub vehicles: Vec<RouteDivergence>,
+    // Per planned origin and destination edge, most diverged first
+    #[serde(rename = "odPairs")]
+    pub od_pairs: Vec<OdDivergence>,
+    // Vehicles compared, diverged or not
+    pub compared: usize,
+    // Realized routes without a planned route to compare against: unknown
+    // ids, and trips or flows that SUMO routed itself
+    pub unplanned: usize,
+}
+
+// Normal edges only; vehroute output leaves out internal edges
+fn normal_edges(edges: &[String]) -> Vec<&str> {
+    edges.iter().map(String::as_str).filter(|e| !e.starts_with(':')).collect()
+}
+
+fn route_cost(net: &NetModel, edges: &[&str]) -> (f64, f64) {
+    edges
+        .iter()
+        .filter_map(|id| net.edge(id))
+        .fold((0.0, 0.0), |(length, time), e| (length + e.length(), time + e.free_flow_time(None)))
+}
+
+// Flow vehicles are named `<flow id>.<index>` in the output
+fn planned_for<'a>(planned: &HashMap<&str, &'a PlannedVehicle>, id: &str) -> Option<&'a PlannedVehicle> {
+    planned.get(id).copied().or_else(|| {
+        let (flow, _) = id.rsplit_once('.')?;
+        planned.get(flow).copied().filter(|p| p.kind == "flow")
+    })
+}
+
+fn missing_from(edges: &[&str], other: &[&str]) -> Vec<String> {
+    let other: HashSet<&str> = other.iter().copied().collect();
+    let mut seen = HashSet::new();
+    edges
+        .iter()
+        .filter(|e| !other.contains(*e) && seen.insert(**e))
+        .map(|e| e.to_string())
+        .collect()
+}
+
+#[derive(Default)]
+struct OdSum {
+    vehicles: usize,
+    diverged: usize,
+    extra_distance: f64,
+    extra_time: f64,
+}
+
+pub(crate) fn route_divergence(
+    net: &NetModel,
+    planned: &[PlannedVehicle],
+    realized: &[RealizedRoute],
+) -> PlannedRouteComparison {
+    let planned: HashMap<&str, &PlannedVehicle> = planned.iter().map(|p| (p.id.as_str(), p)).collect();
+    let mut vehicles = Vec::new();
+    let mut by_od: BTreeMap<(&str, &str), OdSum> = BTreeMap::new();
+    let (mut compared, mut unplanned) = (0, 0);
+
+    for route in realized {
+        let plan = planned_for(&planned, &route.id).filter(|p| p.routed);
+        let plan_edges = plan.map(|p| normal_edges(&p.edges)).unwrap_or_default();
+        let (Some(&from), Some(&to)) = (plan_edges.first(), plan_edges.last()) else {
+            unplanned += 1;
+            continue;
+        };
+        let driven = normal_edges(&route.edges);
+        let (planned_length, planned_time) = route_cost(net, &plan_edges);
+        let (driven_length, driven_time) = route_cost(net, &driven);
+        let extra_distance = driven_length - planned_length;
+        let extra_time = driven_time - planned_time;
+
+        compared += 1;
+        let sum = by_od.entry((from, to)).or_default();
+        sum.vehicles += 1;
+        sum.extra_distance += extra_distance;
+        sum.extra_time += extra_time;
+        if driven == plan_edges {
+            continue;
+        }
+        sum.diverged += 1;
+        vehicles.push(RouteDivergence {
+            id: route.id.clone(),
+            from: from.to_string(),
+            to: to.to_string(),
+            added: missing_from(&driven, &plan_edges),
+            removed: missing_from(&plan_edges, &driven),
+            extra_distance,
+            extra_time,
+        });
+    }
+    vehicles.sort_by(|a, b| b.extra_time.total_cmp(&a.extra_time));
+
+    let mut od_pairs: Vec<OdDivergence> = by_od
+        .into_iter()
+        .map(|((from, to), sum)| OdDivergence {
+            from: from.to_string(),
+            to: to.to_string(),
+            vehicles: sum.vehicles,
+            diverged: sum.diverged,
+            mean_extra_distance: sum.extra_distance / sum.vehicles as f64,
+            mean_extra_time: sum.extra_time / sum.vehicles as f64,
+        })
+        .collect();
+    od_pairs.sort_by_key(|o| std::cmp::Reverse(o.diverged));
+
+    PlannedRouteComparison {
+        vehicles,
+        od_pairs,
+        compared,
+        unplanned,
+    }
+}
+
+// Compares the routes planned in `route_xmls` (all route files of the
+// scenario) with those driven according to a vehroute output
+#[wasm_bindgen(unchecked_return_type = "PlannedRouteComparison")]
+pub fn compare_planned_routes(
+    net_xml: &str,
+    route_xmls: Vec<String>,
+    vehroute_xml: &str,
+) -> Result<JsValue, JsValue> {
+    let net_doc = parse_xml(net_xml)?;
+    let route_docs = route_xmls.iter().map(|x| parse_xml(x)).collect::<Result<Vec<_>, _>>()?;
+    let vehroute_doc = parse_xml(vehroute_xml)?;
+    let net = NetModel::from_root(net_doc.root_element());
+    let roots: Vec<roxmltree::Node> = route_docs.iter().map(|d| d.root_element()).collect();
+    let comparison = route_divergence(
+        &net,
+        &read_planned_vehicles(&roots),
+        &read_realized_routes(vehroute_doc.root_element()),
+    );
+
+    console_log!(
+        "Compared {} routes: {} diverged, {} without a plan",
+        comparison.compared,
+        comparison.vehicles.len(),
+        comparison.unplanned
+    );
+
+    to_js(&comparison)
+}
@@ -0,0 +1,138 @@
+// DXF export of selected lanes and junction outlines for handing corridor
+// geometry to CAD. Written as ASCII DXF R12 with POLYLINE entities, the
+// dialect every CAD package still reads, in projected meters (the network's
+// coordinates with the netOffset removed).
+use serde::Deserialize;
+use std::fmt::Write;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::markings::DEFAULT_LANE_WIDTH;
+use crate::net::NetModel;
+use crate::{parse_options, parse_xml};
+
+const LANE_LAYER: &str = "SUMO_LANES";
+const INTERNAL_LANE_LAYER: &str = "SUMO_INTERNAL_LANES";
+const JUNCTION_LAYER: &str = "SUMO_JUNCTIONS";
+
+#[derive(Deserialize, Default, Tsify)]
+#[serde(default)]
+pub struct DxfSelection {
+    // Lane ids
+    pub lanes: Vec<String>,
+    // Edge ids; all lanes of each edge
+    pub edges: Vec<String>,
+    // Junction ids; exported as closed outlines
+    pub junctions: Vec<String>,
+    // Give lane polylines their lane width instead of a hairline
+    #[serde(rename = "laneWidths")]
+    pub lane_widths: bool,
+}
+
+struct DxfWriter {
+    out: String,
+    // Added to network coordinates to get projected ones
+    shift: (f64, f64),
+}
+
+impl DxfWriter {
+    fn new(shift: (f64, f64)) -> DxfWriter {
+        let mut writer = DxfWriter { out: String::new(), shift };
+        writer.pair(0, "SECTION");
+        writer.pair(2, "HEADER");
+        writer.pair(9, "$ACADVER");
+        writer.pair(1, "AC1009");
+        // 6 = meters
+        writer.pair(9, "$INSUNITS");
+        writer.pair(70, "6");
+        writer.pair(0, "ENDSEC");
+        writer.pair(0, "SECTION");
+        writer.pair(2, "ENTITIES");
+        writer
+    }
+
+    fn pair(&mut self, code: u16, value: &str) {
+        let _ = write!(self.out, "{:>3}\n{}\n", code, value);
+    }
+
+    fn number(&mut self, code: u16, value: f64) {
+        let _ = write!(self.out, "{:>3}\n{:.4}\n", code, value);
+    }
+
+    fn polyline(&mut self, layer: &str, points: &[(f64, f64)], closed: bool, width: Option<f64>) {
+        if points.len() < 2 {
+            return;
+        }
+        self.pair(0, "POLYLINE");
+        self.pair(8, layer);
+        self.pair(66, "1");
+        self.number(10, 0.0);
+        self.number(20, 0.0);
+        self.number(30, 0.0);
+        self.pair(70, if closed { "1" } else { "0" });
+        if let Some(w) = width {
+            self.number(40, w);
+            self.number(41, w);
+        }
+        for &(x, y) in points {
+            self.pair(0, "VERTEX");
+            self.pair(8, layer);
+            self.number(10, x + self.shift.0);
+            self.number(20, y + self.shift.1);
+            self.number(30, 0.0);
+        }
+        self.pair(0, "SEQEND");
+        self.pair(8, layer);
+    }
+
+    fn finish(mut self) -> String {
+        self.pair(0, "ENDSEC");
+        self.pair(0, "EOF");
+        self.out
+    }
+}
+
+pub(crate) fn write_dxf(net: &NetModel, selection: &DxfSelection) -> String {
+    let shift = net.location.as_ref().map(|l| (-l.net_offset.0, -l.net_offset.1)).unwrap_or((0.0, 0.0));
+    let mut writer = DxfWriter::new(shift);
+
+    let mut lanes: Vec<&str> = selection.lanes.iter().map(String::as_str).collect();
+    for edge in selection.edges.iter().filter_map(|id| net.edge(id)) {
+        lanes.extend(edge.lanes.iter().map(|l| l.id.as_str()));
+    }
+    lanes.sort_unstable();
+    lanes.dedup();
+    for id in lanes {
+        let Some(lane) = net.lane(id) else { continue };
+        let layer = if id.starts_with(':') { INTERNAL_LANE_LAYER } else { LANE_LAYER };
+        let width = selection.lane_widths.then(|| lane.width.unwrap_or(DEFAULT_LANE_WIDTH));
+        writer.polyline(layer, &lane.shape, false, width);
+    }
+    for junction in selection.junctions.iter().filter_map(|id| net.junction(id)) {
+        writer.polyline(JUNCTION_LAYER, &junction.shape, true, None);
+    }
+    writer.finish()
+}
+
+// ASCII DXF of the selected lanes (centerlines) and junction outlines, in
+// projected meters, on one layer per kind
+#[wasm_bindgen]
+pub fn export_dxf(
+    xml_text: &str,
+    #[wasm_bindgen(unchecked_param_type = "DxfSelection")] selection: JsValue,
+) -> Result<String, JsValue> {
+    let selection: DxfSelection = parse_options(selection)?;
+    let doc = parse_xml(xml_text)?;
+    let net = NetModel::from_root(doc.root_element());
+    let dxf = write_dxf(&net, &selection);
+
+    console_log!(
+        "Exported DXF: {} lanes, {} edges, {} junctions selected ({} bytes)",
+        selection.lanes.len(),
+        selection.edges.len(),
+        selection.junctions.len(),
+        dxf.len()
+    );
+
+    Ok(dxf)
+}
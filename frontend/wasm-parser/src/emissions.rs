@@ -0,0 +1,253 @@
+// Emission output (--emission-output) summed per edge and time interval for
+// an emissions heatmap. The files hold one record per vehicle per step and
+// reach gigabytes, so they are streamed: only the running sums are kept.
+use std::collections::{BTreeMap, HashMap};
+use wasm_bindgen::prelude::*;
+
+use crate::heatmap::lane_edge;
+use crate::stream::{parse_fragment, ElementScanner};
+use crate::{attr_f64, parse_xml};
+
+// Attributes summed, in the order of the `pollutant` indices below. SUMO
+// writes them as per-step rates: mg/s, fuel in mg/s (ml/s before 1.14) and
+// electricity in Wh/s.
+const POLLUTANTS: [&str; 7] = ["CO2", "CO", "HC", "NOx", "PMx", "fuel", "electricity"];
+const DEFAULT_INTERVAL: f64 = 900.0;
+
+type Sums = [f64; POLLUTANTS.len()];
+
+// Running per-edge sums, fed one <timestep> at a time
+struct EmissionSums {
+    interval: f64,
+    edge_ids: Vec<String>,
+    edge_index: HashMap<String, u32>,
+    // Interval number (time / interval) -> edge -> summed rates
+    intervals: BTreeMap<i64, HashMap<u32, Sums>>,
+    last_time: Option<f64>,
+    // Smallest gap between timesteps seen so far
+    step_length: Option<f64>,
+    records: usize,
+}
+
+impl EmissionSums {
+    fn new(interval: Option<f64>) -> EmissionSums {
+        EmissionSums {
+            interval: interval.filter(|i| *i > 0.0).unwrap_or(DEFAULT_INTERVAL),
+            edge_ids: Vec::new(),
+            edge_index: HashMap::new(),
+            intervals: BTreeMap::new(),
+            last_time: None,
+            step_length: None,
+            records: 0,
+        }
+    }
+
+    fn add_timestep(&mut self, step: roxmltree::Node) {
+        let Some(time) = attr_f64(step, "time") else { return };
+        if let Some(gap) = self.last_time.map(|t| time - t).filter(|g| *g > 0.0) {
+            self.step_length = Some(self.step_length.map_or(gap, |s| s.min(gap)));
+        }
+        self.last_time = Some(time);
+
+        let edges = self.intervals.entry((time / self.interval).floor() as i64).or_default();
+        for vehicle in step.children().filter(|n| n.tag_name().name() == "vehicle") {
+            let Some(lane) = vehicle.attribute("lane") else { continue };
+            let edge = lane_edge(lane);
+            let index = match self.edge_index.get(edge) {
+                Some(i) => *i,
+                None => {
+                    self.edge_ids.push(edge.to_string());
+                    let i = (self.edge_ids.len() - 1) as u32;
+                    self.edge_index.insert(edge.to_string(), i);
+                    i
+                }
+            };
+            let sums = edges.entry(index).or_insert([0.0; POLLUTANTS.len()]);
+            for (sum, name) in sums.iter_mut().zip(POLLUTANTS) {
+                *sum += attr_f64(vehicle, name).unwrap_or(0.0);
+            }
+            self.records += 1;
+        }
+    }
+
+    fn finish(self) -> EmissionGrid {
+        // Rates times the step length give amounts per step
+        let step_length = self.step_length.unwrap_or(1.0);
+        let edge_count = self.edge_ids.len();
+        let mut values = vec![Vec::with_capacity(edge_count * self.intervals.len()); POLLUTANTS.len()];
+        for edges in self.intervals.values() {
+            for column in values.iter_mut() {
+                column.resize(column.len() + edge_count, 0.0);
+            }
+            let offset = values[0].len() - edge_count;
+            for (edge, sums) in edges {
+                for (column, sum) in values.iter_mut().zip(sums) {
+                    column[offset + *edge as usize] = (sum * step_length) as f32;
+                }
+            }
+        }
+        EmissionGrid {
+            edge_ids: self.edge_ids,
+            begins: self.intervals.keys().map(|i| *i as f64 * self.interval).collect(),
+            interval: self.interval,
+            step_length,
+            values,
+        }
+    }
+}
+
+// Emissions per edge per interval. Interval-major: interval i occupies
+// values[i * edgeCount .. (i + 1) * edgeCount], edges in edgeIds order.
+// Amounts are per interval: mg, fuel in mg (ml before SUMO 1.14),
+// electricity in Wh. Intervals without any vehicle are left out.
+#[wasm_bindgen]
+pub struct EmissionGrid {
+    edge_ids: Vec<String>,
+    begins: Vec<f64>,
+    interval: f64,
+    step_length: f64,
+    // One column per entry of POLLUTANTS
+    values: Vec<Vec<f32>>,
+}
+
+#[wasm_bindgen]
+impl EmissionGrid {
+    // Edges in order of first appearance, internal edges included
+    #[wasm_bindgen(getter, js_name = edgeIds)]
+    pub fn edge_ids(&self) -> Vec<String> {
+        self.edge_ids.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name = edgeCount)]
+    pub fn edge_count(&self) -> usize {
+        self.edge_ids.len()
+    }
+
+    #[wasm_bindgen(getter, js_name = intervalCount)]
+    pub fn interval_count(&self) -> usize {
+        self.begins.len()
+    }
+
+    // Interval start times (seconds); each lasts `interval`
+    #[wasm_bindgen(getter)]
+    pub fn begins(&self) -> Vec<f64> {
+        self.begins.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn interval(&self) -> f64 {
+        self.interval
+    }
+
+    // Seconds between timesteps in the file
+    #[wasm_bindgen(getter, js_name = stepLength)]
+    pub fn step_length(&self) -> f64 {
+        self.step_length
+    }
+
+    // Names accepted by values() and totals()
+    pub fn pollutants() -> Vec<String> {
+        POLLUTANTS.iter().map(|p| p.to_string()).collect()
+    }
+
+    // Amount of `pollutant` per edge per interval
+    pub fn values(&self, pollutant: &str) -> Result<Vec<f32>, JsValue> {
+        Ok(self.column(pollutant)?.clone())
+    }
+
+    // Amount of `pollutant` per edge over all intervals
+    pub fn totals(&self, pollutant: &str) -> Result<Vec<f32>, JsValue> {
+        let mut totals = vec![0.0; self.edge_ids.len()];
+        if !totals.is_empty() {
+            for interval in self.column(pollutant)?.chunks(totals.len()) {
+                totals.iter_mut().zip(interval).for_each(|(t, v)| *t += v);
+            }
+        }
+        Ok(totals)
+    }
+}
+
+impl EmissionGrid {
+    fn column(&self, pollutant: &str) -> Result<&Vec<f32>, JsValue> {
+        POLLUTANTS
+            .iter()
+            .position(|p| p.eq_ignore_ascii_case(pollutant))
+            .map(|i| &self.values[i])
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown pollutant {}", pollutant)))
+    }
+}
+
+// Push-style emission output parser for files too large to hold as text:
+// feed chunks as they are read, then finish() for the per-edge sums.
+#[wasm_bindgen]
+pub struct EmissionParser {
+    scanner: ElementScanner,
+    sums: EmissionSums,
+}
+
+#[wasm_bindgen]
+impl EmissionParser {
+    // `interval`: seconds per aggregation interval (default 900)
+    #[wasm_bindgen(constructor)]
+    pub fn new(interval: Option<f64>) -> EmissionParser {
+        EmissionParser {
+            scanner: ElementScanner::default(),
+            sums: EmissionSums::new(interval),
+        }
+    }
+
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<(), JsValue> {
+        self.feed_bytes(chunk).map_err(|e| JsValue::from_str(&e))
+    }
+
+    pub fn finish(self) -> Result<EmissionGrid, JsValue> {
+        self.finish_grid().map_err(|e| JsValue::from_str(&e))
+    }
+}
+
+impl EmissionParser {
+    pub(crate) fn feed_bytes(&mut self, chunk: &[u8]) -> Result<(), String> {
+        let sums = &mut self.sums;
+        self.scanner.feed(chunk, |bytes| add_fragment(sums, bytes))
+    }
+
+    pub(crate) fn finish_grid(mut self) -> Result<EmissionGrid, String> {
+        let sums = &mut self.sums;
+        self.scanner.finish(|bytes| add_fragment(sums, bytes))?;
+
+        console_log!(
+            "Streamed {} emission records over {} edges",
+            self.sums.records,
+            self.sums.edge_ids.len()
+        );
+
+        Ok(self.sums.finish())
+    }
+}
+
+fn add_fragment(sums: &mut EmissionSums, bytes: &[u8]) -> Result<(), String> {
+    let doc = parse_fragment(bytes)?;
+    if doc.root_element().tag_name().name() == "timestep" {
+        sums.add_timestep(doc.root_element());
+    }
+    Ok(())
+}
+
+pub(crate) fn read_emissions(root: roxmltree::Node, interval: Option<f64>) -> EmissionGrid {
+    let mut sums = EmissionSums::new(interval);
+    for step in root.children().filter(|n| n.tag_name().name() == "timestep") {
+        sums.add_timestep(step);
+    }
+    sums.finish()
+}
+
+// Whole emission output already in memory; see EmissionParser for large files
+#[wasm_bindgen]
+pub fn parse_emission_output(xml_text: &str, interval: Option<f64>) -> Result<EmissionGrid, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let grid = read_emissions(doc.root_element(), interval);
+
+    console_log!("Aggregated emissions of {} edges over {} intervals", grid.edge_ids.len(), grid.begins.len());
+
+    Ok(grid)
+}
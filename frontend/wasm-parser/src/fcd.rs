@@ -0,0 +1,428 @@
+// Floating car data (--fcd-output): every vehicle's position each step,
+// regrouped into one track per vehicle.
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::heatmap::lane_edge;
+use crate::mapmatch::TraceMatchOptions;
+use crate::network::Network;
+use crate::projection::Crs;
+use crate::{attr_f64, parse_options, parse_xml, to_js};
+
+// Track records without a lane attribute
+const NO_LANE: u32 = u32::MAX;
+// Most interval x edge cells of an edge speed grid, which is dense between
+// the first and last record; 8 bytes each, so at most 128 MB
+const MAX_GRID_CELLS: usize = 16_000_000;
+
+#[derive(Default)]
+pub(crate) struct Track {
+    pub time: Vec<f64>,
+    pub x: Vec<f64>,
+    pub y: Vec<f64>,
+    pub speed: Vec<f64>,
+    // Index into FcdData.lanes, or NO_LANE
+    pub lane: Vec<u32>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct Trajectory {
+    pub id: String,
+    // [lat, lng] per record, in the file's frame (network coordinates, or
+    // lon/lat with --fcd-output.geo)
+    pub points: Vec<Vec<f64>>,
+    // Seconds
+    pub times: Vec<f64>,
+    // m/s
+    pub speeds: Vec<f64>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct TrajectoryProperties {
+    pub id: String,
+    pub times: Vec<f64>,
+    pub speeds: Vec<f64>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct LineString {
+    #[serde(rename = "type")]
+    pub geometry_type: String,
+    // [x, y, 0, time]: GeoJSON order, with the timestamp as the fourth
+    // coordinate that kepler.gl's trip layer animates
+    pub coordinates: Vec<[f64; 4]>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct TrajectoryFeature {
+    #[serde(rename = "type")]
+    pub feature_type: String,
+    pub geometry: LineString,
+    pub properties: TrajectoryProperties,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct TrajectoryCollection {
+    #[serde(rename = "type")]
+    pub collection_type: String,
+    pub features: Vec<TrajectoryFeature>,
+}
+
+// A parsed FCD file kept in WASM; query single vehicles or export all
+#[wasm_bindgen]
+pub struct FcdData {
+    tracks: BTreeMap<String, Track>,
+    lanes: Vec<String>,
+}
+
+#[wasm_bindgen]
+impl FcdData {
+    #[wasm_bindgen(getter, js_name = vehicleCount)]
+    pub fn vehicle_count(&self) -> usize {
+        self.tracks.len()
+    }
+
+    pub fn ids(&self) -> Vec<String> {
+        self.tracks.keys().cloned().collect()
+    }
+
+    // The vehicle's whole path in time order
+    #[wasm_bindgen(unchecked_return_type = "Trajectory | undefined")]
+    pub fn trajectory_of(&self, vehicle_id: &str) -> Result<JsValue, JsValue> {
+        let trajectory = self.tracks.get(vehicle_id).map(|t| Trajectory {
+            id: vehicle_id.to_string(),
+            points: t.x.iter().zip(&t.y).map(|(x, y)| vec![*y, *x]).collect(),
+            times: t.time.clone(),
+            speeds: t.speed.clone(),
+        });
+        to_js(&trajectory)
+    }
+
+    // Every vehicle as a GeoJSON LineString feature, e.g. for kepler.gl.
+    // Vehicles seen only once have no line and are left out.
+    #[wasm_bindgen(unchecked_return_type = "TrajectoryCollection")]
+    pub fn trajectories_geojson(&self) -> Result<JsValue, JsValue> {
+        to_js(&trajectory_collection(&self.tracks))
+    }
+}
+
+impl FcdData {
+    // (x, y, time, speed) of every record, vehicle by vehicle
+    pub(crate) fn records(&self) -> impl Iterator<Item = (f64, f64, f64, f64)> + '_ {
+        self.tracks
+            .values()
+            .flat_map(|t| (0..t.time.len()).map(move |i| (t.x[i], t.y[i], t.time[i], t.speed[i])))
+    }
+}
+
+pub(crate) fn trajectory_collection(tracks: &BTreeMap<String, Track>) -> TrajectoryCollection {
+    let features = tracks
+        .iter()
+        .filter(|(_, t)| t.time.len() >= 2)
+        .map(|(id, t)| TrajectoryFeature {
+            feature_type: "Feature".to_string(),
+            geometry: LineString {
+                geometry_type: "LineString".to_string(),
+                coordinates: (0..t.time.len()).map(|i| [t.x[i], t.y[i], 0.0, t.time[i]]).collect(),
+            },
+            properties: TrajectoryProperties {
+                id: id.clone(),
+                times: t.time.clone(),
+                speeds: t.speed.clone(),
+            },
+        })
+        .collect();
+    TrajectoryCollection {
+        collection_type: "FeatureCollection".to_string(),
+        features,
+    }
+}
+
+// <fcd-export><timestep time><vehicle id x y speed lane .../>
+pub(crate) fn read_fcd(root: roxmltree::Node) -> FcdData {
+    let mut tracks: BTreeMap<String, Track> = BTreeMap::new();
+    let mut lanes = Vec::new();
+    let mut lane_index: HashMap<&str, u32> = HashMap::new();
+    for step in root.children().filter(|n| n.tag_name().name() == "timestep") {
+        let Some(time) = attr_f64(step, "time") else { continue };
+        for vehicle in step.children().filter(|n| n.tag_name().name() == "vehicle") {
+            let (Some(id), Some(x), Some(y)) = (vehicle.attribute("id"), attr_f64(vehicle, "x"), attr_f64(vehicle, "y"))
+            else {
+                continue;
+            };
+            let track = match tracks.get_mut(id) {
+                Some(track) => track,
+                None => tracks.entry(id.to_string()).or_default(),
+            };
+            track.time.push(time);
+            track.x.push(x);
+            track.y.push(y);
+            track.speed.push(attr_f64(vehicle, "speed").unwrap_or(f64::NAN));
+            track.lane.push(vehicle.attribute("lane").map_or(NO_LANE, |lane| {
+                *lane_index.entry(lane).or_insert_with(|| {
+                    lanes.push(lane.to_string());
+                    (lanes.len() - 1) as u32
+                })
+            }));
+        }
+    }
+    FcdData { tracks, lanes }
+}
+
+// Mean speed and record count per edge per interval. Interval-major: interval
+// i occupies [i * edgeCount .. (i + 1) * edgeCount], edges in edgeIds order.
+#[wasm_bindgen]
+pub struct EdgeSpeedGrid {
+    edge_ids: Vec<String>,
+    begins: Vec<f64>,
+    interval: f64,
+    mean_speed: Vec<f32>,
+    count: Vec<u32>,
+    unmatched: usize,
+}
+
+#[wasm_bindgen]
+impl EdgeSpeedGrid {
+    // Edges with at least one record, in order of first appearance
+    #[wasm_bindgen(getter, js_name = edgeIds)]
+    pub fn edge_ids(&self) -> Vec<String> {
+        self.edge_ids.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name = edgeCount)]
+    pub fn edge_count(&self) -> usize {
+        self.edge_ids.len()
+    }
+
+    #[wasm_bindgen(getter, js_name = intervalCount)]
+    pub fn interval_count(&self) -> usize {
+        self.begins.len()
+    }
+
+    // Interval start times (seconds), from the first interval with data to
+    // the last; each lasts `interval`
+    #[wasm_bindgen(getter)]
+    pub fn begins(&self) -> Vec<f64> {
+        self.begins.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn interval(&self) -> f64 {
+        self.interval
+    }
+
+    // m/s, averaged over the records (time-mean speed); NaN without records
+    #[wasm_bindgen(getter, js_name = meanSpeed)]
+    pub fn mean_speed(&self) -> Vec<f32> {
+        self.mean_speed.clone()
+    }
+
+    // FCD records (vehicle-steps) on the edge in the interval
+    #[wasm_bindgen(getter)]
+    pub fn count(&self) -> Vec<u32> {
+        self.count.clone()
+    }
+
+    // Records that could not be placed on an edge
+    #[wasm_bindgen(getter)]
+    pub fn unmatched(&self) -> usize {
+        self.unmatched
+    }
+}
+
+#[cfg(feature = "arrow")]
+#[wasm_bindgen]
+impl EdgeSpeedGrid {
+    // Arrow IPC stream, one row (begin, edge_id, mean_speed, count) per edge
+    // and interval with records
+    #[wasm_bindgen(js_name = toArrow)]
+    pub fn to_arrow(&self) -> Result<Vec<u8>, JsValue> {
+        crate::arrow::edge_speed_table(&self.edge_ids, &self.begins, &self.mean_speed, &self.count)
+    }
+}
+
+// Edge of every record of a track: from its lanes when the file has them
+// (fcd-output with lane attributes), by map-matching the positions otherwise
+fn track_edges(
+    fcd: &FcdData,
+    track: &Track,
+    network: &Network,
+    options: &TraceMatchOptions,
+) -> Result<Vec<Option<String>>, JsValue> {
+    if track.lane.iter().all(|l| *l != NO_LANE) {
+        return Ok(track.lane.iter().map(|l| Some(lane_edge(&fcd.lanes[*l as usize]).to_string())).collect());
+    }
+    let points = track.x.iter().copied().zip(track.y.iter().copied()).collect();
+    let xy = network.to_network_xy(points, options.crs.unwrap_or(Crs::Network))?;
+    let times: Vec<Option<f64>> = track.time.iter().map(|t| Some(*t)).collect();
+    Ok(network.match_xy(&xy, &times, options).points.into_iter().map(|p| p.edge_id).collect())
+}
+
+// Cells of a grid of `span` intervals by `edges` edges, None above
+// MAX_GRID_CELLS
+fn grid_cells(span: i64, edges: usize) -> Option<usize> {
+    usize::try_from(span).ok()?.checked_mul(edges).filter(|c| *c <= MAX_GRID_CELLS)
+}
+
+pub(crate) fn aggregate_by_edge(
+    fcd: &FcdData,
+    network: &Network,
+    interval: f64,
+    options: &TraceMatchOptions,
+) -> Result<EdgeSpeedGrid, JsValue> {
+    let mut edge_ids: Vec<String> = Vec::new();
+    let mut edge_index: HashMap<String, usize> = HashMap::new();
+    // Interval number -> edge -> (speed sum, records with a speed, records)
+    let mut bins: BTreeMap<i64, HashMap<usize, (f64, u32, u32)>> = BTreeMap::new();
+    let mut unmatched = 0;
+    for track in fcd.tracks.values() {
+        for (i, edge) in track_edges(fcd, track, network, options)?.into_iter().enumerate() {
+            let Some(edge) = edge else {
+                unmatched += 1;
+                continue;
+            };
+            let index = match edge_index.get(&edge) {
+                Some(index) => *index,
+                None => {
+                    edge_ids.push(edge.clone());
+                    edge_index.insert(edge, edge_ids.len() - 1);
+                    edge_ids.len() - 1
+                }
+            };
+            let bin = bins.entry((track.time[i] / interval).floor() as i64).or_default();
+            let sum = bin.entry(index).or_default();
+            if track.speed[i].is_finite() {
+                sum.0 += track.speed[i];
+                sum.1 += 1;
+            }
+            sum.2 += 1;
+        }
+    }
+
+    // Dense over the covered range, so the slider steps evenly
+    let (first, last) = match (bins.keys().next(), bins.keys().next_back()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => (0, -1),
+    };
+    let span = last.saturating_sub(first).saturating_add(1).max(0);
+    let Some(cells) = grid_cells(span, edge_ids.len()) else {
+        return Err(JsValue::from_str(&format!(
+            "FCD spans {} intervals of {} s on {} edges, more than {} cells; use a longer interval",
+            span,
+            interval,
+            edge_ids.len(),
+            MAX_GRID_CELLS
+        )));
+    };
+    let mut mean_speed = vec![f32::NAN; cells];
+    let mut count = vec![0; cells];
+    for (bin, edges) in &bins {
+        let offset = (bin - first) as usize * edge_ids.len();
+        for (edge, (sum, with_speed, n)) in edges {
+            if *with_speed > 0 {
+                mean_speed[offset + edge] = (sum / *with_speed as f64) as f32;
+            }
+            count[offset + edge] = *n;
+        }
+    }
+
+    Ok(EdgeSpeedGrid {
+        begins: (first..=last).map(|i| i as f64 * interval).collect(),
+        edge_ids,
+        interval,
+        mean_speed,
+        count,
+        unmatched,
+    })
+}
+
+// Per-edge mean speed and record count per `interval_s` seconds, for a
+// congestion time slider. Records are placed by their lane when the FCD has
+// lane attributes and map-matched onto `network` otherwise; `options` are
+// the map-matching options, with crs "network" (the default) or "wgs84" for
+// --fcd-output.geo files.
+#[wasm_bindgen]
+pub fn aggregate_fcd_by_edge(
+    fcd: &FcdData,
+    network: &Network,
+    interval_s: f64,
+    #[wasm_bindgen(unchecked_param_type = "TraceMatchOptions | undefined")] options: JsValue,
+) -> Result<EdgeSpeedGrid, JsValue> {
+    if !(interval_s.is_finite() && interval_s > 0.0) {
+        return Err(JsValue::from_str("interval_s must be positive"));
+    }
+    let options: TraceMatchOptions = parse_options(options)?;
+    let grid = aggregate_by_edge(fcd, network, interval_s, &options)?;
+
+    console_log!(
+        "Aggregated FCD onto {} edges over {} intervals ({} records unmatched)",
+        grid.edge_ids.len(),
+        grid.begins.len(),
+        grid.unmatched
+    );
+
+    Ok(grid)
+}
+
+#[wasm_bindgen]
+pub fn parse_fcd_output(xml_text: &str) -> Result<FcdData, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let fcd = read_fcd(doc.root_element());
+
+    console_log!("Parsed FCD tracks of {} vehicles", fcd.tracks.len());
+
+    Ok(fcd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NET: &str = r#"<net>
+        <edge id="a" from="j0" to="j1"><lane id="a_0" index="0" speed="13.89" length="100" shape="0,0 100,0"/></edge>
+        <edge id="b" from="j1" to="j2"><lane id="b_0" index="0" speed="13.89" length="100" shape="100,0 200,0"/></edge>
+        <junction id="j0" type="priority" x="0" y="0"/>
+        <junction id="j1" type="priority" x="100" y="0"/>
+        <junction id="j2" type="priority" x="200" y="0"/>
+    </net>"#;
+
+    const FCD: &str = r#"<fcd-export>
+        <timestep time="0"><vehicle id="v0" x="10" y="0" speed="10" lane="a_0"/><vehicle id="v1" x="20" y="0" lane="a_0"/></timestep>
+        <timestep time="5"><vehicle id="v0" x="60" y="0" speed="6" lane="a_0"/><vehicle id="v1" x="30" y="0" speed="2" lane="a_0"/></timestep>
+        <timestep time="25"><vehicle id="v0" x="150" y="0" speed="8" lane="b_0"/></timestep>
+    </fcd-export>"#;
+
+    fn grid(interval: f64) -> EdgeSpeedGrid {
+        let network = Network::new(NET).unwrap();
+        let doc = roxmltree::Document::parse(FCD).unwrap();
+        aggregate_by_edge(&read_fcd(doc.root_element()), &network, interval, &TraceMatchOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn records_are_binned_by_lane_edge_and_interval() {
+        let grid = grid(10.0);
+        assert_eq!(grid.edge_ids, ["a", "b"]);
+        // The empty interval 10..20 is kept so the slider steps evenly
+        assert_eq!(grid.begins, [0.0, 10.0, 20.0]);
+        assert_eq!(grid.count, [4, 0, 0, 0, 0, 1]);
+        assert_eq!(grid.unmatched, 0);
+    }
+
+    #[test]
+    fn mean_speed_skips_records_without_a_speed() {
+        let grid = grid(10.0);
+        assert_eq!(grid.mean_speed[0], 6.0);
+        assert!(grid.mean_speed[1].is_nan());
+        assert_eq!(grid.mean_speed[5], 8.0);
+    }
+
+    #[test]
+    fn grid_cells_are_capped() {
+        assert_eq!(grid_cells(3, 2), Some(6));
+        assert_eq!(grid_cells(0, 5), Some(0));
+        assert_eq!(grid_cells(MAX_GRID_CELLS as i64 + 1, 1), None);
+        assert_eq!(grid_cells(i64::MAX, usize::MAX), None);
+    }
+}
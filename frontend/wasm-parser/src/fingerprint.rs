@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+
+use crate::geometry;
+use crate::hash::Fnv64;
+use crate::net::{EdgeModel, NetModel};
+use crate::{parse_xml, rdp_simplify, to_js};
+
+// Tolerances chosen so netconvert re-runs (which jitter coordinates by
+// centimeters) keep fingerprints while real geometry edits change them
+const SIMPLIFY_EPS: f64 = 2.0;
+const GRID: f64 = 1.0;
+const PROXIMITY_TOLERANCE: f64 = 3.0;
+
+type Endpoints = ((f64, f64), (f64, f64));
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct EdgeFingerprint {
+    pub id: String,
+    pub fingerprint: String,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct FingerprintMatch {
+    #[serde(rename = "idA")]
+    pub id_a: String,
+    #[serde(rename = "idB")]
+    pub id_b: String,
+    // "id", "fingerprint" or "proximity"
+    pub method: String,
+}
+
+// Reference geometry of an edge in original projected coordinates, so that a
+// changed netOffset between builds does not change the fingerprint
+fn reference_shape(net: &NetModel, edge: &EdgeModel) -> Option<Vec<(f64, f64)>> {
+    let lane = edge.lane(0).or_else(|| edge.lanes.first())?;
+    if lane.shape.len() < 2 {
+        return None;
+    }
+    let (ox, oy) = net.location.as_ref().map(|l| l.net_offset).unwrap_or((0.0, 0.0));
+    Some(lane.shape.iter().map(|(x, y)| (x - ox, y - oy)).collect())
+}
+
+fn hash_shape(shape: &[(f64, f64)]) -> String {
+    let simplified = rdp_simplify(shape, SIMPLIFY_EPS);
+    let mut h = Fnv64::new();
+    for (x, y) in simplified {
+        h.write_i64((x / GRID).round() as i64);
+        h.write_i64((y / GRID).round() as i64);
+    }
+    format!("{:016x}", h.finish())
+}
+
+pub(crate) fn edge_fingerprints(net: &NetModel) -> Vec<EdgeFingerprint> {
+    net.edges
+        .iter()
+        .filter(|e| !e.is_internal())
+        .filter_map(|e| {
+            Some(EdgeFingerprint {
+                id: e.id.clone(),
+                fingerprint: hash_shape(&reference_shape(net, e)?),
+            })
+        })
+        .collect()
+}
+
+// Pair edges of two builds: same id and geometry first, then identical
+// fingerprints, then endpoints within tolerance for geometry that drifted
+pub(crate) fn match_edges(a: &NetModel, b: &NetModel) -> Vec<FingerprintMatch> {
+    let fa = edge_fingerprints(a);
+    let fb = edge_fingerprints(b);
+    let mut used_b: HashMap<&str, bool> = fb.iter().map(|f| (f.id.as_str(), false)).collect();
+    let mut matches = Vec::new();
+    let mut pending: Vec<&EdgeFingerprint> = Vec::new();
+
+    let fb_by_id: HashMap<&str, &EdgeFingerprint> = fb.iter().map(|f| (f.id.as_str(), f)).collect();
+    for f in &fa {
+        match fb_by_id.get(f.id.as_str()) {
+            Some(other) if other.fingerprint == f.fingerprint => {
+                used_b.insert(other.id.as_str(), true);
+                matches.push(FingerprintMatch {
+                    id_a: f.id.clone(),
+                    id_b: other.id.clone(),
+                    method: "id".to_string(),
+                });
+            }
+            _ => pending.push(f),
+        }
+    }
+
+    let mut by_fingerprint: HashMap<&str, Vec<&str>> = HashMap::new();
+    for f in &fb {
+        if !used_b[f.id.as_str()] {
+            by_fingerprint.entry(f.fingerprint.as_str()).or_default().push(f.id.as_str());
+        }
+    }
+
+    let mut unmatched: Vec<&EdgeFingerprint> = Vec::new();
+    for f in pending {
+        let candidate = by_fingerprint
+            .get_mut(f.fingerprint.as_str())
+            .and_then(|ids| ids.pop());
+        match candidate {
+            Some(id_b) => {
+                used_b.insert(id_b, true);
+                matches.push(FingerprintMatch {
+                    id_a: f.id.clone(),
+                    id_b: id_b.to_string(),
+                    method: "fingerprint".to_string(),
+                });
+            }
+            None => unmatched.push(f),
+        }
+    }
+
+    let endpoints = |net: &NetModel, id: &str| -> Option<Endpoints> {
+        let shape = reference_shape(net, net.edge(id)?)?;
+        Some((*shape.first()?, *shape.last()?))
+    };
+    let remaining_b: Vec<(&str, Endpoints)> = fb
+        .iter()
+        .filter(|f| !used_b[f.id.as_str()])
+        .filter_map(|f| Some((f.id.as_str(), endpoints(b, &f.id)?)))
+        .collect();
+
+    for f in unmatched {
+        let Some((a0, a1)) = endpoints(a, &f.id) else { continue };
+        let best = remaining_b
+            .iter()
+            .filter(|(id, _)| !used_b[id])
+            .map(|(id, (b0, b1))| {
+                (*id, geometry::distance(a0, *b0).max(geometry::distance(a1, *b1)))
+            })
+            .filter(|(_, d)| *d <= PROXIMITY_TOLERANCE)
+            .min_by(|x, y| x.1.total_cmp(&y.1));
+        if let Some((id_b, _)) = best {
+            used_b.insert(id_b, true);
+            matches.push(FingerprintMatch {
+                id_a: f.id.clone(),
+                id_b: id_b.to_string(),
+                method: "proximity".to_string(),
+            });
+        }
+    }
+
+    matches
+}
+
+#[wasm_bindgen(unchecked_return_type = "EdgeFingerprint[]")]
+pub fn fingerprint_edges(xml_text: &str) -> Result<JsValue, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let net = NetModel::from_root(doc.root_element());
+    to_js(&edge_fingerprints(&net))
+}
+
+#[wasm_bindgen(unchecked_return_type = "FingerprintMatch[]")]
+pub fn match_edges_across_builds(old_xml: &str, new_xml: &str) -> Result<JsValue, JsValue> {
+    let old_doc = parse_xml(old_xml)?;
+    let new_doc = parse_xml(new_xml)?;
+    let old_net = NetModel::from_root(old_doc.root_element());
+    let new_net = NetModel::from_root(new_doc.root_element());
+
+    let matches = match_edges(&old_net, &new_net);
+    console_log!("Matched {} edges across builds", matches.len());
+
+    to_js(&matches)
+}
@@ -0,0 +1,223 @@
+// Speed funnels: places where the speed limit or the number of lanes drops
+// sharply within a short distance along a road. Real roads step down
+// gradually; abrupt drops are usually import artifacts (a mistagged OSM way,
+// a lane count lost at a split) that create artificial bottlenecks.
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::net::{EdgeModel, LaneModel, NetModel};
+use crate::units::speed_limit_kmh;
+use crate::{parse_options, parse_xml, to_js};
+
+const DEFAULT_MIN_SPEED_DROP: f64 = 30.0;
+const DEFAULT_MIN_LANE_DROP: usize = 2;
+const DEFAULT_MAX_DISTANCE: f64 = 100.0;
+
+#[derive(Deserialize, Default, Tsify)]
+#[serde(default)]
+pub struct FunnelOptions {
+    // km/h (default 30)
+    #[serde(rename = "minSpeedDrop")]
+    pub min_speed_drop: Option<f64>,
+    // Lanes (default 2)
+    #[serde(rename = "minLaneDrop")]
+    pub min_lane_drop: Option<usize>,
+    // Meters downstream within which the drop must happen (default 100)
+    #[serde(rename = "maxDistance")]
+    pub max_distance: Option<f64>,
+    // Vehicle class whose lanes and limits are compared (default "passenger")
+    #[serde(rename = "vClass")]
+    pub v_class: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Tsify, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum FunnelKind {
+    SpeedDrop,
+    LaneDrop,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct SpeedFunnel {
+    pub kind: FunnelKind,
+    // Last edge before the drop
+    #[serde(rename = "fromEdge")]
+    pub from_edge: String,
+    // Edge where the lowest value is reached
+    #[serde(rename = "toEdge")]
+    pub to_edge: String,
+    // km/h for speed drops, lanes for lane drops
+    pub before: f64,
+    pub after: f64,
+    // Meters between the end of fromEdge and the start of toEdge
+    pub distance: f64,
+    // [lat, lng] at the end of fromEdge
+    pub position: Vec<f64>,
+}
+
+// What is compared per edge: the fastest limit and the lane count usable by
+// the vehicle class
+struct EdgeProfile {
+    speed: f64,
+    lanes: usize,
+    length: f64,
+}
+
+fn profile(edge: &EdgeModel, v_class: &str) -> Option<EdgeProfile> {
+    let usable: Vec<&LaneModel> = edge.lanes.iter().filter(|l| l.permits(v_class)).collect();
+    let speed = usable.iter().filter_map(|l| l.speed).reduce(f64::max)?;
+    Some(EdgeProfile {
+        speed: speed_limit_kmh(speed),
+        lanes: usable.len(),
+        length: edge.length(),
+    })
+}
+
+// The edges a road continues on: straight-on successors, or the only
+// successor where the road bends without a straight connection
+fn continuations(net: &NetModel) -> HashMap<&str, Vec<&str>> {
+    let mut next: HashMap<&str, Vec<(&str, bool)>> = HashMap::new();
+    for c in &net.connections {
+        if c.from.starts_with(':') || c.to.starts_with(':') || c.dir == "t" {
+            continue;
+        }
+        let list = next.entry(c.from.as_str()).or_default();
+        match list.iter_mut().find(|(to, _)| *to == c.to) {
+            Some(entry) => entry.1 |= c.dir == "s",
+            None => list.push((c.to.as_str(), c.dir == "s")),
+        }
+    }
+    next.into_iter()
+        .map(|(from, list)| {
+            let straight: Vec<&str> = list.iter().filter(|(_, s)| *s).map(|(to, _)| *to).collect();
+            let onward = if straight.is_empty() && list.len() == 1 { vec![list[0].0] } else { straight };
+            (from, onward)
+        })
+        .collect()
+}
+
+struct Finder<'a> {
+    net: &'a NetModel,
+    profiles: HashMap<&'a str, EdgeProfile>,
+    next: HashMap<&'a str, Vec<&'a str>>,
+    max_distance: f64,
+}
+
+impl<'a> Finder<'a> {
+    // Edges downstream of `from` whose start lies within max_distance of its
+    // end, with that distance
+    fn downstream(&self, from: &'a str) -> Vec<(&'a str, f64)> {
+        let mut reached = Vec::new();
+        let mut seen = HashSet::from([from]);
+        let mut stack: Vec<(&str, f64)> = self.next.get(from).into_iter().flatten().map(|to| (*to, 0.0)).collect();
+        while let Some((edge, distance)) = stack.pop() {
+            if !seen.insert(edge) {
+                continue;
+            }
+            let Some(p) = self.profiles.get(edge) else { continue };
+            reached.push((edge, distance));
+            let beyond = distance + p.length;
+            if beyond <= self.max_distance {
+                stack.extend(self.next.get(edge).into_iter().flatten().map(|to| (*to, beyond)));
+            }
+        }
+        reached
+    }
+
+    // The lowest value downstream, if the drop starts right at `from`'s end
+    // and reaches at least `min_drop`
+    fn drop_after(
+        &self,
+        from: &'a str,
+        reached: &[(&'a str, f64)],
+        value: impl Fn(&EdgeProfile) -> f64,
+        min_drop: f64,
+    ) -> Option<(&'a str, f64, f64)> {
+        let before = value(&self.profiles[from]);
+        let starts_here = reached.iter().any(|(e, d)| *d == 0.0 && value(&self.profiles[e]) < before);
+        if !starts_here {
+            return None;
+        }
+        let (to, distance) = reached
+            .iter()
+            .min_by(|a, b| value(&self.profiles[a.0]).total_cmp(&value(&self.profiles[b.0])).then(a.1.total_cmp(&b.1)))?;
+        let after = value(&self.profiles[to]);
+        (before - after >= min_drop).then_some((*to, after, *distance))
+    }
+
+    fn funnel(&self, kind: FunnelKind, from: &'a str, drop: (&str, f64, f64), before: f64) -> SpeedFunnel {
+        let (to, after, distance) = drop;
+        let end = self
+            .net
+            .edge(from)
+            .and_then(|e| e.lane(0).or_else(|| e.lanes.first()))
+            .and_then(|l| l.shape.last().copied())
+            .unwrap_or_default();
+        SpeedFunnel {
+            kind,
+            from_edge: from.to_string(),
+            to_edge: to.to_string(),
+            before,
+            after,
+            distance,
+            position: vec![end.1, end.0],
+        }
+    }
+}
+
+pub(crate) fn find_funnels(net: &NetModel, options: &FunnelOptions) -> Vec<SpeedFunnel> {
+    let v_class = options.v_class.as_deref().unwrap_or("passenger");
+    let min_speed_drop = options.min_speed_drop.unwrap_or(DEFAULT_MIN_SPEED_DROP);
+    let min_lane_drop = options.min_lane_drop.unwrap_or(DEFAULT_MIN_LANE_DROP).max(1);
+    let finder = Finder {
+        net,
+        profiles: net
+            .edges
+            .iter()
+            .filter(|e| !e.is_internal())
+            .filter_map(|e| Some((e.id.as_str(), profile(e, v_class)?)))
+            .collect(),
+        next: continuations(net),
+        max_distance: options.max_distance.unwrap_or(DEFAULT_MAX_DISTANCE),
+    };
+
+    let mut funnels = Vec::new();
+    for edge in &net.edges {
+        let Some(p) = finder.profiles.get(edge.id.as_str()) else { continue };
+        let reached = finder.downstream(&edge.id);
+        if let Some(drop) = finder.drop_after(&edge.id, &reached, |p| p.speed, min_speed_drop) {
+            funnels.push(finder.funnel(FunnelKind::SpeedDrop, &edge.id, drop, p.speed));
+        }
+        if let Some(drop) = finder.drop_after(&edge.id, &reached, |p| p.lanes as f64, min_lane_drop as f64) {
+            funnels.push(finder.funnel(FunnelKind::LaneDrop, &edge.id, drop, p.lanes as f64));
+        }
+    }
+    funnels.sort_by(|a, b| {
+        let lane_drop = |f: &SpeedFunnel| f.kind == FunnelKind::LaneDrop;
+        lane_drop(a).cmp(&lane_drop(b)).then((b.before - b.after).total_cmp(&(a.before - a.after)))
+    });
+    funnels
+}
+
+// Flags abrupt speed-limit drops (e.g. 80 to 30 km/h within 100 m) and lane
+// count drops along roads: speed drops first, each kind largest first
+#[wasm_bindgen(unchecked_return_type = "SpeedFunnel[]")]
+pub fn find_speed_funnels(
+    xml_text: &str,
+    #[wasm_bindgen(unchecked_param_type = "FunnelOptions | undefined")] options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let options: FunnelOptions = parse_options(options)?;
+    let doc = parse_xml(xml_text)?;
+    let net = NetModel::from_root(doc.root_element());
+    let funnels = find_funnels(&net, &options);
+
+    console_log!(
+        "Found {} speed drops and {} lane drops",
+        funnels.iter().filter(|f| f.kind == FunnelKind::SpeedDrop).count(),
+        funnels.iter().filter(|f| f.kind == FunnelKind::LaneDrop).count()
+    );
+
+    to_js(&funnels)
+}
@@ -0,0 +1,102 @@
+// Native entry points for the fuzz targets in fuzz/. Each runs the parsing
+// core behind a wasm function on arbitrary bytes, below the JsValue boundary
+// (JsValue only exists inside a wasm instance). Any panic here would abort the
+// instance in the browser, so the targets only check that none occurs.
+use crate::mapmatch::{self, MatchParams, RoadGraph};
+use crate::net::NetModel;
+use crate::network::Network;
+use crate::plain;
+use crate::simframe::SimFrameDecoder;
+use crate::stream::NetParser;
+use crate::traci::TraciDecoder;
+use crate::{deckgl, netstate, od, NetAccumulator};
+
+// parse_sumo_net_xml_with_options, then the Network handle: tiles and
+// map-matching a trace made from the input's own coordinates
+pub fn net_xml(data: &[u8]) {
+    let Ok(text) = std::str::from_utf8(data) else { return };
+    let Ok(doc) = roxmltree::Document::parse(text) else { return };
+    let mut acc = NetAccumulator::new();
+    acc.add_document(doc.root_element());
+    let geo = acc.geo.clone();
+    let mut parsed = acc.finish();
+    let trace: Vec<(f64, f64)> = parsed
+        .lanes
+        .iter()
+        .flat_map(|l| l.points.iter())
+        .map(|p| (p[1], p[0]))
+        .take(64)
+        .collect();
+
+    let mut geo_parsed = parsed.clone();
+    let _ = geo.to_wgs84(&mut geo_parsed);
+    parsed.reduce_precision(Some(2), None);
+    let _ = deckgl::pack_paths(parsed.lanes.iter(), (0.0, 0.0), "speed");
+
+    let model = NetModel::from_root(doc.root_element());
+    let roads = RoadGraph::from_model(&model);
+    let _ = plain::write_plain(&model, None);
+    let params = MatchParams { radius: 50.0, sigma: 10.0, beta: 20.0, scale: 1.0 };
+    mapmatch::match_points(&roads, &model, &trace, &vec![None; trace.len()], &params);
+
+    let network = Network::from_parsed(parsed, geo, model);
+    for (z, x, y) in [(0, 0, 0), (3, 2, 5), (12, 1000, 3000)] {
+        let _ = network.tile(z, x, y, None);
+    }
+}
+
+// NetParser fed in chunks whose sizes come from the input itself
+pub fn net_stream(data: &[u8]) {
+    let Some((&split, rest)) = data.split_first() else { return };
+    let chunk = (split as usize).max(1);
+    let mut parser = NetParser::new();
+    for piece in rest.chunks(chunk) {
+        if parser.feed_bytes(piece).is_err() {
+            return;
+        }
+    }
+    let _ = parser.finish_parsed();
+}
+
+// parse_od_matrix: VISUM text and tazRelation XML
+pub fn od_matrix(data: &[u8]) {
+    let Ok(text) = std::str::from_utf8(data) else { return };
+    let _ = od::parse_visum_matrix(text);
+    if let Ok(doc) = roxmltree::Document::parse(text) {
+        od::parse_taz_relations(doc.root_element());
+    }
+}
+
+// parse_netstate_dump and its dense per-step arrays
+pub fn netstate(data: &[u8]) {
+    let Ok(text) = std::str::from_utf8(data) else { return };
+    let Ok(doc) = roxmltree::Document::parse(text) else { return };
+    let dump = netstate::read_netstate(doc.root_element());
+    for step in 0..dump.length() {
+        let _ = dump.occupancy(step);
+        let _ = dump.speeds(step);
+    }
+}
+
+// TraciDecoder fed in chunks whose sizes come from the input itself, taking
+// every complete message after each chunk
+pub fn traci(data: &[u8]) {
+    let Some((&split, rest)) = data.split_first() else { return };
+    let chunk = (split as usize).max(1);
+    let mut decoder = TraciDecoder::new();
+    for piece in rest.chunks(chunk) {
+        decoder.feed(piece);
+        while let Ok(Some(_)) = decoder.next_message() {}
+    }
+}
+
+// One SimFrameDecoder over a stream of frames whose sizes come from the
+// input itself, so deltas apply on top of earlier frames
+pub fn simframe(data: &[u8]) {
+    let Some((&split, rest)) = data.split_first() else { return };
+    let frame = (split as usize).max(1);
+    let mut decoder = SimFrameDecoder::new();
+    for bytes in rest.chunks(frame) {
+        let _ = decoder.decode_frame(bytes);
+    }
+}
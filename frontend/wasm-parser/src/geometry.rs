@@ -0,0 +1,375 @@
+// Shared planar geometry helpers (network coordinates, meters)
+use crate::point_to_segment_distance_sq;
+
+// Mean radius (IUGG), meters
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+pub(crate) fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt()
+}
+
+pub(crate) fn polyline_length(points: &[(f64, f64)]) -> f64 {
+    points.windows(2).map(|w| distance(w[0], w[1])).sum()
+}
+
+// Great-circle meters between two lon/lat points
+pub(crate) fn haversine((lon1, lat1): (f64, f64), (lon2, lat2): (f64, f64)) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let dphi = phi2 - phi1;
+    let dlambda = (lon2 - lon1).to_radians();
+    let a = (dphi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (dlambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().asin()
+}
+
+// Meters along a lon/lat polyline
+pub(crate) fn geo_polyline_length(points: &[(f64, f64)]) -> f64 {
+    points.windows(2).map(|w| haversine(w[0], w[1])).sum()
+}
+
+// Unit vector pointing from `from` to `to`, or None for degenerate segments
+pub(crate) fn unit_direction(from: (f64, f64), to: (f64, f64)) -> Option<(f64, f64)> {
+    let len = distance(from, to);
+    if len > 0.0 {
+        Some(((to.0 - from.0) / len, (to.1 - from.1) / len))
+    } else {
+        None
+    }
+}
+
+// Sample a cubic Bézier curve into `segments + 1` points
+pub(crate) fn cubic_bezier(
+    p0: (f64, f64),
+    p1: (f64, f64),
+    p2: (f64, f64),
+    p3: (f64, f64),
+    segments: usize,
+) -> Vec<(f64, f64)> {
+    (0..=segments)
+        .map(|i| {
+            let t = i as f64 / segments as f64;
+            let u = 1.0 - t;
+            let a = u * u * u;
+            let b = 3.0 * u * u * t;
+            let c = 3.0 * u * t * t;
+            let d = t * t * t;
+            (
+                a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0,
+                a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1,
+            )
+        })
+        .collect()
+}
+
+// Compass bearing of a direction vector in degrees, clockwise from north (+y)
+pub(crate) fn compass_bearing(dx: f64, dy: f64) -> f64 {
+    let deg = dx.atan2(dy).to_degrees();
+    if deg < 0.0 {
+        deg + 360.0
+    } else {
+        deg
+    }
+}
+
+// Part of segment a-b inside the axis-aligned box (Liang-Barsky clipping)
+pub(crate) fn clip_segment(
+    a: (f64, f64),
+    b: (f64, f64),
+    min: (f64, f64),
+    max: (f64, f64),
+) -> Option<((f64, f64), (f64, f64))> {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let mut t0 = 0.0_f64;
+    let mut t1 = 1.0_f64;
+    for (p, q) in [
+        (-dx, a.0 - min.0),
+        (dx, max.0 - a.0),
+        (-dy, a.1 - min.1),
+        (dy, max.1 - a.1),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let r = q / p;
+            if p < 0.0 {
+                t0 = t0.max(r);
+            } else {
+                t1 = t1.min(r);
+            }
+            if t0 > t1 {
+                return None;
+            }
+        }
+    }
+    let at = |t: f64| (a.0 + t * dx, a.1 + t * dy);
+    Some((at(t0), at(t1)))
+}
+
+// Whether segment a-b touches the axis-aligned box
+pub(crate) fn segment_intersects_box(a: (f64, f64), b: (f64, f64), min: (f64, f64), max: (f64, f64)) -> bool {
+    clip_segment(a, b, min, max).is_some()
+}
+
+// Point at `offset` meters along a polyline, clamped to its ends
+pub(crate) fn point_at(points: &[(f64, f64)], offset: f64) -> Option<(f64, f64)> {
+    let first = *points.first()?;
+    let mut remaining = offset.max(0.0);
+    for w in points.windows(2) {
+        let len = distance(w[0], w[1]);
+        if remaining <= len && len > 0.0 {
+            let t = remaining / len;
+            return Some((w[0].0 + t * (w[1].0 - w[0].0), w[0].1 + t * (w[1].1 - w[0].1)));
+        }
+        remaining -= len;
+    }
+    Some(*points.last().unwrap_or(&first))
+}
+
+// Even-odd rule; the ring may or may not repeat its first point
+pub(crate) fn point_in_polygon(p: (f64, f64), ring: &[(f64, f64)]) -> bool {
+    let mut inside = false;
+    for (a, b) in ring.iter().zip(ring.iter().cycle().skip(1)) {
+        if (a.1 > p.1) != (b.1 > p.1) && p.0 < a.0 + (p.1 - a.1) * (b.0 - a.0) / (b.1 - a.1) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+fn segments_cross(a: (f64, f64), b: (f64, f64), c: (f64, f64), d: (f64, f64)) -> bool {
+    let orient = |p: (f64, f64), q: (f64, f64), r: (f64, f64)| (q.0 - p.0) * (r.1 - p.1) - (q.1 - p.1) * (r.0 - p.0);
+    let (d1, d2) = (orient(c, d, a), orient(c, d, b));
+    let (d3, d4) = (orient(a, b, c), orient(a, b, d));
+    d1 * d2 < 0.0 && d3 * d4 < 0.0
+}
+
+// Where segments a-b and c-d cross, as the fraction along each; None when
+// they only touch, overlap or are parallel
+pub(crate) fn segment_intersection(
+    a: (f64, f64),
+    b: (f64, f64),
+    c: (f64, f64),
+    d: (f64, f64),
+) -> Option<(f64, f64)> {
+    let cross = |p: (f64, f64), q: (f64, f64)| p.0 * q.1 - p.1 * q.0;
+    let r = (b.0 - a.0, b.1 - a.1);
+    let s = (d.0 - c.0, d.1 - c.1);
+    let denom = cross(r, s);
+    if denom == 0.0 {
+        return None;
+    }
+    let ac = (c.0 - a.0, c.1 - a.1);
+    let t = cross(ac, s) / denom;
+    let u = cross(ac, r) / denom;
+    (t > 0.0 && t < 1.0 && u > 0.0 && u < 1.0).then_some((t, u))
+}
+
+// Shortest distance between two polylines (a single point counts as one)
+pub(crate) fn polyline_distance(a: &[(f64, f64)], b: &[(f64, f64)]) -> f64 {
+    let segments = |pts: &[(f64, f64)]| -> Vec<((f64, f64), (f64, f64))> {
+        match pts.len() {
+            0 => Vec::new(),
+            1 => vec![(pts[0], pts[0])],
+            _ => pts.windows(2).map(|w| (w[0], w[1])).collect(),
+        }
+    };
+    let (sa, sb) = (segments(a), segments(b));
+    let mut best = f64::INFINITY;
+    for &(p, q) in &sa {
+        for &(r, s) in &sb {
+            if segments_cross(p, q, r, s) {
+                return 0.0;
+            }
+            best = best
+                .min(point_to_segment_distance_sq(p, r, s))
+                .min(point_to_segment_distance_sq(q, r, s))
+                .min(point_to_segment_distance_sq(r, p, q))
+                .min(point_to_segment_distance_sq(s, p, q));
+        }
+    }
+    best.sqrt()
+}
+
+// Closest point of a polyline to `p`, as (offset along the line, distance)
+pub(crate) fn project_onto(points: &[(f64, f64)], p: (f64, f64)) -> Option<(f64, f64)> {
+    if points.len() == 1 {
+        return Some((0.0, distance(points[0], p)));
+    }
+    let mut best: Option<(f64, f64)> = None;
+    let mut walked = 0.0;
+    for w in points.windows(2) {
+        let len = distance(w[0], w[1]);
+        let t = if len > 0.0 {
+            (((p.0 - w[0].0) * (w[1].0 - w[0].0) + (p.1 - w[0].1) * (w[1].1 - w[0].1)) / (len * len)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let foot = (w[0].0 + t * (w[1].0 - w[0].0), w[0].1 + t * (w[1].1 - w[0].1));
+        let d = distance(foot, p);
+        if best.is_none_or(|(_, bd)| d < bd) {
+            best = Some((walked + t * len, d));
+        }
+        walked += len;
+    }
+    best
+}
+
+// Counter-clockwise convex hull (Andrew's monotone chain), without repeating
+// the first point
+pub(crate) fn convex_hull(points: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    sorted.dedup();
+    if sorted.len() < 3 {
+        return sorted;
+    }
+    let turn = |o: (f64, f64), a: (f64, f64), b: (f64, f64)| (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0);
+    let mut hull: Vec<(f64, f64)> = Vec::with_capacity(sorted.len() + 1);
+    for pass in [&sorted[..], &sorted.iter().rev().copied().collect::<Vec<_>>()[..]] {
+        let floor = hull.len();
+        for &p in pass {
+            while hull.len() >= floor + 2 && turn(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0 {
+                hull.pop();
+            }
+            hull.push(p);
+        }
+        // The last point of each chain starts the next one
+        hull.pop();
+    }
+    hull
+}
+
+// Parallel polyline `d` meters to the left of the direction of travel
+// (negative: to the right), with miter joins limited to twice the offset
+pub(crate) fn offset_polyline(points: &[(f64, f64)], d: f64) -> Vec<(f64, f64)> {
+    let normals: Vec<Option<(f64, f64)>> = points
+        .windows(2)
+        .map(|w| unit_direction(w[0], w[1]).map(|(dx, dy)| (-dy, dx)))
+        .collect();
+    points
+        .iter()
+        .enumerate()
+        .map(|(i, &p)| {
+            let before = i.checked_sub(1).and_then(|j| normals[j]);
+            let after = normals.get(i).copied().flatten();
+            let n = match (before, after) {
+                (Some(a), Some(b)) => {
+                    let sum = (a.0 + b.0, a.1 + b.1);
+                    let len = (sum.0 * sum.0 + sum.1 * sum.1).sqrt();
+                    // cos of half the turn; near-reversals fall back to one side
+                    let cos = if len > 1e-9 { len / 2.0 } else { 1.0 };
+                    let m = if len > 1e-9 { (sum.0 / len, sum.1 / len) } else { a };
+                    let scale = (1.0 / cos).min(2.0);
+                    (m.0 * scale, m.1 * scale)
+                }
+                (Some(n), None) | (None, Some(n)) => n,
+                (None, None) => (0.0, 0.0),
+            };
+            (p.0 + n.0 * d, p.1 + n.1 * d)
+        })
+        .collect()
+}
+
+fn in_triangle(p: (f64, f64), a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> bool {
+    let cross = |o: (f64, f64), u: (f64, f64), v: (f64, f64)| (u.0 - o.0) * (v.1 - o.1) - (u.1 - o.1) * (v.0 - o.0);
+    cross(a, b, p) >= 0.0 && cross(b, c, p) >= 0.0 && cross(c, a, p) >= 0.0
+}
+
+// Triangles covering a simple polygon by ear clipping (as earcut does without
+// holes), as indices into `ring`, counter-clockwise. The ring may repeat its
+// first point. Self-intersecting rings still yield triangles, some of them
+// degenerate or outside.
+pub(crate) fn triangulate(ring: &[(f64, f64)]) -> Vec<[usize; 3]> {
+    let n = if ring.len() > 1 && ring.first() == ring.last() { ring.len() - 1 } else { ring.len() };
+    if n < 3 {
+        return Vec::new();
+    }
+    let area: f64 = (0..n)
+        .map(|i| {
+            let (a, b) = (ring[i], ring[(i + 1) % n]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum();
+    let mut remaining: Vec<usize> = if area >= 0.0 { (0..n).collect() } else { (0..n).rev().collect() };
+
+    let mut triangles = Vec::with_capacity(n - 2);
+    let mut i = 0;
+    let mut misses = 0;
+    while remaining.len() > 3 {
+        let m = remaining.len();
+        let (a, b, c) = (remaining[(i + m - 1) % m], remaining[i % m], remaining[(i + 1) % m]);
+        let (pa, pb, pc) = (ring[a], ring[b], ring[c]);
+        let convex = (pb.0 - pa.0) * (pc.1 - pa.1) - (pb.1 - pa.1) * (pc.0 - pa.0) > 0.0;
+        let ear = convex
+            && remaining
+                .iter()
+                .map(|&k| ring[k])
+                .filter(|p| *p != pa && *p != pb && *p != pc)
+                .all(|p| !in_triangle(p, pa, pb, pc));
+        // After a full round without an ear the rest is degenerate; cut anyway
+        if ear || misses >= m {
+            triangles.push([a, b, c]);
+            remaining.remove(i % m);
+            misses = 0;
+        } else {
+            i += 1;
+            misses += 1;
+        }
+        i %= remaining.len();
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
+
+// Chaikin corner cutting, `iterations` times: every segment keeps the points at
+// 1/4 and 3/4 of its length. Points may have any number of coordinates; the
+// end points stay.
+pub(crate) fn chaikin(points: &[Vec<f64>], iterations: u32) -> Vec<Vec<f64>> {
+    let mut current = points.to_vec();
+    for _ in 0..iterations {
+        if current.len() < 3 {
+            break;
+        }
+        let mix = |a: &[f64], b: &[f64], t: f64| a.iter().zip(b).map(|(a, b)| a + (b - a) * t).collect::<Vec<f64>>();
+        let mut next = Vec::with_capacity(current.len() * 2);
+        next.push(current[0].clone());
+        for w in current.windows(2) {
+            next.push(mix(&w[0], &w[1], 0.25));
+            next.push(mix(&w[0], &w[1], 0.75));
+        }
+        next.push(current[current.len() - 1].clone());
+        current = next;
+    }
+    current
+}
+
+// Positions and compass bearings along a polyline, about `spacing` apart and
+// centered on it (a line shorter than `spacing` gets one sample, halfway)
+pub(crate) fn heading_samples(points: &[(f64, f64)], spacing: f64) -> Vec<((f64, f64), f64)> {
+    let length = polyline_length(points);
+    if !(length > 0.0 && spacing > 0.0) {
+        return Vec::new();
+    }
+    let count = ((length / spacing).floor() as usize).max(1);
+    let step = length / count as f64;
+    let mut samples = Vec::with_capacity(count);
+    let mut start = 0.0;
+    let mut segments = points.windows(2).filter(|w| distance(w[0], w[1]) > 0.0).peekable();
+    for i in 0..count {
+        let offset = (i as f64 + 0.5) * step;
+        while let Some(w) = segments.peek() {
+            let len = distance(w[0], w[1]);
+            if offset <= start + len {
+                break;
+            }
+            start += len;
+            segments.next();
+        }
+        let Some(w) = segments.peek() else { break };
+        let t = ((offset - start) / distance(w[0], w[1])).min(1.0);
+        let (dx, dy) = (w[1].0 - w[0].0, w[1].1 - w[0].1);
+        samples.push(((w[0].0 + t * dx, w[0].1 + t * dy), compass_bearing(dx, dy)));
+    }
+    samples
+}
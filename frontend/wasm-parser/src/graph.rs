@@ -0,0 +1,177 @@
+// Shared pieces of the shortest-path searches
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::net::NetModel;
+
+// Min-heap entry for Dijkstra: (distance, node)
+pub(crate) struct Queued(pub f64, pub usize);
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.total_cmp(&self.0)
+    }
+}
+
+// Directed graph of the normal edges usable by a vehicle class, with
+// free-flow travel times, for whole-network shortest-path analyses
+pub(crate) struct EdgeGraph<'a> {
+    pub ids: Vec<&'a str>,
+    // Free-flow seconds to traverse each edge
+    pub costs: Vec<f64>,
+    pub successors: Vec<Vec<usize>>,
+    pub index: HashMap<&'a str, usize>,
+}
+
+impl<'a> EdgeGraph<'a> {
+    pub fn new(net: &'a NetModel, v_class: &str) -> EdgeGraph<'a> {
+        let edges: Vec<_> = net
+            .edges
+            .iter()
+            .filter(|e| !e.is_internal() && e.lanes.iter().any(|l| l.permits(v_class)))
+            .collect();
+        let index: HashMap<&str, usize> = edges.iter().enumerate().map(|(i, e)| (e.id.as_str(), i)).collect();
+        let mut successors = vec![Vec::new(); edges.len()];
+        for c in &net.connections {
+            if let (Some(&from), Some(&to)) = (index.get(c.from.as_str()), index.get(c.to.as_str())) {
+                if !successors[from].contains(&to) {
+                    successors[from].push(to);
+                }
+            }
+        }
+        let costs = edges.iter().map(|e| e.free_flow_time(Some(v_class))).collect();
+        EdgeGraph {
+            ids: edges.iter().map(|e| e.id.as_str()).collect(),
+            costs,
+            successors,
+            index,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    // Make edges unreachable, as if closed to traffic
+    pub fn close(&mut self, closed: &[usize]) {
+        for successors in &mut self.successors {
+            successors.retain(|s| !closed.contains(s));
+        }
+    }
+
+    // Dijkstra from `sources` (each entered at its own cost). Returns the
+    // settle order, distances, path counts and all tied predecessors.
+    pub fn search(&self, sources: &[usize]) -> Search {
+        let n = self.len();
+        let mut search = Search {
+            order: Vec::new(),
+            dist: vec![f64::INFINITY; n],
+            sigma: vec![0.0; n],
+            preds: vec![Vec::new(); n],
+        };
+        let mut heap = BinaryHeap::new();
+        for &s in sources {
+            search.dist[s] = self.costs[s];
+            search.sigma[s] = 1.0;
+            heap.push(Queued(self.costs[s], s));
+        }
+        let mut settled = vec![false; n];
+        while let Some(Queued(d, u)) = heap.pop() {
+            if settled[u] {
+                continue;
+            }
+            settled[u] = true;
+            search.order.push(u);
+            for &v in self.successors[u].iter().filter(|v| !settled[**v]) {
+                let nd = d + self.costs[v];
+                let tie = (nd - search.dist[v]).abs() <= 1e-9 * nd.max(1.0);
+                if tie {
+                    search.sigma[v] += search.sigma[u];
+                    search.preds[v].push(u);
+                } else if nd < search.dist[v] {
+                    search.dist[v] = nd;
+                    search.sigma[v] = search.sigma[u];
+                    search.preds[v] = vec![u];
+                    heap.push(Queued(nd, v));
+                }
+            }
+        }
+        search
+    }
+}
+
+pub(crate) struct Search {
+    pub order: Vec<usize>,
+    pub dist: Vec<f64>,
+    pub sigma: Vec<f64>,
+    pub preds: Vec<Vec<usize>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a -> b -> d and a -> c -> d, 100 m each at 10 m/s; e is unconnected
+    const NET: &str = r#"<net>
+        <edge id="a" from="j0" to="j1"><lane id="a_0" index="0" speed="10" shape="0,0 100,0"/></edge>
+        <edge id="b" from="j1" to="j2"><lane id="b_0" index="0" speed="10" shape="100,0 100,100"/></edge>
+        <edge id="c" from="j1" to="j3"><lane id="c_0" index="0" speed="10" shape="100,0 200,0"/></edge>
+        <edge id="d" from="j2" to="j4"><lane id="d_0" index="0" speed="10" shape="200,100 300,100"/></edge>
+        <edge id="e" from="j5" to="j6"><lane id="e_0" index="0" speed="10" allow="tram" shape="0,50 100,50"/></edge>
+        <connection from="a" to="b" fromLane="0" toLane="0"/>
+        <connection from="a" to="c" fromLane="0" toLane="0"/>
+        <connection from="b" to="d" fromLane="0" toLane="0"/>
+        <connection from="c" to="d" fromLane="0" toLane="0"/>
+    </net>"#;
+
+    fn with_graph(v_class: &str, f: impl FnOnce(&mut EdgeGraph)) {
+        let doc = roxmltree::Document::parse(NET).unwrap();
+        let net = NetModel::from_root(doc.root_element());
+        f(&mut EdgeGraph::new(&net, v_class));
+    }
+
+    #[test]
+    fn graph_keeps_edges_the_class_may_use() {
+        // Lanes without allow / disallow admit every class
+        with_graph("passenger", |g| assert_eq!(g.ids, ["a", "b", "c", "d"]));
+        with_graph("tram", |g| assert_eq!(g.ids, ["a", "b", "c", "d", "e"]));
+    }
+
+    #[test]
+    fn search_counts_tied_shortest_paths() {
+        with_graph("passenger", |g| {
+            let search = g.search(&[g.index["a"]]);
+            let d = g.index["d"];
+            // Source cost included: four edges of 10 s on either path
+            assert_eq!(search.dist[d], 30.0);
+            assert_eq!(search.sigma[d], 2.0);
+            assert_eq!(search.preds[d].len(), 2);
+            assert_eq!(search.order.first(), Some(&g.index["a"]));
+            assert_eq!(search.order.last(), Some(&d));
+        });
+    }
+
+    #[test]
+    fn closed_edges_are_unreachable() {
+        with_graph("passenger", |g| {
+            g.close(&[g.index["b"]]);
+            let search = g.search(&[g.index["a"]]);
+            assert!(search.dist[g.index["b"]].is_infinite());
+            assert_eq!(search.sigma[g.index["d"]], 1.0);
+        });
+    }
+}
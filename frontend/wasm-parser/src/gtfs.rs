@@ -0,0 +1,361 @@
+// GTFS static feeds (stops.txt, shapes.txt, trips.txt) for overlaying the
+// official transit network on the simulated one: stops and shapes projected
+// into the network's frame, and shapes map-matched onto its edges.
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::mapmatch::TraceMatchOptions;
+use crate::network::Network;
+use crate::projection::Crs;
+use crate::{parse_options, to_js};
+
+// A CSV file with a header row. Fields may be quoted, with "" for a quote;
+// quoted fields may span lines.
+struct CsvTable {
+    columns: HashMap<String, usize>,
+    rows: Vec<Vec<String>>,
+}
+
+impl CsvTable {
+    fn parse(text: &str) -> CsvTable {
+        let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+        let mut records = Vec::new();
+        let mut record = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted => {
+                    if chars.peek() == Some(&'"') {
+                        chars.next();
+                        field.push('"');
+                    } else {
+                        quoted = false;
+                    }
+                }
+                '"' if field.is_empty() => quoted = true,
+                ',' if !quoted => record.push(std::mem::take(&mut field)),
+                '\r' if !quoted => {}
+                '\n' if !quoted => {
+                    record.push(std::mem::take(&mut field));
+                    records.push(std::mem::take(&mut record));
+                }
+                _ => field.push(c),
+            }
+        }
+        if !field.is_empty() || !record.is_empty() {
+            record.push(field);
+            records.push(record);
+        }
+        // Blank lines
+        records.retain(|r| !(r.len() == 1 && r[0].is_empty()));
+
+        let mut records = records.into_iter();
+        let columns = records
+            .next()
+            .unwrap_or_default()
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| (name.trim().to_string(), i))
+            .collect();
+        CsvTable {
+            columns,
+            rows: records.collect(),
+        }
+    }
+
+    fn has(&self, column: &str) -> bool {
+        self.columns.contains_key(column)
+    }
+
+    // Trimmed value, None when missing or empty
+    fn get<'a>(&self, row: &'a [String], column: &str) -> Option<&'a str> {
+        let value = row.get(*self.columns.get(column)?)?.trim();
+        (!value.is_empty()).then_some(value)
+    }
+
+    fn get_f64(&self, row: &[String], column: &str) -> Option<f64> {
+        self.get(row, column)?.parse().ok()
+    }
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct GtfsStop {
+    pub id: String,
+    pub name: Option<String>,
+    pub lat: f64,
+    pub lng: f64,
+    // 0 stop or platform, 1 station, 2 entrance, 3 generic node, 4 boarding
+    // area
+    #[serde(rename = "locationType")]
+    pub location_type: u8,
+    #[serde(rename = "parentStation")]
+    pub parent_station: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct GtfsTrip {
+    pub id: String,
+    #[serde(rename = "routeId")]
+    pub route_id: String,
+    #[serde(rename = "serviceId")]
+    pub service_id: String,
+    #[serde(rename = "shapeId")]
+    pub shape_id: Option<String>,
+    pub headsign: Option<String>,
+    #[serde(rename = "directionId")]
+    pub direction_id: Option<u8>,
+}
+
+// Stop projected into the network: [lat, lng] in the network's render frame
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct ProjectedStop {
+    pub id: String,
+    pub name: Option<String>,
+    pub position: Vec<f64>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct ProjectedShape {
+    pub id: String,
+    // GTFS routes with a trip along this shape
+    pub routes: Vec<String>,
+    // [lat, lng] in the network's render frame
+    pub points: Vec<Vec<f64>>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct ShapeMatch {
+    #[serde(rename = "shapeId")]
+    pub shape_id: String,
+    pub routes: Vec<String>,
+    // Network edges the shape follows, in order
+    pub edges: Vec<String>,
+    // Shape points with no edge within the search radius
+    pub unmatched: usize,
+    #[serde(rename = "pointCount")]
+    pub point_count: usize,
+}
+
+#[wasm_bindgen]
+pub struct GtfsFeed {
+    stops: Vec<GtfsStop>,
+    // Shape id -> lng, lat points in sequence order
+    shapes: BTreeMap<String, Vec<(f64, f64)>>,
+    trips: Vec<GtfsTrip>,
+}
+
+fn read_stops(table: &CsvTable) -> Vec<GtfsStop> {
+    table
+        .rows
+        .iter()
+        .filter_map(|row| {
+            Some(GtfsStop {
+                id: table.get(row, "stop_id")?.to_string(),
+                name: table.get(row, "stop_name").map(String::from),
+                // Entrances and generic nodes may lack a position
+                lat: table.get_f64(row, "stop_lat")?,
+                lng: table.get_f64(row, "stop_lon")?,
+                location_type: table.get(row, "location_type").and_then(|t| t.parse().ok()).unwrap_or(0),
+                parent_station: table.get(row, "parent_station").map(String::from),
+            })
+        })
+        .collect()
+}
+
+fn read_shapes(table: &CsvTable) -> BTreeMap<String, Vec<(f64, f64)>> {
+    let mut points: BTreeMap<String, Vec<(f64, f64, f64)>> = BTreeMap::new();
+    for row in &table.rows {
+        let (Some(id), Some(lat), Some(lng), Some(sequence)) = (
+            table.get(row, "shape_id"),
+            table.get_f64(row, "shape_pt_lat"),
+            table.get_f64(row, "shape_pt_lon"),
+            table.get_f64(row, "shape_pt_sequence"),
+        ) else {
+            continue;
+        };
+        points.entry(id.to_string()).or_default().push((sequence, lng, lat));
+    }
+    points
+        .into_iter()
+        .map(|(id, mut pts)| {
+            pts.sort_by(|a, b| a.0.total_cmp(&b.0));
+            (id, pts.into_iter().map(|(_, lng, lat)| (lng, lat)).collect())
+        })
+        .collect()
+}
+
+fn read_trips(table: &CsvTable) -> Vec<GtfsTrip> {
+    table
+        .rows
+        .iter()
+        .filter_map(|row| {
+            Some(GtfsTrip {
+                id: table.get(row, "trip_id")?.to_string(),
+                route_id: table.get(row, "route_id")?.to_string(),
+                service_id: table.get(row, "service_id").unwrap_or("").to_string(),
+                shape_id: table.get(row, "shape_id").map(String::from),
+                headsign: table.get(row, "trip_headsign").map(String::from),
+                direction_id: table.get(row, "direction_id").and_then(|d| d.parse().ok()),
+            })
+        })
+        .collect()
+}
+
+fn require_columns(table: &CsvTable, file: &str, columns: &[&str]) -> Result<(), JsValue> {
+    match columns.iter().find(|c| !table.has(c)) {
+        Some(missing) => Err(JsValue::from_str(&format!("{} has no {} column", file, missing))),
+        None => Ok(()),
+    }
+}
+
+impl GtfsFeed {
+    fn from_tables(stops: &CsvTable, shapes: Option<&CsvTable>, trips: Option<&CsvTable>) -> GtfsFeed {
+        GtfsFeed {
+            stops: read_stops(stops),
+            shapes: shapes.map(read_shapes).unwrap_or_default(),
+            trips: trips.map(read_trips).unwrap_or_default(),
+        }
+    }
+
+    // Route ids per shape id
+    fn shape_routes(&self) -> HashMap<&str, BTreeSet<&str>> {
+        let mut routes: HashMap<&str, BTreeSet<&str>> = HashMap::new();
+        for trip in &self.trips {
+            if let Some(shape) = &trip.shape_id {
+                routes.entry(shape).or_default().insert(&trip.route_id);
+            }
+        }
+        routes
+    }
+
+    fn routes_of(routes: &HashMap<&str, BTreeSet<&str>>, shape: &str) -> Vec<String> {
+        routes.get(shape).into_iter().flatten().map(|r| r.to_string()).collect()
+    }
+}
+
+#[wasm_bindgen]
+impl GtfsFeed {
+    // Contents of the feed's stops.txt and, optionally, shapes.txt and
+    // trips.txt
+    #[wasm_bindgen(constructor)]
+    pub fn new(stops_txt: &str, shapes_txt: Option<String>, trips_txt: Option<String>) -> Result<GtfsFeed, JsValue> {
+        let stops = CsvTable::parse(stops_txt);
+        require_columns(&stops, "stops.txt", &["stop_id", "stop_lat", "stop_lon"])?;
+        let shapes = shapes_txt.as_deref().map(CsvTable::parse);
+        if let Some(shapes) = &shapes {
+            require_columns(shapes, "shapes.txt", &["shape_id", "shape_pt_lat", "shape_pt_lon", "shape_pt_sequence"])?;
+        }
+        let trips = trips_txt.as_deref().map(CsvTable::parse);
+        if let Some(trips) = &trips {
+            require_columns(trips, "trips.txt", &["trip_id", "route_id"])?;
+        }
+        let feed = GtfsFeed::from_tables(&stops, shapes.as_ref(), trips.as_ref());
+
+        console_log!(
+            "Parsed GTFS feed: {} stops, {} shapes, {} trips",
+            feed.stops.len(),
+            feed.shapes.len(),
+            feed.trips.len()
+        );
+
+        Ok(feed)
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "GtfsStop[]")]
+    pub fn stops(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.stops)
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "GtfsTrip[]")]
+    pub fn trips(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.trips)
+    }
+
+    // Stops in the frame of the network's render output; fails for networks
+    // without a geo reference
+    #[wasm_bindgen(unchecked_return_type = "ProjectedStop[]")]
+    pub fn project_stops(&self, network: &Network) -> Result<JsValue, JsValue> {
+        let xy = network.to_network_xy(self.stops.iter().map(|s| (s.lng, s.lat)).collect(), Crs::Wgs84)?;
+        let stops: Vec<ProjectedStop> = self
+            .stops
+            .iter()
+            .zip(xy)
+            .map(|(s, (x, y))| ProjectedStop {
+                id: s.id.clone(),
+                name: s.name.clone(),
+                position: vec![y, x],
+            })
+            .collect();
+        to_js(&stops)
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "ProjectedShape[]")]
+    pub fn project_shapes(&self, network: &Network) -> Result<JsValue, JsValue> {
+        let routes = self.shape_routes();
+        let mut shapes = Vec::with_capacity(self.shapes.len());
+        for (id, points) in &self.shapes {
+            let xy = network.to_network_xy(points.clone(), Crs::Wgs84)?;
+            shapes.push(ProjectedShape {
+                id: id.clone(),
+                routes: GtfsFeed::routes_of(&routes, id),
+                points: xy.into_iter().map(|(x, y)| vec![y, x]).collect(),
+            });
+        }
+        to_js(&shapes)
+    }
+
+    // Every shape map-matched onto the network's edges (see
+    // Network.match_trace); `crs` in the options is ignored
+    #[wasm_bindgen(unchecked_return_type = "ShapeMatch[]")]
+    pub fn match_shapes(
+        &self,
+        network: &Network,
+        #[wasm_bindgen(unchecked_param_type = "TraceMatchOptions | undefined")] options: JsValue,
+    ) -> Result<JsValue, JsValue> {
+        let options: TraceMatchOptions = parse_options(options)?;
+        let routes = self.shape_routes();
+        let mut matches = Vec::with_capacity(self.shapes.len());
+        for (id, points) in &self.shapes {
+            let xy = network.to_network_xy(points.clone(), Crs::Wgs84)?;
+            let matched = network.match_xy(&xy, &vec![None; xy.len()], &options);
+            matches.push(ShapeMatch {
+                shape_id: id.clone(),
+                routes: GtfsFeed::routes_of(&routes, id),
+                edges: matched.edges,
+                unmatched: matched.points.iter().filter(|p| p.edge_id.is_none()).count(),
+                point_count: xy.len(),
+            });
+        }
+
+        console_log!("Matched {} GTFS shapes", matches.len());
+
+        to_js(&matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_handles_quotes_bom_and_line_endings() {
+        let table = CsvTable::parse("\u{feff}stop_id, stop_name ,stop_lat\r\n1,\"Meskel, Square\",9.01\r\n\r\n2,\"Say \"\"hi\"\"\n again\",\n");
+        assert!(table.has("stop_name"));
+        assert_eq!(table.rows.len(), 2);
+        assert_eq!(table.get(&table.rows[0], "stop_name"), Some("Meskel, Square"));
+        assert_eq!(table.get_f64(&table.rows[0], "stop_lat"), Some(9.01));
+        assert_eq!(table.get(&table.rows[1], "stop_name"), Some("Say \"hi\"\n again"));
+        assert_eq!(table.get(&table.rows[1], "stop_lat"), None);
+        assert_eq!(table.get(&table.rows[1], "missing"), None);
+    }
+
+    #[test]
+    fn csv_without_trailing_newline_keeps_the_last_row() {
+        let table = CsvTable::parse("a,b\n1,2");
+        assert_eq!(table.rows, [["1", "2"]]);
+    }
+}
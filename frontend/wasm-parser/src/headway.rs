@@ -0,0 +1,298 @@
+// Transit performance: scheduled stop times of the PT lines (stops with
+// `arrival` / `until` in the route file) joined with the times vehicles
+// actually served them in the stop output (<stopinfo started ended>).
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::scenario::{flow_count, flow_duration};
+use crate::stats::Stats;
+use crate::{attr_f64, parse_xml, to_js};
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct StopAdherence {
+    pub line: String,
+    #[serde(rename = "busStop")]
+    pub bus_stop: String,
+    // Vehicles of the line seen at the stop
+    pub served: usize,
+    // Seconds between consecutive actual arrivals, and as scheduled
+    pub headway: Stats,
+    #[serde(rename = "scheduledHeadway")]
+    pub scheduled_headway: Stats,
+    // Actual minus scheduled time (seconds, positive = late)
+    pub deviation: Stats,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct LineAdherence {
+    pub line: String,
+    // Over all stops of the line
+    pub headway: Stats,
+    pub deviation: Stats,
+    pub stops: Vec<StopAdherence>,
+}
+
+// One scheduled call of a vehicle at a stop. Deviation compares `until`
+// against the departure when given, otherwise `arrival` against the arrival.
+#[derive(Clone)]
+struct ScheduledCall {
+    line: String,
+    time: f64,
+    is_departure: bool,
+}
+
+fn read_calls(node: roxmltree::Node, line: &str) -> Calls {
+    node.children()
+        .filter(|n| n.tag_name().name() == "stop")
+        .filter_map(|s| {
+            let stop = s.attribute("busStop").or(s.attribute("trainStop"))?;
+            let (time, is_departure) = match (attr_f64(s, "until"), attr_f64(s, "arrival")) {
+                (Some(until), _) => (until, true),
+                (None, Some(arrival)) => (arrival, false),
+                (None, None) => return None,
+            };
+            Some((
+                stop.to_string(),
+                ScheduledCall {
+                    line: line.to_string(),
+                    time,
+                    is_departure,
+                },
+            ))
+        })
+        .collect()
+}
+
+type Calls = Vec<(String, ScheduledCall)>;
+
+// The calls of a flow's first vehicle; vehicle n calls `n * period` later
+struct FlowSchedule {
+    calls: Calls,
+    count: u64,
+    period: f64,
+}
+
+// Scheduled calls per vehicle id, in route order; stops of a referenced
+// route come before the vehicle's own
+struct Schedule {
+    vehicles: HashMap<String, Calls>,
+    flows: HashMap<String, FlowSchedule>,
+}
+
+impl Schedule {
+    // Flow vehicles ("<flow id>.<n>") are expanded on lookup, each shifted by
+    // its departure offset as SUMO does, so only vehicles that show up in the
+    // stop output are ever materialized
+    fn calls(&self, vehicle: &str) -> Option<Calls> {
+        if let Some(calls) = self.vehicles.get(vehicle) {
+            return Some(calls.clone());
+        }
+        let (flow, n) = vehicle.rsplit_once('.')?;
+        let flow = self.flows.get(flow)?;
+        let n: u64 = n.parse().ok().filter(|n| *n < flow.count)?;
+        let shift = n as f64 * flow.period;
+        Some(
+            flow.calls
+                .iter()
+                .map(|(stop, call)| (stop.clone(), ScheduledCall { time: call.time + shift, ..call.clone() }))
+                .collect(),
+        )
+    }
+}
+
+fn read_schedule(root: roxmltree::Node) -> Schedule {
+    let routes: HashMap<&str, roxmltree::Node> = root
+        .children()
+        .filter(|n| n.tag_name().name() == "route")
+        .filter_map(|r| Some((r.attribute("id")?, r)))
+        .collect();
+
+    let mut schedule = Schedule {
+        vehicles: HashMap::new(),
+        flows: HashMap::new(),
+    };
+    for node in root.children().filter(|n| matches!(n.tag_name().name(), "vehicle" | "trip" | "flow")) {
+        let (Some(id), Some(line)) = (node.attribute("id"), node.attribute("line")) else {
+            continue;
+        };
+        let route = node
+            .children()
+            .find(|n| n.tag_name().name() == "route")
+            .or_else(|| routes.get(node.attribute("route")?).copied());
+        let mut calls = route.map(|r| read_calls(r, line)).unwrap_or_default();
+        calls.extend(read_calls(node, line));
+
+        if node.tag_name().name() == "flow" {
+            let count = flow_count(node);
+            let period = attr_f64(node, "period").unwrap_or(flow_duration(node) / count.max(1) as f64);
+            schedule.flows.insert(id.to_string(), FlowSchedule { calls, count, period });
+        } else {
+            schedule.vehicles.insert(id.to_string(), calls);
+        }
+    }
+    schedule
+}
+
+#[derive(Default)]
+struct StopTimes {
+    arrivals: Vec<f64>,
+    scheduled: Vec<f64>,
+    deviations: Vec<f64>,
+}
+
+// Per-stop results of a line, with the samples its totals pool
+#[derive(Default)]
+struct LineTimes {
+    stops: Vec<StopAdherence>,
+    headways: Vec<f64>,
+    deviations: Vec<f64>,
+}
+
+pub(crate) fn headways(times: &mut [f64]) -> Vec<f64> {
+    times.sort_by(f64::total_cmp);
+    times.windows(2).map(|w| w[1] - w[0]).collect()
+}
+
+pub(crate) fn schedule_adherence(routes: roxmltree::Node, stop_output: roxmltree::Node) -> Vec<LineAdherence> {
+    let schedule = read_schedule(routes);
+    // Calls are matched in order, so a line serving a stop twice (loops)
+    // pairs each visit with its own scheduled time
+    let mut seen: HashMap<&str, (Calls, usize)> = HashMap::new();
+    let mut by_stop: BTreeMap<(String, String), StopTimes> = BTreeMap::new();
+
+    for info in stop_output.children().filter(|n| n.tag_name().name() == "stopinfo") {
+        let (Some(vehicle), Some(stop)) = (info.attribute("id"), info.attribute("busStop").or(info.attribute("trainStop")))
+        else {
+            continue;
+        };
+        let (calls, cursor) = match seen.entry(vehicle) {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => {
+                let Some(calls) = schedule.calls(vehicle) else { continue };
+                e.insert((calls, 0))
+            }
+        };
+        let Some(offset) = calls[*cursor..].iter().position(|(s, _)| s == stop) else {
+            continue;
+        };
+        *cursor += offset + 1;
+        let call = &calls[*cursor - 1].1;
+
+        let times = by_stop.entry((call.line.clone(), stop.to_string())).or_default();
+        let (started, ended) = (attr_f64(info, "started"), attr_f64(info, "ended"));
+        if let Some(t) = started {
+            times.arrivals.push(t);
+        }
+        times.scheduled.push(call.time);
+        if let Some(actual) = if call.is_departure { ended } else { started } {
+            times.deviations.push(actual - call.time);
+        }
+    }
+
+    let mut lines: BTreeMap<String, LineTimes> = BTreeMap::new();
+    for ((line, bus_stop), mut times) in by_stop {
+        let headway = headways(&mut times.arrivals);
+        let pooled = lines.entry(line.clone()).or_default();
+        pooled.headways.extend_from_slice(&headway);
+        pooled.deviations.extend_from_slice(&times.deviations);
+        pooled.stops.push(StopAdherence {
+            line,
+            bus_stop,
+            served: times.arrivals.len(),
+            headway: Stats::from_values(&headway),
+            scheduled_headway: Stats::from_values(&headways(&mut times.scheduled)),
+            deviation: Stats::from_values(&times.deviations),
+        });
+    }
+
+    lines
+        .into_iter()
+        .map(|(line, pooled)| LineAdherence {
+            line,
+            headway: Stats::from_values(&pooled.headways),
+            deviation: Stats::from_values(&pooled.deviations),
+            stops: pooled.stops,
+        })
+        .collect()
+}
+
+// Headway distribution and schedule deviation per line and stop. `routes_xml`
+// holds the line vehicles with scheduled stops (arrival / until), `stop_output`
+// the simulation's --stop-output. Only vehicles with a `line` are considered.
+#[wasm_bindgen(unchecked_return_type = "LineAdherence[]")]
+pub fn analyze_schedule_adherence(routes_xml: &str, stop_output: &str) -> Result<JsValue, JsValue> {
+    let routes = parse_xml(routes_xml)?;
+    let stops = parse_xml(stop_output)?;
+    let lines = schedule_adherence(routes.root_element(), stops.root_element());
+
+    console_log!(
+        "Schedule adherence for {} lines at {} stops",
+        lines.len(),
+        lines.iter().map(|l| l.stops.len()).sum::<usize>()
+    );
+
+    to_js(&lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adherence(routes: &str, stops: &str) -> Vec<LineAdherence> {
+        let routes = roxmltree::Document::parse(routes).unwrap();
+        let stops = roxmltree::Document::parse(stops).unwrap();
+        schedule_adherence(routes.root_element(), stops.root_element())
+    }
+
+    const ROUTES: &str = r#"<routes>
+        <route id="r" edges="a b"><stop busStop="s1" until="100"/><stop busStop="s2" arrival="200"/></route>
+        <flow id="bus" line="4" route="r" begin="0" end="3600" period="600"/>
+        <vehicle id="car" route="r" depart="0"/>
+    </routes>"#;
+
+    #[test]
+    fn flow_vehicles_are_shifted_by_their_departure() {
+        let lines = adherence(
+            ROUTES,
+            r#"<stops>
+                <stopinfo id="bus.0" busStop="s1" started="95" ended="110"/>
+                <stopinfo id="bus.2" busStop="s1" started="1290" ended="1300"/>
+                <stopinfo id="bus.2" busStop="s2" started="1410" ended="1420"/>
+                <stopinfo id="car" busStop="s1" started="1" ended="2"/>
+            </stops>"#,
+        );
+        assert_eq!(lines.len(), 1);
+        let s1 = &lines[0].stops[0];
+        assert_eq!((s1.line.as_str(), s1.bus_stop.as_str(), s1.served), ("4", "s1", 2));
+        // `until` compares against the departure, `arrival` against the arrival
+        assert_eq!(s1.headway.mean, 1195.0);
+        assert_eq!(s1.scheduled_headway.mean, 1200.0);
+        assert_eq!(s1.deviation.mean, 5.0);
+        assert_eq!(lines[0].stops[1].deviation.mean, 10.0);
+    }
+
+    #[test]
+    fn flow_vehicles_beyond_the_count_are_ignored() {
+        let lines = adherence(
+            ROUTES,
+            r#"<stops>
+                <stopinfo id="bus.6" busStop="s1" started="3700" ended="3710"/>
+                <stopinfo id="bus.x" busStop="s1" started="3700" ended="3710"/>
+            </stops>"#,
+        );
+        assert!(lines.is_empty());
+    }
+
+    #[test]
+    fn huge_flows_are_not_expanded() {
+        let routes = r#"<routes>
+            <route id="r" edges="a"><stop busStop="s1" until="10"/></route>
+            <flow id="bus" line="1" route="r" begin="0" number="1000000000" period="1"/>
+        </routes>"#;
+        let lines = adherence(routes, r#"<stops><stopinfo id="bus.999999999" busStop="s1" started="1000000005" ended="1000000012"/></stops>"#);
+        assert_eq!(lines[0].stops[0].deviation.mean, 3.0);
+    }
+}
@@ -0,0 +1,162 @@
+// Heatmap keyframes for the time slider: every interval of an edgeData or
+// laneData output colored once, up front, into one buffer, so scrubbing only
+// picks a slice instead of recoloring every lane per frame.
+use serde::Deserialize;
+use std::collections::HashMap;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::color::ramp_color;
+use crate::{attr_f64, Lane};
+
+// Lanes without data in an interval
+const NO_DATA_COLOR: [u8; 4] = [120, 120, 120, 80];
+
+#[derive(Deserialize, Default, Tsify)]
+#[serde(default)]
+pub struct HeatmapOptions {
+    // Value mapped to the low / high end of the ramp; default the range over
+    // all intervals, so colors compare across the animation
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    // High values get the low end of the ramp (e.g. for speed, where low is bad)
+    pub reverse: bool,
+}
+
+// Per-lane colors for every interval. Interval-major: interval i occupies
+// colors[i * laneCount * 4 .. (i + 1) * laneCount * 4], lanes in the order of
+// the network's path buffers.
+#[wasm_bindgen]
+pub struct HeatmapKeyframes {
+    lane_count: usize,
+    begins: Vec<f64>,
+    ends: Vec<f64>,
+    colors: Vec<u8>,
+    values: Vec<f32>,
+    min: f64,
+    max: f64,
+}
+
+#[wasm_bindgen]
+impl HeatmapKeyframes {
+    #[wasm_bindgen(getter, js_name = laneCount)]
+    pub fn lane_count(&self) -> usize {
+        self.lane_count
+    }
+
+    #[wasm_bindgen(getter, js_name = intervalCount)]
+    pub fn interval_count(&self) -> usize {
+        self.begins.len()
+    }
+
+    // Interval start times (seconds)
+    #[wasm_bindgen(getter)]
+    pub fn begins(&self) -> Vec<f64> {
+        self.begins.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn ends(&self) -> Vec<f64> {
+        self.ends.clone()
+    }
+
+    // r, g, b, a per lane per interval
+    #[wasm_bindgen(getter)]
+    pub fn colors(&self) -> Vec<u8> {
+        self.colors.clone()
+    }
+
+    // Raw value per lane per interval, same layout; NaN without data
+    #[wasm_bindgen(getter)]
+    pub fn values(&self) -> Vec<f32> {
+        self.values.clone()
+    }
+
+    // Range the ramp spans, for the legend
+    #[wasm_bindgen(getter)]
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+}
+
+// Lane ids are "<edge id>_<index>"
+pub(crate) fn lane_edge(lane: &str) -> &str {
+    lane.rsplit_once('_').map_or(lane, |(edge, _)| edge)
+}
+
+// Values of one interval by lane and by edge id. laneData lanes are averaged per edge as
+// well, for networks that render one lane per edge.
+type IntervalValues<'a> = (HashMap<&'a str, f64>, HashMap<&'a str, f64>);
+
+fn interval_values<'a>(interval: roxmltree::Node<'a, '_>, attribute: &str) -> IntervalValues<'a> {
+    let mut lanes = HashMap::new();
+    let mut edge_sums: HashMap<&str, (f64, usize)> = HashMap::new();
+    for node in interval.descendants().filter(|n| n.is_element()) {
+        let (Some(id), Some(value)) = (node.attribute("id"), attr_f64(node, attribute)) else { continue };
+        match node.tag_name().name() {
+            "edge" => {
+                edge_sums.insert(id, (value, 1));
+            }
+            "lane" => {
+                lanes.insert(id, value);
+                let sum = edge_sums.entry(lane_edge(id)).or_default();
+                sum.0 += value;
+                sum.1 += 1;
+            }
+            _ => {}
+        }
+    }
+    let edges = edge_sums.into_iter().map(|(id, (sum, n))| (id, sum / n as f64)).collect();
+    (lanes, edges)
+}
+
+pub(crate) fn keyframes<'a>(
+    lanes: impl Iterator<Item = &'a Lane>,
+    root: roxmltree::Node,
+    attribute: &str,
+    options: &HeatmapOptions,
+) -> HeatmapKeyframes {
+    let lanes: Vec<&Lane> = lanes.collect();
+    let intervals: Vec<roxmltree::Node> = root.children().filter(|n| n.tag_name().name() == "interval").collect();
+
+    let mut values = Vec::with_capacity(lanes.len() * intervals.len());
+    for interval in &intervals {
+        let (by_lane, by_edge) = interval_values(*interval, attribute);
+        values.extend(lanes.iter().map(|l| {
+            by_lane
+                .get(l.id.as_str())
+                .or_else(|| by_edge.get(l.edge_id.as_deref().unwrap_or(lane_edge(&l.id))))
+                .map_or(f32::NAN, |v| *v as f32)
+        }));
+    }
+
+    let observed = values.iter().filter(|v| v.is_finite()).map(|v| *v as f64);
+    let min = options.min.unwrap_or_else(|| observed.clone().fold(f64::INFINITY, f64::min));
+    let max = options.max.unwrap_or_else(|| observed.fold(f64::NEG_INFINITY, f64::max));
+    let span = max - min;
+    let colors = values
+        .iter()
+        .flat_map(|v| {
+            if !v.is_finite() {
+                return NO_DATA_COLOR;
+            }
+            let t = if span > 0.0 { (*v as f64 - min) / span } else { 0.5 };
+            ramp_color(if options.reverse { 1.0 - t } else { t })
+        })
+        .collect();
+
+    HeatmapKeyframes {
+        lane_count: lanes.len(),
+        begins: intervals.iter().map(|i| attr_f64(*i, "begin").unwrap_or(0.0)).collect(),
+        ends: intervals.iter().map(|i| attr_f64(*i, "end").unwrap_or(0.0)).collect(),
+        colors,
+        values,
+        min: if min.is_finite() { min } else { 0.0 },
+        max: if max.is_finite() { max } else { 0.0 },
+    }
+}
@@ -0,0 +1,80 @@
+// Id interning: long SUMO ids (":cluster_1234567_..._c12_0") repeated on every
+// lane dominate the serialized output of large networks. An IdTable numbers
+// each distinct lane, edge and junction id once; interned output carries the
+// u32 index instead of the string.
+use std::collections::HashMap;
+
+use crate::ParsedNetwork;
+
+pub(crate) struct IdTable {
+    ids: Vec<String>,
+    index: HashMap<String, u32>,
+}
+
+impl IdTable {
+    // Ids in output order: lanes with their edges, then junctions
+    pub fn from_network(net: &ParsedNetwork) -> IdTable {
+        let mut table = IdTable {
+            ids: Vec::new(),
+            index: HashMap::new(),
+        };
+        for lane in &net.lanes {
+            table.intern(&lane.id);
+            if let Some(edge) = &lane.edge_id {
+                table.intern(edge);
+            }
+        }
+        for id in net.junctions.iter().map(|j| &j.id).chain(net.junction_points.iter().map(|j| &j.id)) {
+            table.intern(id);
+        }
+        table.ids.shrink_to_fit();
+        table
+    }
+
+    fn intern(&mut self, id: &str) {
+        if !self.index.contains_key(id) {
+            self.index.insert(id.to_string(), self.ids.len() as u32);
+            self.ids.push(id.to_string());
+        }
+    }
+
+    pub fn index_of(&self, id: &str) -> Option<u32> {
+        self.index.get(id).copied()
+    }
+
+    pub fn id(&self, index: u32) -> Option<&str> {
+        self.ids.get(index as usize).map(String::as_str)
+    }
+
+    pub fn ids(&self) -> &[String] {
+        &self.ids
+    }
+
+    pub fn into_ids(self) -> Vec<String> {
+        self.ids
+    }
+
+    // Replace ids in `net` by indices into this table; ids the table does not
+    // know stay strings
+    pub fn apply(&self, net: &mut ParsedNetwork) {
+        let swap = |id: &mut String, index: &mut Option<u32>| {
+            if let Some(i) = self.index_of(id) {
+                *index = Some(i);
+                id.clear();
+            }
+        };
+        for lane in &mut net.lanes {
+            swap(&mut lane.id, &mut lane.id_index);
+            if let Some(i) = lane.edge_id.as_deref().and_then(|e| self.index_of(e)) {
+                lane.edge_index = Some(i);
+                lane.edge_id = None;
+            }
+        }
+        for junction in &mut net.junctions {
+            swap(&mut junction.id, &mut junction.id_index);
+        }
+        for point in &mut net.junction_points {
+            swap(&mut point.id, &mut point.id_index);
+        }
+    }
+}
@@ -0,0 +1,45 @@
+// Render hints for junction types: the control sign the map draws at a
+// junction. Types without a sign (dead ends, unregulated, districts) map to
+// None.
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+
+#[derive(Serialize, Deserialize, Tsify, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum JunctionHint {
+    #[default]
+    None,
+    // traffic_light, traffic_light_unregulated, traffic_light_right_on_red
+    TrafficLight,
+    // priority: the minor roads yield
+    Yield,
+    // priority_stop: the minor roads stop
+    Stop,
+    // allway_stop
+    AllwayStop,
+    // right_before_left, left_before_right
+    RightBeforeLeft,
+    Zipper,
+    RailCrossing,
+    RailSignal,
+}
+
+impl JunctionHint {
+    pub(crate) fn from_type(junction_type: &str) -> JunctionHint {
+        match junction_type {
+            "traffic_light" | "traffic_light_unregulated" | "traffic_light_right_on_red" => JunctionHint::TrafficLight,
+            "priority" => JunctionHint::Yield,
+            "priority_stop" => JunctionHint::Stop,
+            "allway_stop" => JunctionHint::AllwayStop,
+            "right_before_left" | "left_before_right" => JunctionHint::RightBeforeLeft,
+            "zipper" => JunctionHint::Zipper,
+            "rail_crossing" => JunctionHint::RailCrossing,
+            "rail_signal" => JunctionHint::RailSignal,
+            _ => JunctionHint::None,
+        }
+    }
+
+    pub(crate) fn is_none(&self) -> bool {
+        *self == JunctionHint::None
+    }
+}
@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::to_js;
+
+const GRID_CELL_PX: f64 = 64.0;
+// Larger labels (thousands of cells) are skipped rather than indexed
+const MAX_LABEL_CELLS: i64 = 4096;
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct LabelCandidate {
+    pub id: String,
+    pub lat: f64,
+    pub lng: f64,
+    // Screen rotation in degrees, clockwise; flipped to stay upright
+    #[serde(default)]
+    pub angle: f64,
+    // Rendered size in pixels
+    pub width: f64,
+    pub height: f64,
+    // Higher wins when labels collide
+    #[serde(default)]
+    pub priority: f64,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct LabelViewport {
+    #[serde(rename = "minLat")]
+    pub min_lat: f64,
+    #[serde(rename = "minLng")]
+    pub min_lng: f64,
+    #[serde(rename = "maxLat")]
+    pub max_lat: f64,
+    #[serde(rename = "maxLng")]
+    pub max_lng: f64,
+    // Viewport size in pixels
+    pub width: f64,
+    pub height: f64,
+    // Minimum gap between labels in pixels (default 2)
+    #[serde(default)]
+    pub padding: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct PlacedLabel {
+    pub id: String,
+    // Center in viewport pixels, origin top-left
+    pub x: f64,
+    pub y: f64,
+    pub angle: f64,
+}
+
+// Oriented rectangle: center, unit axes and half extents
+struct LabelBox {
+    center: (f64, f64),
+    axes: [(f64, f64); 2],
+    half: (f64, f64),
+}
+
+impl LabelBox {
+    fn corners(&self) -> [(f64, f64); 4] {
+        let (c, [u, v], (hw, hh)) = (self.center, self.axes, self.half);
+        [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].map(|(su, sv)| {
+            (
+                c.0 + su * hw * u.0 + sv * hh * v.0,
+                c.1 + su * hw * u.1 + sv * hh * v.1,
+            )
+        })
+    }
+
+    fn aabb(&self) -> ((f64, f64), (f64, f64)) {
+        let corners = self.corners();
+        corners.iter().fold(
+            ((f64::INFINITY, f64::INFINITY), (f64::NEG_INFINITY, f64::NEG_INFINITY)),
+            |(lo, hi), p| ((lo.0.min(p.0), lo.1.min(p.1)), (hi.0.max(p.0), hi.1.max(p.1))),
+        )
+    }
+
+    // Separating axis test over both boxes' axes
+    fn overlaps(&self, other: &LabelBox) -> bool {
+        let (a, b) = (self.corners(), other.corners());
+        self.axes.iter().chain(other.axes.iter()).all(|axis| {
+            let project = |pts: &[(f64, f64); 4]| {
+                pts.iter()
+                    .map(|p| p.0 * axis.0 + p.1 * axis.1)
+                    .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), d| (lo.min(d), hi.max(d)))
+            };
+            let ((a0, a1), (b0, b1)) = (project(&a), project(&b));
+            a0 < b1 && b0 < a1
+        })
+    }
+}
+
+// Screen angles in (-90, 90] so text never renders upside down
+fn upright(angle: f64) -> f64 {
+    let a = angle.rem_euclid(360.0);
+    if a > 270.0 {
+        a - 360.0
+    } else if a > 90.0 {
+        a - 180.0
+    } else {
+        a
+    }
+}
+
+// Greedy placement: candidates in priority order, each kept if it lies fully
+// in the viewport and overlaps no label kept before it. Map coordinates are
+// mapped linearly onto the viewport, which matches L.CRS.Simple and is close
+// enough for city-scale Web Mercator views.
+pub(crate) fn place_labels(mut candidates: Vec<LabelCandidate>, viewport: &LabelViewport) -> Vec<PlacedLabel> {
+    let span_lng = viewport.max_lng - viewport.min_lng;
+    let span_lat = viewport.max_lat - viewport.min_lat;
+    if span_lng <= 0.0 || span_lat <= 0.0 || viewport.width <= 0.0 || viewport.height <= 0.0 {
+        return Vec::new();
+    }
+    let padding = viewport.padding.unwrap_or(2.0).max(0.0);
+
+    // Stable sort keeps input order among equal priorities
+    candidates.sort_by(|a, b| b.priority.total_cmp(&a.priority));
+
+    let mut placed: Vec<(LabelBox, PlacedLabel)> = Vec::new();
+    let mut grid: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    let cell = |v: f64| (v / GRID_CELL_PX).floor() as i64;
+
+    for c in candidates {
+        if !(c.width > 0.0 && c.height > 0.0 && c.lat.is_finite() && c.lng.is_finite()) {
+            continue;
+        }
+        let x = (c.lng - viewport.min_lng) / span_lng * viewport.width;
+        let y = (viewport.max_lat - c.lat) / span_lat * viewport.height;
+        let angle = upright(c.angle);
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let label_box = LabelBox {
+            center: (x, y),
+            axes: [(cos, sin), (-sin, cos)],
+            half: ((c.width + padding) / 2.0, (c.height + padding) / 2.0),
+        };
+
+        let (lo, hi) = label_box.aabb();
+        if lo.0 < 0.0 || lo.1 < 0.0 || hi.0 > viewport.width || hi.1 > viewport.height {
+            continue;
+        }
+        let extent = |a: f64, b: f64| cell(b).saturating_sub(cell(a)).saturating_add(1);
+        let span = extent(lo.0, hi.0).saturating_mul(extent(lo.1, hi.1));
+        if span > MAX_LABEL_CELLS {
+            continue;
+        }
+        let cells: Vec<(i64, i64)> = (cell(lo.0)..=cell(hi.0))
+            .flat_map(|cx| (cell(lo.1)..=cell(hi.1)).map(move |cy| (cx, cy)))
+            .collect();
+        let collides = cells
+            .iter()
+            .filter_map(|k| grid.get(k))
+            .flatten()
+            .any(|&i| placed[i].0.overlaps(&label_box));
+        if collides {
+            continue;
+        }
+
+        let index = placed.len();
+        for k in cells {
+            grid.entry(k).or_default().push(index);
+        }
+        placed.push((label_box, PlacedLabel { id: c.id, x, y, angle }));
+    }
+
+    placed.into_iter().map(|(_, label)| label).collect()
+}
+
+// Collision-free subset of label candidates for the current viewport
+#[wasm_bindgen(unchecked_return_type = "PlacedLabel[]")]
+pub fn layout_labels(
+    #[wasm_bindgen(unchecked_param_type = "LabelCandidate[]")] candidates: JsValue,
+    #[wasm_bindgen(unchecked_param_type = "LabelViewport")] viewport: JsValue,
+) -> Result<JsValue, JsValue> {
+    let candidates: Vec<LabelCandidate> = serde_wasm_bindgen::from_value(candidates)
+        .map_err(|e| JsValue::from_str(&format!("Invalid label candidates: {}", e)))?;
+    let viewport: LabelViewport = serde_wasm_bindgen::from_value(viewport)
+        .map_err(|e| JsValue::from_str(&format!("Invalid viewport: {}", e)))?;
+
+    let placed = place_labels(candidates, &viewport);
+    console_debug!("Placed {} labels", placed.len());
+
+    to_js(&placed)
+}
@@ -0,0 +1,125 @@
+// Lane changes from a lane change output (--lanechange-output): one <change>
+// per maneuver, and their counts along each edge in fixed-length segments, to
+// find weaving hotspots around merges and diverges.
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::heatmap::lane_edge;
+use crate::{attr_f64, parse_options, parse_xml, to_js};
+
+const DEFAULT_SEGMENT_LENGTH: f64 = 50.0;
+
+#[derive(Deserialize, Default, Tsify)]
+#[serde(default)]
+pub struct LaneChangeOptions {
+    // Meters per segment along an edge (default 50)
+    #[serde(rename = "segmentLength")]
+    pub segment_length: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct LaneChange {
+    pub time: f64,
+    // Vehicle
+    pub id: String,
+    #[serde(rename = "vType")]
+    pub v_type: Option<String>,
+    // Lane ids
+    pub from: String,
+    pub to: String,
+    // Lanes moved, positive to the left
+    pub dir: i32,
+    pub speed: Option<f64>,
+    // Meters along the lane
+    pub pos: Option<f64>,
+    // strategic, cooperative, speedGain, keepRight, ... as written, possibly
+    // with flags ("strategic|urgent")
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct LaneChangeSegment {
+    pub edge: String,
+    // Meters along the edge
+    pub start: f64,
+    pub end: f64,
+    pub count: usize,
+    pub left: usize,
+    pub right: usize,
+    // Changes by their main reason (the part before any '|')
+    pub reasons: BTreeMap<String, usize>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct LaneChangeOutput {
+    pub changes: Vec<LaneChange>,
+    // Segments with at least one change, by edge id and position; changes
+    // without a position are only in `changes`
+    pub segments: Vec<LaneChangeSegment>,
+}
+
+pub(crate) fn read_lane_changes(root: roxmltree::Node) -> Vec<LaneChange> {
+    root.children()
+        .filter(|n| n.has_tag_name("change"))
+        .filter_map(|c| {
+            Some(LaneChange {
+                time: attr_f64(c, "time")?,
+                id: c.attribute("id")?.to_string(),
+                v_type: c.attribute("type").map(String::from),
+                from: c.attribute("from")?.to_string(),
+                to: c.attribute("to")?.to_string(),
+                dir: c.attribute("dir").and_then(|d| d.parse().ok()).unwrap_or(0),
+                speed: attr_f64(c, "speed"),
+                pos: attr_f64(c, "pos"),
+                reason: c.attribute("reason").map(String::from),
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn count_by_segment(changes: &[LaneChange], segment_length: f64) -> Vec<LaneChangeSegment> {
+    let mut segments: BTreeMap<(&str, i64), LaneChangeSegment> = BTreeMap::new();
+    for change in changes {
+        let Some(pos) = change.pos else { continue };
+        let edge = lane_edge(&change.from);
+        let index = (pos.max(0.0) / segment_length).floor() as i64;
+        let segment = segments.entry((edge, index)).or_insert_with(|| LaneChangeSegment {
+            edge: edge.to_string(),
+            start: index as f64 * segment_length,
+            end: (index + 1) as f64 * segment_length,
+            count: 0,
+            left: 0,
+            right: 0,
+            reasons: BTreeMap::new(),
+        });
+        segment.count += 1;
+        if change.dir > 0 {
+            segment.left += 1;
+        } else if change.dir < 0 {
+            segment.right += 1;
+        }
+        if let Some(reason) = change.reason.as_deref().and_then(|r| r.split('|').next()) {
+            *segment.reasons.entry(reason.to_string()).or_default() += 1;
+        }
+    }
+    segments.into_values().collect()
+}
+
+#[wasm_bindgen(unchecked_return_type = "LaneChangeOutput")]
+pub fn parse_lanechange_output(
+    xml_text: &str,
+    #[wasm_bindgen(unchecked_param_type = "LaneChangeOptions | undefined")] options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let options: LaneChangeOptions = parse_options(options)?;
+    let segment_length = options.segment_length.filter(|l| *l > 0.0).unwrap_or(DEFAULT_SEGMENT_LENGTH);
+
+    let doc = parse_xml(xml_text)?;
+    let changes = read_lane_changes(doc.root_element());
+    let segments = count_by_segment(&changes, segment_length);
+
+    console_log!("Parsed {} lane changes in {} segments", changes.len(), segments.len());
+
+    to_js(&LaneChangeOutput { changes, segments })
+}
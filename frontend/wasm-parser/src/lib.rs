@@ -1,35 +1,319 @@
 use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use tsify::Tsify;
+use std::collections::{HashMap, HashSet};
 
-#[wasm_bindgen]
-extern "C" {
-    #[wasm_bindgen(js_namespace = console)]
-    fn log(s: &str);
+use junctiontypes::JunctionHint;
+use projection::GeoReference;
+
+#[cfg(feature = "logging")]
+macro_rules! log_at {
+    ($level:expr, $($t:tt)*) => {
+        if $crate::logging::enabled($level) {
+            $crate::logging::emit(&format_args!($($t)*).to_string())
+        }
+    };
+}
+
+// Without the `logging` feature, messages are type-checked but never built
+#[cfg(not(feature = "logging"))]
+macro_rules! log_at {
+    ($level:expr, $($t:tt)*) => {
+        if false {
+            let _ = ($level, format_args!($($t)*));
+        }
+    };
 }
 
 macro_rules! console_log {
-    ($($t:tt)*) => (log(&format_args!($($t)*).to_string()))
+    ($($t:tt)*) => (log_at!($crate::logging::LogLevel::Info, $($t)*))
+}
+
+macro_rules! console_debug {
+    ($($t:tt)*) => (log_at!($crate::logging::LogLevel::Debug, $($t)*))
+}
+
+#[cfg(feature = "arrow")]
+mod arrow;
+#[cfg(any(feature = "msgpack", feature = "cbor"))]
+mod binary;
+mod battery;
+mod budget;
+mod buslanes;
+mod capacity;
+mod centrality;
+mod closure;
+mod collisions;
+mod color;
+mod crop;
+mod crossings;
+mod deckgl;
+mod decimal;
+mod delay;
+mod detectors;
+mod diff;
+mod divergence;
+mod dxf;
+mod emissions;
+mod fcd;
+mod fingerprint;
+mod funnels;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+mod geometry;
+mod graph;
+mod gtfs;
+mod hash;
+mod headway;
+mod heatmap;
+mod intern;
+mod junctiontypes;
+mod labels;
+mod lanechanges;
+mod linref;
+mod live;
+mod logging;
+mod mapmatch;
+mod markings;
+mod matsim;
+mod memory;
+mod mesh;
+mod movements;
+mod mvt;
+mod net;
+mod netstate;
+mod network;
+mod noise;
+mod od;
+mod opendrive;
+mod osm;
+mod parking;
+mod persons;
+mod plain;
+mod projection;
+mod queues;
+#[cfg(feature = "raster")]
+mod raster;
+mod routecompare;
+mod routes;
+mod routing;
+mod sanity;
+mod scan;
+mod scenario;
+mod session;
+mod signaltiming;
+mod simframe;
+#[cfg(feature = "snapshot")]
+mod snapshot;
+mod spacetime;
+mod spatial;
+mod ssm;
+mod stats;
+mod stoplines;
+mod stopinfo;
+mod stream;
+mod streets;
+mod summary;
+mod sumocfg;
+mod svg;
+mod taz;
+#[cfg(all(feature = "parallel", target_arch = "wasm32"))]
+mod threads;
+mod tlsoutput;
+mod traci;
+mod transit;
+mod tripinfo;
+mod units;
+mod vehroutes;
+mod vtypes;
+
+pub(crate) fn parse_xml(xml_text: &str) -> Result<roxmltree::Document<'_>, JsValue> {
+    roxmltree::Document::parse(xml_text)
+        .map_err(|e| JsValue::from_str(&format!("XML parse error: {}", e)))
+}
+
+pub(crate) fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsValue> {
+    serde_wasm_bindgen::to_value(value)
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
+
+#[derive(Deserialize, Default, Tsify)]
+#[serde(default)]
+pub struct ParseOptions {
+    // Overrides the global level (see `set_log_level`) for this call
+    #[serde(rename = "logLevel")]
+    pub log_level: Option<logging::LogLevel>,
+    // Output frame; defaults to the frame the network is stored in. "wgs84"
+    // converts projected networks to lon/lat.
+    pub crs: Option<projection::Crs>,
+    // Round coordinates to this many decimal places (e.g. 2 = centimeters
+    // for network coordinates, 6 for WGS84)
+    pub precision: Option<u32>,
+    // Store coordinates and elevation as integers round(v * quantize); divide by the
+    // returned `quantization` to decode. Takes precedence over `precision`.
+    pub quantize: Option<f64>,
+    // Replace lane, edge and junction ids by indices into a shared `ids`
+    // table, which shrinks the output when ids are long
+    #[serde(rename = "internIds")]
+    pub intern_ids: bool,
+    // Keep only junctions (polygons and points) of these SUMO types, e.g.
+    // ["allway_stop", "priority_stop", "rail_crossing"] for a sign layer
+    #[serde(rename = "junctionTypes")]
+    pub junction_types: Option<Vec<String>>,
+    // Keep only lanes this SUMO vehicle class may use, e.g. "passenger"
+    #[serde(rename = "vClass")]
+    pub v_class: Option<String>,
+    // With vClass: only lanes reserved for it, which passenger cars may not
+    // use, e.g. "bus" for the bus lane layer
+    #[serde(rename = "vClassDedicated")]
+    pub v_class_dedicated: bool,
+    // Chaikin smoothing passes over internal lanes after simplification, for
+    // round turning movements (default 0 = off, at most 4); each pass
+    // doubles their points
+    #[serde(rename = "smoothInternal")]
+    pub smooth_internal: u32,
+    // Add `headings` to every lane, samples about this many meters apart
+    // (at least one per lane); default none
+    #[serde(rename = "headingSpacing")]
+    pub heading_spacing: Option<f64>,
+    // Keep SUMO's own x/y (`xy` of lanes and junctions) next to converted
+    // points, for matching TraCI positions with crs "wgs84"
+    #[serde(rename = "rawXy")]
+    pub raw_xy: bool,
+    // Only these parts of the result, e.g. ["lanes", "bounds"] for a road
+    // layer. The rest is neither parsed nor serialized: its arrays stay
+    // empty (bounds null). Default all.
+    pub fields: Option<Vec<NetField>>,
+}
+
+impl ParseOptions {
+    fn v_class_filter(&self) -> Option<net::VClassFilter> {
+        Some(net::VClassFilter {
+            v_class: self.v_class.clone()?,
+            dedicated: self.v_class_dedicated,
+        })
+    }
+}
+
+// Selectable parts of a ParsedNetwork
+#[derive(Serialize, Deserialize, Tsify, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum NetField {
+    // Lanes of normal edges
+    Lanes,
+    // Lanes of internal edges, and the connection curves synthesized for
+    // networks without them
+    InternalLanes,
+    Bounds,
+    Tls,
+    Junctions,
+    JunctionPoints,
+    Roundabouts,
+}
+
+// Which NetFields to build; None = all
+#[derive(Clone, Default)]
+struct FieldSelection(Option<Vec<NetField>>);
+
+impl FieldSelection {
+    fn wants(&self, field: NetField) -> bool {
+        self.0.as_ref().is_none_or(|f| f.contains(&field))
+    }
+
+    // Normal edges feed the lanes, the roundabout rings and the lane ends
+    // that connection curves are synthesized from
+    fn wants_edge(&self, edge: roxmltree::Node) -> bool {
+        if edge.attribute("function") == Some("internal") {
+            self.wants(NetField::InternalLanes)
+        } else {
+            self.wants(NetField::Lanes) || self.wants(NetField::Roundabouts) || self.wants(NetField::InternalLanes)
+        }
+    }
+
+    fn wants_junction(&self) -> bool {
+        self.wants(NetField::Tls) || self.wants(NetField::Junctions) || self.wants(NetField::JunctionPoints)
+    }
+}
+
+// Options objects are optional on the JS side; undefined/null means defaults
+pub(crate) fn parse_options<T: serde::de::DeserializeOwned + Default>(options: JsValue) -> Result<T, JsValue> {
+    if options.is_undefined() || options.is_null() {
+        return Ok(T::default());
+    }
+    serde_wasm_bindgen::from_value(options)
+        .map_err(|e| JsValue::from_str(&format!("Invalid options: {}", e)))
 }
 
-#[derive(Serialize, Deserialize)]
+pub(crate) fn attr_f64(node: roxmltree::Node, name: &str) -> Option<f64> {
+    node.attribute(name)
+        .and_then(decimal::parse_f64)
+        .filter(|v| v.is_finite())
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
 pub struct Point {
     pub lat: f64,
     pub lng: f64,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Tsify, Clone)]
 pub struct Lane {
+    // Empty (and omitted) when interned; see `id_index`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub id: String,
-    #[serde(rename = "edgeId")]
+    // Index of the id in ParsedNetwork.ids when ids are interned
+    #[serde(rename = "idIndex", default, skip_serializing_if = "Option::is_none")]
+    pub id_index: Option<u32>,
+    #[serde(rename = "edgeId", default, skip_serializing_if = "Option::is_none")]
     pub edge_id: Option<String>,
+    #[serde(rename = "edgeIndex", default, skip_serializing_if = "Option::is_none")]
+    pub edge_index: Option<u32>,
     pub points: Vec<Vec<f64>>,
+    // [x, y] per point in SUMO's network coordinates, when requested with
+    // ParseOptions.rawXy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xy: Option<Vec<Vec<f64>>>,
+    // z per point (meters) when the shape is 3D, parallel to `points`
+    pub elevation: Option<Vec<f64>>,
+    // [lat, lng, bearing] every ParseOptions.headingSpacing meters along the
+    // lane, for direction arrows and sprites; bearing in degrees clockwise
+    // from the network's north (+y)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub headings: Option<Vec<Vec<f64>>>,
     pub speed: Option<f64>,
+    // Speed snapped to signposted limits, for display
+    #[serde(rename = "speedKmh")]
+    pub speed_kmh: Option<f64>,
+    #[serde(rename = "speedMph")]
+    pub speed_mph: Option<f64>,
+    #[serde(rename = "speedClass")]
+    pub speed_class: Option<units::SpeedClass>,
+    // Meters: SUMO's length attribute (which positions along the lane refer
+    // to), else the length of the unsimplified shape
+    pub length: Option<f64>,
+    // Meters, that of the edge's rightmost lane as in SUMO; none for the
+    // connection curves of networks without internal lanes
+    #[serde(rename = "edgeLength", default, skip_serializing_if = "Option::is_none")]
+    pub edge_length: Option<f64>,
+    // Vehicles per hour, for formats that carry one (MATSim links)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capacity: Option<f64>,
+    // Vehicle class permissions, space separated as in SUMO; `allow` wins
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disallow: Option<String>,
     #[serde(rename = "isInternal")]
     pub is_internal: bool,
+    // Part of a <roundabout>, including the internal lanes of its junctions
+    #[serde(rename = "isRoundabout")]
+    pub is_roundabout: bool,
+    // Closed by a live update of the Network handle (see live.rs)
+    #[serde(rename = "isClosed", default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_closed: bool,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Tsify, Clone)]
 pub struct TrafficLight {
     pub id: String,
     #[serde(rename = "clusterId")]
@@ -38,22 +322,56 @@ pub struct TrafficLight {
     pub lng: f64,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Tsify, Clone)]
 pub struct Junction {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub id: String,
+    #[serde(rename = "idIndex", default, skip_serializing_if = "Option::is_none")]
+    pub id_index: Option<u32>,
     #[serde(rename = "type")]
     pub junction_type: String,
+    // Control sign to draw, derived from the type
+    #[serde(default, skip_serializing_if = "JunctionHint::is_none")]
+    pub hint: JunctionHint,
     pub polygon: Vec<Vec<f64>>,
+    // [x, y] per polygon point in network coordinates, see Lane.xy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xy: Option<Vec<Vec<f64>>>,
+    #[serde(rename = "isRoundabout")]
+    pub is_roundabout: bool,
+    // Lanes ending at the junction, for highlighting its approaches
+    #[serde(rename = "incLanes", default, skip_serializing_if = "Vec::is_empty")]
+    pub inc_lanes: Vec<String>,
+    // Internal lanes of the links across the junction
+    #[serde(rename = "intLanes", default, skip_serializing_if = "Vec::is_empty")]
+    pub int_lanes: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Tsify, Clone)]
+pub struct Roundabout {
+    pub nodes: Vec<String>,
+    // Member edges in driving order around the ring
+    pub edges: Vec<String>,
+    // Stitched centerline of the member edges, closed, as [lat, lng]
+    pub ring: Vec<Vec<f64>>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Tsify, Clone)]
 pub struct JunctionPoint {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub id: String,
+    #[serde(rename = "idIndex", default, skip_serializing_if = "Option::is_none")]
+    pub id_index: Option<u32>,
+    #[serde(rename = "type", default, skip_serializing_if = "String::is_empty")]
+    pub junction_type: String,
+    // Where to place the control sign of the junction
+    #[serde(default, skip_serializing_if = "JunctionHint::is_none")]
+    pub hint: JunctionHint,
     pub lat: f64,
     pub lng: f64,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Tsify, Clone)]
 pub struct Bounds {
     #[serde(rename = "minX")]
     pub min_x: f64,
@@ -65,7 +383,21 @@ pub struct Bounds {
     pub max_y: f64,
 }
 
-#[derive(Serialize, Deserialize)]
+impl Bounds {
+    // Smallest box holding every (x, y); None without points
+    pub(crate) fn of_points(points: impl IntoIterator<Item = (f64, f64)>) -> Option<Bounds> {
+        let mut points = points.into_iter();
+        let (x, y) = points.next()?;
+        Some(points.fold(Bounds { min_x: x, min_y: y, max_x: x, max_y: y }, |b, (x, y)| Bounds {
+            min_x: b.min_x.min(x),
+            min_y: b.min_y.min(y),
+            max_x: b.max_x.max(x),
+            max_y: b.max_y.max(y),
+        }))
+    }
+}
+
+#[derive(Serialize, Deserialize, Tsify, Clone)]
 pub struct ParsedNetwork {
     pub lanes: Vec<Lane>,
     pub bounds: Option<Bounds>,
@@ -73,12 +405,178 @@ pub struct ParsedNetwork {
     pub junctions: Vec<Junction>,
     #[serde(rename = "junctionPoints")]
     pub junction_points: Vec<JunctionPoint>,
+    pub roundabouts: Vec<Roundabout>,
+    // Frame of all coordinates above
+    pub crs: projection::Crs,
+    // Scale factor when coordinates were quantized to integers
+    pub quantization: Option<f64>,
+    // Id string table that `idIndex` / `edgeIndex` point into, when interned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ids: Option<Vec<String>>,
+}
+
+impl ParsedNetwork {
+    // Apply `f(x, y) -> (x, y)` to every coordinate; output points are [lat, lng] = [y, x]
+    fn map_coords(&mut self, f: impl Fn(f64, f64) -> (f64, f64)) {
+        let map_point = |p: &mut Vec<f64>| {
+            let (x, y) = f(p[1], p[0]);
+            p[0] = y;
+            p[1] = x;
+        };
+        for lane in &mut self.lanes {
+            lane.points.iter_mut().for_each(map_point);
+            lane.headings.iter_mut().flatten().for_each(map_point);
+        }
+        for junction in &mut self.junctions {
+            junction.polygon.iter_mut().for_each(map_point);
+        }
+        for roundabout in &mut self.roundabouts {
+            roundabout.ring.iter_mut().for_each(map_point);
+        }
+        for tl in &mut self.tls {
+            (tl.lng, tl.lat) = f(tl.lng, tl.lat);
+        }
+        for jp in &mut self.junction_points {
+            (jp.lng, jp.lat) = f(jp.lng, jp.lat);
+        }
+        if let Some(b) = self.bounds.as_mut() {
+            // Map all four corners so rotations (projections) keep the box enclosing
+            let corners = [
+                f(b.min_x, b.min_y),
+                f(b.max_x, b.min_y),
+                f(b.min_x, b.max_y),
+                f(b.max_x, b.max_y),
+            ];
+            b.min_x = corners.iter().map(|c| c.0).fold(f64::INFINITY, f64::min);
+            b.max_x = corners.iter().map(|c| c.0).fold(f64::NEG_INFINITY, f64::max);
+            b.min_y = corners.iter().map(|c| c.1).fold(f64::INFINITY, f64::min);
+            b.max_y = corners.iter().map(|c| c.1).fold(f64::NEG_INFINITY, f64::max);
+        }
+    }
+
+    // Drop spare capacity before the network is kept around (see memory.rs)
+    fn shrink_to_fit(&mut self) {
+        self.lanes.shrink_to_fit();
+        self.tls.shrink_to_fit();
+        self.junctions.shrink_to_fit();
+        self.junction_points.shrink_to_fit();
+        self.roundabouts.shrink_to_fit();
+    }
+
+    fn retain_junction_types(&mut self, types: &[String]) {
+        let keep = |t: &String| types.contains(t);
+        self.junctions.retain(|j| keep(&j.junction_type));
+        self.junction_points.retain(|j| keep(&j.junction_type));
+    }
+
+    // Smoothed in the source frame, before any reprojection or rounding
+    fn smooth_internal_lanes(&mut self, passes: u32) {
+        if passes == 0 {
+            return;
+        }
+        for lane in self.lanes.iter_mut().filter(|l| l.is_internal) {
+            // Elevation rides along as a third coordinate
+            let points: Vec<Vec<f64>> = match &lane.elevation {
+                Some(z) => lane.points.iter().zip(z).map(|(p, z)| vec![p[0], p[1], *z]).collect(),
+                None => lane.points.clone(),
+            };
+            let smooth = geometry::chaikin(&points, passes);
+            if lane.elevation.is_some() {
+                lane.elevation = Some(smooth.iter().map(|p| p[2]).collect());
+            }
+            lane.points = smooth.into_iter().map(|p| vec![p[0], p[1]]).collect();
+        }
+    }
+
+    // Copy the current points, [lat, lng] = [y, x], into `xy`
+    fn keep_raw_xy(&mut self) {
+        let xy = |points: &[Vec<f64>]| Some(points.iter().map(|p| vec![p[1], p[0]]).collect());
+        for lane in &mut self.lanes {
+            lane.xy = xy(&lane.points);
+        }
+        for junction in &mut self.junctions {
+            junction.xy = xy(&junction.polygon);
+        }
+    }
+
+    // Bearings are taken in the network frame, before any conversion to WGS84
+    fn add_headings(&mut self, spacing: f64) {
+        for lane in &mut self.lanes {
+            let samples = geometry::heading_samples(&network::to_xy(&lane.points), spacing);
+            lane.headings = Some(samples.into_iter().map(|((x, y), bearing)| vec![y, x, bearing]).collect());
+        }
+    }
+
+    // Lanes `filter` matches. SUMO networks are filtered while parsing
+    // instead, so each edge's representative lane is picked among these.
+    fn retain_v_class(&mut self, filter: &net::VClassFilter) {
+        self.lanes.retain(|l| filter.matches(l.allow.as_deref(), l.disallow.as_deref()));
+    }
+
+    // Internal lane smoothing, output frame, precision, junction type and
+    // vehicle class filters and id interning of the parse options, for any
+    // source format
+    fn apply_options(&mut self, geo: &projection::GeoReference, options: &ParseOptions) -> Result<(), JsValue> {
+        self.smooth_internal_lanes(options.smooth_internal.min(MAX_SMOOTH_PASSES));
+        if let Some(spacing) = options.heading_spacing.filter(|s| *s > 0.0) {
+            self.add_headings(spacing / network::shape_scale(geo));
+        }
+        if options.raw_xy {
+            self.keep_raw_xy();
+        }
+        if options.crs == Some(projection::Crs::Wgs84) {
+            geo.to_wgs84(self)
+                .map_err(|e| JsValue::from_str(&format!("Cannot convert to WGS84: {}", e)))?;
+        }
+        self.reduce_precision(options.precision, options.quantize);
+        if let Some(types) = &options.junction_types {
+            self.retain_junction_types(types);
+        }
+        if let Some(filter) = options.v_class_filter() {
+            self.retain_v_class(&filter);
+        }
+        if options.intern_ids {
+            let table = intern::IdTable::from_network(self);
+            table.apply(self);
+            self.ids = Some(table.into_ids());
+        }
+        Ok(())
+    }
+
+    // Shrink the serialized output by dropping meaningless coordinate digits
+    fn reduce_precision(&mut self, precision: Option<u32>, quantize: Option<f64>) {
+        let round: Box<dyn Fn(f64) -> f64> = match (quantize.filter(|q| q.is_finite() && *q > 0.0), precision) {
+            (Some(scale), _) => {
+                self.quantization = Some(scale);
+                Box::new(move |v| (v * scale).round())
+            }
+            (None, Some(digits)) => {
+                let factor = 10f64.powi(digits.min(15) as i32);
+                Box::new(move |v| (v * factor).round() / factor)
+            }
+            (None, None) => return,
+        };
+        self.map_coords(|x, y| (round(x), round(y)));
+        for z in self.lanes.iter_mut().filter_map(|l| l.elevation.as_mut()).flatten() {
+            *z = round(*z);
+        }
+        let raw = self.lanes.iter_mut().filter_map(|l| l.xy.as_mut());
+        for v in raw.chain(self.junctions.iter_mut().filter_map(|j| j.xy.as_mut())).flatten().flatten() {
+            *v = round(*v);
+        }
+    }
 }
 
 // Ramer-Douglas-Peucker algorithm for line simplification
 fn rdp_simplify(points: &[(f64, f64)], epsilon: f64) -> Vec<(f64, f64)> {
+    retain_kept(points, &rdp_keep(points, epsilon))
+}
+
+// Which points Ramer-Douglas-Peucker keeps, so parallel per-point data
+// (elevation) can be filtered the same way
+fn rdp_keep(points: &[(f64, f64)], epsilon: f64) -> Vec<bool> {
     if points.len() <= 2 {
-        return points.to_vec();
+        return vec![true; points.len()];
     }
 
     let epsilon_squared = epsilon * epsilon;
@@ -107,10 +605,14 @@ fn rdp_simplify(points: &[(f64, f64)], epsilon: f64) -> Vec<(f64, f64)> {
         }
     }
 
-    points.iter()
-        .enumerate()
-        .filter(|(i, _)| keep[*i])
-        .map(|(_, p)| *p)
+    keep
+}
+
+fn retain_kept<T: Clone>(items: &[T], keep: &[bool]) -> Vec<T> {
+    items.iter()
+        .zip(keep)
+        .filter(|(_, k)| **k)
+        .map(|(item, _)| item.clone())
         .collect()
 }
 
@@ -120,247 +622,716 @@ fn point_to_segment_distance_sq(p: (f64, f64), v: (f64, f64), w: (f64, f64)) ->
         return (p.0 - v.0).powi(2) + (p.1 - v.1).powi(2);
     }
 
-    let t = (((p.0 - v.0) * (w.0 - v.0) + (p.1 - v.1) * (w.1 - v.1)) / l2).max(0.0).min(1.0);
+    let t = (((p.0 - v.0) * (w.0 - v.0) + (p.1 - v.1) * (w.1 - v.1)) / l2).clamp(0.0, 1.0);
     let proj_x = v.0 + t * (w.0 - v.0);
     let proj_y = v.1 + t * (w.1 - v.1);
 
     (p.0 - proj_x).powi(2) + (p.1 - proj_y).powi(2)
 }
 
-fn sample_points(points: &[(f64, f64)], max_points: usize) -> Vec<(f64, f64)> {
-    if points.len() <= max_points {
-        return points.to_vec();
+// Evenly spaced subset of at most about `max_points`, always including the last point
+fn sample_keep(len: usize, max_points: usize) -> Vec<bool> {
+    if len <= max_points {
+        return vec![true; len];
     }
 
-    let step = (points.len() as f64 / max_points as f64).ceil() as usize;
-    let mut result: Vec<(f64, f64)> = points.iter()
-        .step_by(step)
-        .copied()
+    let step = (len as f64 / max_points as f64).ceil() as usize;
+    let mut keep: Vec<bool> = (0..len).map(|i| i % step == 0).collect();
+    keep[len - 1] = true;
+    keep
+}
+
+// Which points of each lane of one edge to keep: Ramer-Douglas-Peucker (for
+// more than 4 points), then at most MAX_POINTS_PER_LANE. Lanes with equally
+// many points, as netconvert builds parallel lanes, share one mask (the union
+// of theirs) so their vertices stay abreast and no gaps open between them.
+// First and last points, where lanes meet the junction shapes, always stay.
+fn edge_keep_masks(shapes: &[&[(f64, f64)]], epsilon: f64) -> Vec<Vec<bool>> {
+    let masks: Vec<Vec<bool>> = shapes
+        .iter()
+        .map(|p| if p.len() > 4 { rdp_keep(p, epsilon) } else { vec![true; p.len()] })
         .collect();
+    let masks = match shapes.first() {
+        Some(first) if shapes.len() > 1 && shapes.iter().all(|p| p.len() == first.len()) => {
+            let union: Vec<bool> = (0..first.len()).map(|i| masks.iter().any(|k| k[i])).collect();
+            vec![union; shapes.len()]
+        }
+        _ => masks,
+    };
+    masks.into_iter().map(|keep| limit_kept(keep, MAX_POINTS_PER_LANE)).collect()
+}
 
-    // Always include the last point
-    if result.last() != points.last() {
-        if let Some(last) = points.last() {
-            result.push(*last);
+// Thin a keep mask to about `max_points` kept points with sample_keep
+fn limit_kept(keep: Vec<bool>, max_points: usize) -> Vec<bool> {
+    let kept = keep.iter().filter(|k| **k).count();
+    if kept <= max_points {
+        return keep;
+    }
+    let mut sample = sample_keep(kept, max_points).into_iter();
+    keep.into_iter().map(|k| k && sample.next().unwrap_or(false)).collect()
+}
+
+// End segments of a raw lane shape, used to synthesize missing connection curves
+struct LaneEnds {
+    start: (f64, f64),
+    start_dir: (f64, f64),
+    end: (f64, f64),
+    end_dir: (f64, f64),
+}
+
+impl LaneEnds {
+    fn from_points(points: &[(f64, f64)]) -> Option<LaneEnds> {
+        let n = points.len();
+        if n < 2 {
+            return None;
         }
+        Some(LaneEnds {
+            start: points[0],
+            start_dir: geometry::unit_direction(points[0], points[1])?,
+            end: points[n - 1],
+            end_dir: geometry::unit_direction(points[n - 2], points[n - 1])?,
+        })
     }
+}
 
-    result
+// A connection without a `via` lane, waiting for its lanes' geometry
+struct PendingConnection {
+    from: String,
+    from_lane: String,
+    to: String,
+    to_lane: String,
 }
 
-fn parse_point_string(shape: &str) -> Vec<(f64, f64)> {
-    shape
-        .split_whitespace()
-        .filter_map(|pair| {
-            let coords: Vec<&str> = pair.split(',').collect();
-            if coords.len() == 2 {
-                if let (Ok(x), Ok(y)) = (coords[0].parse::<f64>(), coords[1].parse::<f64>()) {
-                    if x.is_finite() && y.is_finite() {
-                        return Some((x, y));
-                    }
-                }
+// Nets built with --no-internal-links have connections without `via` lanes, so
+// intersections render without turning paths. Bridge each such connection with a
+// cubic Bézier from the end of the incoming lane to the start of the outgoing one.
+fn synthesize_connection_lanes(
+    pending: &[PendingConnection],
+    lane_ends: &HashMap<String, LaneEnds>,
+    plain_geo: bool,
+) -> Vec<Lane> {
+    const CURVE_SEGMENTS: usize = 8;
+
+    pending
+        .iter()
+        .filter_map(|c| {
+            let incoming = lane_ends.get(&format!("{}_{}", c.from, c.from_lane))?;
+            let outgoing = lane_ends.get(&format!("{}_{}", c.to, c.to_lane))?;
+
+            let p0 = incoming.end;
+            let p3 = outgoing.start;
+            let handle = geometry::distance(p0, p3) / 3.0;
+            if handle <= 0.0 {
+                return None;
             }
-            None
+            let p1 = (p0.0 + incoming.end_dir.0 * handle, p0.1 + incoming.end_dir.1 * handle);
+            let p2 = (p3.0 - outgoing.start_dir.0 * handle, p3.1 - outgoing.start_dir.1 * handle);
+            let curve = geometry::cubic_bezier(p0, p1, p2, p3, CURVE_SEGMENTS);
+
+            Some(Lane {
+                id: format!("{}_{}->{}_{}", c.from, c.from_lane, c.to, c.to_lane),
+                id_index: None,
+                edge_id: None,
+                edge_index: None,
+                length: Some(shape_length(&curve, plain_geo)),
+                edge_length: None,
+                points: curve.iter().map(|(x, y)| vec![*y, *x]).collect(),
+                xy: None,
+                elevation: None,
+                headings: None,
+                speed: None,
+                speed_kmh: None,
+                speed_mph: None,
+                speed_class: None,
+                capacity: None,
+                allow: None,
+                disallow: None,
+                is_internal: true,
+                is_roundabout: false,
+                is_closed: false,
+            })
         })
         .collect()
 }
 
-#[wasm_bindgen]
-pub fn parse_sumo_net_xml(xml_text: &str) -> Result<JsValue, JsValue> {
-    console_log!("Starting WASM XML parsing...");
-    
-    let doc = roxmltree::Document::parse(xml_text)
-        .map_err(|e| JsValue::from_str(&format!("XML parse error: {}", e)))?;
-
-    let root = doc.root_element();
-    
-    // Parse bounds
-    let bounds = root
-        .descendants()
-        .find(|n| n.tag_name().name() == "location")
-        .and_then(|loc| {
-            loc.attribute("convBoundary").and_then(|cb| {
-                let parts: Vec<f64> = cb
-                    .split(',')
-                    .filter_map(|s| s.parse::<f64>().ok())
-                    .collect();
-                if parts.len() == 4 {
-                    Some(Bounds {
-                        min_x: parts[0],
-                        min_y: parts[1],
-                        max_x: parts[2],
-                        max_y: parts[3],
-                    })
-                } else {
-                    None
-                }
-            })
-        });
+// Meters along a shape; plain-geo shapes are lon/lat
+fn shape_length(points: &[(f64, f64)], plain_geo: bool) -> f64 {
+    if plain_geo {
+        geometry::geo_polyline_length(points)
+    } else {
+        geometry::polyline_length(points)
+    }
+}
 
-    console_log!("Parsed bounds: {:?}", bounds.is_some());
+// One "x,y" or "x,y,z" shape point
+fn parse_shape_point(pair: &str) -> Option<(f64, f64, Option<f64>)> {
+    let coord = |c: &str| decimal::parse_f64(c).filter(|v| v.is_finite());
+    let (x, rest) = pair.split_once(',')?;
+    // A fourth coordinate leaves a comma in `z`, which then fails to parse
+    let (y, z) = match rest.split_once(',') {
+        Some((y, z)) => (y, Some(coord(z)?)),
+        None => (rest, None),
+    };
+    Some((coord(x)?, coord(y)?, z))
+}
 
-    // Include ALL edges to match the simple JS parser's connectivity
-    let all_edges: Vec<_> = root
-        .descendants()
-        .filter(|n| n.tag_name().name() == "edge")
+// Order roundabout edges around the ring by chaining each edge's end to the
+// nearest start of a remaining one (the <roundabout> lists them unordered),
+// and concatenate their representative lane shapes.
+fn stitch_roundabout(nodes: Vec<String>, edges: &[String], rep_by_edge: &HashMap<String, Lane>) -> Roundabout {
+    let mut remaining: Vec<(&String, &Lane)> = edges
+        .iter()
+        .filter_map(|e| Some((e, rep_by_edge.get(e)?)))
         .collect();
-    console_log!("Total edges found: {}", all_edges.len());
-
-    // Geometry settings close to JS
-    const SIMPLIFY_EPS: f64 = 5.0;
-    const MAX_POINTS_PER_LANE: usize = 20;
-
-    // Collect ALL internal lanes; for non-internal, keep one representative per edge
-    let mut lanes: Vec<Lane> = Vec::new();
-    let mut rep_by_edge: std::collections::HashMap<String, Lane> = std::collections::HashMap::new();
-    let mut internal_count: usize = 0;
-
-    for edge in all_edges {
-        let edge_id_str = edge
-            .attribute("id")
-            .map(String::from)
-            .unwrap_or_else(|| String::from(""));
-        let function = edge.attribute("function").unwrap_or("");
-        let is_internal_edge = function == "internal";
-
-        for lane_node in edge.descendants().filter(|n| n.tag_name().name() == "lane") {
-            let lane_id = lane_node.attribute("id").unwrap_or("");
-            let shape = lane_node.attribute("shape");
-            let speed = lane_node.attribute("speed").and_then(|s| s.parse::<f64>().ok());
-
-            if let Some(shape_str) = shape {
-                let mut points = parse_point_string(shape_str);
-                if points.len() >= 2 {
-                    if points.len() > 4 { points = rdp_simplify(&points, SIMPLIFY_EPS); }
-                    if points.len() > MAX_POINTS_PER_LANE { points = sample_points(&points, MAX_POINTS_PER_LANE); }
-
-                    let latlngs: Vec<Vec<f64>> = points.iter().map(|(x, y)| vec![*y, *x]).collect();
-                    if latlngs.len() >= 2 {
-                        let lane = Lane {
-                            id: lane_id.to_string(),
-                            edge_id: Some(edge_id_str.clone()),
-                            points: latlngs,
-                            speed,
-                            is_internal: is_internal_edge,
-                        };
-                        if is_internal_edge {
-                            lanes.push(lane);
-                            internal_count += 1;
-                        } else {
-                            // Keep the lane with most points as representative for the edge
-                            let keep = match rep_by_edge.get(&edge_id_str) {
-                                Some(existing) => lane.points.len() > existing.points.len(),
-                                None => true,
-                            };
-                            if keep {
-                                rep_by_edge.insert(edge_id_str.clone(), lane);
-                            }
-                        }
-                    }
-                }
+    let mut ordered = Vec::with_capacity(remaining.len());
+    let mut ring: Vec<Vec<f64>> = Vec::new();
+
+    while !remaining.is_empty() {
+        let next = match ring.last() {
+            None => 0,
+            Some(end) => {
+                let dist = |l: &Lane| (l.points[0][0] - end[0]).powi(2) + (l.points[0][1] - end[1]).powi(2);
+                (0..remaining.len())
+                    .min_by(|a, b| dist(remaining[*a].1).total_cmp(&dist(remaining[*b].1)))
+                    .unwrap_or(0)
             }
+        };
+        let (edge_id, lane) = remaining.swap_remove(next);
+        ordered.push(edge_id.clone());
+        let skip = usize::from(ring.last() == lane.points.first());
+        ring.extend(lane.points.iter().skip(skip).cloned());
+    }
+    if ring.len() > 2 && ring.first() != ring.last() {
+        ring.push(ring[0].clone());
+    }
+
+    Roundabout {
+        nodes,
+        edges: ordered,
+        ring,
+    }
+}
+
+fn parse_point_string(shape: &str) -> Vec<(f64, f64)> {
+    shape
+        .split_ascii_whitespace()
+        .filter_map(parse_shape_point)
+        .map(|(x, y, _)| (x, y))
+        .collect()
+}
+
+// Planar points plus per-point elevation when any point carries a z value
+// (missing ones count as 0, as in SUMO)
+fn parse_point_string_z(shape: &str) -> (Vec<(f64, f64)>, Option<Vec<f64>>) {
+    let mut points = Vec::new();
+    let mut elevation: Option<Vec<f64>> = None;
+    for (x, y, z) in shape.split_ascii_whitespace().filter_map(parse_shape_point) {
+        match (z, elevation.as_mut()) {
+            (Some(z), None) => elevation = Some(std::iter::repeat_n(0.0, points.len()).chain([z]).collect()),
+            (z, Some(e)) => e.push(z.unwrap_or(0.0)),
+            (None, None) => {}
         }
+        points.push((x, y));
     }
+    (points, elevation)
+}
+
+fn parse_bounds(location: roxmltree::Node) -> Option<Bounds> {
+    location.attribute("convBoundary").and_then(|cb| {
+        let parts: Vec<f64> = cb
+            .split(',')
+            .filter_map(|s| s.parse::<f64>().ok())
+            .collect();
+        if parts.len() == 4 {
+            Some(Bounds {
+                min_x: parts[0],
+                min_y: parts[1],
+                max_x: parts[2],
+                max_y: parts[3],
+            })
+        } else {
+            None
+        }
+    })
+}
 
-    // Append representative non-internal lanes
-    lanes.extend(rep_by_edge.into_values());
+// Everything one <edge> contributes to the output, computed without the
+// accumulator so edges can be extracted independently
+struct EdgeParts {
+    id: String,
+    is_internal: bool,
+    lanes: Vec<Lane>,
+    // Raw end segments of non-internal lanes, by lane id
+    lane_ends: Vec<(String, LaneEnds)>,
+}
 
-    console_log!("Output lanes: {} (internals: {})", lanes.len(), internal_count);
+fn extract_edge(edge: roxmltree::Node, epsilon: f64, plain_geo: bool) -> EdgeParts {
+    let edge_id_str = edge
+        .attribute("id")
+        .map(String::from)
+        .unwrap_or_else(|| String::from(""));
+    let function = edge.attribute("function").unwrap_or("");
+    let is_internal_edge = function == "internal";
+    let mut parts = EdgeParts {
+        id: edge_id_str.clone(),
+        is_internal: is_internal_edge,
+        lanes: Vec::new(),
+        lane_ends: Vec::new(),
+    };
 
-    // Parse traffic lights
-    let tls: Vec<TrafficLight> = root
-        .descendants()
-        .filter(|n| {
-            n.tag_name().name() == "junction" 
-            && n.attribute("type") == Some("traffic_light")
-        })
-        .filter_map(|j| {
-            let id = j.attribute("id")?;
-            let cluster_id = j.attribute("tl").unwrap_or(id);
-            let x = j.attribute("x")?.parse::<f64>().ok()?;
-            let y = j.attribute("y")?.parse::<f64>().ok()?;
-            
-            if x.is_finite() && y.is_finite() {
-                Some(TrafficLight {
-                    id: id.to_string(),
-                    cluster_id: cluster_id.to_string(),
-                    lat: y,
-                    lng: x,
-                })
-            } else {
-                None
+    let mut shapes = Vec::new();
+    for lane_node in edge.descendants().filter(|n| n.tag_name().name() == "lane") {
+        let Some(shape) = lane_node.attribute("shape") else { continue };
+        let (points, elevation) = parse_point_string_z(shape);
+        if !is_internal_edge {
+            if let Some(ends) = LaneEnds::from_points(&points) {
+                parts.lane_ends.push((lane_node.attribute("id").unwrap_or("").to_string(), ends));
             }
-        })
-        .collect();
+        }
+        shapes.push((lane_node, points, elevation));
+    }
+    // Lanes are listed by index, rightmost first
+    let lane_length = |(lane_node, points, _): &(roxmltree::Node, Vec<(f64, f64)>, _)| {
+        lane_node.attribute("length").and_then(decimal::parse_f64).unwrap_or_else(|| shape_length(points, plain_geo))
+    };
+    let edge_length = shapes.first().map(lane_length);
 
-    console_log!("Parsed {} traffic lights", tls.len());
-
-    // Parse junctions with polygons
-    let junctions: Vec<Junction> = root
-        .descendants()
-        .filter(|n| n.tag_name().name() == "junction" && n.attribute("shape").is_some())
-        .filter_map(|j| {
-            let id = j.attribute("id")?;
-            let junction_type = j.attribute("type").unwrap_or("");
-            let shape_str = j.attribute("shape")?;
-            
-            let points = parse_point_string(shape_str);
-            if points.len() >= 3 {
-                let polygon: Vec<Vec<f64>> = points
-                    .iter()
-                    .map(|(x, y)| vec![*y, *x])
-                    .collect();
-                
-                Some(Junction {
-                    id: id.to_string(),
-                    junction_type: junction_type.to_string(),
-                    polygon,
-                })
-            } else {
-                None
+    let masks = edge_keep_masks(&shapes.iter().map(|s| &s.1[..]).collect::<Vec<_>>(), epsilon);
+    for (shape, keep) in shapes.iter().zip(masks) {
+        let (lane_node, points, elevation) = shape;
+        let points = retain_kept(points, &keep);
+        if points.len() < 2 {
+            continue;
+        }
+        let speed = lane_node.attribute("speed").and_then(decimal::parse_f64);
+        parts.lanes.push(Lane {
+            id: lane_node.attribute("id").unwrap_or("").to_string(),
+            id_index: None,
+            edge_id: Some(edge_id_str.clone()),
+            edge_index: None,
+            points: points.iter().map(|(x, y)| vec![*y, *x]).collect(),
+            xy: None,
+            elevation: elevation.as_ref().map(|z| retain_kept(z, &keep)),
+            headings: None,
+            speed,
+            speed_kmh: speed.map(units::speed_limit_kmh),
+            speed_mph: speed.map(units::speed_limit_mph),
+            speed_class: speed.map(|s| units::SpeedClass::from_kmh(units::speed_limit_kmh(s))),
+            length: Some(lane_length(shape)),
+            edge_length,
+            capacity: None,
+            allow: lane_node.attribute("allow").map(String::from),
+            disallow: lane_node.attribute("disallow").map(String::from),
+            is_internal: is_internal_edge,
+            is_roundabout: false,
+            is_closed: false,
+        });
+    }
+    parts
+}
+
+#[derive(Default)]
+struct JunctionParts {
+    tl: Option<TrafficLight>,
+    junction: Option<Junction>,
+    point: Option<JunctionPoint>,
+}
+
+fn lane_list(ids: Option<&str>) -> Vec<String> {
+    ids.unwrap_or("").split_whitespace().map(String::from).collect()
+}
+
+// The junction's x, y attributes
+pub(crate) fn junction_position(j: roxmltree::Node) -> Option<(f64, f64)> {
+    j.attribute("x")
+        .zip(j.attribute("y"))
+        .and_then(|(x, y)| Some((decimal::parse_f64(x)?, decimal::parse_f64(y)?)))
+        .filter(|(x, y)| x.is_finite() && y.is_finite())
+}
+
+pub(crate) fn traffic_light(j: roxmltree::Node, position: Option<(f64, f64)>) -> Option<TrafficLight> {
+    if j.attribute("type") != Some("traffic_light") {
+        return None;
+    }
+    let id = j.attribute("id")?;
+    let (x, y) = position?;
+    Some(TrafficLight {
+        id: id.to_string(),
+        cluster_id: j.attribute("tl").unwrap_or(id).to_string(),
+        lat: y,
+        lng: x,
+    })
+}
+
+fn extract_junction(j: roxmltree::Node) -> JunctionParts {
+    let mut parts = JunctionParts::default();
+    let Some(id) = j.attribute("id") else { return parts };
+    let junction_type = j.attribute("type").unwrap_or("");
+    let position = junction_position(j);
+
+    // Traffic lights
+    parts.tl = traffic_light(j, position);
+
+    // Junctions with polygons
+    if let Some(shape_str) = j.attribute("shape") {
+        let points = parse_point_string(shape_str);
+        if points.len() >= 3 {
+            let polygon: Vec<Vec<f64>> = points
+                .iter()
+                .map(|(x, y)| vec![*y, *x])
+                .collect();
+
+            parts.junction = Some(Junction {
+                id: id.to_string(),
+                id_index: None,
+                junction_type: junction_type.to_string(),
+                hint: JunctionHint::from_type(junction_type),
+                polygon,
+                xy: None,
+                is_roundabout: false,
+                inc_lanes: lane_list(j.attribute("incLanes")),
+                int_lanes: lane_list(j.attribute("intLanes")),
+            });
+        }
+    }
+
+    // Junction points (fallback)
+    if let Some((x, y)) = position {
+        parts.point = Some(JunctionPoint {
+            id: id.to_string(),
+            id_index: None,
+            junction_type: junction_type.to_string(),
+            hint: JunctionHint::from_type(junction_type),
+            lat: y,
+            lng: x,
+        });
+    }
+    parts
+}
+
+// Geometry settings close to JS
+const SIMPLIFY_EPS: f64 = 5.0;
+const MAX_POINTS_PER_LANE: usize = 20;
+// Chaikin passes for ParseOptions::smooth_internal; 4 already gives 16x the points
+const MAX_SMOOTH_PASSES: u32 = 4;
+
+// Edges and junctions to build when loading part of a scanned network (see
+// scan.rs); internal edges come with their junction
+pub(crate) struct Subset {
+    pub edges: HashSet<String>,
+    pub junctions: HashSet<String>,
+}
+
+impl Subset {
+    fn keeps(&self, node: roxmltree::Node) -> bool {
+        let id = node.attribute("id").unwrap_or("");
+        match node.tag_name().name() {
+            // Internal edges are ":<junction id>_<n>"
+            "edge" => match id.strip_prefix(':') {
+                Some(internal) => internal.rsplit_once('_').is_some_and(|(j, _)| self.junctions.contains(j)),
+                None => self.edges.contains(id),
+            },
+            "junction" => self.junctions.contains(id),
+            "roundabout" => node.attribute("edges").unwrap_or("").split_whitespace().all(|e| self.edges.contains(e)),
+            _ => true,
+        }
+    }
+}
+
+// Builds a ParsedNetwork from top-level net.xml elements fed in document order,
+// so the same logic serves whole-document and streamed parsing.
+struct NetAccumulator {
+    bounds: Option<Bounds>,
+    geo: GeoReference,
+    // ALL internal lanes; for non-internal, one representative per edge
+    lanes: Vec<Lane>,
+    rep_by_edge: HashMap<String, Lane>,
+    lane_ends: HashMap<String, LaneEnds>,
+    pending_connections: Vec<PendingConnection>,
+    internal_count: usize,
+    edge_count: usize,
+    tls: Vec<TrafficLight>,
+    junctions: Vec<Junction>,
+    junction_points: Vec<JunctionPoint>,
+    // (nodes, edges) of each <roundabout>
+    roundabouts: Vec<(Vec<String>, Vec<String>)>,
+    fields: FieldSelection,
+    v_class: Option<net::VClassFilter>,
+    subset: Option<Subset>,
+}
+
+impl NetAccumulator {
+    fn new() -> NetAccumulator {
+        NetAccumulator {
+            bounds: None,
+            geo: GeoReference::Unreferenced,
+            lanes: Vec::new(),
+            rep_by_edge: HashMap::new(),
+            lane_ends: HashMap::new(),
+            pending_connections: Vec::new(),
+            internal_count: 0,
+            edge_count: 0,
+            tls: Vec::new(),
+            junctions: Vec::new(),
+            junction_points: Vec::new(),
+            roundabouts: Vec::new(),
+            fields: FieldSelection::default(),
+            v_class: None,
+            subset: None,
+        }
+    }
+
+    // Build only `fields` of the result (see ParseOptions::fields)
+    fn select(&mut self, fields: Option<Vec<NetField>>) {
+        self.fields = FieldSelection(fields);
+    }
+
+    // Keep only lanes `filter` matches (see ParseOptions::v_class)
+    fn filter_v_class(&mut self, filter: Option<net::VClassFilter>) {
+        self.v_class = filter;
+    }
+
+    // Build only the edges and junctions of `subset`
+    fn restrict(&mut self, subset: Subset) {
+        self.subset = Some(subset);
+    }
+
+    fn add_element(&mut self, node: roxmltree::Node) {
+        if self.subset.as_ref().is_some_and(|s| !s.keeps(node)) {
+            return;
+        }
+        match node.tag_name().name() {
+            "location" if self.bounds.is_none() => {
+                self.bounds = parse_bounds(node);
+                self.geo = GeoReference::from_location(node);
             }
-        })
-        .collect();
+            "edge" if self.fields.wants_edge(node) => self.add_edge(node),
+            "junction" if self.fields.wants_junction() => self.add_junction(node),
+            "connection" if self.fields.wants(NetField::InternalLanes) => self.add_connection(node),
+            "roundabout" => {
+                let list = |name: &str| -> Vec<String> {
+                    node.attribute(name).unwrap_or("").split_whitespace().map(String::from).collect()
+                };
+                self.roundabouts.push((list("nodes"), list("edges")));
+            }
+            _ => {}
+        }
+    }
+
+    // The tolerance is in meters; plain-geo shapes are in degrees
+    fn simplify_epsilon(&self) -> f64 {
+        if self.geo.is_plain_geo() {
+            SIMPLIFY_EPS / projection::METERS_PER_DEGREE
+        } else {
+            SIMPLIFY_EPS
+        }
+    }
 
-    console_log!("Parsed {} junctions", junctions.len());
+    fn add_edge(&mut self, edge: roxmltree::Node) {
+        let parts = extract_edge(edge, self.simplify_epsilon(), self.geo.is_plain_geo());
+        self.add_edge_parts(parts);
+    }
 
-    // Parse junction points (fallback)
-    let junction_points: Vec<JunctionPoint> = root
-        .descendants()
-        .filter(|n| {
-            n.tag_name().name() == "junction" 
-            && n.attribute("x").is_some()
-            && n.attribute("y").is_some()
-        })
-        .filter_map(|j| {
-            let id = j.attribute("id")?;
-            let x = j.attribute("x")?.parse::<f64>().ok()?;
-            let y = j.attribute("y")?.parse::<f64>().ok()?;
-            
-            if x.is_finite() && y.is_finite() {
-                Some(JunctionPoint {
-                    id: id.to_string(),
-                    lat: y,
-                    lng: x,
-                })
+    fn add_edge_parts(&mut self, mut parts: EdgeParts) {
+        self.edge_count += 1;
+        if let Some(filter) = &self.v_class {
+            parts.lanes.retain(|l| filter.matches(l.allow.as_deref(), l.disallow.as_deref()));
+            // No connection curves to or from dropped lanes
+            parts.lane_ends.retain(|(id, _)| parts.lanes.iter().any(|l| l.id == *id));
+        }
+        self.lane_ends.extend(parts.lane_ends);
+        for lane in parts.lanes {
+            if parts.is_internal {
+                self.lanes.push(lane);
+                self.internal_count += 1;
             } else {
-                None
+                // Keep the lane with most points as representative for the edge
+                let keep = match self.rep_by_edge.get(&parts.id) {
+                    Some(existing) => lane.points.len() > existing.points.len(),
+                    None => true,
+                };
+                if keep {
+                    self.rep_by_edge.insert(parts.id.clone(), lane);
+                }
             }
-        })
-        .collect();
+        }
+    }
 
-    console_log!("Parsed {} junction points", junction_points.len());
+    fn add_junction(&mut self, j: roxmltree::Node) {
+        let parts = extract_junction(j);
+        self.add_junction_parts(parts);
+    }
 
-    let result = ParsedNetwork {
-        lanes,
-        bounds,
-        tls,
-        junctions,
-        junction_points,
-    };
+    fn add_junction_parts(&mut self, parts: JunctionParts) {
+        if self.fields.wants(NetField::Tls) {
+            self.tls.extend(parts.tl);
+        }
+        if self.fields.wants(NetField::Junctions) {
+            self.junctions.extend(parts.junction);
+        }
+        if self.fields.wants(NetField::JunctionPoints) {
+            self.junction_points.extend(parts.point);
+        }
+    }
 
-    console_log!("WASM parsing complete!");
-    
-    serde_wasm_bindgen::to_value(&result)
-        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+    // A whole parsed document. With the `parallel` feature, edges and
+    // junctions (the bulk of the work: shape parsing and simplification) are
+    // extracted across the rayon pool, then merged in document order so the
+    // output matches the serial parse exactly.
+    #[cfg(not(feature = "parallel"))]
+    fn add_document(&mut self, root: roxmltree::Node) {
+        for node in root.descendants() {
+            self.add_element(node);
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    fn add_document(&mut self, root: roxmltree::Node) {
+        use rayon::prelude::*;
+
+        let nodes: Vec<roxmltree::Node> = root.descendants().filter(|n| n.is_element()).collect();
+        // The simplification tolerance depends on the <location>, which
+        // precedes all edges
+        if let Some(location) = nodes.iter().find(|n| n.has_tag_name("location")) {
+            self.add_element(*location);
+        }
+        let epsilon = self.simplify_epsilon();
+        let plain_geo = self.geo.is_plain_geo();
+        let fields = self.fields.clone();
+        let subset = self.subset.as_ref();
+
+        enum Parts {
+            Edge(EdgeParts),
+            Junction(Box<JunctionParts>),
+            Other,
+        }
+        let parts: Vec<Parts> = nodes
+            .par_iter()
+            .map(|n| match n.tag_name().name() {
+                _ if subset.is_some_and(|s| !s.keeps(*n)) => Parts::Other,
+                "edge" if fields.wants_edge(*n) => Parts::Edge(extract_edge(*n, epsilon, plain_geo)),
+                "junction" if fields.wants_junction() => Parts::Junction(Box::new(extract_junction(*n))),
+                _ => Parts::Other,
+            })
+            .collect();
+        for (node, parts) in nodes.into_iter().zip(parts) {
+            match parts {
+                Parts::Edge(p) => self.add_edge_parts(p),
+                Parts::Junction(p) => self.add_junction_parts(*p),
+                Parts::Other => self.add_element(node),
+            }
+        }
+    }
+
+    fn add_connection(&mut self, c: roxmltree::Node) {
+        if c.attribute("via").is_some() {
+            return;
+        }
+        let (Some(from), Some(to), Some(from_lane), Some(to_lane)) = (
+            c.attribute("from"),
+            c.attribute("to"),
+            c.attribute("fromLane"),
+            c.attribute("toLane"),
+        ) else {
+            return;
+        };
+        if from.starts_with(':') || to.starts_with(':') {
+            return;
+        }
+        self.pending_connections.push(PendingConnection {
+            from: from.to_string(),
+            from_lane: from_lane.to_string(),
+            to: to.to_string(),
+            to_lane: to_lane.to_string(),
+        });
+    }
+
+    fn finish(mut self) -> ParsedNetwork {
+        console_debug!("Parsed bounds: {:?}", self.bounds.is_some());
+        console_debug!("Total edges found: {}", self.edge_count);
+
+        // Roundabouts come last in the file, so flag their members now
+        let roundabouts: Vec<Roundabout> = if self.fields.wants(NetField::Roundabouts) {
+            self.roundabouts
+                .iter()
+                .map(|(nodes, edges)| stitch_roundabout(nodes.clone(), edges, &self.rep_by_edge))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let member_edges: HashSet<&str> = self.roundabouts.iter().flat_map(|r| r.1.iter().map(String::as_str)).collect();
+        let member_nodes: HashSet<&str> = self.roundabouts.iter().flat_map(|r| r.0.iter().map(String::as_str)).collect();
+        for junction in &mut self.junctions {
+            junction.is_roundabout = member_nodes.contains(junction.id.as_str());
+        }
+        for lane in self.lanes.iter_mut().chain(self.rep_by_edge.values_mut()) {
+            let Some(edge_id) = lane.edge_id.as_deref() else { continue };
+            // Internal edges are ":<junction id>_<n>"
+            lane.is_roundabout = match edge_id.strip_prefix(':') {
+                Some(internal) => internal
+                    .rsplit_once('_')
+                    .is_some_and(|(junction, _)| member_nodes.contains(junction)),
+                None => member_edges.contains(edge_id),
+            };
+        }
+        console_debug!("Parsed {} roundabouts", roundabouts.len());
+
+        // Append representative non-internal lanes
+        if self.fields.wants(NetField::Lanes) {
+            self.lanes.extend(self.rep_by_edge.into_values());
+        }
+
+        let plain_geo = self.geo.is_plain_geo();
+        let synthesized = synthesize_connection_lanes(&self.pending_connections, &self.lane_ends, plain_geo);
+        console_debug!("Synthesized {} connection curves", synthesized.len());
+        self.internal_count += synthesized.len();
+        self.lanes.extend(synthesized);
+
+        console_debug!("Output lanes: {} (internals: {})", self.lanes.len(), self.internal_count);
+        console_debug!("Parsed {} traffic lights", self.tls.len());
+        console_debug!("Parsed {} junctions", self.junctions.len());
+        console_debug!("Parsed {} junction points", self.junction_points.len());
+
+        ParsedNetwork {
+            lanes: self.lanes,
+            bounds: self.bounds.filter(|_| self.fields.wants(NetField::Bounds)),
+            tls: self.tls,
+            crs: self.geo.native_crs(),
+            junctions: self.junctions,
+            junction_points: self.junction_points,
+            roundabouts,
+            quantization: None,
+            ids: None,
+        }
+    }
+}
+
+#[wasm_bindgen(unchecked_return_type = "ParsedNetwork")]
+pub fn parse_sumo_net_xml(xml_text: &str) -> Result<JsValue, JsValue> {
+    parse_sumo_net_xml_with_options(xml_text, JsValue::UNDEFINED)
+}
+
+#[wasm_bindgen(unchecked_return_type = "ParsedNetwork")]
+pub fn parse_sumo_net_xml_with_options(
+    xml_text: &str,
+    #[wasm_bindgen(unchecked_param_type = "ParseOptions | undefined")] options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let options: ParseOptions = parse_options(options)?;
+    logging::with_level(options.log_level, || {
+        console_log!("Starting WASM XML parsing...");
+
+        let doc = parse_xml(xml_text)?;
+
+        let mut acc = NetAccumulator::new();
+        acc.select(options.fields.clone());
+        acc.filter_v_class(options.v_class_filter());
+        acc.add_document(doc.root_element());
+        let geo = acc.geo.clone();
+        let mut result = acc.finish();
+        result.apply_options(&geo, &options)?;
+
+        console_log!("WASM parsing complete!");
+
+        to_js(&result)
+    })
 }
@@ -0,0 +1,90 @@
+// Linear referencing on lanes: SUMO places detectors, stops and vehicles by
+// (lane, position in meters). Positions refer to the lane's length attribute,
+// which may differ from its drawn shape (netconvert keeps the real length when
+// geometry is shortened at junctions), so they are scaled onto the shape as
+// SUMO does, on the unsimplified geometry. That geometry stays in WASM and is
+// handed out per lane on request, since render output is simplified.
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+
+use crate::geometry;
+use crate::net::NetModel;
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct LaneShape {
+    // [lat, lng], every point of the network file
+    pub points: Vec<Vec<f64>>,
+    // z per point when the shape is 3D
+    pub elevation: Option<Vec<f64>>,
+    // Meters, the lane's length attribute (what SUMO positions refer to)
+    pub length: f64,
+}
+
+// Lane shapes of a NetModel, which holds the only full-resolution copy,
+// viewed with lengths for linear referencing
+pub(crate) struct LaneGeometry<'a> {
+    net: &'a NetModel,
+    // Meters per shape unit (degrees in plain-geo networks), for lanes
+    // without a length attribute
+    scale: f64,
+}
+
+struct LaneLine<'a> {
+    shape: &'a [(f64, f64)],
+    elevation: Option<&'a [f64]>,
+    shape_length: f64,
+    // Meters that positions run over
+    length: f64,
+}
+
+impl LaneLine<'_> {
+    // Shape units per position meter
+    fn factor(&self) -> f64 {
+        if self.length > 0.0 {
+            self.shape_length / self.length
+        } else {
+            1.0
+        }
+    }
+}
+
+impl<'a> LaneGeometry<'a> {
+    pub fn new(net: &'a NetModel, scale: f64) -> LaneGeometry<'a> {
+        LaneGeometry { net, scale }
+    }
+
+    fn line(&self, lane_id: &str) -> Option<LaneLine<'a>> {
+        let lane = self.net.lane(lane_id).filter(|l| !l.shape.is_empty())?;
+        let shape_length = geometry::polyline_length(&lane.shape);
+        Some(LaneLine {
+            shape: &lane.shape,
+            elevation: lane.elevation.as_deref(),
+            shape_length,
+            length: lane.length.filter(|len| *len > 0.0).unwrap_or(shape_length * self.scale),
+        })
+    }
+
+    // Point at `pos` meters along the lane; negative positions count back
+    // from the end, as in SUMO. Clamped to the lane's ends.
+    pub fn point_at(&self, lane_id: &str, pos: f64) -> Option<(f64, f64)> {
+        let lane = self.line(lane_id)?;
+        let pos = if pos < 0.0 { lane.length + pos } else { pos };
+        geometry::point_at(lane.shape, pos * lane.factor())
+    }
+
+    // Position along the lane (meters) of the point on it closest to `p`
+    pub fn offset_of(&self, lane_id: &str, p: (f64, f64)) -> Option<f64> {
+        let lane = self.line(lane_id)?;
+        let (offset, _) = geometry::project_onto(lane.shape, p)?;
+        Some((offset / lane.factor()).min(lane.length))
+    }
+
+    pub fn shape(&self, lane_id: &str) -> Option<LaneShape> {
+        let lane = self.line(lane_id)?;
+        Some(LaneShape {
+            points: lane.shape.iter().map(|(x, y)| vec![*y, *x]).collect(),
+            elevation: lane.elevation.map(<[f64]>::to_vec),
+            length: lane.length,
+        })
+    }
+}
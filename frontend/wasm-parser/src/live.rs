@@ -0,0 +1,202 @@
+// Live changes to a Network handle (speed limits, closures) with snapshot
+// semantics. The state is copy-on-write behind an Rc: an update builds the
+// next state completely before swapping it in under a new generation, and a
+// caller that spreads a query over several calls (tiles of one viewport,
+// slices of a pan) pins a generation so later updates don't show up halfway
+// through.
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::{units, Lane};
+
+// Updates remembered for changes_since(); older generations need a full refresh
+const CHANGE_LOG_LENGTH: usize = 1024;
+
+// One atomic change: all of it applies, or none if any edge is unknown
+#[derive(Deserialize, Default, Tsify)]
+#[serde(default)]
+pub struct NetworkUpdate {
+    // New speed limit (m/s) by edge id; null restores the network's own
+    pub speeds: HashMap<String, Option<f64>>,
+    // Edge ids to close / reopen
+    pub close: Vec<String>,
+    pub reopen: Vec<String>,
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct LiveState {
+    pub generation: u64,
+    speeds: HashMap<String, f64>,
+    closed: HashSet<String>,
+}
+
+impl LiveState {
+    pub fn is_empty(&self) -> bool {
+        self.speeds.is_empty() && self.closed.is_empty()
+    }
+
+    // Speed limit set for the edge, if changed
+    pub fn speed(&self, edge: &str) -> Option<f64> {
+        self.speeds.get(edge).copied()
+    }
+
+    pub fn is_closed(&self, edge: &str) -> bool {
+        self.closed.contains(edge)
+    }
+
+    // Lane as it is in this state
+    pub fn apply(&self, lane: &mut Lane) {
+        let Some(edge) = lane.edge_id.as_deref() else { return };
+        let speed = self.speeds.get(edge).copied();
+        lane.is_closed = self.closed.contains(edge);
+        if let Some(speed) = speed {
+            lane.set_speed(speed);
+        }
+    }
+}
+
+impl Lane {
+    fn set_speed(&mut self, speed: f64) {
+        self.speed = Some(speed);
+        self.speed_kmh = Some(units::speed_limit_kmh(speed));
+        self.speed_mph = Some(units::speed_limit_mph(speed));
+        self.speed_class = Some(units::SpeedClass::from_kmh(units::speed_limit_kmh(speed)));
+    }
+}
+
+// Current state plus the generations pinned by readers
+#[derive(Default)]
+pub(crate) struct LiveStates {
+    current: Rc<LiveState>,
+    pinned: HashMap<u64, Rc<LiveState>>,
+    // Edges each recent generation touched, oldest first
+    changes: Vec<(u64, Vec<String>)>,
+}
+
+impl LiveStates {
+    pub fn generation(&self) -> u64 {
+        self.current.generation
+    }
+
+    // `edges`: ids an update may refer to. Returns the new generation.
+    pub fn update(&mut self, update: NetworkUpdate, edges: &HashSet<&str>) -> Result<u64, String> {
+        let unknown: Vec<&str> = update
+            .speeds
+            .keys()
+            .chain(&update.close)
+            .chain(&update.reopen)
+            .map(String::as_str)
+            .filter(|id| !edges.contains(id))
+            .collect();
+        if !unknown.is_empty() {
+            return Err(format!("Unknown edges in update: {}", unknown.join(", ")));
+        }
+        if let Some((id, _)) = update.speeds.iter().find(|(_, s)| s.is_some_and(|s| !(s.is_finite() && s >= 0.0))) {
+            return Err(format!("Invalid speed for edge {}", id));
+        }
+
+        let mut touched: Vec<String> =
+            update.speeds.keys().chain(&update.close).chain(&update.reopen).cloned().collect();
+        touched.sort_unstable();
+        touched.dedup();
+
+        // Clones only when a pinned snapshot still shares the state
+        let state = Rc::make_mut(&mut self.current);
+        for (edge, speed) in update.speeds {
+            match speed {
+                Some(speed) => state.speeds.insert(edge, speed),
+                None => state.speeds.remove(&edge),
+            };
+        }
+        for edge in &update.reopen {
+            state.closed.remove(edge);
+        }
+        state.closed.extend(update.close);
+        state.generation += 1;
+
+        let generation = state.generation;
+        if self.changes.len() == CHANGE_LOG_LENGTH {
+            self.changes.remove(0);
+        }
+        self.changes.push((generation, touched));
+        Ok(generation)
+    }
+
+    // Edges touched by the updates after generation `since`
+    pub fn changed_since(&self, since: u64) -> Result<HashSet<&str>, String> {
+        let oldest = self.changes.first().map_or(self.current.generation, |(g, _)| g - 1);
+        if since < oldest || since > self.current.generation {
+            return Err(format!("No change log from generation {}; refresh everything", since));
+        }
+        Ok(self
+            .changes
+            .iter()
+            .filter(|(g, _)| *g > since)
+            .flat_map(|(_, edges)| edges.iter().map(String::as_str))
+            .collect())
+    }
+
+    pub fn pin(&mut self) -> u64 {
+        let generation = self.current.generation;
+        self.pinned.insert(generation, self.current.clone());
+        generation
+    }
+
+    pub fn unpin(&mut self, generation: u64) {
+        self.pinned.remove(&generation);
+    }
+
+    // The current state, or a pinned one
+    pub fn get(&self, generation: Option<u64>) -> Result<Rc<LiveState>, String> {
+        match generation {
+            None => Ok(self.current.clone()),
+            Some(g) if g == self.current.generation => Ok(self.current.clone()),
+            Some(g) => self
+                .pinned
+                .get(&g)
+                .cloned()
+                .ok_or_else(|| format!("Generation {} is not pinned", g)),
+        }
+    }
+}
+
+// What to repaint after updates: the vector tiles their lanes cross and the
+// new colors of their paths in path_buffers()
+#[wasm_bindgen]
+pub struct LiveDelta {
+    pub(crate) generation: u64,
+    pub(crate) tiles: Vec<u32>,
+    pub(crate) path_indices: Vec<u32>,
+    pub(crate) colors: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl LiveDelta {
+    // Generation the delta brings the caller up to
+    #[wasm_bindgen(getter)]
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    // x, y pairs of the dirty tiles at the requested zoom
+    #[wasm_bindgen(getter)]
+    pub fn tiles(&self) -> Vec<u32> {
+        self.tiles.clone()
+    }
+
+    // Indices of the changed paths in path_buffers(), ascending
+    #[wasm_bindgen(getter, js_name = pathIndices)]
+    pub fn path_indices(&self) -> Vec<u32> {
+        self.path_indices.clone()
+    }
+
+    // r, g, b, a per vertex of those paths, in the same order; each path's
+    // run replaces its range of path_buffers().colors
+    #[wasm_bindgen(getter)]
+    pub fn colors(&self) -> Vec<u8> {
+        self.colors.clone()
+    }
+}
@@ -0,0 +1,82 @@
+use serde::Deserialize;
+use std::cell::{Cell, RefCell};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+#[derive(Deserialize, Tsify, Clone, Copy, PartialEq, PartialOrd)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Off,
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    fn parse(level: &str) -> Option<LogLevel> {
+        match level {
+            "off" => Some(LogLevel::Off),
+            "info" => Some(LogLevel::Info),
+            "debug" => Some(LogLevel::Debug),
+            _ => None,
+        }
+    }
+}
+
+thread_local! {
+    static LEVEL: Cell<LogLevel> = const { Cell::new(LogLevel::Info) };
+    static CALLBACK: RefCell<Option<js_sys::Function>> = const { RefCell::new(None) };
+}
+
+#[cfg(all(feature = "logging", target_arch = "wasm32"))]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = console)]
+    fn log(s: &str);
+}
+
+// Native builds (tests, fuzzing) have no console
+#[cfg(all(feature = "logging", not(target_arch = "wasm32")))]
+fn log(s: &str) {
+    eprintln!("{}", s);
+}
+
+// Checked before formatting, so disabled messages cost nothing
+#[cfg(feature = "logging")]
+pub(crate) fn enabled(level: LogLevel) -> bool {
+    level != LogLevel::Off && LEVEL.with(|l| level <= l.get())
+}
+
+#[cfg(feature = "logging")]
+pub(crate) fn emit(message: &str) {
+    CALLBACK.with(|cb| match cb.borrow().as_ref() {
+        Some(f) => {
+            let _ = f.call1(&JsValue::NULL, &JsValue::from_str(message));
+        }
+        None => log(message),
+    });
+}
+
+// Run `f` with a temporary level, e.g. from ParseOptions.logLevel
+pub(crate) fn with_level<T>(level: Option<LogLevel>, f: impl FnOnce() -> T) -> T {
+    let Some(level) = level else { return f() };
+    let previous = LEVEL.with(|l| l.replace(level));
+    let result = f();
+    LEVEL.with(|l| l.set(previous));
+    result
+}
+
+// Global level for every entry point: "off", "info" (default) or "debug"
+#[wasm_bindgen]
+pub fn set_log_level(level: &str) -> Result<(), JsValue> {
+    let level = LogLevel::parse(level)
+        .ok_or_else(|| JsValue::from_str(&format!("Unknown log level: {}", level)))?;
+    LEVEL.with(|l| l.set(level));
+    Ok(())
+}
+
+// Route log messages to `callback(message)` instead of console.log; pass
+// undefined to restore the console
+#[wasm_bindgen]
+pub fn set_log_callback(callback: Option<js_sys::Function>) {
+    CALLBACK.with(|cb| *cb.borrow_mut() = callback);
+}
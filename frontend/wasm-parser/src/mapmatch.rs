@@ -0,0 +1,317 @@
+// Map-matching of GPS traces onto network edges: a hidden Markov model in the
+// style of Newson & Krumm (2009). Each point's candidates are the edges within
+// the search radius; emission scores fall off with the GPS error and
+// transitions with the difference between route and straight-line distance.
+use serde::{Deserialize, Serialize};
+use std::collections::{BinaryHeap, HashMap};
+use tsify::Tsify;
+
+use crate::geometry;
+use crate::graph::Queued;
+use crate::net::NetModel;
+use crate::projection::Crs;
+use crate::spatial::SegmentGrid;
+
+pub(crate) const DEFAULT_SEARCH_RADIUS: f64 = 50.0;
+// Standard deviation of the GPS error (meters)
+pub(crate) const DEFAULT_SIGMA: f64 = 10.0;
+// Tolerated detour per transition (meters)
+pub(crate) const DEFAULT_BETA: f64 = 20.0;
+// Edges some lane of which admits one of these are matched against
+const ROAD_CLASSES: [&str; 2] = ["passenger", "bus"];
+const MAX_CANDIDATES: usize = 8;
+const INDEX_CELL_SIZE: f64 = 100.0;
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct TracePoint {
+    pub lat: f64,
+    pub lng: f64,
+    // Seconds; passed through to the result
+    #[serde(default)]
+    pub time: Option<f64>,
+}
+
+#[derive(Deserialize, Default, Tsify)]
+#[serde(default)]
+pub struct TraceMatchOptions {
+    // Frame of the trace points (default "wgs84"; "network" for points
+    // already in network coordinates)
+    pub crs: Option<Crs>,
+    // Meters around each point searched for candidate edges (default 50)
+    #[serde(rename = "searchRadius")]
+    pub search_radius: Option<f64>,
+    // GPS error standard deviation in meters (default 10)
+    pub sigma: Option<f64>,
+    // Detour scale in meters (default 20); larger trusts the network less
+    pub beta: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct MatchedPoint {
+    pub time: Option<f64>,
+    // None when no edge lies within the search radius
+    #[serde(rename = "edgeId")]
+    pub edge_id: Option<String>,
+    // Meters along the edge
+    pub offset: Option<f64>,
+    // Meters between the GPS point and the matched position
+    pub distance: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct MatchedTrace {
+    // Traversed edges in order, including those between matched points
+    pub edges: Vec<String>,
+    // One entry per input point
+    pub points: Vec<MatchedPoint>,
+}
+
+#[derive(Clone, Copy)]
+struct Candidate {
+    edge: usize,
+    offset: f64,
+    distance: f64,
+}
+
+// Edge-level graph of the road network, with one centerline per edge. The
+// centerlines stay in the NetModel the graph was built from, which every
+// query takes.
+pub(crate) struct RoadGraph {
+    ids: Vec<String>,
+    // (edge, lane) positions in the model's edges and that edge's lanes
+    centerlines: Vec<(usize, usize)>,
+    lengths: Vec<f64>,
+    successors: Vec<Vec<usize>>,
+    // Owners are indices into `ids`
+    index: SegmentGrid,
+}
+
+// Distance from the end of the source edge to the start of each reachable
+// edge, with the edge it was reached from (None for direct successors)
+type Reach = HashMap<usize, (f64, Option<usize>)>;
+
+impl RoadGraph {
+    pub fn from_model(net: &NetModel) -> RoadGraph {
+        let (model_index, edges): (Vec<usize>, Vec<_>) = net
+            .edges
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| !e.is_internal() && e.lanes.iter().any(|l| ROAD_CLASSES.iter().any(|c| l.permits(c))))
+            .unzip();
+        let position: HashMap<&str, usize> = edges.iter().enumerate().map(|(i, e)| (e.id.as_str(), i)).collect();
+
+        let mut successors = vec![Vec::new(); edges.len()];
+        for c in &net.connections {
+            if let (Some(&from), Some(&to)) = (position.get(c.from.as_str()), position.get(c.to.as_str())) {
+                if !successors[from].contains(&to) {
+                    successors[from].push(to);
+                }
+            }
+        }
+
+        let mut index = SegmentGrid::new(INDEX_CELL_SIZE);
+        let mut centerlines = Vec::with_capacity(edges.len());
+        let mut lengths = Vec::with_capacity(edges.len());
+        for (i, edge) in edges.iter().enumerate() {
+            // The middle lane approximates the centerline GPS points scatter around
+            let lane = edge.lanes.len() / 2;
+            let shape = &edge.lanes[lane].shape;
+            index.insert_polyline(i, shape);
+            centerlines.push((model_index[i], lane));
+            lengths.push(geometry::polyline_length(shape));
+        }
+        index.shrink_to_fit();
+
+        RoadGraph {
+            ids: edges.iter().map(|e| e.id.clone()).collect(),
+            centerlines,
+            lengths,
+            successors,
+            index,
+        }
+    }
+
+    fn centerline<'n>(&self, net: &'n NetModel, edge: usize) -> &'n [(f64, f64)] {
+        let (edge, lane) = self.centerlines[edge];
+        &net.edges[edge].lanes[lane].shape
+    }
+
+    fn candidates(&self, net: &NetModel, p: (f64, f64), radius: f64) -> Vec<Candidate> {
+        let mut found: Vec<Candidate> = self
+            .index
+            .owners_in_box((p.0 - radius, p.1 - radius), (p.0 + radius, p.1 + radius))
+            .into_iter()
+            .filter_map(|edge| {
+                let (offset, distance) = geometry::project_onto(self.centerline(net, edge), p)?;
+                (distance <= radius).then_some(Candidate { edge, offset, distance })
+            })
+            .collect();
+        found.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        found.truncate(MAX_CANDIDATES);
+        found
+    }
+
+    fn reachable(&self, from: usize, limit: f64) -> Reach {
+        let mut reach: Reach = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        for &s in &self.successors[from] {
+            reach.insert(s, (0.0, None));
+            queue.push(Queued(0.0, s));
+        }
+        while let Some(Queued(d, edge)) = queue.pop() {
+            if d > reach.get(&edge).map_or(f64::INFINITY, |r| r.0) {
+                continue;
+            }
+            let next = d + self.lengths[edge];
+            if next > limit {
+                continue;
+            }
+            for &s in &self.successors[edge] {
+                if next < reach.get(&s).map_or(f64::INFINITY, |r| r.0) {
+                    reach.insert(s, (next, Some(edge)));
+                    queue.push(Queued(next, s));
+                }
+            }
+        }
+        reach
+    }
+
+    // Route length between two candidates, if `b` is reachable from `a`.
+    // Small backward moves on the same edge count as standing still.
+    fn route_distance(&self, a: &Candidate, b: &Candidate, reach: &Reach, tolerance: f64) -> Option<f64> {
+        if a.edge == b.edge && b.offset >= a.offset - tolerance {
+            return Some((b.offset - a.offset).max(0.0));
+        }
+        reach.get(&b.edge).map(|(d, _)| self.lengths[a.edge] - a.offset + d + b.offset)
+    }
+
+    // Edges after the source up to and including `to`
+    fn path(reach: &Reach, to: usize) -> Vec<usize> {
+        let mut path = vec![to];
+        let mut current = to;
+        while let Some(&(_, Some(prev))) = reach.get(&current) {
+            if path.contains(&prev) {
+                break;
+            }
+            path.push(prev);
+            current = prev;
+        }
+        path.reverse();
+        path
+    }
+}
+
+// Thresholds in network units; `scale` converts them back to meters for output
+pub(crate) struct MatchParams {
+    pub radius: f64,
+    pub sigma: f64,
+    pub beta: f64,
+    pub scale: f64,
+}
+
+// `net` is the model `graph` was built from
+pub(crate) fn match_points(
+    graph: &RoadGraph,
+    net: &NetModel,
+    points: &[(f64, f64)],
+    times: &[Option<f64>],
+    params: &MatchParams,
+) -> MatchedTrace {
+    let candidates: Vec<Vec<Candidate>> = points.iter().map(|p| graph.candidates(net, *p, params.radius)).collect();
+    let limit_for = |gc: f64| 3.0 * gc + 2.0 * params.radius + 10.0 * params.beta;
+
+    // Viterbi over the points that have candidates; a point no candidate of
+    // the previous one can reach starts a new chain
+    let n = points.len();
+    let mut scores: Vec<Vec<f64>> = vec![Vec::new(); n];
+    let mut back: Vec<Vec<usize>> = vec![Vec::new(); n];
+    let mut prev_point: Vec<Option<usize>> = vec![None; n];
+    let mut chain_ends = Vec::new();
+    let mut last: Option<usize> = None;
+
+    for t in 0..n {
+        if candidates[t].is_empty() {
+            continue;
+        }
+        let emission: Vec<f64> = candidates[t].iter().map(|c| -0.5 * (c.distance / params.sigma).powi(2)).collect();
+
+        let mut linked = false;
+        if let Some(s) = last {
+            let gc = geometry::distance(points[s], points[t]);
+            let reaches: Vec<Reach> = candidates[s].iter().map(|c| graph.reachable(c.edge, limit_for(gc))).collect();
+            let mut row = vec![f64::NEG_INFINITY; candidates[t].len()];
+            let mut from = vec![0; candidates[t].len()];
+            for (j, b) in candidates[t].iter().enumerate() {
+                for (i, a) in candidates[s].iter().enumerate() {
+                    let Some(route) = graph.route_distance(a, b, &reaches[i], params.sigma) else { continue };
+                    let score = scores[s][i] - (route - gc).abs() / params.beta + emission[j];
+                    if score > row[j] {
+                        row[j] = score;
+                        from[j] = i;
+                    }
+                }
+            }
+            if row.iter().any(|s| s.is_finite()) {
+                scores[t] = row;
+                back[t] = from;
+                prev_point[t] = Some(s);
+                linked = true;
+            } else {
+                chain_ends.push(s);
+            }
+        }
+        if !linked {
+            scores[t] = emission;
+        }
+        last = Some(t);
+    }
+    chain_ends.extend(last);
+
+    let argmax = |row: &[f64]| (0..row.len()).max_by(|a, b| row[*a].total_cmp(&row[*b])).unwrap_or(0);
+    let mut chosen: Vec<Option<usize>> = vec![None; n];
+    for end in chain_ends {
+        let mut t = end;
+        let mut i = argmax(&scores[t]);
+        loop {
+            chosen[t] = Some(i);
+            let Some(s) = prev_point[t] else { break };
+            i = back[t][i];
+            t = s;
+        }
+    }
+
+    // Chained points are joined by the route the transition scored
+    let mut edges: Vec<usize> = Vec::new();
+    for t in 0..n {
+        let Some(j) = chosen[t] else { continue };
+        let b = candidates[t][j];
+        if let Some(s) = prev_point[t] {
+            let a = candidates[s][chosen[s].unwrap_or(0)];
+            let same_edge = a.edge == b.edge && b.offset >= a.offset - params.sigma;
+            if !same_edge {
+                let reach = graph.reachable(a.edge, limit_for(geometry::distance(points[s], points[t])));
+                if reach.contains_key(&b.edge) {
+                    edges.extend(RoadGraph::path(&reach, b.edge));
+                }
+            }
+        }
+        if edges.last() != Some(&b.edge) {
+            edges.push(b.edge);
+        }
+    }
+
+    MatchedTrace {
+        edges: edges.into_iter().map(|e| graph.ids[e].clone()).collect(),
+        points: (0..n)
+            .map(|t| {
+                let c = chosen[t].map(|j| candidates[t][j]);
+                MatchedPoint {
+                    time: times[t],
+                    edge_id: c.map(|c| graph.ids[c.edge].clone()),
+                    offset: c.map(|c| c.offset * params.scale),
+                    distance: c.map(|c| c.distance * params.scale),
+                }
+            })
+            .collect(),
+    }
+}
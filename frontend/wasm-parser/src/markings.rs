@@ -0,0 +1,103 @@
+// Lane dividers for close-up rendering. SUMO stores no markings, so the style
+// of the line between two lanes is inferred from whether a car may change
+// across it: the changeLeft / changeRight lists and the lanes' permissions.
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::geometry;
+use crate::net::{EdgeModel, LaneModel, NetModel};
+use crate::{parse_xml, to_js};
+
+// SUMO's default lane width
+pub(crate) const DEFAULT_LANE_WIDTH: f64 = 3.2;
+// Dividers are drawn for the lane changes of this vehicle class
+const MARKING_CLASS: &str = "passenger";
+
+#[derive(Serialize, Deserialize, Tsify, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum MarkingStyle {
+    // Crossable both ways
+    Dashed,
+    // Not crossable
+    Solid,
+    // Crossable only from the lower-index lane (`lane`)
+    DashedFromLane,
+    // Crossable only from the higher-index lane (`neighbor`)
+    DashedFromNeighbor,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct LaneMarking {
+    pub edge: String,
+    // The divider runs between `lane` (index i) and `neighbor` (index i + 1)
+    pub lane: String,
+    pub neighbor: String,
+    pub style: MarkingStyle,
+    // [lat, lng]
+    pub points: Vec<Vec<f64>>,
+}
+
+fn may_change(from: &LaneModel, to: &LaneModel, allowed: &Option<String>) -> bool {
+    let listed = allowed
+        .as_deref()
+        .is_none_or(|list| list.split_whitespace().any(|c| c == MARKING_CLASS || c == "all"));
+    listed && from.permits(MARKING_CLASS) && to.permits(MARKING_CLASS)
+}
+
+fn style(lane: &LaneModel, neighbor: &LaneModel) -> MarkingStyle {
+    // changeLeft leads to the next higher index, changeRight to the lower
+    match (may_change(lane, neighbor, &lane.change_left), may_change(neighbor, lane, &neighbor.change_right)) {
+        (true, true) => MarkingStyle::Dashed,
+        (true, false) => MarkingStyle::DashedFromLane,
+        (false, true) => MarkingStyle::DashedFromNeighbor,
+        (false, false) => MarkingStyle::Solid,
+    }
+}
+
+fn edge_markings(edge: &EdgeModel, lefthand: bool, markings: &mut Vec<LaneMarking>) {
+    let mut lanes: Vec<&LaneModel> = edge.lanes.iter().filter(|l| l.shape.len() >= 2).collect();
+    lanes.sort_by_key(|l| l.index);
+    for pair in lanes.windows(2) {
+        let (lane, neighbor) = (pair[0], pair[1]);
+        if neighbor.index != lane.index + 1 {
+            continue;
+        }
+        // Higher indices lie to the left, or to the right in lefthand networks
+        let half = lane.width.unwrap_or(DEFAULT_LANE_WIDTH) / 2.0;
+        let line = geometry::offset_polyline(&lane.shape, if lefthand { -half } else { half });
+        markings.push(LaneMarking {
+            edge: edge.id.clone(),
+            lane: lane.id.clone(),
+            neighbor: neighbor.id.clone(),
+            style: style(lane, neighbor),
+            points: line.into_iter().map(|(x, y)| vec![y, x]).collect(),
+        });
+    }
+}
+
+pub(crate) fn lane_markings(net: &NetModel) -> Vec<LaneMarking> {
+    let mut markings = Vec::new();
+    for edge in net.edges.iter().filter(|e| !e.is_internal()) {
+        edge_markings(edge, net.lefthand, &mut markings);
+    }
+    markings
+}
+
+// Divider lines between adjacent lanes of every edge, with the style a car
+// would see: dashed where it may change lanes, solid where it may not, and
+// one-sided where only one direction is allowed
+#[wasm_bindgen(unchecked_return_type = "LaneMarking[]")]
+pub fn infer_lane_markings(xml_text: &str) -> Result<JsValue, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let net = NetModel::from_root(doc.root_element());
+    let markings = lane_markings(&net);
+
+    console_log!(
+        "Inferred {} lane dividers, {} solid",
+        markings.len(),
+        markings.iter().filter(|m| m.style == MarkingStyle::Solid).count()
+    );
+
+    to_js(&markings)
+}
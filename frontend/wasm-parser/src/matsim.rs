@@ -0,0 +1,162 @@
+// MATSim network files (network.xml: <nodes> and <links>) as a
+// ParsedNetwork. Links are straight from node to node; each becomes an edge
+// with `permlanes` lanes laid out to the right of the node line, so the two
+// directions between a pair of nodes sit side by side as in SUMO.
+use wasm_bindgen::prelude::*;
+
+use crate::junctiontypes::JunctionHint;
+use crate::projection::{self, GeoReference};
+use crate::{
+    logging, parse_options, parse_xml, to_js, units, Bounds, JunctionPoint, Lane, ParseOptions, ParsedNetwork,
+};
+
+// Meters, when the <links> element gives no effectivelanewidth
+const DEFAULT_LANE_WIDTH: f64 = 3.75;
+// Seconds, when the <links> element gives no capperiod
+const DEFAULT_CAPACITY_PERIOD: f64 = 3600.0;
+
+fn attr(node: roxmltree::Node, name: &str) -> Option<f64> {
+    node.attribute(name).and_then(|v| v.trim().parse().ok())
+}
+
+// capperiod is "hh:mm:ss" (or plain seconds)
+fn parse_period(value: &str) -> Option<f64> {
+    value
+        .split(':')
+        .try_fold(0.0, |total, part| Some(total * 60.0 + part.trim().parse::<f64>().ok()?))
+        .filter(|p| *p > 0.0)
+}
+
+// The network's coordinateReferenceSystem attribute; MATSim leaves the frame
+// to the scenario config when absent
+fn read_geo_reference(root: roxmltree::Node) -> GeoReference {
+    let crs = root
+        .children()
+        .filter(|n| n.tag_name().name() == "attributes")
+        .flat_map(|a| a.children())
+        .find(|n| n.attribute("name") == Some("coordinateReferenceSystem"))
+        .and_then(|n| n.text())
+        .map(str::trim);
+    match crs {
+        None | Some("") => GeoReference::Unreferenced,
+        Some("EPSG:4326" | "WGS84") => GeoReference::PlainGeo,
+        Some(param) => GeoReference::from_proj(param, (0.0, 0.0)),
+    }
+}
+
+pub(crate) fn read_matsim(root: roxmltree::Node) -> (ParsedNetwork, GeoReference) {
+    let geo = read_geo_reference(root);
+    let section = |name| root.children().find(|n| n.tag_name().name() == name);
+
+    let mut junction_points = Vec::new();
+    let mut positions = std::collections::HashMap::new();
+    for node in section("nodes").into_iter().flat_map(|n| n.children()).filter(|n| n.tag_name().name() == "node") {
+        let (Some(id), Some(x), Some(y)) = (node.attribute("id"), attr(node, "x"), attr(node, "y")) else {
+            continue;
+        };
+        positions.insert(id, (x, y));
+        junction_points.push(JunctionPoint {
+            id: id.to_string(),
+            id_index: None,
+            junction_type: node.attribute("type").unwrap_or("").to_string(),
+            hint: JunctionHint::None,
+            lat: y,
+            lng: x,
+        });
+    }
+
+    let links = section("links");
+    // Meters per coordinate unit; lon/lat networks measure in degrees
+    let scale = if geo.is_plain_geo() { projection::METERS_PER_DEGREE } else { 1.0 };
+    let lane_width = links.and_then(|l| attr(l, "effectivelanewidth")).unwrap_or(DEFAULT_LANE_WIDTH) / scale;
+    let period = links
+        .and_then(|l| l.attribute("capperiod"))
+        .and_then(parse_period)
+        .unwrap_or(DEFAULT_CAPACITY_PERIOD);
+    let mut lanes = Vec::new();
+    for link in links.into_iter().flat_map(|l| l.children()).filter(|n| n.tag_name().name() == "link") {
+        let (Some(id), Some(&from), Some(&to)) = (
+            link.attribute("id"),
+            link.attribute("from").and_then(|f| positions.get(f)),
+            link.attribute("to").and_then(|t| positions.get(t)),
+        ) else {
+            continue;
+        };
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let norm = dx.hypot(dy);
+        if norm == 0.0 {
+            continue;
+        }
+        // Unit normal to the right of the direction of travel
+        let (rx, ry) = (dy / norm, -dx / norm);
+        let count = attr(link, "permlanes").map_or(1, |l| l.round().max(1.0) as usize);
+        let speed = attr(link, "freespeed").filter(|s| *s > 0.0);
+        let capacity = attr(link, "capacity").map(|c| c * 3600.0 / period / count as f64);
+        let length = attr(link, "length").unwrap_or(norm * scale);
+        for index in 0..count {
+            // Index 0 is the rightmost lane
+            let offset = (count - index) as f64 * lane_width - lane_width / 2.0;
+            let shift = |(x, y): (f64, f64)| vec![y + ry * offset, x + rx * offset];
+            lanes.push(Lane {
+                id: format!("{}_{}", id, index),
+                id_index: None,
+                edge_id: Some(id.to_string()),
+                edge_index: None,
+                points: vec![shift(from), shift(to)],
+                xy: None,
+                elevation: None,
+                headings: None,
+                speed,
+                speed_kmh: speed.map(units::speed_limit_kmh),
+                speed_mph: speed.map(units::speed_limit_mph),
+                speed_class: speed.map(|s| units::SpeedClass::from_kmh(units::speed_limit_kmh(s))),
+                length: Some(length),
+                edge_length: Some(length),
+                capacity,
+                allow: None,
+                disallow: None,
+                is_internal: false,
+                is_roundabout: false,
+                is_closed: false,
+            });
+        }
+    }
+
+    let bounds = Bounds::of_points(junction_points.iter().map(|p| (p.lng, p.lat)));
+    let network = ParsedNetwork {
+        lanes,
+        bounds,
+        tls: Vec::new(),
+        junctions: Vec::new(),
+        junction_points,
+        roundabouts: Vec::new(),
+        crs: geo.native_crs(),
+        quantization: None,
+        ids: None,
+    };
+    (network, geo)
+}
+
+// A MATSim network.xml as a ParsedNetwork: links as lanes (with capacity per
+// lane in vehicles per hour), nodes as junction points. `fields` in the
+// options is ignored.
+#[wasm_bindgen(unchecked_return_type = "ParsedNetwork")]
+pub fn parse_matsim_network(
+    xml_text: &str,
+    #[wasm_bindgen(unchecked_param_type = "ParseOptions | undefined")] options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let options: ParseOptions = parse_options(options)?;
+    logging::with_level(options.log_level, || {
+        let doc = parse_xml(xml_text)?;
+        let (mut network, geo) = read_matsim(doc.root_element());
+        network.apply_options(&geo, &options)?;
+
+        console_log!(
+            "Converted MATSim network: {} nodes, {} lanes",
+            network.junction_points.len(),
+            network.lanes.len()
+        );
+
+        to_js(&network)
+    })
+}
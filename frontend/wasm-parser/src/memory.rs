@@ -0,0 +1,98 @@
+// Heap accounting for sessions that load several networks. Wasm linear memory
+// only grows: blocks freed by `free()` (or `shrink()`) are reused by later
+// allocations, but the pages are never handed back to the browser. The only
+// way to return them is to drop the whole module instance, e.g. by parsing in
+// a Worker and terminating it; `memory_stats` tells when that is worth it.
+use serde::{Deserialize, Serialize};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::to_js;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+// The system allocator (dlmalloc on wasm), counting live bytes
+struct CountingAllocator;
+
+fn grew(bytes: usize) {
+    let now = ALLOCATED.fetch_add(bytes, Ordering::Relaxed) + bytes;
+    PEAK.fetch_max(now, Ordering::Relaxed);
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            grew(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            grew(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+            grew(new_size);
+        }
+        new_ptr
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct MemoryStats {
+    // Size of the wasm linear memory (0 outside wasm)
+    #[serde(rename = "reservedBytes")]
+    pub reserved_bytes: usize,
+    // Bytes in live allocations
+    #[serde(rename = "allocatedBytes")]
+    pub allocated_bytes: usize,
+    // Most bytes ever live at once (since the last reset)
+    #[serde(rename = "peakBytes")]
+    pub peak_bytes: usize,
+}
+
+#[cfg(target_arch = "wasm32")]
+fn reserved_bytes() -> usize {
+    core::arch::wasm32::memory_size::<0>() * 65536
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn reserved_bytes() -> usize {
+    0
+}
+
+// reservedBytes - allocatedBytes is memory the module holds but does not use;
+// it stays reserved until the instance is dropped
+#[wasm_bindgen(unchecked_return_type = "MemoryStats")]
+pub fn memory_stats() -> Result<JsValue, JsValue> {
+    to_js(&MemoryStats {
+        reserved_bytes: reserved_bytes(),
+        allocated_bytes: ALLOCATED.load(Ordering::Relaxed),
+        peak_bytes: PEAK.load(Ordering::Relaxed),
+    })
+}
+
+// Start peak tracking over, e.g. before loading the next city
+#[wasm_bindgen]
+pub fn reset_peak_memory() {
+    PEAK.store(ALLOCATED.load(Ordering::Relaxed), Ordering::Relaxed);
+}
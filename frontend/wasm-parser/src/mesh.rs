@@ -0,0 +1,143 @@
+// Filled road surfaces for WebGL: junction polygons triangulated and lanes
+// buffered to their width as triangle strips, one indexed mesh per layer so
+// the renderer draws each layer with a single drawElements call.
+use wasm_bindgen::prelude::*;
+
+use crate::deckgl::{self, is_path};
+use crate::geometry;
+use crate::network::to_xy;
+use crate::{Junction, Lane};
+
+const JUNCTION_COLOR: [u8; 4] = [208, 208, 208, 255];
+
+// Positions are float32 offsets from `origin`, as in PathBuffers; colors are
+// per vertex. Every triangle lies within one feature, so a triangle's
+// feature follows from its first vertex and `startIndices`.
+#[wasm_bindgen]
+pub struct MeshBuffers {
+    ids: Vec<String>,
+    origin: (f64, f64),
+    start_indices: Vec<u32>,
+    positions: Vec<f32>,
+    colors: Vec<u8>,
+    indices: Vec<u32>,
+}
+
+#[wasm_bindgen]
+impl MeshBuffers {
+    // Number of features (lanes or junctions)
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.ids.len()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn ids(&self) -> Vec<String> {
+        self.ids.clone()
+    }
+
+    // [x, y] subtracted from every position (lng, lat order)
+    #[wasm_bindgen(getter)]
+    pub fn origin(&self) -> Vec<f64> {
+        vec![self.origin.0, self.origin.1]
+    }
+
+    // Index of each feature's first vertex, plus a final entry for the total
+    #[wasm_bindgen(getter, js_name = startIndices)]
+    pub fn start_indices(&self) -> Vec<u32> {
+        self.start_indices.clone()
+    }
+
+    // x, y per vertex
+    #[wasm_bindgen(getter)]
+    pub fn positions(&self) -> Vec<f32> {
+        self.positions.clone()
+    }
+
+    // r, g, b, a per vertex
+    #[wasm_bindgen(getter)]
+    pub fn colors(&self) -> Vec<u8> {
+        self.colors.clone()
+    }
+
+    // Three vertex indices per triangle, counter-clockwise
+    #[wasm_bindgen(getter)]
+    pub fn indices(&self) -> Vec<u32> {
+        self.indices.clone()
+    }
+}
+
+impl MeshBuffers {
+    pub(crate) fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    fn new(origin: (f64, f64)) -> MeshBuffers {
+        MeshBuffers {
+            ids: Vec::new(),
+            origin,
+            start_indices: Vec::new(),
+            positions: Vec::new(),
+            colors: Vec::new(),
+            indices: Vec::new(),
+        }
+    }
+
+    // `triangles` index into `vertices`
+    fn add(&mut self, id: &str, color: [u8; 4], vertices: &[(f64, f64)], triangles: &[[usize; 3]]) {
+        if triangles.is_empty() {
+            return;
+        }
+        let base = (self.positions.len() / 2) as u32;
+        self.ids.push(id.to_string());
+        self.start_indices.push(base);
+        for &(x, y) in vertices {
+            self.positions.push((x - self.origin.0) as f32);
+            self.positions.push((y - self.origin.1) as f32);
+            self.colors.extend_from_slice(&color);
+        }
+        self.indices.extend(triangles.iter().flatten().map(|&i| base + i as u32));
+    }
+
+    fn finish(mut self) -> MeshBuffers {
+        self.start_indices.push((self.positions.len() / 2) as u32);
+        self.ids.shrink_to_fit();
+        self.start_indices.shrink_to_fit();
+        self.positions.shrink_to_fit();
+        self.colors.shrink_to_fit();
+        self.indices.shrink_to_fit();
+        self
+    }
+}
+
+// Each lane as a strip `width(lane)` network units wide around its
+// centerline: left vertices first, then right ones
+pub(crate) fn lane_mesh(
+    lanes: &[Lane],
+    width: impl Fn(&Lane) -> f64,
+    origin: (f64, f64),
+    color_by: &str,
+) -> Result<MeshBuffers, String> {
+    let color_of = deckgl::lane_color(color_by)?;
+    let mut mesh = MeshBuffers::new(origin);
+    for lane in lanes.iter().filter(|l| is_path(l)) {
+        let center = to_xy(&lane.points);
+        let half = width(lane) / 2.0;
+        let mut vertices = geometry::offset_polyline(&center, half);
+        vertices.extend(geometry::offset_polyline(&center, -half));
+        let n = center.len();
+        let triangles: Vec<[usize; 3]> =
+            (0..n - 1).flat_map(|i| [[i, n + i, n + i + 1], [i, n + i + 1, i + 1]]).collect();
+        mesh.add(&lane.id, color_of(lane), &vertices, &triangles);
+    }
+    Ok(mesh.finish())
+}
+
+pub(crate) fn junction_mesh(junctions: &[Junction], origin: (f64, f64)) -> MeshBuffers {
+    let mut mesh = MeshBuffers::new(origin);
+    for junction in junctions {
+        let ring = to_xy(&junction.polygon);
+        mesh.add(&junction.id, JUNCTION_COLOR, &ring, &geometry::triangulate(&ring));
+    }
+    mesh.finish()
+}
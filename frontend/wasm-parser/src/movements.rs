@@ -0,0 +1,257 @@
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+
+use crate::geometry;
+use crate::net::{ConnectionModel, NetModel};
+use crate::{parse_xml, to_js};
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct Movement {
+    #[serde(rename = "linkNumber")]
+    pub link_number: usize,
+    #[serde(rename = "tlLinkIndex")]
+    pub tl_link_index: Option<usize>,
+    pub from: String,
+    #[serde(rename = "fromLane")]
+    pub from_lane: usize,
+    pub to: String,
+    #[serde(rename = "toLane")]
+    pub to_lane: usize,
+    pub dir: String,
+    pub via: Option<String>,
+    pub state: String,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct JunctionMovements {
+    #[serde(rename = "junctionId")]
+    pub junction_id: String,
+    pub tl: Option<String>,
+    // Incoming edge ids, clockwise from 12 o'clock
+    pub approaches: Vec<String>,
+    pub movements: Vec<Movement>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct LaneAssignment {
+    #[serde(rename = "laneId")]
+    pub lane_id: String,
+    pub index: usize,
+    // Turn letters allowed from this lane, e.g. "L", "T", "TR", "UL"
+    pub movements: String,
+    // "left", "through", "right", "uturn" or "shared"
+    pub kind: String,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct ApproachLanes {
+    #[serde(rename = "junctionId")]
+    pub junction_id: String,
+    #[serde(rename = "edgeId")]
+    pub edge_id: String,
+    // Inventory notation, leftmost lane first: "L | T | TR"
+    pub assignment: String,
+    // Leftmost lane first, matching `assignment`
+    pub lanes: Vec<LaneAssignment>,
+}
+
+// Right-to-left order of connection directions within one lane
+fn dir_rank(dir: &str) -> u8 {
+    match dir {
+        "r" => 0,
+        "R" => 1,
+        "s" => 2,
+        "L" => 3,
+        "l" => 4,
+        "t" => 5,
+        _ => 6,
+    }
+}
+
+// Bearing from the junction towards where an incoming edge comes from,
+// measured at the junction end of its rightmost lane
+fn approach_bearing(net: &NetModel, edge_id: &str) -> Option<f64> {
+    let edge = net.edge(edge_id)?;
+    let lane = edge.lane(0).or_else(|| edge.lanes.first())?;
+    let n = lane.shape.len();
+    if n < 2 {
+        return None;
+    }
+    let (dx, dy) = geometry::unit_direction(lane.shape[n - 2], lane.shape[n - 1])?;
+    Some(geometry::compass_bearing(-dx, -dy))
+}
+
+// Number connections the way netconvert does (and SUMO-GUI shows them):
+// incoming edges clockwise starting at 12 o'clock, then lanes right to left,
+// then each lane's connections from rightmost to leftmost direction.
+pub(crate) fn junction_movements(net: &NetModel) -> Vec<JunctionMovements> {
+    let mut by_junction: HashMap<&str, Vec<&ConnectionModel>> = HashMap::new();
+    for c in &net.connections {
+        let Some(edge) = net.edge(&c.from) else { continue };
+        if edge.is_internal() {
+            continue;
+        }
+        if let Some(to) = edge.to.as_deref() {
+            by_junction.entry(to).or_default().push(c);
+        }
+    }
+
+    let mut result: Vec<JunctionMovements> = by_junction
+        .into_iter()
+        .map(|(junction_id, conns)| {
+            let mut approaches: Vec<(String, f64)> = Vec::new();
+            for c in &conns {
+                if !approaches.iter().any(|(id, _)| id == &c.from) {
+                    let bearing = approach_bearing(net, &c.from).unwrap_or(0.0);
+                    approaches.push((c.from.clone(), bearing));
+                }
+            }
+            approaches.sort_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+
+            let rank: HashMap<&str, usize> = approaches
+                .iter()
+                .enumerate()
+                .map(|(i, (id, _))| (id.as_str(), i))
+                .collect();
+
+            // sort_by_key is stable, so document order breaks remaining ties
+            let mut ordered = conns;
+            ordered.sort_by_key(|c| (rank[c.from.as_str()], c.from_lane, dir_rank(&c.dir)));
+
+            let movements = ordered
+                .iter()
+                .enumerate()
+                .map(|(i, c)| Movement {
+                    link_number: i,
+                    tl_link_index: c.link_index,
+                    from: c.from.clone(),
+                    from_lane: c.from_lane,
+                    to: c.to.clone(),
+                    to_lane: c.to_lane,
+                    dir: c.dir.clone(),
+                    via: c.via.clone(),
+                    state: c.state.clone(),
+                })
+                .collect();
+
+            JunctionMovements {
+                junction_id: junction_id.to_string(),
+                tl: ordered.iter().find_map(|c| c.tl.clone()),
+                approaches: approaches.into_iter().map(|(id, _)| id).collect(),
+                movements,
+            }
+        })
+        .collect();
+
+    result.sort_by(|a, b| a.junction_id.cmp(&b.junction_id));
+    result
+}
+
+// Inventory turn letter for a SUMO connection direction
+fn turn_letter(dir: &str) -> Option<char> {
+    match dir {
+        "t" => Some('U'),
+        "l" | "L" => Some('L'),
+        "s" => Some('T'),
+        "r" | "R" => Some('R'),
+        _ => None,
+    }
+}
+
+fn lane_kind(movements: &str) -> &'static str {
+    match movements {
+        "L" => "left",
+        "T" => "through",
+        "R" => "right",
+        "U" => "uturn",
+        _ => "shared",
+    }
+}
+
+// Per-approach lane usage derived from the connections leaving each lane.
+// Lanes without vehicle movements (sidewalks, bike lanes ending at crossings)
+// are left out.
+pub(crate) fn lane_assignments(net: &NetModel) -> Vec<ApproachLanes> {
+    let mut letters: HashMap<(&str, usize), Vec<char>> = HashMap::new();
+    for c in &net.connections {
+        if c.to.starts_with(':') {
+            continue;
+        }
+        if let Some(letter) = turn_letter(&c.dir) {
+            let entry = letters.entry((c.from.as_str(), c.from_lane)).or_default();
+            if !entry.contains(&letter) {
+                entry.push(letter);
+            }
+        }
+    }
+
+    let mut result: Vec<ApproachLanes> = net
+        .edges
+        .iter()
+        .filter(|e| e.is_normal())
+        .filter_map(|edge| {
+            let mut lanes: Vec<LaneAssignment> = edge
+                .lanes
+                .iter()
+                .filter_map(|lane| {
+                    let mut found = letters.get(&(edge.id.as_str(), lane.index))?.clone();
+                    found.sort_by_key(|l| "ULTR".find(*l));
+                    let movements: String = found.into_iter().collect();
+                    Some(LaneAssignment {
+                        lane_id: lane.id.clone(),
+                        index: lane.index,
+                        kind: lane_kind(&movements).to_string(),
+                        movements,
+                    })
+                })
+                .collect();
+            if lanes.is_empty() {
+                return None;
+            }
+            // Lane 0 is rightmost in right-hand networks
+            if net.lefthand {
+                lanes.sort_by_key(|l| l.index);
+            } else {
+                lanes.sort_by_key(|l| std::cmp::Reverse(l.index));
+            }
+
+            Some(ApproachLanes {
+                junction_id: edge.to.clone().unwrap_or_default(),
+                edge_id: edge.id.clone(),
+                assignment: lanes.iter().map(|l| l.movements.as_str()).collect::<Vec<_>>().join(" | "),
+                lanes,
+            })
+        })
+        .collect();
+
+    // Approaches clockwise from 12 o'clock within each junction, as in JunctionMovements
+    result.sort_by_cached_key(|a| {
+        let bearing = approach_bearing(net, &a.edge_id).unwrap_or(0.0);
+        (a.junction_id.clone(), (bearing * 1000.0) as i64, a.edge_id.clone())
+    });
+    result
+}
+
+#[wasm_bindgen(unchecked_return_type = "JunctionMovements[]")]
+pub fn parse_junction_movements(xml_text: &str) -> Result<JsValue, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let net = NetModel::from_root(doc.root_element());
+    let movements = junction_movements(&net);
+
+    console_log!("Numbered movements for {} junctions", movements.len());
+
+    to_js(&movements)
+}
+
+#[wasm_bindgen(unchecked_return_type = "ApproachLanes[]")]
+pub fn parse_lane_assignments(xml_text: &str) -> Result<JsValue, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let net = NetModel::from_root(doc.root_element());
+    let approaches = lane_assignments(&net);
+
+    console_log!("Classified lanes on {} approaches", approaches.len());
+
+    to_js(&approaches)
+}
@@ -0,0 +1,350 @@
+// Mapbox Vector Tile (spec v2) encoding of render output. The protobuf
+// messages are small and fixed, so they are written by hand rather than
+// pulling in a protobuf runtime.
+use std::collections::HashMap;
+
+use crate::geometry;
+use crate::network::to_xy;
+use crate::{Junction, Lane, TrafficLight};
+
+pub(crate) const EXTENT: u32 = 4096;
+// Geometry kept outside the tile edge, in tile units, so strokes join seamlessly
+const BUFFER: f64 = 64.0;
+
+const GEOM_POINT: u32 = 1;
+const GEOM_LINESTRING: u32 = 2;
+const GEOM_POLYGON: u32 = 3;
+
+const CMD_MOVE_TO: u32 = 1;
+const CMD_LINE_TO: u32 = 2;
+const CMD_CLOSE_PATH: u32 = 7;
+
+fn write_varint(buf: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        buf.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+fn write_key(buf: &mut Vec<u8>, field: u32, wire_type: u32) {
+    write_varint(buf, ((field << 3) | wire_type) as u64);
+}
+
+fn write_uint(buf: &mut Vec<u8>, field: u32, v: u64) {
+    write_key(buf, field, 0);
+    write_varint(buf, v);
+}
+
+fn write_bytes(buf: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+    write_key(buf, field, 2);
+    write_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+fn write_packed(buf: &mut Vec<u8>, field: u32, values: &[u32]) {
+    let mut packed = Vec::with_capacity(values.len() * 2);
+    for v in values {
+        write_varint(&mut packed, *v as u64);
+    }
+    write_bytes(buf, field, &packed);
+}
+
+fn zigzag(v: i32) -> u32 {
+    ((v << 1) ^ (v >> 31)) as u32
+}
+
+fn command(id: u32, count: usize) -> u32 {
+    (id & 0x7) | ((count as u32) << 3)
+}
+
+// Property value; doubles are keyed by their bits so values can be deduplicated
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub(crate) enum TagValue {
+    Str(String),
+    Double(u64),
+    Bool(bool),
+}
+
+impl TagValue {
+    pub fn double(v: f64) -> TagValue {
+        TagValue::Double(v.to_bits())
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        match self {
+            TagValue::Str(s) => write_bytes(&mut buf, 1, s.as_bytes()),
+            TagValue::Double(bits) => {
+                write_key(&mut buf, 3, 1);
+                buf.extend_from_slice(&bits.to_le_bytes());
+            }
+            TagValue::Bool(b) => write_uint(&mut buf, 7, *b as u64),
+        }
+        buf
+    }
+}
+
+// Maps network coordinates of one tile's box onto the 0..EXTENT grid (y down)
+pub(crate) struct TileFrame {
+    pub min: (f64, f64),
+    pub max: (f64, f64),
+}
+
+impl TileFrame {
+    fn to_tile(&self, p: (f64, f64)) -> (i32, i32) {
+        let sx = EXTENT as f64 / (self.max.0 - self.min.0);
+        let sy = EXTENT as f64 / (self.max.1 - self.min.1);
+        (
+            ((p.0 - self.min.0) * sx).round() as i32,
+            ((self.max.1 - p.1) * sy).round() as i32,
+        )
+    }
+
+    // Tile box grown by the buffer, in network coordinates
+    fn clip_box(&self) -> ((f64, f64), (f64, f64)) {
+        let bx = (self.max.0 - self.min.0) * BUFFER / EXTENT as f64;
+        let by = (self.max.1 - self.min.1) * BUFFER / EXTENT as f64;
+        ((self.min.0 - bx, self.min.1 - by), (self.max.0 + bx, self.max.1 + by))
+    }
+
+    // Network meters per tile unit, used as the simplification tolerance
+    pub fn resolution(&self) -> f64 {
+        (self.max.0 - self.min.0) / EXTENT as f64
+    }
+}
+
+// Command stream for one feature; the cursor carries across parts as the spec requires
+struct GeometryEncoder {
+    cursor: (i32, i32),
+    out: Vec<u32>,
+}
+
+impl GeometryEncoder {
+    fn new() -> GeometryEncoder {
+        GeometryEncoder { cursor: (0, 0), out: Vec::new() }
+    }
+
+    fn push_point(&mut self, p: (i32, i32)) {
+        // Unclipped junction polygons can saturate i32 far outside the tile
+        self.out.push(zigzag(p.0.wrapping_sub(self.cursor.0)));
+        self.out.push(zigzag(p.1.wrapping_sub(self.cursor.1)));
+        self.cursor = p;
+    }
+
+    fn path(&mut self, points: &[(i32, i32)], close: bool) {
+        self.out.push(command(CMD_MOVE_TO, 1));
+        self.push_point(points[0]);
+        self.out.push(command(CMD_LINE_TO, points.len() - 1));
+        for p in &points[1..] {
+            self.push_point(*p);
+        }
+        if close {
+            self.out.push(command(CMD_CLOSE_PATH, 1));
+        }
+    }
+}
+
+fn quantize(frame: &TileFrame, points: &[(f64, f64)]) -> Vec<(i32, i32)> {
+    let mut out: Vec<(i32, i32)> = Vec::with_capacity(points.len());
+    for p in points {
+        let q = frame.to_tile(*p);
+        if out.last() != Some(&q) {
+            out.push(q);
+        }
+    }
+    out
+}
+
+// Pieces of a polyline inside the buffered tile box
+fn clip_polyline(points: &[(f64, f64)], min: (f64, f64), max: (f64, f64)) -> Vec<Vec<(f64, f64)>> {
+    let mut parts: Vec<Vec<(f64, f64)>> = Vec::new();
+    let mut current: Vec<(f64, f64)> = Vec::new();
+    for w in points.windows(2) {
+        match geometry::clip_segment(w[0], w[1], min, max) {
+            Some((a, b)) => {
+                if current.last() != Some(&a) {
+                    if current.len() >= 2 {
+                        parts.push(std::mem::take(&mut current));
+                    }
+                    current.clear();
+                    current.push(a);
+                }
+                current.push(b);
+            }
+            None => {
+                if current.len() >= 2 {
+                    parts.push(std::mem::take(&mut current));
+                }
+                current.clear();
+            }
+        }
+    }
+    if current.len() >= 2 {
+        parts.push(current);
+    }
+    parts
+}
+
+pub(crate) struct LayerBuilder {
+    name: &'static str,
+    features: Vec<Vec<u8>>,
+    keys: Vec<&'static str>,
+    values: Vec<TagValue>,
+    value_index: HashMap<TagValue, u32>,
+}
+
+impl LayerBuilder {
+    pub fn new(name: &'static str) -> LayerBuilder {
+        LayerBuilder {
+            name,
+            features: Vec::new(),
+            keys: Vec::new(),
+            values: Vec::new(),
+            value_index: HashMap::new(),
+        }
+    }
+
+    fn tags(&mut self, properties: Vec<(&'static str, TagValue)>) -> Vec<u32> {
+        let mut tags = Vec::with_capacity(properties.len() * 2);
+        for (key, value) in properties {
+            let k = match self.keys.iter().position(|k| *k == key) {
+                Some(i) => i,
+                None => {
+                    self.keys.push(key);
+                    self.keys.len() - 1
+                }
+            };
+            let v = *self.value_index.entry(value.clone()).or_insert_with(|| {
+                self.values.push(value);
+                (self.values.len() - 1) as u32
+            });
+            tags.push(k as u32);
+            tags.push(v);
+        }
+        tags
+    }
+
+    fn add(&mut self, id: u64, geom_type: u32, geometry: Vec<u32>, properties: Vec<(&'static str, TagValue)>) {
+        let tags = self.tags(properties);
+        let mut buf = Vec::new();
+        write_uint(&mut buf, 1, id);
+        write_packed(&mut buf, 2, &tags);
+        write_uint(&mut buf, 3, geom_type as u64);
+        write_packed(&mut buf, 4, &geometry);
+        self.features.push(buf);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        write_uint(&mut buf, 15, 2);
+        write_bytes(&mut buf, 1, self.name.as_bytes());
+        for f in &self.features {
+            write_bytes(&mut buf, 2, f);
+        }
+        for k in &self.keys {
+            write_bytes(&mut buf, 3, k.as_bytes());
+        }
+        for v in &self.values {
+            write_bytes(&mut buf, 4, &v.encode());
+        }
+        write_uint(&mut buf, 5, EXTENT as u64);
+        buf
+    }
+}
+
+pub(crate) fn add_lane(layer: &mut LayerBuilder, frame: &TileFrame, id: u64, lane: &Lane) {
+    let (min, max) = frame.clip_box();
+    let points = crate::rdp_simplify(&to_xy(&lane.points), frame.resolution());
+    let mut enc = GeometryEncoder::new();
+    for part in clip_polyline(&points, min, max) {
+        let q = quantize(frame, &part);
+        if q.len() >= 2 {
+            enc.path(&q, false);
+        }
+    }
+    if enc.out.is_empty() {
+        return;
+    }
+
+    let mut properties = vec![
+        ("id", TagValue::Str(lane.id.clone())),
+        ("isInternal", TagValue::Bool(lane.is_internal)),
+    ];
+    if let Some(edge_id) = &lane.edge_id {
+        properties.push(("edgeId", TagValue::Str(edge_id.clone())));
+    }
+    if let Some(speed) = lane.speed {
+        properties.push(("speed", TagValue::double(speed)));
+    }
+    if lane.is_closed {
+        properties.push(("isClosed", TagValue::Bool(true)));
+    }
+    layer.add(id, GEOM_LINESTRING, enc.out, properties);
+}
+
+pub(crate) fn add_junction(layer: &mut LayerBuilder, frame: &TileFrame, id: u64, junction: &Junction) {
+    let mut ring = quantize(frame, &to_xy(&junction.polygon));
+    if ring.len() > 1 && ring.first() == ring.last() {
+        ring.pop();
+    }
+    if ring.len() < 3 {
+        return;
+    }
+    // Exterior rings must have positive area in tile coordinates (y down)
+    let area: f64 = (0..ring.len())
+        .map(|i| {
+            let (a, b) = (ring[i], ring[(i + 1) % ring.len()]);
+            a.0 as f64 * b.1 as f64 - b.0 as f64 * a.1 as f64
+        })
+        .sum();
+    if area == 0.0 {
+        return;
+    }
+    if area < 0.0 {
+        ring.reverse();
+    }
+
+    let mut enc = GeometryEncoder::new();
+    enc.path(&ring, true);
+    layer.add(
+        id,
+        GEOM_POLYGON,
+        enc.out,
+        vec![
+            ("id", TagValue::Str(junction.id.clone())),
+            ("type", TagValue::Str(junction.junction_type.clone())),
+        ],
+    );
+}
+
+pub(crate) fn add_traffic_light(layer: &mut LayerBuilder, frame: &TileFrame, id: u64, tl: &TrafficLight) {
+    let (min, max) = (frame.min, frame.max);
+    if tl.lng < min.0 || tl.lng > max.0 || tl.lat < min.1 || tl.lat > max.1 {
+        return;
+    }
+    let mut enc = GeometryEncoder::new();
+    enc.out.push(command(CMD_MOVE_TO, 1));
+    enc.push_point(frame.to_tile((tl.lng, tl.lat)));
+    layer.add(
+        id,
+        GEOM_POINT,
+        enc.out,
+        vec![
+            ("id", TagValue::Str(tl.id.clone())),
+            ("clusterId", TagValue::Str(tl.cluster_id.clone())),
+        ],
+    );
+}
+
+pub(crate) fn encode_tile(layers: &[LayerBuilder]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for layer in layers.iter().filter(|l| !l.is_empty()) {
+        write_bytes(&mut buf, 3, &layer.encode());
+    }
+    buf
+}
@@ -0,0 +1,468 @@
+// Full-fidelity topology model of a .net.xml document. Unlike the render
+// output of `parse_sumo_net_xml`, this keeps every lane with its raw shape plus
+// the connection graph and traffic light programs, for analyses (and plain XML
+// export) that need more than display geometry.
+#[cfg(feature = "snapshot")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::geometry;
+use crate::{attr_f64, parse_point_string, parse_point_string_z};
+
+// m/s (50 km/h), for lanes without a speed limit
+const DEFAULT_SPEED: f64 = 13.89;
+
+#[derive(Clone)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+pub(crate) struct LaneModel {
+    pub id: String,
+    pub index: usize,
+    pub speed: Option<f64>,
+    // The length attribute, which positions along the lane refer to; it can
+    // differ from the shape's length
+    pub length: Option<f64>,
+    pub allow: Option<String>,
+    pub disallow: Option<String>,
+    pub width: Option<f64>,
+    // vClasses allowed to change to the next higher / lower lane; None = all
+    pub change_left: Option<String>,
+    pub change_right: Option<String>,
+    pub end_offset: Option<f64>,
+    pub shape: Vec<(f64, f64)>,
+    // z per shape point, when the shape is 3D
+    pub elevation: Option<Vec<f64>>,
+}
+
+// SUMO permission semantics: `allow` wins over `disallow`, both accept "all"
+pub(crate) fn permits(allow: Option<&str>, disallow: Option<&str>, v_class: &str) -> bool {
+    let listed = |list: &str| list.split_whitespace().any(|c| c == v_class || c == "all");
+    match (allow, disallow) {
+        (Some(allow), _) => listed(allow),
+        (None, Some(disallow)) => !listed(disallow),
+        (None, None) => true,
+    }
+}
+
+// Lanes usable by a vehicle class or, `dedicated`, reserved for it: usable by
+// the class but not by passenger cars, like bus and bike lanes
+#[derive(Clone)]
+pub(crate) struct VClassFilter {
+    pub v_class: String,
+    pub dedicated: bool,
+}
+
+impl VClassFilter {
+    pub fn matches(&self, allow: Option<&str>, disallow: Option<&str>) -> bool {
+        permits(allow, disallow, &self.v_class) && !(self.dedicated && permits(allow, disallow, "passenger"))
+    }
+}
+
+impl LaneModel {
+    pub fn permits(&self, v_class: &str) -> bool {
+        permits(self.allow.as_deref(), self.disallow.as_deref(), v_class)
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+pub(crate) struct EdgeModel {
+    pub id: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub function: String,
+    pub edge_type: String,
+    pub priority: Option<i32>,
+    pub name: Option<String>,
+    pub spread_type: Option<String>,
+    // The edge's own geometry, when the network stores one
+    pub shape: Vec<(f64, f64)>,
+    pub lanes: Vec<LaneModel>,
+}
+
+impl EdgeModel {
+    pub fn is_internal(&self) -> bool {
+        self.function == "internal"
+    }
+
+    // A road edge, not internal, crossing, walkingarea or connector
+    pub fn is_normal(&self) -> bool {
+        self.function.is_empty() || self.function == "normal"
+    }
+
+    pub fn lane(&self, index: usize) -> Option<&LaneModel> {
+        self.lanes.iter().find(|l| l.index == index)
+    }
+
+    // Length along the rightmost lane's shape
+    pub fn length(&self) -> f64 {
+        self.lane(0)
+            .or_else(|| self.lanes.first())
+            .map(|l| geometry::polyline_length(&l.shape))
+            .unwrap_or(0.0)
+    }
+
+    // Seconds to drive the edge at the speed limit of its fastest lane (of
+    // the lanes `v_class` may use, when given)
+    pub fn free_flow_time(&self, v_class: Option<&str>) -> f64 {
+        let speed = self
+            .lanes
+            .iter()
+            .filter(|l| v_class.is_none_or(|c| l.permits(c)))
+            .filter_map(|l| l.speed)
+            .fold(0.0, f64::max);
+        self.length() / if speed > 0.0 { speed } else { DEFAULT_SPEED }
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+pub(crate) struct JunctionModel {
+    pub id: String,
+    pub junction_type: String,
+    pub x: f64,
+    pub y: f64,
+    pub z: Option<f64>,
+    // Outline polygon; empty when the network has none
+    pub shape: Vec<(f64, f64)>,
+    pub radius: Option<f64>,
+    pub right_of_way: Option<String>,
+    pub fringe: Option<String>,
+}
+
+// The <location> element: how network coordinates relate to the original projection
+#[derive(Clone)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+pub(crate) struct LocationModel {
+    pub net_offset: (f64, f64),
+    pub proj_parameter: String,
+}
+
+impl LocationModel {
+    // Translation that maps this network's coordinates into `base`'s frame,
+    // or None when the two were built with different projections
+    pub fn offset_to(&self, base: &LocationModel) -> Option<(f64, f64)> {
+        if self.proj_parameter != base.proj_parameter {
+            return None;
+        }
+        Some((
+            base.net_offset.0 - self.net_offset.0,
+            base.net_offset.1 - self.net_offset.1,
+        ))
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+pub(crate) struct ConnectionModel {
+    pub from: String,
+    pub to: String,
+    pub from_lane: usize,
+    pub to_lane: usize,
+    pub via: Option<String>,
+    pub dir: String,
+    pub state: String,
+    pub tl: Option<String>,
+    pub link_index: Option<usize>,
+    // Optional attributes present on the element (keepClear, contPos, ...),
+    // as written, for re-export
+    #[cfg_attr(feature = "snapshot", serde(deserialize_with = "connection_options"))]
+    pub options: Vec<(&'static str, String)>,
+}
+
+// Connection attributes carried over verbatim when present
+const CONNECTION_OPTIONS: [&str; 12] = [
+    "pass",
+    "keepClear",
+    "contPos",
+    "visibility",
+    "speed",
+    "allow",
+    "disallow",
+    "changeLeft",
+    "changeRight",
+    "indirect",
+    "type",
+    "uncontrolled",
+];
+
+// Names back to the CONNECTION_OPTIONS entries, dropping unknown ones
+#[cfg(feature = "snapshot")]
+fn connection_options<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Vec<(&'static str, String)>, D::Error> {
+    let options: Vec<(String, String)> = Deserialize::deserialize(d)?;
+    Ok(options
+        .into_iter()
+        .filter_map(|(name, value)| Some((*CONNECTION_OPTIONS.iter().find(|o| **o == name)?, value)))
+        .collect())
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+pub(crate) struct PhaseModel {
+    pub duration: String,
+    pub state: String,
+    // minDur, maxDur, name, next and the other optional attributes, as written
+    pub options: Vec<(String, String)>,
+}
+
+// One <tlLogic> program
+#[derive(Clone)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+pub(crate) struct TlLogicModel {
+    pub id: String,
+    pub program_id: String,
+    pub logic_type: String,
+    pub offset: Option<String>,
+    pub phases: Vec<PhaseModel>,
+    // <param key value> children (actuated and delay-based settings)
+    pub params: Vec<(String, String)>,
+}
+
+pub(crate) struct NetModel {
+    // Built for left-hand traffic (lane 0 is then the leftmost lane)
+    pub lefthand: bool,
+    pub location: Option<LocationModel>,
+    pub edges: Vec<EdgeModel>,
+    pub edge_index: HashMap<String, usize>,
+    pub junctions: Vec<JunctionModel>,
+    pub junction_index: HashMap<String, usize>,
+    pub connections: Vec<ConnectionModel>,
+    pub tl_logics: Vec<TlLogicModel>,
+}
+
+impl NetModel {
+    pub fn from_root(root: roxmltree::Node) -> NetModel {
+        let mut location = None;
+        let mut edges = Vec::new();
+        let mut junctions = Vec::new();
+        let mut connections = Vec::new();
+        let mut tl_logics = Vec::new();
+
+        for node in root.children().filter(|n| n.is_element()) {
+            match node.tag_name().name() {
+                "location" if location.is_none() => location = Some(read_location(node)),
+                "edge" => edges.push(read_edge(node)),
+                "junction" => {
+                    if let Some(j) = read_junction(node) {
+                        junctions.push(j);
+                    }
+                }
+                "connection" => {
+                    if let Some(c) = read_connection(node) {
+                        connections.push(c);
+                    }
+                }
+                "tlLogic" => {
+                    if let Some(t) = read_tl_logic(node) {
+                        tl_logics.push(t);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        NetModel::new(root.attribute("lefthand") == Some("true"), location, edges, junctions, connections, tl_logics)
+    }
+
+    pub fn new(
+        lefthand: bool,
+        location: Option<LocationModel>,
+        edges: Vec<EdgeModel>,
+        junctions: Vec<JunctionModel>,
+        connections: Vec<ConnectionModel>,
+        tl_logics: Vec<TlLogicModel>,
+    ) -> NetModel {
+        let edge_index = edges.iter().enumerate().map(|(i, e)| (e.id.clone(), i)).collect();
+        let junction_index = junctions.iter().enumerate().map(|(i, j)| (j.id.clone(), i)).collect();
+
+        NetModel {
+            lefthand,
+            location,
+            edges,
+            edge_index,
+            junctions,
+            junction_index,
+            connections,
+            tl_logics,
+        }
+    }
+
+    pub fn edge(&self, id: &str) -> Option<&EdgeModel> {
+        self.edge_index.get(id).map(|&i| &self.edges[i])
+    }
+
+    // Lane ids are "<edge id>_<index>"
+    pub fn lane(&self, lane_id: &str) -> Option<&LaneModel> {
+        let (edge_id, index) = lane_id.rsplit_once('_')?;
+        self.edge(edge_id)?.lane(index.parse().ok()?)
+    }
+
+    pub fn junction(&self, id: &str) -> Option<&JunctionModel> {
+        self.junction_index.get(id).map(|&i| &self.junctions[i])
+    }
+}
+
+fn read_location(node: roxmltree::Node) -> LocationModel {
+    let net_offset = node
+        .attribute("netOffset")
+        .and_then(|s| {
+            let (x, y) = s.split_once(',')?;
+            Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+        })
+        .unwrap_or((0.0, 0.0));
+
+    LocationModel {
+        net_offset,
+        proj_parameter: node.attribute("projParameter").unwrap_or("!").to_string(),
+    }
+}
+
+fn read_edge(node: roxmltree::Node) -> EdgeModel {
+    let lanes = node
+        .children()
+        .filter(|n| n.tag_name().name() == "lane")
+        .enumerate()
+        .map(|(pos, l)| {
+            let (shape, elevation) = l.attribute("shape").map(parse_point_string_z).unwrap_or_default();
+            LaneModel {
+                id: l.attribute("id").unwrap_or("").to_string(),
+                index: l.attribute("index").and_then(|s| s.parse().ok()).unwrap_or(pos),
+                speed: attr_f64(l, "speed"),
+                length: attr_f64(l, "length"),
+                allow: l.attribute("allow").map(String::from),
+                disallow: l.attribute("disallow").map(String::from),
+                width: attr_f64(l, "width"),
+                change_left: l.attribute("changeLeft").map(String::from),
+                change_right: l.attribute("changeRight").map(String::from),
+                end_offset: attr_f64(l, "endOffset"),
+                shape,
+                elevation,
+            }
+        })
+        .collect();
+
+    EdgeModel {
+        id: node.attribute("id").unwrap_or("").to_string(),
+        from: node.attribute("from").map(String::from),
+        to: node.attribute("to").map(String::from),
+        function: node.attribute("function").unwrap_or("").to_string(),
+        edge_type: node.attribute("type").unwrap_or("").to_string(),
+        priority: node.attribute("priority").and_then(|p| p.parse().ok()),
+        name: node.attribute("name").map(String::from),
+        spread_type: node.attribute("spreadType").map(String::from),
+        shape: node.attribute("shape").map(parse_point_string).unwrap_or_default(),
+        lanes,
+    }
+}
+
+fn read_junction(node: roxmltree::Node) -> Option<JunctionModel> {
+    Some(JunctionModel {
+        id: node.attribute("id")?.to_string(),
+        junction_type: node.attribute("type").unwrap_or("").to_string(),
+        x: attr_f64(node, "x")?,
+        y: attr_f64(node, "y")?,
+        z: attr_f64(node, "z"),
+        shape: node.attribute("shape").map(parse_point_string).unwrap_or_default(),
+        radius: attr_f64(node, "radius"),
+        right_of_way: node.attribute("rightOfWay").map(String::from),
+        fringe: node.attribute("fringe").map(String::from),
+    })
+}
+
+fn read_connection(node: roxmltree::Node) -> Option<ConnectionModel> {
+    Some(ConnectionModel {
+        from: node.attribute("from")?.to_string(),
+        to: node.attribute("to")?.to_string(),
+        from_lane: node.attribute("fromLane")?.parse().ok()?,
+        to_lane: node.attribute("toLane")?.parse().ok()?,
+        via: node.attribute("via").map(String::from),
+        dir: node.attribute("dir").unwrap_or("").to_string(),
+        state: node.attribute("state").unwrap_or("").to_string(),
+        tl: node.attribute("tl").map(String::from),
+        link_index: node.attribute("linkIndex").and_then(|s| s.parse().ok()),
+        options: CONNECTION_OPTIONS
+            .iter()
+            .filter_map(|&name| Some((name, node.attribute(name)?.to_string())))
+            .collect(),
+    })
+}
+
+fn read_tl_logic(node: roxmltree::Node) -> Option<TlLogicModel> {
+    let phases = node
+        .children()
+        .filter(|n| n.tag_name().name() == "phase")
+        .filter_map(|p| {
+            Some(PhaseModel {
+                duration: p.attribute("duration")?.to_string(),
+                state: p.attribute("state")?.to_string(),
+                options: p
+                    .attributes()
+                    .filter(|a| a.name() != "duration" && a.name() != "state")
+                    .map(|a| (a.name().to_string(), a.value().to_string()))
+                    .collect(),
+            })
+        })
+        .collect();
+    Some(TlLogicModel {
+        id: node.attribute("id")?.to_string(),
+        program_id: node.attribute("programID").unwrap_or("0").to_string(),
+        logic_type: node.attribute("type").unwrap_or("static").to_string(),
+        offset: node.attribute("offset").map(String::from),
+        phases,
+        params: node
+            .children()
+            .filter(|n| n.tag_name().name() == "param")
+            .filter_map(|p| Some((p.attribute("key")?.to_string(), p.attribute("value")?.to_string())))
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(xml: &str) -> NetModel {
+        let doc = roxmltree::Document::parse(xml).unwrap();
+        NetModel::from_root(doc.root_element())
+    }
+
+    const NET: &str = r#"<net>
+        <edge id="a" from="j0" to="j1">
+            <lane id="a_0" index="0" speed="10" shape="0,0 100,0"/>
+            <lane id="a_1" index="1" speed="20" allow="bus" shape="0,3 100,3"/>
+        </edge>
+        <edge id="b" from="j1" to="j2"><lane id="b_0" index="0" shape="100,0 100,50"/></edge>
+        <edge id=":j1_c0" function="crossing" crossingEdges="a"><lane id=":j1_c0_0" index="0" shape="95,-5 95,5"/></edge>
+        <edge id=":j1_0" function="internal"><lane id=":j1_0_0" index="0" shape="100,0 100,0"/></edge>
+    </net>"#;
+
+    #[test]
+    fn free_flow_time_uses_the_fastest_permitted_lane() {
+        let net = model(NET);
+        let a = net.edge("a").unwrap();
+        assert_eq!(a.free_flow_time(None), 5.0);
+        assert_eq!(a.free_flow_time(Some("passenger")), 10.0);
+        assert_eq!(a.free_flow_time(Some("bus")), 5.0);
+    }
+
+    #[test]
+    fn free_flow_time_defaults_to_50_kmh() {
+        let net = model(NET);
+        assert_eq!(net.edge("b").unwrap().free_flow_time(None), 50.0 / DEFAULT_SPEED);
+    }
+
+    #[test]
+    fn normal_edges_exclude_internal_and_pedestrian_edges() {
+        let net = model(NET);
+        let normal: Vec<&str> = net.edges.iter().filter(|e| e.is_normal()).map(|e| e.id.as_str()).collect();
+        assert_eq!(normal, ["a", "b"]);
+    }
+
+    #[test]
+    fn lanes_are_found_by_edge_and_index() {
+        let net = model(NET);
+        assert_eq!(net.lane("a_1").and_then(|l| l.speed), Some(20.0));
+        assert_eq!(net.lane(":j1_c0_0").map(|l| l.id.as_str()), Some(":j1_c0_0"));
+        assert!(net.lane("a_2").is_none());
+        assert!(net.lane("a").is_none());
+    }
+}
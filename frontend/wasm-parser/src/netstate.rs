@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+
+use crate::{attr_f64, parse_xml};
+
+// A --netstate-dump, stored sparsely: for each timestep only the lanes that
+// carry vehicles, as (lane, count, mean speed) runs. `occupancy(step)` and
+// `speeds(step)` expand one timestep into dense per-lane arrays for replay.
+#[wasm_bindgen]
+pub struct NetstateDump {
+    times: Vec<f64>,
+    // Column order of the dense arrays: lanes in order of first appearance
+    lane_ids: Vec<String>,
+    // Entries of timestep i are step_start[i]..step_start[i + 1]
+    step_start: Vec<u32>,
+    lane: Vec<u32>,
+    count: Vec<u16>,
+    speed: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl NetstateDump {
+    // Number of timesteps
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.times.len()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn times(&self) -> Vec<f64> {
+        self.times.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name = laneIds)]
+    pub fn lane_ids(&self) -> Vec<String> {
+        self.lane_ids.clone()
+    }
+
+    // Vehicles on each lane (laneIds order) at timestep `step`
+    pub fn occupancy(&self, step: usize) -> Result<Vec<u16>, JsValue> {
+        let mut dense = vec![0; self.lane_ids.len()];
+        for i in self.entries(step)? {
+            dense[self.lane[i] as usize] = self.count[i];
+        }
+        Ok(dense)
+    }
+
+    // Mean vehicle speed (m/s) on each lane at timestep `step`; NaN when empty
+    pub fn speeds(&self, step: usize) -> Result<Vec<f32>, JsValue> {
+        let mut dense = vec![f32::NAN; self.lane_ids.len()];
+        for i in self.entries(step)? {
+            dense[self.lane[i] as usize] = self.speed[i];
+        }
+        Ok(dense)
+    }
+}
+
+impl NetstateDump {
+    fn shrink_to_fit(&mut self) {
+        self.times.shrink_to_fit();
+        self.lane_ids.shrink_to_fit();
+        self.step_start.shrink_to_fit();
+        self.lane.shrink_to_fit();
+        self.count.shrink_to_fit();
+        self.speed.shrink_to_fit();
+    }
+
+    fn entries(&self, step: usize) -> Result<std::ops::Range<usize>, JsValue> {
+        if step >= self.times.len() {
+            return Err(JsValue::from_str(&format!("Timestep {} out of range", step)));
+        }
+        Ok(self.step_start[step] as usize..self.step_start[step + 1] as usize)
+    }
+}
+
+// <netstate><timestep time><edge id><lane id><vehicle id pos speed/>
+pub(crate) fn read_netstate(root: roxmltree::Node) -> NetstateDump {
+    let mut dump = NetstateDump {
+        times: Vec::new(),
+        lane_ids: Vec::new(),
+        step_start: vec![0],
+        lane: Vec::new(),
+        count: Vec::new(),
+        speed: Vec::new(),
+    };
+    let mut lane_index: HashMap<&str, u32> = HashMap::new();
+
+    for step in root.children().filter(|n| n.tag_name().name() == "timestep") {
+        dump.times.push(attr_f64(step, "time").unwrap_or(f64::NAN));
+        let lanes = step
+            .children()
+            .filter(|n| n.tag_name().name() == "edge")
+            .flat_map(|e| e.children().filter(|n| n.tag_name().name() == "lane"));
+        for lane in lanes {
+            let Some(id) = lane.attribute("id") else { continue };
+            let speeds: Vec<f64> = lane
+                .children()
+                .filter(|n| n.tag_name().name() == "vehicle")
+                .map(|v| attr_f64(v, "speed").unwrap_or(f64::NAN))
+                .collect();
+            if speeds.is_empty() {
+                continue;
+            }
+            let index = *lane_index.entry(id).or_insert_with(|| {
+                dump.lane_ids.push(id.to_string());
+                (dump.lane_ids.len() - 1) as u32
+            });
+            dump.lane.push(index);
+            dump.count.push(speeds.len().min(u16::MAX as usize) as u16);
+            dump.speed.push((speeds.iter().sum::<f64>() / speeds.len() as f64) as f32);
+        }
+        dump.step_start.push(dump.lane.len() as u32);
+    }
+    dump.shrink_to_fit();
+    dump
+}
+
+#[wasm_bindgen]
+pub fn parse_netstate_dump(xml_text: &str) -> Result<NetstateDump, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let dump = read_netstate(doc.root_element());
+
+    console_log!(
+        "Parsed netstate dump: {} timesteps, {} lanes occupied",
+        dump.times.len(),
+        dump.lane_ids.len()
+    );
+
+    Ok(dump)
+}
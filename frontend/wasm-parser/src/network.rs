@@ -0,0 +1,781 @@
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+#[cfg(any(feature = "msgpack", feature = "cbor"))]
+use crate::binary;
+use crate::budget::FrameBudget;
+use crate::crop::{self, CropRegion, Region};
+use crate::deckgl::{self, PathBuffers};
+use crate::heatmap::{self, HeatmapKeyframes, HeatmapOptions};
+use crate::intern::IdTable;
+use crate::linref::LaneGeometry;
+use crate::live::{LiveDelta, LiveState, LiveStates, NetworkUpdate};
+use crate::markings::DEFAULT_LANE_WIDTH;
+use crate::mapmatch::{self, MatchParams, MatchedTrace, RoadGraph, TraceMatchOptions, TracePoint};
+use crate::mesh::{self, MeshBuffers};
+use crate::mvt::{self, LayerBuilder, TileFrame};
+use crate::net::NetModel;
+use crate::plain;
+use crate::projection::{self, Crs, GeoReference};
+#[cfg(feature = "raster")]
+use crate::raster::{self, RasterImage, RasterOptions};
+use crate::scan::{self, NetworkScan};
+#[cfg(feature = "snapshot")]
+use crate::snapshot;
+use crate::spatial::SegmentGrid;
+use crate::svg::{self, SvgOptions};
+use crate::{
+    parse_options, parse_xml, rdp_keep, retain_kept, to_js, Bounds, Lane, NetAccumulator, ParsedNetwork, TrafficLight,
+    SIMPLIFY_EPS,
+};
+
+// Grid cell edge in network meters; a few city blocks per cell
+const INDEX_CELL_SIZE: f64 = 100.0;
+// Internal lanes only appear in tiles at most this wide (meters)
+const INTERNAL_LANES_MAX_TILE: f64 = 1000.0;
+
+pub(crate) fn to_xy(points: &[Vec<f64>]) -> Vec<(f64, f64)> {
+    // Output points are [lat, lng] = [y, x]
+    points.iter().map(|p| (p[1], p[0])).collect()
+}
+
+// Meters per shape unit; plain-geo networks measure in degrees
+pub(crate) fn shape_scale(geo: &GeoReference) -> f64 {
+    if geo.is_plain_geo() {
+        projection::METERS_PER_DEGREE
+    } else {
+        1.0
+    }
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct NearbyTrafficLight {
+    #[serde(flatten)]
+    pub tl: TrafficLight,
+    // Meters from the query point (network units)
+    pub distance: f64,
+}
+
+// A parsed network kept inside WASM, so the frontend can ask for just the part
+// on screen instead of holding every lane as a Leaflet layer.
+#[wasm_bindgen]
+pub struct Network {
+    parsed: ParsedNetwork,
+    // Owners are indices into parsed.lanes / parsed.junctions / parsed.tls
+    lane_index: SegmentGrid,
+    junction_index: SegmentGrid,
+    tls_index: SegmentGrid,
+    // Square covered by tile 0/0/0: lower-left corner and side length
+    tile_origin: (f64, f64),
+    tile_size: f64,
+    budget: FrameBudget,
+    geo: GeoReference,
+    // Edge graph for map-matching, over the shapes in `model`
+    roads: RoadGraph,
+    // Full topology and attributes: the unsimplified lane shapes for linear
+    // referencing and map-matching, and plain XML export
+    model: NetModel,
+    // Built on first use; indices stay valid for the lifetime of the handle
+    ids: OnceCell<IdTable>,
+    intern_ids: bool,
+    // Speed limits and closures applied since loading, see live.rs
+    live: LiveStates,
+}
+
+#[wasm_bindgen]
+impl Network {
+    #[wasm_bindgen(constructor)]
+    pub fn new(xml_text: &str) -> Result<Network, JsValue> {
+        let doc = parse_xml(xml_text)?;
+        let mut acc = NetAccumulator::new();
+        acc.add_document(doc.root_element());
+        let geo = acc.geo.clone();
+        let model = NetModel::from_root(doc.root_element());
+        Ok(Network::from_parsed(acc.finish(), geo, model))
+    }
+
+    // Quick first pass over a large file: bounds, counts, traffic lights and
+    // edge ids right away, geometry later with NetworkScan.load_geometry
+    pub fn scan(xml_text: &str) -> Result<NetworkScan, JsValue> {
+        scan::scan_net(xml_text)
+    }
+
+    #[wasm_bindgen(getter, js_name = laneCount)]
+    pub fn lane_count(&self) -> usize {
+        self.parsed.lanes.len()
+    }
+
+    // A new network with only the edges inside or crossing `region`, their
+    // junctions and the connections between them (see crop.rs). Live updates
+    // are not carried over.
+    pub fn crop(
+        &self,
+        #[wasm_bindgen(unchecked_param_type = "CropRegion")] region: JsValue,
+    ) -> Result<Network, JsValue> {
+        let region: CropRegion = parse_options(region)?;
+        let region = Region::from_options(&region).map_err(|e| JsValue::from_str(&e))?;
+        let model = crop::crop_model(&self.model, &region);
+        let parsed = crop::crop_parsed(&self.parsed, &model);
+
+        console_log!(
+            "Cropped network to {} of {} edges and {} of {} junctions",
+            model.edges.len(),
+            self.model.edges.len(),
+            model.junctions.len(),
+            self.model.junctions.len()
+        );
+
+        Ok(Network::from_parsed(parsed, self.geo.clone(), model))
+    }
+
+    // The network as a standalone SVG document, with current live updates
+    // applied (closed lanes drawn dark)
+    pub fn render_svg(
+        &self,
+        #[wasm_bindgen(unchecked_param_type = "SvgOptions | undefined")] options: JsValue,
+    ) -> Result<String, JsValue> {
+        let options: SvgOptions = parse_options(options)?;
+        let lanes = self.live_lanes()?;
+        let scale = shape_scale(&self.geo);
+        let svg = svg::write_svg(&lanes, &self.parsed.junctions, &self.view_bounds(), scale, &options)
+            .map_err(|e| JsValue::from_str(&e))?;
+        console_debug!("Rendered SVG of {} bytes", svg.len());
+        Ok(svg)
+    }
+
+    // The network as plain XML files for netconvert, with the live updates of
+    // `generation` (default current) baked in: changed speed limits, and
+    // closed edges disallowing all vehicles
+    #[wasm_bindgen(unchecked_return_type = "PlainXml")]
+    pub fn export_plain(&self, generation: Option<u64>) -> Result<JsValue, JsValue> {
+        let live = self.live_state(generation)?;
+        to_js(&plain::write_plain(&self.model, Some(&live)))
+    }
+
+    // Everything, as returned by parse_sumo_net_xml, with live updates applied
+    #[wasm_bindgen(unchecked_return_type = "ParsedNetwork")]
+    pub fn all(&self, generation: Option<u64>) -> Result<JsValue, JsValue> {
+        to_js(&self.snapshot(generation)?)
+    }
+
+    // `all()` as MessagePack, for IndexedDB or another worker (decode with
+    // network_from_msgpack)
+    #[cfg(feature = "msgpack")]
+    pub fn to_msgpack(&self, generation: Option<u64>) -> Result<Vec<u8>, JsValue> {
+        binary::to_msgpack(&*self.snapshot(generation)?)
+    }
+
+    // `all()` as CBOR (decode with network_from_cbor)
+    #[cfg(feature = "cbor")]
+    pub fn to_cbor(&self, generation: Option<u64>) -> Result<Vec<u8>, JsValue> {
+        binary::to_cbor(&*self.snapshot(generation)?)
+    }
+
+    // Output ids as indices into `id_table()` from now on. Slices then carry
+    // no table of their own, so fetch it once and resolve indices locally.
+    pub fn set_intern_ids(&mut self, intern: bool) {
+        self.intern_ids = intern;
+    }
+
+    #[wasm_bindgen(js_name = idTable)]
+    pub fn id_table(&self) -> Vec<String> {
+        self.table().ids().to_vec()
+    }
+
+    // Lane, edge or junction id of an interned index
+    pub fn id(&self, index: u32) -> Option<String> {
+        self.table().id(index).map(String::from)
+    }
+
+    #[wasm_bindgen(js_name = idIndex)]
+    pub fn id_index(&self, id: &str) -> Option<u32> {
+        self.table().index_of(id)
+    }
+
+    // Apply speed limit changes and closures as one step; returns the new
+    // generation. Fails without changing anything if an edge is unknown.
+    pub fn apply_update(
+        &mut self,
+        #[wasm_bindgen(unchecked_param_type = "NetworkUpdate")] update: JsValue,
+    ) -> Result<u64, JsValue> {
+        let update: NetworkUpdate = serde_wasm_bindgen::from_value(update)
+            .map_err(|e| JsValue::from_str(&format!("Invalid update: {}", e)))?;
+        let edges: HashSet<&str> = self.parsed.lanes.iter().filter_map(|l| l.edge_id.as_deref()).collect();
+        let generation = self.live.update(update, &edges).map_err(|e| JsValue::from_str(&e))?;
+        console_debug!("Network updated to generation {}", generation);
+        Ok(generation)
+    }
+
+    // Number of updates applied so far
+    #[wasm_bindgen(getter)]
+    pub fn generation(&self) -> u64 {
+        self.live.generation()
+    }
+
+    // Keep the current state readable after later updates: pass the returned
+    // generation to all / slice_bbox / slice_adaptive / tile until unpin()
+    pub fn pin(&mut self) -> u64 {
+        self.live.pin()
+    }
+
+    pub fn unpin(&mut self, generation: u64) {
+        self.live.unpin(generation);
+    }
+
+    // Everything the updates after generation `since` changed: the dirty
+    // tiles at zoom `z` and the recolored paths of path_buffers(color_by), so
+    // live edits repaint without refetching the network
+    pub fn changes_since(&self, since: u64, z: u32, color_by: Option<String>) -> Result<LiveDelta, JsValue> {
+        if z > 30 {
+            return Err(JsValue::from_str(&format!("Invalid zoom {}", z)));
+        }
+        let edges = self.live.changed_since(since).map_err(|e| JsValue::from_str(&e))?;
+        let live = self.live_state(None)?;
+        let color_of = deckgl::lane_color(color_by.as_deref().unwrap_or("speed")).map_err(|e| JsValue::from_str(&e))?;
+
+        let mut delta = LiveDelta {
+            generation: live.generation,
+            tiles: Vec::new(),
+            path_indices: Vec::new(),
+            colors: Vec::new(),
+        };
+        let mut tiles = HashSet::new();
+        let paths = self.parsed.lanes.iter().filter(|l| deckgl::is_path(l));
+        for (i, lane) in paths.enumerate() {
+            if !lane.edge_id.as_deref().is_some_and(|e| edges.contains(e)) {
+                continue;
+            }
+            let mut lane = lane.clone();
+            live.apply(&mut lane);
+            let color = color_of(&lane);
+            delta.path_indices.push(i as u32);
+            for _ in 0..lane.points.len() {
+                delta.colors.extend_from_slice(&color);
+            }
+            tiles.extend(self.tiles_covering(&to_xy(&lane.points), z));
+        }
+        let mut tiles: Vec<(u32, u32)> = tiles.into_iter().collect();
+        tiles.sort_unstable();
+        delta.tiles = tiles.into_iter().flat_map(|(x, y)| [x, y]).collect();
+
+        console_debug!(
+            "{} paths and {} tiles changed since generation {}",
+            delta.path_indices.len(),
+            delta.tiles.len() / 2,
+            since
+        );
+        Ok(delta)
+    }
+
+    // Lanes, junctions and signals intersecting the viewport. `lod` 0 is full
+    // detail; each level doubles the simplification tolerance, level 1 and up
+    // drops internal lanes and level 2 and up drops junction polygons (junction
+    // points are always kept). Bounds stay those of the whole network.
+    // `generation` reads a pinned state instead of the current one.
+    #[wasm_bindgen(unchecked_return_type = "ParsedNetwork")]
+    pub fn slice_bbox(
+        &self,
+        min_lat: f64,
+        min_lng: f64,
+        max_lat: f64,
+        max_lng: f64,
+        lod: u32,
+        generation: Option<u64>,
+    ) -> Result<JsValue, JsValue> {
+        let live = self.live_state(generation)?;
+        let min = (min_lng.min(max_lng), min_lat.min(max_lat));
+        let max = (min_lng.max(max_lng), min_lat.max(max_lat));
+        let inside = |lat: f64, lng: f64| lng >= min.0 && lng <= max.0 && lat >= min.1 && lat <= max.1;
+        let epsilon = if lod == 0 { 0.0 } else { SIMPLIFY_EPS * 2f64.powi(lod.min(16) as i32) };
+
+        let lanes = self
+            .lane_index
+            .owners_in_box(min, max)
+            .into_iter()
+            .map(|i| &self.parsed.lanes[i])
+            .filter(|l| lod == 0 || !l.is_internal)
+            .map(|l| {
+                let mut lane = l.clone();
+                if epsilon > 0.0 {
+                    let keep = rdp_keep(&to_xy(&l.points), epsilon);
+                    lane.points = retain_kept(&l.points, &keep);
+                    lane.elevation = l.elevation.as_ref().map(|z| retain_kept(z, &keep));
+                }
+                live.apply(&mut lane);
+                lane
+            })
+            .collect();
+
+        let junctions = if lod >= 2 {
+            Vec::new()
+        } else {
+            self.junction_index
+                .owners_in_box(min, max)
+                .into_iter()
+                .map(|i| self.parsed.junctions[i].clone())
+                .collect()
+        };
+
+        let mut slice = ParsedNetwork {
+            lanes,
+            bounds: self.parsed.bounds.clone(),
+            tls: self.parsed.tls.iter().filter(|t| inside(t.lat, t.lng)).cloned().collect(),
+            junctions,
+            junction_points: self
+                .parsed
+                .junction_points
+                .iter()
+                .filter(|j| inside(j.lat, j.lng))
+                .cloned()
+                .collect(),
+            roundabouts: self
+                .parsed
+                .roundabouts
+                .iter()
+                .filter(|r| r.ring.iter().any(|p| inside(p[0], p[1])))
+                .cloned()
+                .collect(),
+            crs: self.parsed.crs,
+            quantization: self.parsed.quantization,
+            ids: None,
+        };
+
+        console_debug!(
+            "slice_bbox lod {}: {} lanes, {} junctions",
+            lod,
+            slice.lanes.len(),
+            slice.junctions.len()
+        );
+
+        if self.intern_ids {
+            self.table().apply(&mut slice);
+        }
+        to_js(&slice)
+    }
+
+    // Frame time the adaptive level of detail aims for (default 1000/60 ms)
+    pub fn set_frame_budget(&mut self, target_ms: f64) {
+        self.budget.set_target(target_ms);
+    }
+
+    // Report the last frame's render time; returns the level of detail that
+    // slice_adaptive will use from now on
+    pub fn report_frame_time(&mut self, frame_ms: f64) -> u32 {
+        self.budget.report(frame_ms)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn lod(&self) -> u32 {
+        self.budget.lod()
+    }
+
+    // slice_bbox at the level of detail chosen from reported frame times
+    #[wasm_bindgen(unchecked_return_type = "ParsedNetwork")]
+    pub fn slice_adaptive(
+        &self,
+        min_lat: f64,
+        min_lng: f64,
+        max_lat: f64,
+        max_lng: f64,
+        generation: Option<u64>,
+    ) -> Result<JsValue, JsValue> {
+        self.slice_bbox(min_lat, min_lng, max_lat, max_lng, self.budget.lod(), generation)
+    }
+
+    // Mapbox Vector Tile z/x/y (y = 0 at the top) with layers "lanes",
+    // "junctions" and "tls". The pyramid is laid over the network's own
+    // coordinates: tile 0/0/0 is the square enclosing the network bounds.
+    pub fn tile(&self, z: u32, x: u32, y: u32, generation: Option<u64>) -> Result<Vec<u8>, JsValue> {
+        let live = self.live_state(generation)?;
+        if z > 30 || x >= 1 << z || y >= 1 << z {
+            return Err(JsValue::from_str(&format!("Invalid tile {}/{}/{}", z, x, y)));
+        }
+        let size = self.tile_size / (1u64 << z) as f64;
+        let top = self.tile_origin.1 + self.tile_size;
+        let frame = TileFrame {
+            min: (self.tile_origin.0 + x as f64 * size, top - (y + 1) as f64 * size),
+            max: (self.tile_origin.0 + (x + 1) as f64 * size, top - y as f64 * size),
+        };
+        let with_internal = size <= INTERNAL_LANES_MAX_TILE;
+
+        let mut lanes = LayerBuilder::new("lanes");
+        for i in self.lane_index.owners_in_box(frame.min, frame.max) {
+            let lane = &self.parsed.lanes[i];
+            if !with_internal && lane.is_internal {
+                continue;
+            }
+            if live.is_empty() {
+                mvt::add_lane(&mut lanes, &frame, i as u64, lane);
+            } else {
+                let mut lane = lane.clone();
+                live.apply(&mut lane);
+                mvt::add_lane(&mut lanes, &frame, i as u64, &lane);
+            }
+        }
+
+        let mut junctions = LayerBuilder::new("junctions");
+        for i in self.junction_index.owners_in_box(frame.min, frame.max) {
+            mvt::add_junction(&mut junctions, &frame, i as u64, &self.parsed.junctions[i]);
+        }
+
+        let mut tls = LayerBuilder::new("tls");
+        for (i, tl) in self.parsed.tls.iter().enumerate() {
+            mvt::add_traffic_light(&mut tls, &frame, i as u64, tl);
+        }
+
+        Ok(mvt::encode_tile(&[lanes, junctions, tls]))
+    }
+
+    // Snap a GPS trace to the edges it most likely followed. Points are
+    // lon/lat unless options.crs is "network".
+    #[wasm_bindgen(unchecked_return_type = "MatchedTrace")]
+    pub fn match_trace(
+        &self,
+        #[wasm_bindgen(unchecked_param_type = "TracePoint[]")] points: JsValue,
+        #[wasm_bindgen(unchecked_param_type = "TraceMatchOptions | undefined")] options: JsValue,
+    ) -> Result<JsValue, JsValue> {
+        let trace: Vec<TracePoint> = serde_wasm_bindgen::from_value(points)
+            .map_err(|e| JsValue::from_str(&format!("Invalid trace points: {}", e)))?;
+        let options: TraceMatchOptions = parse_options(options)?;
+
+        let lng_lat: Vec<(f64, f64)> = trace.iter().map(|p| (p.lng, p.lat)).collect();
+        let xy = self.to_network_xy(lng_lat, options.crs.unwrap_or(Crs::Wgs84))?;
+        let times: Vec<Option<f64>> = trace.iter().map(|p| p.time).collect();
+        let matched = self.match_xy(&xy, &times, &options);
+
+        console_debug!(
+            "Matched {} of {} trace points onto {} edges",
+            matched.points.iter().filter(|p| p.edge_id.is_some()).count(),
+            trace.len(),
+            matched.edges.len()
+        );
+
+        to_js(&matched)
+    }
+
+    // The `k` (default 1) signalized junctions closest to a point, nearest
+    // first, so a click near a signal selects it without hitting the marker
+    #[wasm_bindgen(unchecked_return_type = "NearbyTrafficLight[]")]
+    pub fn nearest_tls(&self, lat: f64, lng: f64, k: Option<usize>) -> Result<JsValue, JsValue> {
+        if !(lat.is_finite() && lng.is_finite()) {
+            return Err(JsValue::from_str("lat and lng must be finite"));
+        }
+        let nearest: Vec<NearbyTrafficLight> = self
+            .tls_index
+            .k_nearest((lng, lat), k.unwrap_or(1))
+            .into_iter()
+            .map(|(i, distance)| NearbyTrafficLight {
+                tl: self.parsed.tls[i].clone(),
+                distance,
+            })
+            .collect();
+        to_js(&nearest)
+    }
+
+    // Map position [lat, lng] of a SUMO lane position (meters from the lane
+    // start, negative from its end), as used by detectors, stops and TraCI
+    // vehicle positions
+    pub fn lane_point_at(&self, lane_id: &str, pos: f64) -> Option<Vec<f64>> {
+        let (x, y) = self.lane_lines().point_at(lane_id, pos)?;
+        Some(vec![y, x])
+    }
+
+    // WGS84 [lat, lng] of a map position, i.e. of SUMO network coordinates
+    // x = lng, y = lat. Fails for networks without a geo reference.
+    pub fn network_to_wgs84(&self, lat: f64, lng: f64) -> Result<Vec<f64>, JsValue> {
+        let (lon, lat) = self
+            .geo
+            .unproject(lng, lat)
+            .map_err(|e| JsValue::from_str(&format!("Cannot convert to WGS84: {}", e)))?;
+        Ok(vec![lat, lon])
+    }
+
+    // Map position [lat, lng] (network x, y as lng, lat) of a WGS84 position
+    pub fn wgs84_to_network(&self, lat: f64, lng: f64) -> Result<Vec<f64>, JsValue> {
+        let (x, y) = self
+            .geo
+            .project_wgs84(lng, lat)
+            .map_err(|e| JsValue::from_str(&format!("Cannot convert from WGS84: {}", e)))?;
+        Ok(vec![y, x])
+    }
+
+    // Lane position (meters from the lane start) closest to a map point
+    pub fn lane_offset_of(&self, lane_id: &str, lat: f64, lng: f64) -> Option<f64> {
+        self.lane_lines().offset_of(lane_id, (lng, lat))
+    }
+
+    // A lane's full-resolution shape, as in the network file; rendered
+    // lanes are simplified
+    #[wasm_bindgen(unchecked_return_type = "LaneShape | undefined")]
+    pub fn lane_shape(&self, lane_id: &str) -> Result<JsValue, JsValue> {
+        to_js(&self.lane_lines().shape(lane_id))
+    }
+
+    // Per-lane colors of `attribute` (e.g. "speed", "density") for every
+    // interval of an edgeData or laneData output, aligned with path_buffers()
+    pub fn heatmap_keyframes(
+        &self,
+        meandata_xml: &str,
+        attribute: &str,
+        #[wasm_bindgen(unchecked_param_type = "HeatmapOptions | undefined")] options: JsValue,
+    ) -> Result<HeatmapKeyframes, JsValue> {
+        let options: HeatmapOptions = parse_options(options)?;
+        let doc = parse_xml(meandata_xml)?;
+        let frames = heatmap::keyframes(
+            self.parsed.lanes.iter().filter(|l| l.points.len() >= 2),
+            doc.root_element(),
+            attribute,
+            &options,
+        );
+        console_debug!("Precomputed {} heatmap keyframes for {} lanes", frames.interval_count(), frames.lane_count());
+        Ok(frames)
+    }
+
+    // All lanes as deck.gl PathLayer binary attributes, see PathBuffers.
+    // `color_by` is "speed" (default), "id" or "type".
+    pub fn path_buffers(&self, color_by: Option<String>) -> Result<PathBuffers, JsValue> {
+        let origin = self.buffer_origin();
+        let color_by = color_by.as_deref().unwrap_or("speed");
+        let live = self.live_state(None)?;
+        let buffers = if live.is_empty() {
+            deckgl::pack_paths(self.parsed.lanes.iter(), origin, color_by)
+        } else {
+            let mut lanes = self.parsed.lanes.clone();
+            lanes.iter_mut().for_each(|l| live.apply(l));
+            deckgl::pack_paths(lanes.iter(), origin, color_by)
+        }
+        .map_err(|e| JsValue::from_str(&e))?;
+        console_debug!("Packed {} paths", buffers.length());
+        Ok(buffers)
+    }
+
+    // Filled road surfaces as one triangle mesh per `layer`: "lanes" (every
+    // lane buffered to its width, colored as by path_buffers) or "junctions"
+    // (junction polygons). Positions share path_buffers' origin.
+    pub fn surface_mesh(&self, layer: &str, color_by: Option<String>) -> Result<MeshBuffers, JsValue> {
+        let origin = self.buffer_origin();
+        let mesh = match layer {
+            "lanes" => {
+                let scale = shape_scale(&self.geo);
+                let width = |lane: &Lane| {
+                    let width = self.model.lane(&lane.id).and_then(|l| l.width).unwrap_or(DEFAULT_LANE_WIDTH);
+                    width / scale
+                };
+                let lanes = self.live_lanes()?;
+                mesh::lane_mesh(&lanes, width, origin, color_by.as_deref().unwrap_or("speed"))
+                    .map_err(|e| JsValue::from_str(&e))?
+            }
+            "junctions" => mesh::junction_mesh(&self.parsed.junctions, origin),
+            other => return Err(JsValue::from_str(&format!("Unknown mesh layer '{}'", other))),
+        };
+        console_debug!("Built {} mesh: {} features, {} triangles", layer, mesh.length(), mesh.triangle_count());
+        Ok(mesh)
+    }
+}
+
+#[cfg(feature = "raster")]
+#[wasm_bindgen]
+impl Network {
+    // The network drawn into an RGBA image, with current live updates
+    // applied; see RasterOptions
+    pub fn render_image(
+        &self,
+        #[wasm_bindgen(unchecked_param_type = "RasterOptions | undefined")] options: JsValue,
+    ) -> Result<RasterImage, JsValue> {
+        let options: RasterOptions = parse_options(options)?;
+        let lanes = self.live_lanes()?;
+        let scale = shape_scale(&self.geo);
+        let image = raster::rasterize(&lanes, &self.parsed.junctions, &self.view_bounds(), scale, &options)
+            .map_err(|e| JsValue::from_str(&e))?;
+        console_debug!("Rasterized network to {}x{}", image.width(), image.height());
+        Ok(image)
+    }
+}
+
+#[cfg(feature = "snapshot")]
+#[wasm_bindgen]
+impl Network {
+    // Everything needed to restore this handle with from_snapshot, for
+    // IndexedDB; live updates and id interning are not included
+    pub fn serialize_snapshot(&self) -> Result<Vec<u8>, JsValue> {
+        snapshot::write(&self.parsed, &self.model, &self.geo)
+    }
+
+    // A handle from serialize_snapshot output without parsing any XML; fails
+    // on snapshots written by a build with another format version
+    pub fn from_snapshot(bytes: &[u8]) -> Result<Network, JsValue> {
+        let (parsed, model, geo) = snapshot::read(bytes)?;
+        console_log!("Restored {} lanes from a {} byte snapshot", parsed.lanes.len(), bytes.len());
+        Ok(Network::from_parsed(parsed, geo, model))
+    }
+}
+
+impl Network {
+    pub(crate) fn from_parsed(
+        mut parsed: ParsedNetwork,
+        geo: GeoReference,
+        model: NetModel,
+    ) -> Network {
+        parsed.shrink_to_fit();
+        let roads = RoadGraph::from_model(&model);
+        let mut lane_index = SegmentGrid::new(INDEX_CELL_SIZE);
+        for (i, lane) in parsed.lanes.iter().enumerate() {
+            lane_index.insert_polyline(i, &to_xy(&lane.points));
+        }
+
+        let mut junction_index = SegmentGrid::new(INDEX_CELL_SIZE);
+        for (i, junction) in parsed.junctions.iter().enumerate() {
+            let mut ring = to_xy(&junction.polygon);
+            if let Some(first) = ring.first().copied() {
+                ring.push(first);
+            }
+            junction_index.insert_polyline(i, &ring);
+        }
+
+        let mut tls_index = SegmentGrid::new(INDEX_CELL_SIZE);
+        for (i, tl) in parsed.tls.iter().enumerate() {
+            tls_index.insert_point(i, (tl.lng, tl.lat));
+        }
+
+        let (min, max) = match &parsed.bounds {
+            Some(b) => ((b.min_x, b.min_y), (b.max_x, b.max_y)),
+            None => parsed
+                .lanes
+                .iter()
+                .flat_map(|l| to_xy(&l.points))
+                .fold(((f64::MAX, f64::MAX), (f64::MIN, f64::MIN)), |(lo, hi), p| {
+                    ((lo.0.min(p.0), lo.1.min(p.1)), (hi.0.max(p.0), hi.1.max(p.1)))
+                }),
+        };
+        let tile_size = (max.0 - min.0).max(max.1 - min.1);
+        let (tile_origin, tile_size) = if tile_size.is_finite() && tile_size > 0.0 {
+            (min, tile_size)
+        } else {
+            ((0.0, 0.0), 1.0)
+        };
+
+        lane_index.shrink_to_fit();
+        junction_index.shrink_to_fit();
+        tls_index.shrink_to_fit();
+
+        console_log!("Indexed {} lanes and {} junctions", parsed.lanes.len(), parsed.junctions.len());
+
+        Network {
+            parsed,
+            lane_index,
+            junction_index,
+            tls_index,
+            tile_origin,
+            tile_size,
+            budget: FrameBudget::new(),
+            geo,
+            roads,
+            model,
+            ids: OnceCell::new(),
+            intern_ids: false,
+            live: LiveStates::default(),
+        }
+    }
+
+    fn lane_lines(&self) -> LaneGeometry<'_> {
+        LaneGeometry::new(&self.model, shape_scale(&self.geo))
+    }
+
+    // (x, y) = (lng, lat) points in `crs` to network coordinates
+    pub(crate) fn to_network_xy(&self, points: Vec<(f64, f64)>, crs: Crs) -> Result<Vec<(f64, f64)>, JsValue> {
+        match crs {
+            Crs::Network => Ok(points),
+            Crs::Wgs84 => points
+                .into_iter()
+                .map(|(lng, lat)| self.geo.project_wgs84(lng, lat))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| JsValue::from_str(&format!("Cannot project trace: {}", e))),
+        }
+    }
+
+    // Map-match points in network coordinates; `options.crs` is ignored
+    pub(crate) fn match_xy(
+        &self,
+        xy: &[(f64, f64)],
+        times: &[Option<f64>],
+        options: &TraceMatchOptions,
+    ) -> MatchedTrace {
+        let scale = shape_scale(&self.geo);
+        let params = MatchParams {
+            radius: options.search_radius.filter(|r| *r > 0.0).unwrap_or(mapmatch::DEFAULT_SEARCH_RADIUS) / scale,
+            sigma: options.sigma.filter(|s| *s > 0.0).unwrap_or(mapmatch::DEFAULT_SIGMA) / scale,
+            beta: options.beta.filter(|b| *b > 0.0).unwrap_or(mapmatch::DEFAULT_BETA) / scale,
+            scale,
+        };
+        mapmatch::match_points(&self.roads, &self.model, xy, times, &params)
+    }
+
+    // x, y of the tiles at zoom `z` the bounding box of `points` touches
+    fn tiles_covering(&self, points: &[(f64, f64)], z: u32) -> Vec<(u32, u32)> {
+        let Some(first) = points.first() else { return Vec::new() };
+        let (min, max) = points.iter().fold((*first, *first), |(lo, hi), p| {
+            ((lo.0.min(p.0), lo.1.min(p.1)), (hi.0.max(p.0), hi.1.max(p.1)))
+        });
+        let count = 1u64 << z;
+        let size = self.tile_size / count as f64;
+        let top = self.tile_origin.1 + self.tile_size;
+        let index = |v: f64| (v / size).floor().clamp(0.0, (count - 1) as f64) as u32;
+        let (x0, x1) = (index(min.0 - self.tile_origin.0), index(max.0 - self.tile_origin.0));
+        let (y0, y1) = (index(top - max.1), index(top - min.1));
+        (x0..=x1).flat_map(|x| (y0..=y1).map(move |y| (x, y))).collect()
+    }
+
+    // All lanes with the current live updates applied, for drawing
+    fn live_lanes(&self) -> Result<Vec<Lane>, JsValue> {
+        let live = self.live_state(None)?;
+        let mut lanes = self.parsed.lanes.clone();
+        lanes.iter_mut().for_each(|l| live.apply(l));
+        Ok(lanes)
+    }
+
+    // Center of tile 0/0/0, subtracted from float32 buffer positions
+    fn buffer_origin(&self) -> (f64, f64) {
+        let half = self.tile_size / 2.0;
+        (self.tile_origin.0 + half, self.tile_origin.1 + half)
+    }
+
+    // Default area of network images; the square tile 0/0/0 covers for
+    // networks without a convBoundary
+    fn view_bounds(&self) -> Bounds {
+        self.parsed.bounds.clone().unwrap_or(Bounds {
+            min_x: self.tile_origin.0,
+            min_y: self.tile_origin.1,
+            max_x: self.tile_origin.0 + self.tile_size,
+            max_y: self.tile_origin.1 + self.tile_size,
+        })
+    }
+
+    fn live_state(&self, generation: Option<u64>) -> Result<Rc<LiveState>, JsValue> {
+        self.live.get(generation).map_err(|e| JsValue::from_str(&e))
+    }
+
+    fn table(&self) -> &IdTable {
+        self.ids.get_or_init(|| IdTable::from_network(&self.parsed))
+    }
+
+    // The parsed network with the live updates of `generation` and, when
+    // interning, ids as indices plus their table
+    fn snapshot(&self, generation: Option<u64>) -> Result<Cow<'_, ParsedNetwork>, JsValue> {
+        let live = self.live_state(generation)?;
+        if !self.intern_ids && live.is_empty() {
+            return Ok(Cow::Borrowed(&self.parsed));
+        }
+        let mut all = self.parsed.clone();
+        all.lanes.iter_mut().for_each(|l| live.apply(l));
+        if self.intern_ids {
+            self.table().apply(&mut all);
+            all.ids = Some(self.table().ids().to_vec());
+        }
+        Ok(Cow::Owned(all))
+    }
+}
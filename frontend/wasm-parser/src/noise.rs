@@ -0,0 +1,254 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::geometry;
+use crate::net::NetModel;
+use crate::spatial::SegmentGrid;
+use crate::{attr_f64, parse_options, parse_point_string, parse_xml, to_js};
+
+const DEFAULT_BUFFER: f64 = 200.0;
+const DEFAULT_RECEPTOR_TYPES: [&str; 6] = ["school", "kindergarten", "hospital", "clinic", "nursing", "university"];
+// Edge noise levels and volumes are taken to hold at this distance
+const REFERENCE_DISTANCE: f64 = 10.0;
+const INDEX_CELL_SIZE: f64 = 100.0;
+
+#[derive(Deserialize, Default, Tsify)]
+#[serde(default)]
+pub struct NoiseScreeningOptions {
+    // Edges farther than this from a receptor (meters, default 200) are ignored
+    pub buffer: Option<f64>,
+    // Case-insensitive substrings of the POI / polygon `type` that make it a
+    // sensitive receptor (default schools, kindergartens, hospitals, clinics,
+    // nursing homes and universities)
+    pub types: Option<Vec<String>>,
+    // Edge volumes (veh/h) by edge id; override those from edge data
+    pub volumes: HashMap<String, f64>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct ReceptorExposure {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub receptor_type: String,
+    // Position, or the vertex mean of a polygon receptor
+    pub lat: f64,
+    pub lng: f64,
+    // Edges within the buffer
+    pub edges: usize,
+    #[serde(rename = "nearestEdge")]
+    pub nearest_edge: Option<String>,
+    #[serde(rename = "nearestDistance")]
+    pub nearest_distance: Option<f64>,
+    // Energetic sum of edge noise levels (dB) attenuated as line sources,
+    // -3 dB per doubling of distance; None without noise data in range
+    #[serde(rename = "noiseLevel")]
+    pub noise_level: Option<f64>,
+    // Σ veh/h × 10 m / distance: traffic as if it all ran 10 m away
+    #[serde(rename = "trafficExposure")]
+    pub traffic_exposure: f64,
+}
+
+struct Receptor {
+    id: String,
+    receptor_type: String,
+    // A single point, or a closed polygon ring
+    shape: Vec<(f64, f64)>,
+}
+
+#[derive(Default)]
+struct EdgeLoad {
+    // Duration-weighted Σ 10^(L/10) and its weight
+    noise_energy: f64,
+    noise_time: f64,
+    entered: f64,
+    time: f64,
+}
+
+fn read_receptors(root: roxmltree::Node, net: &NetModel, types: &[String]) -> Vec<Receptor> {
+    root.descendants()
+        .filter(|n| matches!(n.tag_name().name(), "poi" | "poly"))
+        .filter_map(|n| {
+            let receptor_type = n.attribute("type").unwrap_or("");
+            let lower = receptor_type.to_ascii_lowercase();
+            if !types.iter().any(|t| lower.contains(t.as_str())) {
+                return None;
+            }
+            let shape = if n.tag_name().name() == "poly" {
+                // Closed, so distances include the last side
+                let mut ring = parse_point_string(n.attribute("shape")?);
+                if ring.len() > 2 && ring.first() != ring.last() {
+                    ring.push(ring[0]);
+                }
+                ring
+            } else if let (Some(x), Some(y)) = (attr_f64(n, "x"), attr_f64(n, "y")) {
+                vec![(x, y)]
+            } else {
+                // POIs placed on a lane; lon/lat POIs need a forward projection
+                let lane = net.lane(n.attribute("lane")?)?;
+                vec![geometry::point_at(&lane.shape, attr_f64(n, "pos").unwrap_or(0.0))?]
+            };
+            (!shape.is_empty()).then(|| Receptor {
+                id: n.attribute("id").unwrap_or("").to_string(),
+                receptor_type: receptor_type.to_string(),
+                shape,
+            })
+        })
+        .collect()
+}
+
+// Per-edge noise level (dB) and volume (veh/h) over all intervals of an
+// edgeData output; noise needs the harmonoise emission output
+fn read_edge_data(root: roxmltree::Node) -> HashMap<String, (Option<f64>, Option<f64>)> {
+    let mut loads: HashMap<&str, EdgeLoad> = HashMap::new();
+    for interval in root.children().filter(|n| n.tag_name().name() == "interval") {
+        let duration = attr_f64(interval, "end").unwrap_or(0.0) - attr_f64(interval, "begin").unwrap_or(0.0);
+        for edge in interval.children().filter(|n| n.tag_name().name() == "edge") {
+            let Some(id) = edge.attribute("id") else { continue };
+            let load = loads.entry(id).or_default();
+            let weight = duration.max(1.0);
+            if let Some(noise) = attr_f64(edge, "noise") {
+                load.noise_energy += 10f64.powf(noise / 10.0) * weight;
+                load.noise_time += weight;
+            }
+            if let Some(entered) = attr_f64(edge, "entered").or_else(|| attr_f64(edge, "left")) {
+                load.entered += entered;
+                load.time += duration;
+            }
+        }
+    }
+    loads
+        .into_iter()
+        .map(|(id, l)| {
+            let noise = (l.noise_time > 0.0).then(|| 10.0 * (l.noise_energy / l.noise_time).log10());
+            let volume = (l.time > 0.0).then(|| l.entered / l.time * 3600.0);
+            (id.to_string(), (noise, volume))
+        })
+        .collect()
+}
+
+pub(crate) fn screen_receptors(
+    net: &NetModel,
+    pois: roxmltree::Node,
+    edge_data: Option<roxmltree::Node>,
+    options: &NoiseScreeningOptions,
+) -> Vec<ReceptorExposure> {
+    let buffer = options.buffer.filter(|b| *b > 0.0).unwrap_or(DEFAULT_BUFFER);
+    let types: Vec<String> = match &options.types {
+        Some(types) => types.iter().map(|t| t.to_ascii_lowercase()).collect(),
+        None => DEFAULT_RECEPTOR_TYPES.iter().map(|t| t.to_string()).collect(),
+    };
+    let receptors = read_receptors(pois, net, &types);
+
+    let mut loads = edge_data.map(read_edge_data).unwrap_or_default();
+    for (id, volume) in &options.volumes {
+        loads.entry(id.clone()).or_insert((None, None)).1 = Some(*volume);
+    }
+
+    let edges: Vec<_> = net.edges.iter().filter(|e| e.is_normal()).collect();
+    let mut index = SegmentGrid::new(INDEX_CELL_SIZE);
+    for (i, edge) in edges.iter().enumerate() {
+        for lane in &edge.lanes {
+            index.insert_polyline(i, &lane.shape);
+        }
+    }
+
+    let mut table: Vec<ReceptorExposure> = receptors
+        .into_iter()
+        .map(|r| {
+            let (lo, hi) = r.shape.iter().fold(
+                ((f64::INFINITY, f64::INFINITY), (f64::NEG_INFINITY, f64::NEG_INFINITY)),
+                |(lo, hi), p| ((lo.0.min(p.0), lo.1.min(p.1)), (hi.0.max(p.0), hi.1.max(p.1))),
+            );
+            let is_area = r.shape.len() > 2;
+
+            let mut in_range = 0;
+            let mut nearest: Option<(&str, f64)> = None;
+            let mut noise_energy = 0.0;
+            let mut traffic_exposure = 0.0;
+            for i in index.owners_in_box((lo.0 - buffer, lo.1 - buffer), (hi.0 + buffer, hi.1 + buffer)) {
+                let edge = edges[i];
+                let inside = is_area
+                    && edge.lanes.iter().flat_map(|l| &l.shape).any(|p| geometry::point_in_polygon(*p, &r.shape));
+                let d = if inside {
+                    0.0
+                } else {
+                    edge.lanes
+                        .iter()
+                        .map(|l| geometry::polyline_distance(&l.shape, &r.shape))
+                        .fold(f64::INFINITY, f64::min)
+                };
+                if d > buffer {
+                    continue;
+                }
+                in_range += 1;
+                if nearest.is_none_or(|(_, best)| d < best) {
+                    nearest = Some((edge.id.as_str(), d));
+                }
+
+                let ratio = REFERENCE_DISTANCE / d.max(REFERENCE_DISTANCE);
+                if let Some((noise, volume)) = loads.get(&edge.id) {
+                    if let Some(noise) = noise {
+                        noise_energy += 10f64.powf(noise / 10.0) * ratio;
+                    }
+                    traffic_exposure += volume.unwrap_or(0.0) * ratio;
+                }
+            }
+
+            // Rings repeat their first vertex at the end
+            let vertices = if is_area { &r.shape[..r.shape.len() - 1] } else { &r.shape[..] };
+            let n = vertices.len() as f64;
+            let (x, y) = vertices.iter().fold((0.0, 0.0), |acc, p| (acc.0 + p.0 / n, acc.1 + p.1 / n));
+            ReceptorExposure {
+                id: r.id,
+                receptor_type: r.receptor_type,
+                lat: y,
+                lng: x,
+                edges: in_range,
+                nearest_edge: nearest.map(|(id, _)| id.to_string()),
+                nearest_distance: nearest.map(|(_, d)| d),
+                noise_level: (noise_energy > 0.0).then(|| 10.0 * noise_energy.log10()),
+                traffic_exposure,
+            }
+        })
+        .collect();
+
+    // Most exposed first: by noise when available, then by traffic
+    table.sort_by(|a, b| {
+        b.noise_level
+            .unwrap_or(f64::NEG_INFINITY)
+            .total_cmp(&a.noise_level.unwrap_or(f64::NEG_INFINITY))
+            .then(b.traffic_exposure.total_cmp(&a.traffic_exposure))
+    });
+    table
+}
+
+// Environmental screening table: sensitive receptors (schools, hospitals, ...
+// from a POI/polygon file) with the traffic noise and volume of the edges
+// within the buffer. `edge_data_xml` is an edgeData output, ideally the
+// harmonoise emission output for noise levels.
+#[wasm_bindgen(unchecked_return_type = "ReceptorExposure[]")]
+pub fn screen_noise_receptors(
+    net_xml: &str,
+    poi_xml: &str,
+    edge_data_xml: Option<String>,
+    #[wasm_bindgen(unchecked_param_type = "NoiseScreeningOptions | undefined")] options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let options: NoiseScreeningOptions = parse_options(options)?;
+    let net_doc = parse_xml(net_xml)?;
+    let net = NetModel::from_root(net_doc.root_element());
+    let poi_doc = parse_xml(poi_xml)?;
+    let data_doc = edge_data_xml.as_deref().map(parse_xml).transpose()?;
+
+    let table = screen_receptors(
+        &net,
+        poi_doc.root_element(),
+        data_doc.as_ref().map(|d| d.root_element()),
+        &options,
+    );
+
+    console_log!("Screened {} noise receptors", table.len());
+
+    to_js(&table)
+}
@@ -0,0 +1,229 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::net::NetModel;
+use crate::taz::read_tazs;
+use crate::{attr_f64, parse_options, parse_xml, to_js};
+
+const DEFAULT_MAX_WIDTH: f64 = 12.0;
+
+#[derive(Serialize, Deserialize, Tsify, Clone)]
+pub struct OdRelation {
+    pub from: String,
+    pub to: String,
+    pub count: f64,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct OdInterval {
+    // Seconds
+    pub begin: f64,
+    pub end: f64,
+    pub relations: Vec<OdRelation>,
+}
+
+#[derive(Deserialize, Default, Tsify)]
+#[serde(default)]
+pub struct DesireLineOptions {
+    // Width of the busiest line in pixels (default 12)
+    #[serde(rename = "maxWidth")]
+    pub max_width: Option<f64>,
+    // Relations below this count are dropped
+    #[serde(rename = "minCount")]
+    pub min_count: f64,
+    // Keep trips that start and end in the same TAZ (drawn as zero-length lines)
+    pub intrazonal: bool,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct DesireLine {
+    pub from: String,
+    pub to: String,
+    pub begin: f64,
+    pub end: f64,
+    pub count: f64,
+    // count / largest count over all slices × maxWidth, so widths compare
+    // across slices
+    pub width: f64,
+    // TAZ centroids as [lat, lng]
+    pub source: Vec<f64>,
+    pub target: Vec<f64>,
+}
+
+// "7.30" = 07:30 in the VISUM formats
+fn parse_visum_time(token: &str) -> Option<f64> {
+    let value: f64 = token.parse().ok()?;
+    let hours = value.trunc();
+    Some(hours * 3600.0 + ((value - hours) * 100.0).round() * 60.0)
+}
+
+fn next_token<'a>(tokens: &mut impl Iterator<Item = &'a str>, what: &str) -> Result<&'a str, String> {
+    tokens.next().ok_or_else(|| format!("OD matrix ends before {}", what))
+}
+
+// VISUM/SUMO text matrices: "$O" (from to count triples) and "$V" (full
+// matrix after the district names). Lines starting with '*' are comments.
+pub(crate) fn parse_visum_matrix(text: &str) -> Result<OdInterval, String> {
+    let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('*'));
+    let header = lines.next().unwrap_or("");
+    let mut tokens = lines.flat_map(str::split_whitespace);
+    let number = |token: &str, what: &str| token.parse::<f64>().map_err(|_| format!("Invalid {} '{}'", what, token));
+
+    let is_v = header.starts_with("$V");
+    if is_v {
+        // Vehicle type
+        next_token(&mut tokens, "vehicle type")?;
+    }
+    let begin = next_token(&mut tokens, "begin time")?;
+    let begin = parse_visum_time(begin).ok_or_else(|| format!("Invalid begin time '{}'", begin))?;
+    let end = next_token(&mut tokens, "end time")?;
+    let end = parse_visum_time(end).ok_or_else(|| format!("Invalid end time '{}'", end))?;
+    let factor = number(next_token(&mut tokens, "factor")?, "factor")?;
+
+    let mut relations = Vec::new();
+    if is_v {
+        let n = number(next_token(&mut tokens, "district count")?, "district count")? as usize;
+        let names = (0..n)
+            .map(|_| next_token(&mut tokens, "district names").map(String::from))
+            .collect::<Result<Vec<_>, _>>()?;
+        for from in &names {
+            for to in &names {
+                let count = number(next_token(&mut tokens, "matrix values")?, "matrix value")? * factor;
+                relations.push(OdRelation { from: from.clone(), to: to.clone(), count });
+            }
+        }
+    } else {
+        while let Some(from) = tokens.next() {
+            let to = next_token(&mut tokens, "destination")?;
+            let count = number(next_token(&mut tokens, "count")?, "count")? * factor;
+            relations.push(OdRelation { from: from.to_string(), to: to.to_string(), count });
+        }
+    }
+    Ok(OdInterval { begin, end, relations })
+}
+
+// <data><interval begin end><tazRelation from to count/></interval></data>
+pub(crate) fn parse_taz_relations(root: roxmltree::Node) -> Vec<OdInterval> {
+    root.descendants()
+        .filter(|n| n.tag_name().name() == "interval")
+        .map(|interval| OdInterval {
+            begin: attr_f64(interval, "begin").unwrap_or(0.0),
+            end: attr_f64(interval, "end").unwrap_or(0.0),
+            relations: interval
+                .children()
+                .filter(|n| n.tag_name().name() == "tazRelation")
+                .filter_map(|r| {
+                    Some(OdRelation {
+                        from: r.attribute("from")?.to_string(),
+                        to: r.attribute("to")?.to_string(),
+                        count: attr_f64(r, "count").unwrap_or(0.0),
+                    })
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+// TAZ centroid from its `center`, else its shape, else the midpoints of its
+// edges (needs the network)
+fn taz_centroids(root: roxmltree::Node, net: Option<&NetModel>) -> HashMap<String, (f64, f64)> {
+    read_tazs(root)
+        .iter()
+        .filter_map(|t| Some((t.id.clone(), t.centroid(net)?)))
+        .collect()
+}
+
+pub(crate) fn desire_lines(
+    intervals: &[OdInterval],
+    centroids: &HashMap<String, (f64, f64)>,
+    options: &DesireLineOptions,
+) -> Vec<DesireLine> {
+    let max_width = options.max_width.filter(|w| *w > 0.0).unwrap_or(DEFAULT_MAX_WIDTH);
+
+    let mut lines = Vec::new();
+    let mut missing = 0;
+    for interval in intervals {
+        // Sum duplicate relations (e.g. per vehicle type) within a slice
+        let mut totals: BTreeMap<(&str, &str), f64> = BTreeMap::new();
+        for r in &interval.relations {
+            if r.from != r.to || options.intrazonal {
+                *totals.entry((r.from.as_str(), r.to.as_str())).or_default() += r.count;
+            }
+        }
+        for ((from, to), count) in totals {
+            if count <= 0.0 || count < options.min_count {
+                continue;
+            }
+            let (Some(a), Some(b)) = (centroids.get(from), centroids.get(to)) else {
+                missing += 1;
+                continue;
+            };
+            lines.push(DesireLine {
+                from: from.to_string(),
+                to: to.to_string(),
+                begin: interval.begin,
+                end: interval.end,
+                count,
+                width: 0.0,
+                source: vec![a.1, a.0],
+                target: vec![b.1, b.0],
+            });
+        }
+    }
+    if missing > 0 {
+        console_log!("Skipped {} OD relations with unknown TAZ", missing);
+    }
+
+    let max_count = lines.iter().map(|l| l.count).fold(0.0, f64::max);
+    for line in &mut lines {
+        line.width = line.count / max_count * max_width;
+    }
+    lines
+}
+
+// Time slices of an OD matrix: SUMO tazRelation XML (one slice per
+// <interval>) or a VISUM $O / $V text matrix (a single slice)
+#[wasm_bindgen(unchecked_return_type = "OdInterval[]")]
+pub fn parse_od_matrix(text: &str) -> Result<JsValue, JsValue> {
+    let intervals = if text.trim_start().starts_with('<') {
+        let doc = parse_xml(text)?;
+        parse_taz_relations(doc.root_element())
+    } else {
+        vec![parse_visum_matrix(text).map_err(|e| JsValue::from_str(&e))?]
+    };
+
+    console_log!(
+        "Parsed {} OD slices with {} relations",
+        intervals.len(),
+        intervals.iter().map(|i| i.relations.len()).sum::<usize>()
+    );
+
+    to_js(&intervals)
+}
+
+// Desire lines between TAZ centroids for every slice of a parsed OD matrix,
+// for arc / flow-map layers. The network is only needed for TAZ defined by
+// edges alone.
+#[wasm_bindgen(unchecked_return_type = "DesireLine[]")]
+pub fn od_desire_lines(
+    #[wasm_bindgen(unchecked_param_type = "OdInterval[]")] matrix: JsValue,
+    taz_xml: &str,
+    net_xml: Option<String>,
+    #[wasm_bindgen(unchecked_param_type = "DesireLineOptions | undefined")] options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let intervals: Vec<OdInterval> = serde_wasm_bindgen::from_value(matrix)
+        .map_err(|e| JsValue::from_str(&format!("Invalid OD matrix: {}", e)))?;
+    let options: DesireLineOptions = parse_options(options)?;
+    let taz_doc = parse_xml(taz_xml)?;
+    let net_doc = net_xml.as_deref().map(parse_xml).transpose()?;
+    let net = net_doc.as_ref().map(|d| NetModel::from_root(d.root_element()));
+
+    let centroids = taz_centroids(taz_doc.root_element(), net.as_ref());
+    let lines = desire_lines(&intervals, &centroids, &options);
+
+    console_log!("Generated {} desire lines from {} TAZ", lines.len(), centroids.len());
+
+    to_js(&lines)
+}
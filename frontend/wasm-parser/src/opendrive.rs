@@ -0,0 +1,393 @@
+// OpenDRIVE (.xodr) roads as a ParsedNetwork: the plan view's parametric
+// geometries (lines, arcs, spirals, cubic polynomials) are sampled into
+// reference line points, and each lane section's lanes offset from it by
+// their widths. Road ids follow netconvert: "<road>" for the lanes driving
+// along the reference line and "-<road>" for the others, with ".<n>" per
+// lane section after the first.
+use wasm_bindgen::prelude::*;
+
+use crate::geometry::polyline_length;
+use crate::projection::{Crs, GeoReference};
+use crate::{attr_f64, logging, parse_options, parse_xml, to_js, units, Bounds, Lane, ParseOptions, ParsedNetwork};
+
+// Meters between samples along the reference line
+const SAMPLE_STEP: f64 = 2.0;
+// Meters per integration step along spirals
+const SPIRAL_STEP: f64 = 0.25;
+// Lane types kept; medians, shoulders, borders and the like are not lanes
+// in the SUMO sense
+const LANE_TYPES: [&str; 13] = [
+    "driving",
+    "bidirectional",
+    "bus",
+    "taxi",
+    "HOV",
+    "entry",
+    "exit",
+    "onRamp",
+    "offRamp",
+    "connectingRamp",
+    "biking",
+    "sidewalk",
+    "walking",
+];
+
+// SUMO vehicle classes of the reserved lane types; None for general traffic
+fn lane_permissions(lane_type: &str) -> Option<&'static str> {
+    match lane_type {
+        "bus" => Some("bus"),
+        "taxi" => Some("taxi"),
+        "biking" => Some("bicycle"),
+        "sidewalk" | "walking" => Some("pedestrian"),
+        _ => None,
+    }
+}
+
+// a + b ds + c ds² + d ds³ from `s` on; records are sorted by s
+#[derive(Clone, Copy)]
+struct Cubic {
+    s: f64,
+    a: f64,
+    b: f64,
+    c: f64,
+    d: f64,
+}
+
+impl Cubic {
+    fn read(node: roxmltree::Node, s_attr: &str) -> Option<Cubic> {
+        let get = |name| attr_f64(node, name);
+        Some(Cubic {
+            s: get(s_attr)?,
+            a: get("a").unwrap_or(0.0),
+            b: get("b").unwrap_or(0.0),
+            c: get("c").unwrap_or(0.0),
+            d: get("d").unwrap_or(0.0),
+        })
+    }
+
+    fn read_all(parent: Option<roxmltree::Node>, tag: &str, s_attr: &str) -> Vec<Cubic> {
+        let mut records: Vec<Cubic> = parent
+            .into_iter()
+            .flat_map(|p| p.children())
+            .filter(|n| n.tag_name().name() == tag)
+            .filter_map(|n| Cubic::read(n, s_attr))
+            .collect();
+        records.sort_by(|a, b| a.s.total_cmp(&b.s));
+        records
+    }
+
+    // Value of the last record starting at or before `s`; 0 before the first
+    fn eval(records: &[Cubic], s: f64) -> f64 {
+        let Some(r) = records.iter().rfind(|r| r.s <= s + 1e-9).or(records.first()) else {
+            return 0.0;
+        };
+        let ds = (s - r.s).max(0.0);
+        r.a + ds * (r.b + ds * (r.c + ds * r.d))
+    }
+}
+
+enum Shape {
+    Line,
+    Arc(f64),
+    Spiral { start: f64, end: f64 },
+    // v = a + b u + c u² + d u³ in the local frame
+    Poly3([f64; 4]),
+    // u and v coefficients; `normalized` when p runs over [0, 1] rather
+    // than [0, length]
+    ParamPoly3 { u: [f64; 4], v: [f64; 4], normalized: bool },
+}
+
+struct Geometry {
+    s: f64,
+    x: f64,
+    y: f64,
+    hdg: f64,
+    length: f64,
+    shape: Shape,
+}
+
+fn cubic(k: &[f64; 4], p: f64) -> (f64, f64) {
+    (k[0] + p * (k[1] + p * (k[2] + p * k[3])), k[1] + p * (2.0 * k[2] + p * 3.0 * k[3]))
+}
+
+impl Geometry {
+    fn read(node: roxmltree::Node) -> Option<Geometry> {
+        let kind = node.children().find(|n| n.is_element())?;
+        let coefficients = |names: [&str; 4]| names.map(|name| attr_f64(kind, name).unwrap_or(0.0));
+        let shape = match kind.tag_name().name() {
+            "line" => Shape::Line,
+            "arc" => Shape::Arc(attr_f64(kind, "curvature")?),
+            "spiral" => Shape::Spiral {
+                start: attr_f64(kind, "curvStart")?,
+                end: attr_f64(kind, "curvEnd")?,
+            },
+            "poly3" => Shape::Poly3(coefficients(["a", "b", "c", "d"])),
+            "paramPoly3" => Shape::ParamPoly3 {
+                u: coefficients(["aU", "bU", "cU", "dU"]),
+                v: coefficients(["aV", "bV", "cV", "dV"]),
+                normalized: kind.attribute("pRange") == Some("normalized"),
+            },
+            _ => return None,
+        };
+        Some(Geometry {
+            s: attr_f64(node, "s")?,
+            x: attr_f64(node, "x")?,
+            y: attr_f64(node, "y")?,
+            hdg: attr_f64(node, "hdg").unwrap_or(0.0),
+            // `at` clamps into [0, length]
+            length: attr_f64(node, "length").filter(|l| *l >= 0.0)?,
+            shape,
+        })
+    }
+
+    // Position and heading `ds` meters into the geometry
+    fn at(&self, ds: f64) -> (f64, f64, f64) {
+        let (sin, cos) = self.hdg.sin_cos();
+        // Local u (along hdg), v (left of it) and local heading
+        let (u, v, heading) = match self.shape {
+            Shape::Line => (ds, 0.0, 0.0),
+            Shape::Arc(k) if k.abs() < 1e-12 => (ds, 0.0, 0.0),
+            Shape::Arc(k) => ((k * ds).sin() / k, (1.0 - (k * ds).cos()) / k, k * ds),
+            Shape::Spiral { start, end } => {
+                // Curvature changes linearly; integrate the heading
+                let rate = if self.length > 0.0 { (end - start) / self.length } else { 0.0 };
+                let steps = (ds / SPIRAL_STEP).ceil().max(1.0) as usize;
+                let h = ds / steps as f64;
+                let (mut u, mut v) = (0.0, 0.0);
+                for i in 0..steps {
+                    let mid = (i as f64 + 0.5) * h;
+                    let theta = start * mid + 0.5 * rate * mid * mid;
+                    u += h * theta.cos();
+                    v += h * theta.sin();
+                }
+                (u, v, start * ds + 0.5 * rate * ds * ds)
+            }
+            // Arc length approximated by u; close for the gentle curves poly3
+            // is used for
+            Shape::Poly3(k) => {
+                let (v, dv) = cubic(&k, ds);
+                (ds, v, dv.atan())
+            }
+            Shape::ParamPoly3 { u, v, normalized } => {
+                let p = if normalized && self.length > 0.0 { ds / self.length } else { ds };
+                let ((pu, du), (pv, dv)) = (cubic(&u, p), cubic(&v, p));
+                (pu, pv, dv.atan2(du))
+            }
+        };
+        (self.x + u * cos - v * sin, self.y + u * sin + v * cos, self.hdg + heading)
+    }
+}
+
+struct RoadLane<'a> {
+    id: i32,
+    node: roxmltree::Node<'a, 'a>,
+    widths: Vec<Cubic>,
+}
+
+struct Section<'a> {
+    s: f64,
+    left: Vec<RoadLane<'a>>,
+    right: Vec<RoadLane<'a>>,
+}
+
+// Lanes of one side, innermost first
+fn read_side<'a>(section: roxmltree::Node<'a, 'a>, side: &str) -> Vec<RoadLane<'a>> {
+    let mut lanes: Vec<RoadLane> = section
+        .children()
+        .filter(|n| n.tag_name().name() == side)
+        .flat_map(|n| n.children())
+        .filter(|n| n.tag_name().name() == "lane")
+        .filter_map(|lane| {
+            let id: i32 = lane.attribute("id")?.parse().ok()?;
+            // Lanes described by <border> (outer edge) instead count as zero
+            // width
+            let widths = Cubic::read_all(Some(lane), "width", "sOffset");
+            Some(RoadLane { id, node: lane, widths })
+        })
+        .collect();
+    lanes.sort_by_key(|l| l.id.abs());
+    lanes
+}
+
+fn speed_of(node: roxmltree::Node) -> Option<f64> {
+    let max: f64 = node.attribute("max")?.parse().ok()?;
+    let factor = match node.attribute("unit").unwrap_or("m/s") {
+        "km/h" => 1.0 / 3.6,
+        "mph" => 0.44704,
+        _ => 1.0,
+    };
+    Some(max * factor).filter(|s| *s > 0.0)
+}
+
+fn read_road(road: roxmltree::Node, lanes: &mut Vec<Lane>) {
+    let Some(road_id) = road.attribute("id") else { return };
+    let road_length = attr_f64(road, "length").filter(|l| *l >= 0.0).unwrap_or(0.0);
+    let child = |name| road.children().find(|n| n.tag_name().name() == name);
+    let mut geometries: Vec<Geometry> = child("planView")
+        .into_iter()
+        .flat_map(|p| p.children())
+        .filter(|n| n.tag_name().name() == "geometry")
+        .filter_map(Geometry::read)
+        .collect();
+    geometries.sort_by(|a, b| a.s.total_cmp(&b.s));
+    if geometries.is_empty() {
+        return;
+    }
+    let elevation = Cubic::read_all(child("elevationProfile"), "elevation", "s");
+    let lanes_node = child("lanes");
+    let lane_offset = Cubic::read_all(lanes_node, "laneOffset", "s");
+    let sections: Vec<Section> = lanes_node
+        .into_iter()
+        .flat_map(|l| l.children())
+        .filter(|n| n.tag_name().name() == "laneSection")
+        .filter_map(|n| {
+            Some(Section {
+                s: attr_f64(n, "s")?,
+                left: read_side(n, "left"),
+                right: read_side(n, "right"),
+            })
+        })
+        .collect();
+    let road_speed = child("type").and_then(|t| t.children().find(|n| n.tag_name().name() == "speed")).and_then(speed_of);
+    let is_internal = road.attribute("junction").is_some_and(|j| j != "-1");
+    let rht = road.attribute("rule") != Some("LHT");
+
+    let reference = |s: f64| {
+        let g = geometries.iter().rfind(|g| g.s <= s + 1e-9).unwrap_or(&geometries[0]);
+        g.at((s - g.s).clamp(0.0, g.length))
+    };
+
+    for (k, section) in sections.iter().enumerate() {
+        let end = sections.get(k + 1).map_or(road_length, |next| next.s);
+        if end <= section.s {
+            continue;
+        }
+        // Sample positions, including every geometry start in the section
+        let mut stations: Vec<f64> = (0..)
+            .map(|i| section.s + i as f64 * SAMPLE_STEP)
+            .take_while(|s| *s < end)
+            .chain(geometries.iter().map(|g| g.s).filter(|s| *s > section.s && *s < end))
+            .chain([end])
+            .collect();
+        stations.sort_by(f64::total_cmp);
+        stations.dedup_by(|a, b| (*a - *b).abs() < 1e-6);
+        let poses: Vec<(f64, f64, f64)> = stations.iter().map(|s| reference(*s)).collect();
+        let z: Vec<f64> = stations.iter().map(|s| Cubic::eval(&elevation, *s)).collect();
+        let suffix = if k == 0 { String::new() } else { format!(".{}", k) };
+
+        for (side, sign) in [(&section.right, -1.0), (&section.left, 1.0)] {
+            let forward = (sign < 0.0) == rht;
+            let edge_id = format!("{}{}{}", if forward { "" } else { "-" }, road_id, suffix);
+            let kept: Vec<&RoadLane> = side
+                .iter()
+                .filter(|l| LANE_TYPES.contains(&l.node.attribute("type").unwrap_or("driving")))
+                .collect();
+            for lane in side {
+                let Some(position) = kept.iter().position(|k| k.id == lane.id) else { continue };
+                let lane_type = lane.node.attribute("type").unwrap_or("driving");
+                let mut points: Vec<(f64, f64)> = stations
+                    .iter()
+                    .zip(&poses)
+                    .map(|(&s, &(x, y, hdg))| {
+                        let ds = s - section.s;
+                        // Distance from the reference line to the lane's center
+                        let inner: f64 = side
+                            .iter()
+                            .take_while(|l| l.id != lane.id)
+                            .map(|l| Cubic::eval(&l.widths, ds))
+                            .sum();
+                        let t = Cubic::eval(&lane_offset, s) + sign * (inner + Cubic::eval(&lane.widths, ds) / 2.0);
+                        (x - t * hdg.sin(), y + t * hdg.cos())
+                    })
+                    .collect();
+                let mut heights = z.clone();
+                if !forward {
+                    points.reverse();
+                    heights.reverse();
+                }
+                // Index 0 is the rightmost lane in driving direction
+                let index = if rht { kept.len() - 1 - position } else { position };
+                let speed = lane
+                    .node
+                    .children()
+                    .find(|n| n.tag_name().name() == "speed")
+                    .and_then(speed_of)
+                    .or(road_speed);
+                lanes.push(Lane {
+                    id: format!("{}_{}", edge_id, index),
+                    id_index: None,
+                    edge_id: Some(edge_id.clone()),
+                    edge_index: None,
+                    length: Some(polyline_length(&points)),
+                    edge_length: Some(end - section.s),
+                    points: points.into_iter().map(|(x, y)| vec![y, x]).collect(),
+                    xy: None,
+                    elevation: (!elevation.is_empty()).then_some(heights),
+                    headings: None,
+                    speed,
+                    speed_kmh: speed.map(units::speed_limit_kmh),
+                    speed_mph: speed.map(units::speed_limit_mph),
+                    speed_class: speed.map(|s| units::SpeedClass::from_kmh(units::speed_limit_kmh(s))),
+                    capacity: None,
+                    allow: lane_permissions(lane_type).map(String::from),
+                    disallow: None,
+                    is_internal,
+                    is_roundabout: false,
+                    is_closed: false,
+                });
+            }
+        }
+    }
+}
+
+// The header's <geoReference> proj string, shifted by its <offset>
+fn read_geo_reference(root: roxmltree::Node) -> GeoReference {
+    let header = root.children().find(|n| n.tag_name().name() == "header");
+    let child = |name| header.and_then(|h| h.children().find(|n| n.tag_name().name() == name));
+    let Some(param) = child("geoReference").and_then(|g| g.text()).map(str::trim).filter(|p| !p.is_empty()) else {
+        return GeoReference::Unreferenced;
+    };
+    let offset = child("offset").map_or((0.0, 0.0), |o| {
+        let get = |name| o.attribute(name).and_then(|v| v.parse::<f64>().ok()).unwrap_or(0.0);
+        (-get("x"), -get("y"))
+    });
+    GeoReference::from_proj(param, offset)
+}
+
+pub(crate) fn read_opendrive(root: roxmltree::Node) -> (ParsedNetwork, GeoReference) {
+    let mut lanes = Vec::new();
+    for road in root.children().filter(|n| n.tag_name().name() == "road") {
+        read_road(road, &mut lanes);
+    }
+    let network = ParsedNetwork {
+        bounds: Bounds::of_points(lanes.iter().flat_map(|l| &l.points).map(|p| (p[1], p[0]))),
+        lanes,
+        tls: Vec::new(),
+        junctions: Vec::new(),
+        junction_points: Vec::new(),
+        roundabouts: Vec::new(),
+        crs: Crs::Network,
+        quantization: None,
+        ids: None,
+    };
+    (network, read_geo_reference(root))
+}
+
+// An OpenDRIVE file as a ParsedNetwork of lanes (no junction shapes or
+// signals). `fields` in the options is ignored.
+#[wasm_bindgen(unchecked_return_type = "ParsedNetwork")]
+pub fn parse_opendrive_xml(
+    xml_text: &str,
+    #[wasm_bindgen(unchecked_param_type = "ParseOptions | undefined")] options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let options: ParseOptions = parse_options(options)?;
+    logging::with_level(options.log_level, || {
+        let doc = parse_xml(xml_text)?;
+        let (mut network, geo) = read_opendrive(doc.root_element());
+        network.apply_options(&geo, &options)?;
+
+        console_log!("Converted OpenDRIVE roads into {} lanes", network.lanes.len());
+
+        to_js(&network)
+    })
+}
@@ -0,0 +1,158 @@
+// Raw OpenStreetMap XML: highway ways as lane-like polylines and the tagged
+// highway nodes (signals, crossings, stop signs), in lon/lat, so the UI can
+// lay OSM over a network parsed with crs "wgs84" and spot import errors.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::geometry;
+use crate::{parse_xml, to_js, units, Bounds};
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct OsmWay {
+    pub id: String,
+    // The highway tag, e.g. "primary", "residential", "footway"
+    pub highway: String,
+    pub name: Option<String>,
+    // [lat, lng]; in driving direction for oneway=-1
+    pub points: Vec<Vec<f64>>,
+    // m/s, from maxspeed (km/h, or "mph" suffixed)
+    pub speed: Option<f64>,
+    #[serde(rename = "speedKmh")]
+    pub speed_kmh: Option<f64>,
+    #[serde(rename = "speedMph")]
+    pub speed_mph: Option<f64>,
+    #[serde(rename = "speedClass")]
+    pub speed_class: Option<units::SpeedClass>,
+    // Meters along the points
+    pub length: f64,
+    pub lanes: Option<u32>,
+    pub oneway: bool,
+    #[serde(rename = "isRoundabout")]
+    pub is_roundabout: bool,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct OsmNode {
+    pub id: String,
+    // The highway tag, e.g. "traffic_signals", "crossing", "stop"
+    pub highway: String,
+    pub lat: f64,
+    pub lng: f64,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct OsmNetwork {
+    pub ways: Vec<OsmWay>,
+    pub nodes: Vec<OsmNode>,
+    // From <bounds>, else around the ways; x = lon, y = lat
+    pub bounds: Option<Bounds>,
+}
+
+fn tags<'a>(node: roxmltree::Node<'a, '_>) -> HashMap<&'a str, &'a str> {
+    node.children()
+        .filter(|n| n.tag_name().name() == "tag")
+        .filter_map(|t| Some((t.attribute("k")?, t.attribute("v")?)))
+        .collect()
+}
+
+// m/s of a maxspeed value: "50", "30 mph", "50;60" (the first); None for
+// "none", "walk", "signals" and zone codes like "RU:urban"
+fn parse_maxspeed(value: &str) -> Option<f64> {
+    let value = value.split(';').next()?.trim();
+    let (number, factor) = match value.strip_suffix("mph") {
+        Some(mph) => (mph.trim(), 0.44704),
+        None => (value.strip_suffix("km/h").unwrap_or(value).trim(), 1.0 / 3.6),
+    };
+    number.parse::<f64>().ok().filter(|s| *s > 0.0).map(|s| s * factor)
+}
+
+fn read_bounds(root: roxmltree::Node) -> Option<Bounds> {
+    let b = root.children().find(|n| n.tag_name().name() == "bounds")?;
+    let get = |name| b.attribute(name)?.parse::<f64>().ok();
+    Some(Bounds {
+        min_x: get("minlon")?,
+        min_y: get("minlat")?,
+        max_x: get("maxlon")?,
+        max_y: get("maxlat")?,
+    })
+}
+
+pub(crate) fn read_osm(root: roxmltree::Node) -> OsmNetwork {
+    let mut positions: HashMap<&str, (f64, f64)> = HashMap::new();
+    let mut nodes = Vec::new();
+    for node in root.children().filter(|n| n.tag_name().name() == "node") {
+        let (Some(id), Some(lat), Some(lon)) = (
+            node.attribute("id"),
+            node.attribute("lat").and_then(|v| v.parse().ok()),
+            node.attribute("lon").and_then(|v| v.parse().ok()),
+        ) else {
+            continue;
+        };
+        positions.insert(id, (lon, lat));
+        if let Some(highway) = tags(node).get("highway") {
+            nodes.push(OsmNode {
+                id: id.to_string(),
+                highway: highway.to_string(),
+                lat,
+                lng: lon,
+            });
+        }
+    }
+
+    let mut ways = Vec::new();
+    for way in root.children().filter(|n| n.tag_name().name() == "way") {
+        let tags = tags(way);
+        let (Some(id), Some(highway)) = (way.attribute("id"), tags.get("highway")) else {
+            continue;
+        };
+        // Extracts often cut ways at the border, leaving refs to absent nodes
+        let mut coords: Vec<(f64, f64)> = way
+            .children()
+            .filter(|n| n.tag_name().name() == "nd")
+            .filter_map(|n| positions.get(n.attribute("ref")?).copied())
+            .collect();
+        if coords.len() < 2 {
+            continue;
+        }
+        let is_roundabout = tags.get("junction").is_some_and(|j| *j == "roundabout" || *j == "circular");
+        let oneway = match tags.get("oneway").copied() {
+            Some("-1" | "reverse") => {
+                coords.reverse();
+                true
+            }
+            Some("yes" | "true" | "1") => true,
+            Some(_) => false,
+            None => is_roundabout || *highway == "motorway",
+        };
+        let speed = tags.get("maxspeed").and_then(|v| parse_maxspeed(v));
+        ways.push(OsmWay {
+            id: id.to_string(),
+            highway: highway.to_string(),
+            name: tags.get("name").map(|n| n.to_string()),
+            length: geometry::geo_polyline_length(&coords),
+            points: coords.into_iter().map(|(lon, lat)| vec![lat, lon]).collect(),
+            speed,
+            speed_kmh: speed.map(units::speed_limit_kmh),
+            speed_mph: speed.map(units::speed_limit_mph),
+            speed_class: speed.map(|s| units::SpeedClass::from_kmh(units::speed_limit_kmh(s))),
+            lanes: tags.get("lanes").and_then(|l| l.split(';').next()?.trim().parse().ok()),
+            oneway,
+            is_roundabout,
+        });
+    }
+
+    let bounds = read_bounds(root).or_else(|| Bounds::of_points(ways.iter().flat_map(|w| &w.points).map(|p| (p[1], p[0]))));
+    OsmNetwork { ways, nodes, bounds }
+}
+
+#[wasm_bindgen(unchecked_return_type = "OsmNetwork")]
+pub fn parse_osm_xml(xml_text: &str) -> Result<JsValue, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let osm = read_osm(doc.root_element());
+
+    console_log!("Parsed {} OSM highway ways and {} highway nodes", osm.ways.len(), osm.nodes.len());
+
+    to_js(&osm)
+}
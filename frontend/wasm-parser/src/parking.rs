@@ -0,0 +1,246 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::geometry;
+use crate::heatmap::lane_edge;
+use crate::net::NetModel;
+use crate::taz::read_tazs;
+use crate::{attr_f64, parse_options, parse_xml, to_js};
+
+const DEFAULT_CELL_SIZE: f64 = 250.0;
+
+#[derive(Deserialize, Default, Tsify)]
+#[serde(default)]
+pub struct ParkingPressureOptions {
+    // Grid cell edge in meters (default 250); ignored when aggregating by TAZ
+    #[serde(rename = "cellSize")]
+    pub cell_size: Option<f64>,
+    // Aggregate over the <taz> elements of the additional file instead of a grid
+    #[serde(rename = "byTaz")]
+    pub by_taz: bool,
+    // Edge volumes (veh/h) by edge id
+    pub volumes: HashMap<String, f64>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct ParkingPressureCell {
+    // TAZ id, or "cx_cy" grid cell
+    pub id: String,
+    // [lat, lng] ring for the choropleth; empty for TAZ without a shape
+    pub polygon: Vec<Vec<f64>>,
+    #[serde(rename = "parkingAreas")]
+    pub parking_areas: usize,
+    pub capacity: u32,
+    #[serde(rename = "meanOccupied")]
+    pub mean_occupied: f64,
+    #[serde(rename = "peakOccupied")]
+    pub peak_occupied: u32,
+    // meanOccupied / capacity
+    #[serde(rename = "occupancyRate")]
+    pub occupancy_rate: f64,
+    // veh/h on the edges inside the cell
+    pub volume: f64,
+    // occupancyRate × vehicles passing per space and hour: high when spaces
+    // are scarce and many vehicles circulate past them
+    pub pressure: f64,
+}
+
+struct ParkingArea {
+    id: String,
+    edge_id: String,
+    position: (f64, f64),
+    capacity: u32,
+}
+
+#[derive(Default)]
+struct Occupancy {
+    mean: f64,
+    peak: u32,
+}
+
+fn read_parking_areas(root: roxmltree::Node, net: &NetModel) -> Vec<ParkingArea> {
+    root.descendants()
+        .filter(|n| n.tag_name().name() == "parkingArea")
+        .filter_map(|p| {
+            let lane_id = p.attribute("lane")?;
+            let lane = net.lane(lane_id)?;
+            let length = geometry::polyline_length(&lane.shape);
+            let start = attr_f64(p, "startPos").unwrap_or(0.0);
+            let end = attr_f64(p, "endPos").unwrap_or(length);
+            // Negative positions count from the lane end
+            let (start, end) = (
+                if start < 0.0 { length + start } else { start },
+                if end < 0.0 { length + end } else { end },
+            );
+            let spaces = p.children().filter(|n| n.tag_name().name() == "space").count() as u32;
+            let roadside = p.attribute("roadsideCapacity").and_then(|s| s.parse::<u32>().ok()).unwrap_or(0);
+
+            Some(ParkingArea {
+                id: p.attribute("id")?.to_string(),
+                edge_id: lane_edge(lane_id).to_string(),
+                position: geometry::point_at(&lane.shape, (start + end) / 2.0)?,
+                capacity: roadside + spaces,
+            })
+        })
+        .collect()
+}
+
+// Time-weighted mean and peak occupancy per parking area from --stop-output
+fn read_occupancy(root: roxmltree::Node) -> HashMap<String, Occupancy> {
+    let mut events: HashMap<&str, Vec<(f64, i32)>> = HashMap::new();
+    for stop in root.children().filter(|n| n.tag_name().name() == "stopinfo") {
+        let (Some(area), Some(started), Some(ended)) =
+            (stop.attribute("parkingArea"), attr_f64(stop, "started"), attr_f64(stop, "ended"))
+        else {
+            continue;
+        };
+        let list = events.entry(area).or_default();
+        list.push((started, 1));
+        list.push((ended.max(started), -1));
+    }
+
+    let (t0, t1) = events
+        .values()
+        .flatten()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), (t, _)| (lo.min(*t), hi.max(*t)));
+    let span = t1 - t0;
+
+    events
+        .into_iter()
+        .map(|(area, mut list)| {
+            // Departures before arrivals at equal times
+            list.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+            let (mut occupied, mut peak, mut area_time, mut last) = (0i32, 0i32, 0.0, t0);
+            for (t, delta) in list {
+                area_time += occupied as f64 * (t - last);
+                occupied += delta;
+                peak = peak.max(occupied);
+                last = t;
+            }
+            let mean = if span > 0.0 { area_time / span } else { occupied.max(0) as f64 };
+            (area.to_string(), Occupancy { mean, peak: peak.max(0) as u32 })
+        })
+        .collect()
+}
+
+fn cell_polygon((cx, cy): (i64, i64), size: f64) -> Vec<Vec<f64>> {
+    let (x0, y0) = (cx as f64 * size, cy as f64 * size);
+    [(x0, y0), (x0 + size, y0), (x0 + size, y0 + size), (x0, y0 + size)]
+        .iter()
+        .map(|(x, y)| vec![*y, *x])
+        .collect()
+}
+
+pub(crate) fn parking_pressure(
+    net: &NetModel,
+    additional: roxmltree::Node,
+    stops: Option<roxmltree::Node>,
+    options: &ParkingPressureOptions,
+) -> Vec<ParkingPressureCell> {
+    let areas = read_parking_areas(additional, net);
+    let occupancy = stops.map(read_occupancy).unwrap_or_default();
+    let cell_size = options.cell_size.filter(|s| *s > 0.0).unwrap_or(DEFAULT_CELL_SIZE);
+
+    // Aggregation unit of an edge: its TAZ, or the grid cell of its midpoint
+    let tazs = if options.by_taz { read_tazs(additional) } else { Vec::new() };
+    let taz_edges: HashMap<&str, &str> = tazs
+        .iter()
+        .flat_map(|t| t.edges.iter().map(move |e| (e.as_str(), t.id.as_str())))
+        .collect();
+    let cell_of = |p: (f64, f64)| ((p.0 / cell_size).floor() as i64, (p.1 / cell_size).floor() as i64);
+    let unit_of = |edge_id: &str, p: Option<(f64, f64)>| -> Option<String> {
+        if options.by_taz {
+            taz_edges.get(edge_id).map(|t| t.to_string())
+        } else {
+            p.map(|p| {
+                let (cx, cy) = cell_of(p);
+                format!("{}_{}", cx, cy)
+            })
+        }
+    };
+
+    let mut cells: BTreeMap<String, ParkingPressureCell> = BTreeMap::new();
+    let new_cell = |id: &str| ParkingPressureCell {
+        id: id.to_string(),
+        polygon: Vec::new(),
+        parking_areas: 0,
+        capacity: 0,
+        mean_occupied: 0.0,
+        peak_occupied: 0,
+        occupancy_rate: 0.0,
+        volume: 0.0,
+        pressure: 0.0,
+    };
+
+    for area in &areas {
+        let Some(unit) = unit_of(&area.edge_id, Some(area.position)) else { continue };
+        let cell = cells.entry(unit.clone()).or_insert_with(|| {
+            let mut cell = new_cell(&unit);
+            if !options.by_taz {
+                cell.polygon = cell_polygon(cell_of(area.position), cell_size);
+            }
+            cell
+        });
+        let occ = occupancy.get(&area.id);
+        cell.parking_areas += 1;
+        cell.capacity += area.capacity;
+        cell.mean_occupied += occ.map(|o| o.mean).unwrap_or(0.0);
+        // Peaks of different areas need not coincide; the sum is an upper bound
+        cell.peak_occupied += occ.map(|o| o.peak).unwrap_or(0);
+    }
+
+    for (edge_id, volume) in &options.volumes {
+        let midpoint = net.edge(edge_id).and_then(|e| {
+            let lane = e.lane(0).or_else(|| e.lanes.first())?;
+            geometry::point_at(&lane.shape, e.length() / 2.0)
+        });
+        if let Some(cell) = unit_of(edge_id, midpoint).and_then(|u| cells.get_mut(&u)) {
+            cell.volume += volume;
+        }
+    }
+
+    let taz_shapes: HashMap<&str, &[(f64, f64)]> = tazs.iter().map(|t| (t.id.as_str(), t.shape.as_slice())).collect();
+
+    cells
+        .into_values()
+        .filter(|c| c.capacity > 0)
+        .map(|mut c| {
+            if let Some(shape) = taz_shapes.get(c.id.as_str()) {
+                c.polygon = shape.iter().map(|(x, y)| vec![*y, *x]).collect();
+            }
+            let capacity = c.capacity as f64;
+            c.occupancy_rate = c.mean_occupied / capacity;
+            c.pressure = c.occupancy_rate * c.volume / capacity;
+            c
+        })
+        .collect()
+}
+
+// Parking pressure per grid cell (or TAZ) from parkingArea definitions, an
+// optional --stop-output for occupancy and edge volumes from the options
+#[wasm_bindgen(unchecked_return_type = "ParkingPressureCell[]")]
+pub fn estimate_parking_pressure(
+    net_xml: &str,
+    additional_xml: &str,
+    stop_output_xml: Option<String>,
+    #[wasm_bindgen(unchecked_param_type = "ParkingPressureOptions | undefined")] options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let options: ParkingPressureOptions = parse_options(options)?;
+    let net_doc = parse_xml(net_xml)?;
+    let net = NetModel::from_root(net_doc.root_element());
+    let add_doc = parse_xml(additional_xml)?;
+    let stop_doc = stop_output_xml.as_deref().map(parse_xml).transpose()?;
+
+    let cells = parking_pressure(
+        &net,
+        add_doc.root_element(),
+        stop_doc.as_ref().map(|d| d.root_element()),
+        &options,
+    );
+
+    console_log!("Computed parking pressure for {} cells", cells.len());
+
+    to_js(&cells)
+}
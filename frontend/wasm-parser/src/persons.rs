@@ -0,0 +1,326 @@
+// Person demand from route files: <person> / <personFlow> plans made of walk,
+// ride, stop and personTrip stages. Walks given only by their ends are routed
+// over the pedestrian graph so they can be drawn like vehicle routes.
+use serde::{Deserialize, Serialize};
+use std::collections::{BinaryHeap, HashMap};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::geometry;
+use crate::graph::Queued;
+use crate::heatmap::lane_edge;
+use crate::net::NetModel;
+use crate::scenario::flow_count;
+use crate::{attr_f64, parse_xml, to_js};
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct PersonStage {
+    // "walk", "ride", "stop" or "personTrip"
+    pub kind: String,
+    // Edges the stage starts and ends on; a ride or walk without `from`
+    // starts where the previous stage ended
+    pub from: Option<String>,
+    pub to: Option<String>,
+    #[serde(rename = "busStop")]
+    pub bus_stop: Option<String>,
+    // Walks: every edge walked along, in order. Only the two ends when the
+    // walk could not be routed (no network given, or no pedestrian path).
+    pub edges: Vec<String>,
+    // Rides: accepted lines; personTrips: allowed modes
+    pub lines: Option<String>,
+    pub modes: Option<String>,
+    // Stops (seconds)
+    pub duration: Option<f64>,
+    pub until: Option<f64>,
+    // Walked edges as [lat, lng] when a network is given
+    pub path: Vec<Vec<f64>>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct PersonPlan {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub person_type: Option<String>,
+    // Seconds; the flow begin for personFlows, None for triggered departures
+    pub depart: Option<f64>,
+    // Persons a personFlow inserts; 1 for a single person
+    pub count: u64,
+    pub stages: Vec<PersonStage>,
+}
+
+// Pedestrian network: junctions joined by every non-internal edge that admits
+// pedestrians, walkable both ways (sidewalks are not one-way)
+pub(crate) struct WalkGraph<'a> {
+    net: &'a NetModel,
+    // Per junction: (neighbor junction, edge index in net.edges, length)
+    adjacency: Vec<Vec<(usize, usize, f64)>>,
+}
+
+impl<'a> WalkGraph<'a> {
+    pub fn new(net: &'a NetModel) -> WalkGraph<'a> {
+        let mut adjacency = vec![Vec::new(); net.junctions.len()];
+        for (i, edge) in net.edges.iter().enumerate() {
+            if edge.is_internal() || !edge.lanes.iter().any(|l| l.permits("pedestrian")) {
+                continue;
+            }
+            let junction = |id: &Option<String>| net.junction_index.get(id.as_deref()?).copied();
+            let (Some(a), Some(b)) = (junction(&edge.from), junction(&edge.to)) else { continue };
+            let length = edge.length();
+            adjacency[a].push((b, i, length));
+            adjacency[b].push((a, i, length));
+        }
+        WalkGraph { net, adjacency }
+    }
+
+    fn ends(&self, edge: &str) -> Option<[usize; 2]> {
+        let edge = self.net.edge(edge)?;
+        let junction = |id: &Option<String>| self.net.junction_index.get(id.as_deref()?).copied();
+        Some([junction(&edge.from)?, junction(&edge.to)?])
+    }
+
+    // Shortest walk from edge `from` to edge `to`, both included
+    pub fn route(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        if from == to {
+            return Some(vec![from.to_string()]);
+        }
+        let (sources, targets) = (self.ends(from)?, self.ends(to)?);
+
+        let mut dist = vec![f64::INFINITY; self.adjacency.len()];
+        let mut prev: Vec<Option<(usize, usize)>> = vec![None; self.adjacency.len()];
+        let mut queue = BinaryHeap::new();
+        for s in sources {
+            dist[s] = 0.0;
+            queue.push(Queued(0.0, s));
+        }
+        let mut reached = None;
+        while let Some(Queued(d, node)) = queue.pop() {
+            if d > dist[node] {
+                continue;
+            }
+            if targets.contains(&node) {
+                reached = Some(node);
+                break;
+            }
+            for &(next, edge, length) in &self.adjacency[node] {
+                if d + length < dist[next] {
+                    dist[next] = d + length;
+                    prev[next] = Some((node, edge));
+                    queue.push(Queued(d + length, next));
+                }
+            }
+        }
+
+        let mut between = Vec::new();
+        let mut node = reached?;
+        while let Some((p, edge)) = prev[node] {
+            between.push(self.net.edges[edge].id.clone());
+            node = p;
+        }
+        between.reverse();
+        Some(std::iter::once(from.to_string()).chain(between).chain([to.to_string()]).collect())
+    }
+
+    // Walking distance from the nearest seed to every junction (infinite when
+    // unreachable). Seeds are points given as (edge id, meters along the edge).
+    pub fn reach(&self, seeds: &[(&str, f64)]) -> Vec<f64> {
+        let mut dist = vec![f64::INFINITY; self.adjacency.len()];
+        let mut queue = BinaryHeap::new();
+        for &(edge, pos) in seeds {
+            let (Some([a, b]), Some(length)) = (self.ends(edge), self.net.edge(edge).map(|e| e.length())) else {
+                continue;
+            };
+            let pos = pos.clamp(0.0, length);
+            for (node, d) in [(a, pos), (b, length - pos)] {
+                if d < dist[node] {
+                    dist[node] = d;
+                    queue.push(Queued(d, node));
+                }
+            }
+        }
+        while let Some(Queued(d, node)) = queue.pop() {
+            if d > dist[node] {
+                continue;
+            }
+            for &(next, _, length) in &self.adjacency[node] {
+                if d + length < dist[next] {
+                    dist[next] = d + length;
+                    queue.push(Queued(d + length, next));
+                }
+            }
+        }
+        dist
+    }
+}
+
+// Chain edge shapes into one line, reversing edges walked against their
+// direction
+fn walk_path(net: &NetModel, edges: &[String]) -> Vec<Vec<f64>> {
+    let shapes: Vec<&[(f64, f64)]> = edges
+        .iter()
+        .filter_map(|e| {
+            let edge = net.edge(e)?;
+            edge.lane(0).or_else(|| edge.lanes.first()).map(|l| l.shape.as_slice())
+        })
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    let mut path: Vec<(f64, f64)> = Vec::new();
+    for (i, shape) in shapes.iter().enumerate() {
+        let (first, last) = (shape[0], shape[shape.len() - 1]);
+        let reverse = match path.last() {
+            Some(&end) => geometry::distance(end, last) < geometry::distance(end, first),
+            // Start from the end away from the next edge
+            None => shapes.get(i + 1).is_some_and(|next| {
+                let gap = |p| geometry::distance(p, next[0]).min(geometry::distance(p, next[next.len() - 1]));
+                gap(first) < gap(last)
+            }),
+        };
+        let points: Box<dyn Iterator<Item = &(f64, f64)>> =
+            if reverse { Box::new(shape.iter().rev()) } else { Box::new(shape.iter()) };
+        for &p in points {
+            if path.last() != Some(&p) {
+                path.push(p);
+            }
+        }
+    }
+    // Output points are [lat, lng] = [y, x]
+    path.into_iter().map(|(x, y)| vec![y, x]).collect()
+}
+
+// Edge of each <busStop> / <trainStop> defined in `root`
+pub(crate) fn read_stop_edges(root: roxmltree::Node, stops: &mut HashMap<String, String>) {
+    for stop in root.descendants().filter(|n| matches!(n.tag_name().name(), "busStop" | "trainStop")) {
+        if let (Some(id), Some(lane)) = (stop.attribute("id"), stop.attribute("lane")) {
+            stops.insert(id.to_string(), lane_edge(lane).to_string());
+        }
+    }
+}
+
+fn read_stage(
+    node: roxmltree::Node,
+    position: Option<&str>,
+    routes: &HashMap<&str, Vec<String>>,
+    stops: &HashMap<String, String>,
+    walks: Option<&WalkGraph>,
+) -> Option<PersonStage> {
+    let kind = node.tag_name().name();
+    if !matches!(kind, "walk" | "ride" | "stop" | "personTrip") {
+        return None;
+    }
+    let bus_stop = node.attribute("busStop").map(String::from);
+    let stop_edge = bus_stop.as_deref().and_then(|s| stops.get(s)).cloned();
+    let from = node.attribute("from").or(position).map(String::from);
+    let mut to = node.attribute("to").map(String::from).or_else(|| stop_edge.clone());
+
+    let mut edges = Vec::new();
+    if kind == "walk" {
+        let listed = node
+            .attribute("edges")
+            .map(|e| e.split_whitespace().map(String::from).collect())
+            .or_else(|| routes.get(node.attribute("route")?).cloned());
+        edges = match listed {
+            Some(listed) => listed,
+            None => match (&from, &to) {
+                (Some(a), Some(b)) => walks.and_then(|w| w.route(a, b)).unwrap_or_else(|| {
+                    let mut ends = vec![a.clone(), b.clone()];
+                    ends.dedup();
+                    ends
+                }),
+                _ => Vec::new(),
+            },
+        };
+        to = edges.last().cloned().or(to);
+    } else if kind == "stop" {
+        // A stop stays put: on its edge, lane or stopping place
+        to = node
+            .attribute("edge")
+            .map(String::from)
+            .or_else(|| node.attribute("lane").map(|l| lane_edge(l).to_string()))
+            .or(stop_edge)
+            .or_else(|| position.map(String::from));
+    }
+
+    Some(PersonStage {
+        kind: kind.to_string(),
+        from: if kind == "stop" { to.clone() } else { edges.first().cloned().or(from) },
+        to,
+        bus_stop,
+        edges,
+        lines: node.attribute("lines").map(String::from),
+        modes: node.attribute("modes").map(String::from),
+        duration: attr_f64(node, "duration"),
+        until: attr_f64(node, "until"),
+        path: Vec::new(),
+    })
+}
+
+pub(crate) fn read_person_plans(
+    root: roxmltree::Node,
+    stops: &HashMap<String, String>,
+    net: Option<&NetModel>,
+) -> Vec<PersonPlan> {
+    let routes: HashMap<&str, Vec<String>> = root
+        .children()
+        .filter(|n| n.tag_name().name() == "route")
+        .filter_map(|r| Some((r.attribute("id")?, r.attribute("edges")?.split_whitespace().map(String::from).collect())))
+        .collect();
+    let walks = net.map(WalkGraph::new);
+
+    root.children()
+        .filter(|n| matches!(n.tag_name().name(), "person" | "personFlow"))
+        .map(|p| {
+            let is_flow = p.tag_name().name() == "personFlow";
+            let mut position: Option<String> = None;
+            let mut stages = Vec::new();
+            for child in p.children().filter(|n| n.is_element()) {
+                let Some(mut stage) = read_stage(child, position.as_deref(), &routes, stops, walks.as_ref()) else {
+                    continue;
+                };
+                if let Some(net) = net {
+                    stage.path = walk_path(net, &stage.edges);
+                }
+                position = stage.to.clone().or(position);
+                stages.push(stage);
+            }
+            PersonPlan {
+                id: p.attribute("id").unwrap_or("").to_string(),
+                person_type: p.attribute("type").map(String::from),
+                depart: attr_f64(p, if is_flow { "begin" } else { "depart" }).or(is_flow.then_some(0.0)),
+                count: if is_flow { flow_count(p) } else { 1 },
+                stages,
+            }
+        })
+        .collect()
+}
+
+// Person and personFlow plans of a route file. With the network, walks given
+// by their ends are routed over the pedestrian graph and every walk gets a
+// path to draw; bus stops used as destinations resolve through the route file
+// itself or `additional_xml`.
+#[wasm_bindgen(unchecked_return_type = "PersonPlan[]")]
+pub fn parse_person_plans(
+    routes_xml: &str,
+    net_xml: Option<String>,
+    additional_xml: Option<String>,
+) -> Result<JsValue, JsValue> {
+    let doc = parse_xml(routes_xml)?;
+    let net_doc = net_xml.as_deref().map(parse_xml).transpose()?;
+    let net = net_doc.as_ref().map(|d| NetModel::from_root(d.root_element()));
+    let additional_doc = additional_xml.as_deref().map(parse_xml).transpose()?;
+
+    let mut stops = HashMap::new();
+    read_stop_edges(doc.root_element(), &mut stops);
+    if let Some(d) = &additional_doc {
+        read_stop_edges(d.root_element(), &mut stops);
+    }
+
+    let plans = read_person_plans(doc.root_element(), &stops, net.as_ref());
+
+    console_log!(
+        "Parsed {} person plans with {} stages",
+        plans.len(),
+        plans.iter().map(|p| p.stages.len()).sum::<usize>()
+    );
+
+    to_js(&plans)
+}
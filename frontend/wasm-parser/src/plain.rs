@@ -0,0 +1,263 @@
+// SUMO plain XML (.nod.xml, .edg.xml, .con.xml, .tll.xml) written back from
+// a network, so an edited network can go through netconvert again. Internal
+// edges, their connections and the junction shapes are left to netconvert to
+// rebuild; crossings and walking areas are not written.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::geometry;
+use crate::live::LiveState;
+use crate::net::{EdgeModel, NetModel};
+use crate::{parse_xml, to_js};
+
+// Meters; SUMO's default lane width, for lanes without one
+const DEFAULT_LANE_WIDTH: f64 = 3.2;
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct PlainXml {
+    pub nodes: String,
+    pub edges: String,
+    pub connections: String,
+    #[serde(rename = "trafficLights")]
+    pub traffic_lights: String,
+}
+
+pub(crate) fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+// One element with its attributes in order, skipping absent ones
+fn element(out: &mut String, indent: &str, tag: &str, attrs: &[(&str, Option<String>)], close: bool) {
+    let _ = write!(out, "{}<{}", indent, tag);
+    for (name, value) in attrs {
+        if let Some(value) = value {
+            let _ = write!(out, " {}=\"{}\"", name, escape(value));
+        }
+    }
+    out.push_str(if close { "/>\n" } else { ">\n" });
+}
+
+fn num(v: f64) -> String {
+    format!("{:.2}", v)
+}
+
+fn shape_string(points: &[(f64, f64)]) -> String {
+    points.iter().map(|(x, y)| format!("{},{}", num(*x), num(*y))).collect::<Vec<_>>().join(" ")
+}
+
+// The geometry netconvert would need to reproduce the lanes: the stored edge
+// shape, else the lanes' outer border (default spread) or their center
+// (spreadType center / roadCenter). None for straight edges.
+fn edge_geometry(edge: &EdgeModel, lefthand: bool) -> Option<Vec<(f64, f64)>> {
+    if !edge.shape.is_empty() {
+        return Some(edge.shape.clone());
+    }
+    let mut lanes: Vec<_> = edge.lanes.iter().collect();
+    lanes.sort_by_key(|l| l.index);
+    let (first, last) = (lanes.first()?, lanes.last()?);
+    if first.shape.len() <= 2 {
+        return None;
+    }
+    // Offsets grow to the left of the direction of travel
+    let side = if lefthand { -1.0 } else { 1.0 };
+    let geometry = match edge.spread_type.as_deref() {
+        Some("center" | "roadCenter") if first.shape.len() == last.shape.len() => first
+            .shape
+            .iter()
+            .zip(&last.shape)
+            .map(|(a, b)| ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0))
+            .collect(),
+        _ => geometry::offset_polyline(&last.shape, side * last.width.unwrap_or(DEFAULT_LANE_WIDTH) / 2.0),
+    };
+    Some(geometry)
+}
+
+fn write_nodes(net: &NetModel) -> String {
+    // A junction's traffic light is the one controlling the links into it
+    let mut tl_of: HashMap<&str, &str> = HashMap::new();
+    for c in &net.connections {
+        if let (Some(tl), Some(to)) = (&c.tl, net.edge(&c.from).and_then(|e| e.to.as_deref())) {
+            tl_of.entry(to).or_insert(tl);
+        }
+    }
+
+    let mut out = String::from("<nodes>\n");
+    if let Some(location) = &net.location {
+        element(
+            &mut out,
+            "    ",
+            "location",
+            &[
+                ("netOffset", Some(format!("{},{}", num(location.net_offset.0), num(location.net_offset.1)))),
+                ("projParameter", Some(location.proj_parameter.clone())),
+            ],
+            true,
+        );
+    }
+    for j in net.junctions.iter().filter(|j| j.junction_type != "internal") {
+        element(
+            &mut out,
+            "    ",
+            "node",
+            &[
+                ("id", Some(j.id.clone())),
+                ("x", Some(num(j.x))),
+                ("y", Some(num(j.y))),
+                ("z", j.z.map(num)),
+                ("type", Some(j.junction_type.clone()).filter(|t| !t.is_empty())),
+                ("tl", tl_of.get(j.id.as_str()).map(|t| t.to_string())),
+                ("radius", j.radius.map(num)),
+                ("rightOfWay", j.right_of_way.clone()),
+                ("fringe", j.fringe.clone()),
+            ],
+            true,
+        );
+    }
+    out.push_str("</nodes>\n");
+    out
+}
+
+// `live`: speed limits and closures to bake in; closed edges disallow all
+// vehicles
+fn write_edges(net: &NetModel, live: Option<&LiveState>) -> String {
+    let mut out = String::from("<edges>\n");
+    for edge in net.edges.iter().filter(|e| e.is_normal()) {
+        let live_speed = live.and_then(|l| l.speed(&edge.id));
+        let closed = live.is_some_and(|l| l.is_closed(&edge.id));
+        element(
+            &mut out,
+            "    ",
+            "edge",
+            &[
+                ("id", Some(edge.id.clone())),
+                ("from", edge.from.clone()),
+                ("to", edge.to.clone()),
+                ("priority", edge.priority.map(|p| p.to_string())),
+                ("type", Some(edge.edge_type.clone()).filter(|t| !t.is_empty())),
+                ("numLanes", Some(edge.lanes.len().to_string())),
+                ("name", edge.name.clone()),
+                ("spreadType", edge.spread_type.clone()),
+                ("shape", edge_geometry(edge, net.lefthand).map(|s| shape_string(&s))),
+            ],
+            edge.lanes.is_empty(),
+        );
+        if edge.lanes.is_empty() {
+            continue;
+        }
+        let mut lanes: Vec<_> = edge.lanes.iter().collect();
+        lanes.sort_by_key(|l| l.index);
+        for lane in lanes {
+            element(
+                &mut out,
+                "        ",
+                "lane",
+                &[
+                    ("index", Some(lane.index.to_string())),
+                    ("allow", if closed { None } else { lane.allow.clone() }),
+                    ("disallow", if closed { Some("all".to_string()) } else { lane.disallow.clone() }),
+                    ("speed", live_speed.or(lane.speed).map(num)),
+                    ("width", lane.width.map(num)),
+                    ("endOffset", lane.end_offset.filter(|o| *o != 0.0).map(num)),
+                    ("changeLeft", lane.change_left.clone()),
+                    ("changeRight", lane.change_right.clone()),
+                ],
+                true,
+            );
+        }
+        out.push_str("    </edge>\n");
+    }
+    out.push_str("</edges>\n");
+    out
+}
+
+// Connections between normal edges; with `tl`, only the signal-controlled
+// ones with their link indices (for the .tll.xml)
+fn write_connections(net: &NetModel, out: &mut String, tl: bool) {
+    for c in &net.connections {
+        if !net.edge(&c.from).is_some_and(EdgeModel::is_normal) || !net.edge(&c.to).is_some_and(EdgeModel::is_normal) {
+            continue;
+        }
+        let mut attrs = vec![
+            ("from", Some(c.from.clone())),
+            ("to", Some(c.to.clone())),
+            ("fromLane", Some(c.from_lane.to_string())),
+            ("toLane", Some(c.to_lane.to_string())),
+        ];
+        if tl {
+            let Some(id) = &c.tl else { continue };
+            attrs.push(("tl", Some(id.clone())));
+            attrs.push(("linkIndex", c.link_index.map(|i| i.to_string())));
+        } else {
+            attrs.extend(c.options.iter().map(|(name, value)| (*name, Some(value.clone()))));
+        }
+        element(out, "    ", "connection", &attrs, true);
+    }
+}
+
+fn write_traffic_lights(net: &NetModel) -> String {
+    let mut out = String::from("<tlLogics>\n");
+    for logic in &net.tl_logics {
+        element(
+            &mut out,
+            "    ",
+            "tlLogic",
+            &[
+                ("id", Some(logic.id.clone())),
+                ("type", Some(logic.logic_type.clone())),
+                ("programID", Some(logic.program_id.clone())),
+                ("offset", logic.offset.clone()),
+            ],
+            false,
+        );
+        for (key, value) in &logic.params {
+            element(&mut out, "        ", "param", &[("key", Some(key.clone())), ("value", Some(value.clone()))], true);
+        }
+        for phase in &logic.phases {
+            let mut attrs = vec![("duration", Some(phase.duration.clone())), ("state", Some(phase.state.clone()))];
+            attrs.extend(phase.options.iter().map(|(name, value)| (name.as_str(), Some(value.clone()))));
+            element(&mut out, "        ", "phase", &attrs, true);
+        }
+        out.push_str("    </tlLogic>\n");
+    }
+    write_connections(net, &mut out, true);
+    out.push_str("</tlLogics>\n");
+    out
+}
+
+pub(crate) fn write_plain(net: &NetModel, live: Option<&LiveState>) -> PlainXml {
+    let mut connections = String::from("<connections>\n");
+    write_connections(net, &mut connections, false);
+    connections.push_str("</connections>\n");
+    PlainXml {
+        nodes: write_nodes(net),
+        edges: write_edges(net, live),
+        connections,
+        traffic_lights: write_traffic_lights(net),
+    }
+}
+
+// A .net.xml as plain XML files for netconvert
+// (`netconvert -n nodes -e edges -x connections -i trafficLights`)
+#[wasm_bindgen(unchecked_return_type = "PlainXml")]
+pub fn export_plain_xml(net_xml: &str) -> Result<JsValue, JsValue> {
+    let doc = parse_xml(net_xml)?;
+    let net = NetModel::from_root(doc.root_element());
+    let plain = write_plain(&net, None);
+
+    console_log!("Wrote plain XML for {} edges and {} junctions", net.edges.len(), net.junctions.len());
+
+    to_js(&plain)
+}
@@ -0,0 +1,304 @@
+// How a network's coordinates relate to WGS84, from its <location> element.
+// netconvert writes one of three kinds of networks:
+//   - projected (projParameter is a proj string): meters, shifted by netOffset
+//   - plain geo (--proj.plain-geo): shapes already hold lon,lat
+//   - unreferenced (projParameter "!"): meters without a geo reference
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+
+use crate::ParsedNetwork;
+
+// WGS84 ellipsoid; GRS80 differs by well under a millimeter
+const WGS84_A: f64 = 6_378_137.0;
+const WGS84_F: f64 = 1.0 / 298.257_223_563;
+
+// For converting meter tolerances to degrees on plain-geo networks
+pub(crate) const METERS_PER_DEGREE: f64 = 111_320.0;
+
+// Coordinate frame of the render output
+#[derive(Serialize, Deserialize, Tsify, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Crs {
+    // Network coordinates in meters (lat = y, lng = x), for L.CRS.Simple
+    #[default]
+    Network,
+    // Longitude/latitude in degrees
+    Wgs84,
+}
+
+// Inverse transverse Mercator parameters (angles in radians)
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+pub(crate) struct TransverseMercator {
+    lat_0: f64,
+    lon_0: f64,
+    k_0: f64,
+    x_0: f64,
+    y_0: f64,
+}
+
+impl TransverseMercator {
+    fn utm(zone: u32, south: bool) -> TransverseMercator {
+        TransverseMercator {
+            lat_0: 0.0,
+            lon_0: ((zone as f64 - 1.0) * 6.0 - 180.0 + 3.0).to_radians(),
+            k_0: 0.9996,
+            x_0: 500_000.0,
+            y_0: if south { 10_000_000.0 } else { 0.0 },
+        }
+    }
+
+    // Meridian arc length from the equator to latitude `phi`
+    fn meridian_arc(phi: f64) -> f64 {
+        let e2 = WGS84_F * (2.0 - WGS84_F);
+        let (e4, e6) = (e2 * e2, e2 * e2 * e2);
+        WGS84_A
+            * ((1.0 - e2 / 4.0 - 3.0 * e4 / 64.0 - 5.0 * e6 / 256.0) * phi
+                - (3.0 * e2 / 8.0 + 3.0 * e4 / 32.0 + 45.0 * e6 / 1024.0) * (2.0 * phi).sin()
+                + (15.0 * e4 / 256.0 + 45.0 * e6 / 1024.0) * (4.0 * phi).sin()
+                - (35.0 * e6 / 3072.0) * (6.0 * phi).sin())
+    }
+
+    // Projected easting/northing to (lon, lat) in degrees (Snyder, USGS PP 1395)
+    fn inverse(&self, x: f64, y: f64) -> (f64, f64) {
+        let e2 = WGS84_F * (2.0 - WGS84_F);
+        let ep2 = e2 / (1.0 - e2);
+        let m = Self::meridian_arc(self.lat_0) + (y - self.y_0) / self.k_0;
+        let mu = m / (WGS84_A * (1.0 - e2 / 4.0 - 3.0 * e2 * e2 / 64.0 - 5.0 * e2 * e2 * e2 / 256.0));
+        let e1 = (1.0 - (1.0 - e2).sqrt()) / (1.0 + (1.0 - e2).sqrt());
+        let phi1 = mu
+            + (3.0 * e1 / 2.0 - 27.0 * e1.powi(3) / 32.0) * (2.0 * mu).sin()
+            + (21.0 * e1 * e1 / 16.0 - 55.0 * e1.powi(4) / 32.0) * (4.0 * mu).sin()
+            + (151.0 * e1.powi(3) / 96.0) * (6.0 * mu).sin()
+            + (1097.0 * e1.powi(4) / 512.0) * (8.0 * mu).sin();
+
+        let (sin1, cos1, tan1) = (phi1.sin(), phi1.cos(), phi1.tan());
+        let c1 = ep2 * cos1 * cos1;
+        let t1 = tan1 * tan1;
+        let n1 = WGS84_A / (1.0 - e2 * sin1 * sin1).sqrt();
+        let r1 = WGS84_A * (1.0 - e2) / (1.0 - e2 * sin1 * sin1).powf(1.5);
+        let d = (x - self.x_0) / (n1 * self.k_0);
+
+        let lat = phi1
+            - (n1 * tan1 / r1)
+                * (d * d / 2.0
+                    - (5.0 + 3.0 * t1 + 10.0 * c1 - 4.0 * c1 * c1 - 9.0 * ep2) * d.powi(4) / 24.0
+                    + (61.0 + 90.0 * t1 + 298.0 * c1 + 45.0 * t1 * t1 - 252.0 * ep2 - 3.0 * c1 * c1) * d.powi(6)
+                        / 720.0);
+        let lon = self.lon_0
+            + (d - (1.0 + 2.0 * t1 + c1) * d.powi(3) / 6.0
+                + (5.0 - 2.0 * c1 + 28.0 * t1 - 3.0 * c1 * c1 + 8.0 * ep2 + 24.0 * t1 * t1) * d.powi(5) / 120.0)
+                / cos1;
+        (lon.to_degrees(), lat.to_degrees())
+    }
+
+    // (lon, lat) in degrees to projected easting/northing
+    fn forward(&self, lon: f64, lat: f64) -> (f64, f64) {
+        let e2 = WGS84_F * (2.0 - WGS84_F);
+        let ep2 = e2 / (1.0 - e2);
+        let phi = lat.to_radians();
+        let (sin, cos, tan) = (phi.sin(), phi.cos(), phi.tan());
+        let n = WGS84_A / (1.0 - e2 * sin * sin).sqrt();
+        let t = tan * tan;
+        let c = ep2 * cos * cos;
+        let a = (lon.to_radians() - self.lon_0) * cos;
+        let m = Self::meridian_arc(phi) - Self::meridian_arc(self.lat_0);
+
+        let x = self.k_0
+            * n
+            * (a + (1.0 - t + c) * a.powi(3) / 6.0
+                + (5.0 - 18.0 * t + t * t + 72.0 * c - 58.0 * ep2) * a.powi(5) / 120.0);
+        let y = self.k_0
+            * (m + n
+                * tan
+                * (a * a / 2.0
+                    + (5.0 - t + 9.0 * c + 4.0 * c * c) * a.powi(4) / 24.0
+                    + (61.0 - 58.0 * t + t * t + 600.0 * c - 330.0 * ep2) * a.powi(6) / 720.0));
+        (x + self.x_0, y + self.y_0)
+    }
+
+    // "+proj=utm +zone=37 ...", "+proj=tmerc +lat_0=.. +lon_0=..", "EPSG:32637"
+    fn from_proj_parameter(param: &str) -> Option<TransverseMercator> {
+        let lower = param.to_ascii_lowercase();
+        if let Some(code) = lower.split(|c: char| !c.is_ascii_alphanumeric() && c != ':').find_map(|t| {
+            t.strip_prefix("epsg:").and_then(|c| c.parse::<u32>().ok())
+        }) {
+            return match code {
+                32601..=32660 => Some(Self::utm(code - 32600, false)),
+                32701..=32760 => Some(Self::utm(code - 32700, true)),
+                _ => None,
+            };
+        }
+
+        let value = |key: &str| {
+            lower.split_whitespace().find_map(|t| {
+                let (k, v) = t.trim_start_matches('+').split_once('=')?;
+                (k == key).then(|| v.parse::<f64>().ok()).flatten()
+            })
+        };
+        let flag = |key: &str| lower.split_whitespace().any(|t| t.trim_start_matches('+') == key);
+
+        match lower.split_whitespace().find_map(|t| t.trim_start_matches('+').strip_prefix("proj=")) {
+            Some("utm") => {
+                let zone = value("zone")? as u32;
+                (1..=60).contains(&zone).then(|| Self::utm(zone, flag("south")))
+            }
+            Some("tmerc") => Some(TransverseMercator {
+                lat_0: value("lat_0").unwrap_or(0.0).to_radians(),
+                lon_0: value("lon_0").unwrap_or(0.0).to_radians(),
+                k_0: value("k_0").or_else(|| value("k")).unwrap_or(1.0),
+                x_0: value("x_0").unwrap_or(0.0),
+                y_0: value("y_0").unwrap_or(0.0),
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "snapshot", derive(Serialize, Deserialize))]
+pub(crate) enum GeoReference {
+    // Meters without a geo reference
+    Unreferenced,
+    // Shapes already in lon,lat
+    PlainGeo,
+    Projected { net_offset: (f64, f64), tmerc: TransverseMercator },
+    // A proj string this parser cannot invert
+    Unsupported(String),
+}
+
+impl GeoReference {
+    pub fn from_location(location: roxmltree::Node) -> GeoReference {
+        let param = location.attribute("projParameter").unwrap_or("!").trim();
+        if param != "!" {
+            let net_offset = location
+                .attribute("netOffset")
+                .and_then(|s| {
+                    let (x, y) = s.split_once(',')?;
+                    Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+                })
+                .unwrap_or((0.0, 0.0));
+            return GeoReference::from_proj(param, net_offset);
+        }
+
+        // --proj.plain-geo also writes "!", but its boundary is in degrees
+        // and written with geo precision (6+ decimals) rather than centimeters
+        let boundary = location.attribute("convBoundary").unwrap_or("");
+        let values: Vec<f64> = boundary.split(',').filter_map(|s| s.trim().parse().ok()).collect();
+        let in_degrees = values.len() == 4
+            && values[0].abs() <= 180.0
+            && values[2].abs() <= 180.0
+            && values[1].abs() <= 90.0
+            && values[3].abs() <= 90.0;
+        let geo_precision = boundary
+            .split(',')
+            .all(|s| s.trim().split_once('.').is_some_and(|(_, frac)| frac.len() >= 5));
+        if in_degrees && geo_precision {
+            GeoReference::PlainGeo
+        } else {
+            GeoReference::Unreferenced
+        }
+    }
+
+    // A proj string, with the offset added to projected coordinates
+    pub fn from_proj(param: &str, net_offset: (f64, f64)) -> GeoReference {
+        match TransverseMercator::from_proj_parameter(param) {
+            Some(tmerc) => GeoReference::Projected { net_offset, tmerc },
+            None => GeoReference::Unsupported(param.to_string()),
+        }
+    }
+
+    pub fn is_plain_geo(&self) -> bool {
+        matches!(self, GeoReference::PlainGeo)
+    }
+
+    // Frame the shapes are stored in
+    pub fn native_crs(&self) -> Crs {
+        if self.is_plain_geo() {
+            Crs::Wgs84
+        } else {
+            Crs::Network
+        }
+    }
+
+    // Network coordinates of a lon/lat position
+    pub fn project_wgs84(&self, lon: f64, lat: f64) -> Result<(f64, f64), String> {
+        match self {
+            GeoReference::PlainGeo => Ok((lon, lat)),
+            GeoReference::Projected { net_offset, tmerc } => {
+                let (x, y) = tmerc.forward(lon, lat);
+                Ok((x + net_offset.0, y + net_offset.1))
+            }
+            GeoReference::Unreferenced => Err("network has no geo reference (projParameter \"!\")".to_string()),
+            GeoReference::Unsupported(param) => Err(format!("unsupported projection '{}'", param)),
+        }
+    }
+
+    // Lon/lat of a position in network coordinates
+    pub fn unproject(&self, x: f64, y: f64) -> Result<(f64, f64), String> {
+        match self {
+            GeoReference::PlainGeo => Ok((x, y)),
+            GeoReference::Projected { net_offset, tmerc } => Ok(tmerc.inverse(x - net_offset.0, y - net_offset.1)),
+            GeoReference::Unreferenced => Err("network has no geo reference (projParameter \"!\")".to_string()),
+            GeoReference::Unsupported(param) => Err(format!("unsupported projection '{}'", param)),
+        }
+    }
+
+    // Rewrite render output into lon/lat
+    pub fn to_wgs84(&self, parsed: &mut ParsedNetwork) -> Result<(), String> {
+        // Fails for networks that cannot be unprojected, before touching any point
+        self.unproject(0.0, 0.0)?;
+        if let GeoReference::Projected { net_offset, tmerc } = self {
+            parsed.map_coords(|x, y| tmerc.inverse(x - net_offset.0, y - net_offset.1));
+        }
+        parsed.crs = Crs::Wgs84;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tmerc(param: &str) -> TransverseMercator {
+        TransverseMercator::from_proj_parameter(param).unwrap()
+    }
+
+    #[test]
+    fn utm_central_meridian_maps_to_false_easting() {
+        let (x, y) = tmerc("+proj=utm +zone=37 +ellps=WGS84 +datum=WGS84 +units=m +no_defs").forward(39.0, 0.0);
+        assert!((x - 500_000.0).abs() < 1e-6 && y.abs() < 1e-6);
+        let (_, y) = tmerc("EPSG:32737").forward(39.0, 0.0);
+        assert!((y - 10_000_000.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn utm_is_symmetric_about_the_central_meridian() {
+        let tm = tmerc("+proj=utm +zone=37");
+        let (east, north_e) = tm.forward(40.0, 9.0);
+        let (west, north_w) = tm.forward(38.0, 9.0);
+        assert!((east - 500_000.0 + (west - 500_000.0)).abs() < 1e-6);
+        assert!((north_e - north_w).abs() < 1e-6);
+    }
+
+    #[test]
+    fn forward_and_inverse_round_trip() {
+        for param in ["+proj=utm +zone=37", "+proj=tmerc +lat_0=0 +lon_0=39 +k_0=0.9999 +x_0=500000"] {
+            let tm = tmerc(param);
+            for (lon, lat) in [(38.7578, 9.0301), (39.5, -3.2), (37.1, 14.0)] {
+                let (x, y) = tm.forward(lon, lat);
+                let (lon2, lat2) = tm.inverse(x, y);
+                assert!((lon - lon2).abs() < 1e-7 && (lat - lat2).abs() < 1e-7, "{} {} {}", param, lon, lat);
+            }
+        }
+    }
+
+    #[test]
+    fn geo_reference_applies_the_net_offset() {
+        let geo = GeoReference::from_proj("+proj=utm +zone=37", (-470_000.0, -990_000.0));
+        let (x, y) = geo.project_wgs84(38.7578, 9.0301).unwrap();
+        let (lon, lat) = geo.unproject(x, y).unwrap();
+        assert!((lon - 38.7578).abs() < 1e-7 && (lat - 9.0301).abs() < 1e-7);
+        assert!(GeoReference::from_proj("+proj=lcc", (0.0, 0.0)).unproject(0.0, 0.0).is_err());
+    }
+}
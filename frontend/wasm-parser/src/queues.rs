@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::{attr_f64, parse_xml, to_js};
+
+// One lane's queue over time as parallel arrays. SUMO only writes lanes with
+// a queue, so steps missing between entries had none.
+#[wasm_bindgen]
+#[derive(Clone, Default)]
+pub struct QueueSeries {
+    time: Vec<f64>,
+    queueing_time: Vec<f64>,
+    queueing_length: Vec<f64>,
+}
+
+#[wasm_bindgen]
+impl QueueSeries {
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.time.len()
+    }
+
+    // Seconds
+    #[wasm_bindgen(getter)]
+    pub fn time(&self) -> Vec<f64> {
+        self.time.clone()
+    }
+
+    // Seconds the vehicles in the queue have waited
+    #[wasm_bindgen(getter, js_name = queueingTime)]
+    pub fn queueing_time(&self) -> Vec<f64> {
+        self.queueing_time.clone()
+    }
+
+    // Meters from the lane end
+    #[wasm_bindgen(getter, js_name = queueingLength)]
+    pub fn queueing_length(&self) -> Vec<f64> {
+        self.queueing_length.clone()
+    }
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct QueueSummary {
+    pub lane: String,
+    // Meters
+    #[serde(rename = "maxLength")]
+    pub max_length: f64,
+    // When the longest queue occurred
+    #[serde(rename = "maxLengthTime")]
+    pub max_length_time: f64,
+    // Seconds
+    #[serde(rename = "maxQueueingTime")]
+    pub max_queueing_time: f64,
+    // Seconds with a queue (number of steps times the step length)
+    #[serde(rename = "queuedSeconds")]
+    pub queued_seconds: f64,
+}
+
+// Every lane that queued in a queue output (--queue-output)
+#[wasm_bindgen]
+pub struct QueueOutput {
+    series: BTreeMap<String, QueueSeries>,
+    step_length: f64,
+}
+
+#[wasm_bindgen]
+impl QueueOutput {
+    pub fn ids(&self) -> Vec<String> {
+        self.series.keys().cloned().collect()
+    }
+
+    pub fn series(&self, lane_id: &str) -> Option<QueueSeries> {
+        self.series.get(lane_id).cloned()
+    }
+
+    // Seconds between timesteps in the file
+    #[wasm_bindgen(getter, js_name = stepLength)]
+    pub fn step_length(&self) -> f64 {
+        self.step_length
+    }
+
+    // Per-lane maxima, longest queue first
+    #[wasm_bindgen(unchecked_return_type = "QueueSummary[]")]
+    pub fn summary(&self) -> Result<JsValue, JsValue> {
+        to_js(&summarize_queues(&self.series, self.step_length))
+    }
+}
+
+pub(crate) fn read_queue_steps(root: roxmltree::Node) -> BTreeMap<String, QueueSeries> {
+    let mut series: BTreeMap<String, QueueSeries> = BTreeMap::new();
+    for data in root.children().filter(|n| n.tag_name().name() == "data") {
+        let Some(time) = attr_f64(data, "timestep") else { continue };
+        for lane in data.descendants().filter(|n| n.tag_name().name() == "lane") {
+            let Some(id) = lane.attribute("id") else { continue };
+            let s = series.entry(id.to_string()).or_default();
+            s.time.push(time);
+            s.queueing_time.push(attr_f64(lane, "queueing_time").unwrap_or(0.0));
+            s.queueing_length.push(attr_f64(lane, "queueing_length").unwrap_or(0.0));
+        }
+    }
+    series
+}
+
+// Smallest gap between consecutive timesteps; 1 s when the file has fewer
+// than two
+fn step_length(root: roxmltree::Node) -> f64 {
+    let times: Vec<f64> = root
+        .children()
+        .filter(|n| n.tag_name().name() == "data")
+        .filter_map(|n| attr_f64(n, "timestep"))
+        .collect();
+    times
+        .windows(2)
+        .map(|w| w[1] - w[0])
+        .filter(|d| *d > 0.0)
+        .reduce(f64::min)
+        .unwrap_or(1.0)
+}
+
+fn summarize_queues(series: &BTreeMap<String, QueueSeries>, step_length: f64) -> Vec<QueueSummary> {
+    let mut summaries: Vec<QueueSummary> = series
+        .iter()
+        .map(|(lane, s)| {
+            let (max_at, max_length) = s
+                .queueing_length
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .map_or((0, 0.0), |(i, l)| (i, *l));
+            QueueSummary {
+                lane: lane.clone(),
+                max_length,
+                max_length_time: s.time.get(max_at).copied().unwrap_or(0.0),
+                max_queueing_time: s.queueing_time.iter().copied().fold(0.0, f64::max),
+                queued_seconds: s.time.len() as f64 * step_length,
+            }
+        })
+        .collect();
+    summaries.sort_by(|a, b| b.max_length.total_cmp(&a.max_length));
+    summaries
+}
+
+#[wasm_bindgen]
+pub fn parse_queue_output(xml_text: &str) -> Result<QueueOutput, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let series = read_queue_steps(doc.root_element());
+
+    console_log!("Parsed queue output for {} lanes", series.len());
+
+    Ok(QueueOutput {
+        series,
+        step_length: step_length(doc.root_element()),
+    })
+}
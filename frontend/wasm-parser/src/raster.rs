@@ -0,0 +1,172 @@
+// Network images drawn in wasm (feature "raster"), for thumbnails, static
+// basemaps and client-side PDF reports: the same picture as render_svg,
+// rasterized with tiny-skia into an RGBA buffer.
+use serde::Deserialize;
+use tiny_skia::{FillRule, LineCap, LineJoin, Paint, PathBuilder, Pixmap, Stroke, Transform};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::deckgl::{self, is_path};
+use crate::markings::DEFAULT_LANE_WIDTH;
+use crate::svg::overlaps;
+use crate::{Bounds, Junction, Lane};
+
+const DEFAULT_PIXEL_WIDTH: u32 = 512;
+// Per side; larger images are better drawn as tiles
+const MAX_PIXELS: u32 = 8192;
+const DEFAULT_JUNCTION_FILL: [u8; 4] = [208, 208, 208, 255];
+
+#[derive(Deserialize, Default, Tsify)]
+#[serde(default)]
+pub struct RasterOptions {
+    // Pixels (default 512 wide); a missing side keeps the aspect ratio of
+    // the view box
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    // "speed" (default), "id" or "type", as for path_buffers
+    #[serde(rename = "colorBy")]
+    pub color_by: Option<String>,
+    // Stroke widths in meters (default 3.2, and half that for internal
+    // lanes); never thinner than a pixel
+    #[serde(rename = "laneWidth")]
+    pub lane_width: Option<f64>,
+    #[serde(rename = "internalLaneWidth")]
+    pub internal_lane_width: Option<f64>,
+    // RGBA (default light grey); alpha 0 leaves junctions out
+    #[serde(rename = "junctionFill")]
+    pub junction_fill: Option<[u8; 4]>,
+    // RGBA; transparent when absent
+    pub background: Option<[u8; 4]>,
+    // Area drawn, x = lng and y = lat in the render frame (default the
+    // network bounds). Stretched when both width and height are given.
+    #[serde(rename = "viewBox")]
+    pub view_box: Option<Bounds>,
+}
+
+#[wasm_bindgen]
+pub struct RasterImage {
+    pixmap: Pixmap,
+}
+
+#[wasm_bindgen]
+impl RasterImage {
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.pixmap.width()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.pixmap.height()
+    }
+
+    // Row-major RGBA, not premultiplied, as ImageData expects:
+    // new ImageData(new Uint8ClampedArray(image.data), image.width)
+    #[wasm_bindgen(getter)]
+    pub fn data(&self) -> Vec<u8> {
+        self.pixmap
+            .pixels()
+            .iter()
+            .flat_map(|p| {
+                let c = p.demultiply();
+                [c.red(), c.green(), c.blue(), c.alpha()]
+            })
+            .collect()
+    }
+
+    // The image as a PNG file
+    pub fn png(&self) -> Result<Vec<u8>, JsValue> {
+        self.pixmap
+            .encode_png()
+            .map_err(|e| JsValue::from_str(&format!("Cannot encode PNG: {}", e)))
+    }
+}
+
+fn paint(color: [u8; 4]) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(color[0], color[1], color[2], color[3]);
+    paint.anti_alias = true;
+    paint
+}
+
+// `scale`: meters per network unit, for the stroke widths. `bounds` is the
+// fallback view box.
+pub(crate) fn rasterize(
+    lanes: &[Lane],
+    junctions: &[Junction],
+    bounds: &Bounds,
+    scale: f64,
+    options: &RasterOptions,
+) -> Result<RasterImage, String> {
+    let color_of = deckgl::lane_color(options.color_by.as_deref().unwrap_or("speed"))?;
+    let view = options.view_box.as_ref().unwrap_or(bounds);
+    let (w, h) = (view.max_x - view.min_x, view.max_y - view.min_y);
+    if !(w > 0.0 && h > 0.0) {
+        return Err("Raster view box is empty".to_string());
+    }
+    let (width, height) = match (options.width, options.height) {
+        (Some(width), Some(height)) => (width, height),
+        (None, Some(height)) => (((height as f64) * w / h).round() as u32, height),
+        (width, None) => {
+            let width = width.unwrap_or(DEFAULT_PIXEL_WIDTH);
+            (width, ((width as f64) * h / w).round() as u32)
+        }
+    };
+    if width.max(height) > MAX_PIXELS {
+        return Err(format!("Raster images are limited to {} pixels per side", MAX_PIXELS));
+    }
+    let mut pixmap =
+        Pixmap::new(width.max(1), height.max(1)).ok_or_else(|| "Cannot allocate raster image".to_string())?;
+    if let Some([r, g, b, a]) = options.background {
+        pixmap.fill(tiny_skia::Color::from_rgba8(r, g, b, a));
+    }
+
+    // Pixels per network unit; points are mapped in f64 since tiny-skia
+    // works in f32, too coarse for degrees
+    let (sx, sy) = (width as f64 / w, height as f64 / h);
+    let pixel = |p: &[f64]| (((p[1] - view.min_x) * sx) as f32, ((view.max_y - p[0]) * sy) as f32);
+    let path_of = |points: &[Vec<f64>], close: bool| {
+        let mut builder = PathBuilder::with_capacity(points.len() + 1, points.len());
+        for (i, p) in points.iter().enumerate() {
+            let (x, y) = pixel(p);
+            if i == 0 {
+                builder.move_to(x, y);
+            } else {
+                builder.line_to(x, y);
+            }
+        }
+        if close {
+            builder.close();
+        }
+        builder.finish()
+    };
+
+    let fill = options.junction_fill.unwrap_or(DEFAULT_JUNCTION_FILL);
+    if fill[3] > 0 {
+        let paint = paint(fill);
+        for junction in junctions.iter().filter(|j| j.polygon.len() >= 3 && overlaps(&j.polygon, view)) {
+            if let Some(path) = path_of(&junction.polygon, true) {
+                pixmap.fill_path(&path, &paint, FillRule::Winding, Transform::identity(), None);
+            }
+        }
+    }
+
+    let lane_width = options.lane_width.unwrap_or(DEFAULT_LANE_WIDTH);
+    let internal_width = options.internal_lane_width.unwrap_or(lane_width / 2.0);
+    // Internal lanes below the regular ones
+    for (internal, meters) in [(true, internal_width), (false, lane_width)] {
+        let stroke = Stroke {
+            width: ((meters / scale * sx.min(sy)) as f32).max(1.0),
+            line_cap: LineCap::Round,
+            line_join: LineJoin::Round,
+            ..Stroke::default()
+        };
+        for lane in lanes.iter().filter(|l| l.is_internal == internal && is_path(l) && overlaps(&l.points, view)) {
+            if let Some(path) = path_of(&lane.points, false) {
+                pixmap.stroke_path(&path, &paint(color_of(lane)), &stroke, Transform::identity(), None);
+            }
+        }
+    }
+
+    Ok(RasterImage { pixmap })
+}
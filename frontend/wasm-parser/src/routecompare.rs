@@ -0,0 +1,196 @@
+// Side-by-side metrics for alternative routes between the same origin and
+// destination, for the route-advisory panel.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::net::{EdgeModel, NetModel};
+use crate::{attr_f64, parse_xml, to_js};
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct RouteMetrics {
+    pub edges: Vec<String>,
+    // Meters
+    pub length: f64,
+    // Seconds at the speed limit
+    #[serde(rename = "freeFlowTime")]
+    pub free_flow_time: f64,
+    // Seconds with edgeData travel times where available (free flow
+    // elsewhere); None without edgeData
+    #[serde(rename = "congestedTime")]
+    pub congested_time: Option<f64>,
+    // Left, right and U-turns between consecutive edges
+    pub turns: usize,
+    // Traffic-light controlled connections passed
+    #[serde(rename = "signalizedJunctions")]
+    pub signalized_junctions: usize,
+    // Meters climbed and descended; 0 for 2D networks
+    #[serde(rename = "elevationGain")]
+    pub elevation_gain: f64,
+    #[serde(rename = "elevationLoss")]
+    pub elevation_loss: f64,
+    // Edge ids not found in the network
+    #[serde(rename = "unknownEdges")]
+    pub unknown_edges: Vec<String>,
+}
+
+// Index of the best route per criterion (lowest value; first on ties)
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct BestRoutes {
+    pub length: Option<usize>,
+    #[serde(rename = "freeFlowTime")]
+    pub free_flow_time: Option<usize>,
+    #[serde(rename = "congestedTime")]
+    pub congested_time: Option<usize>,
+    pub turns: Option<usize>,
+    #[serde(rename = "signalizedJunctions")]
+    pub signalized_junctions: Option<usize>,
+    #[serde(rename = "elevationGain")]
+    pub elevation_gain: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct RouteComparison {
+    pub routes: Vec<RouteMetrics>,
+    // All routes start on the same edge and end on the same edge
+    #[serde(rename = "sameOd")]
+    pub same_od: bool,
+    pub best: BestRoutes,
+}
+
+// Mean edge travel time over all intervals of an edgeData output, weighted
+// by sampledSeconds so quiet intervals count less
+fn read_travel_times(root: roxmltree::Node) -> HashMap<String, f64> {
+    let mut sums: HashMap<&str, (f64, f64)> = HashMap::new();
+    for edge in root
+        .children()
+        .filter(|n| n.tag_name().name() == "interval")
+        .flat_map(|i| i.children().filter(|n| n.tag_name().name() == "edge"))
+    {
+        let (Some(id), Some(time)) = (edge.attribute("id"), attr_f64(edge, "traveltime")) else {
+            continue;
+        };
+        let weight = attr_f64(edge, "sampledSeconds").unwrap_or(1.0);
+        let sum = sums.entry(id).or_default();
+        sum.0 += time * weight;
+        sum.1 += weight;
+    }
+    sums.into_iter()
+        .filter(|(_, (_, w))| *w > 0.0)
+        .map(|(id, (t, w))| (id.to_string(), t / w))
+        .collect()
+}
+
+fn elevation_change(edge: &EdgeModel) -> (f64, f64) {
+    let Some(z) = edge.lane(0).or_else(|| edge.lanes.first()).and_then(|l| l.elevation.as_deref()) else {
+        return (0.0, 0.0);
+    };
+    z.windows(2).fold((0.0, 0.0), |(gain, loss), w| {
+        let dz = w[1] - w[0];
+        (gain + dz.max(0.0), loss + (-dz).max(0.0))
+    })
+}
+
+fn route_metrics(
+    net: &NetModel,
+    edges: Vec<String>,
+    travel_times: Option<&HashMap<String, f64>>,
+    links: &HashMap<(&str, &str), (&str, bool)>,
+) -> RouteMetrics {
+    let mut metrics = RouteMetrics {
+        edges: Vec::new(),
+        length: 0.0,
+        free_flow_time: 0.0,
+        congested_time: travel_times.map(|_| 0.0),
+        turns: 0,
+        signalized_junctions: 0,
+        elevation_gain: 0.0,
+        elevation_loss: 0.0,
+        unknown_edges: Vec::new(),
+    };
+
+    for (i, id) in edges.iter().enumerate() {
+        let Some(edge) = net.edge(id) else {
+            metrics.unknown_edges.push(id.clone());
+            continue;
+        };
+        let free = edge.free_flow_time(None);
+        let (gain, loss) = elevation_change(edge);
+        metrics.length += edge.length();
+        metrics.free_flow_time += free;
+        metrics.elevation_gain += gain;
+        metrics.elevation_loss += loss;
+        if let Some(total) = metrics.congested_time.as_mut() {
+            *total += travel_times.and_then(|t| t.get(id)).copied().unwrap_or(free);
+        }
+        if let Some(&(dir, signalized)) = edges.get(i + 1).and_then(|next| links.get(&(id.as_str(), next.as_str()))) {
+            metrics.turns += usize::from(matches!(dir, "l" | "r" | "L" | "R" | "t"));
+            metrics.signalized_junctions += usize::from(signalized);
+        }
+    }
+    metrics.edges = edges;
+    metrics
+}
+
+fn best_by(routes: &[RouteMetrics], key: impl Fn(&RouteMetrics) -> Option<f64>) -> Option<usize> {
+    routes
+        .iter()
+        .enumerate()
+        .filter_map(|(i, r)| Some((i, key(r)?)))
+        .fold(None, |best: Option<(usize, f64)>, (i, v)| match best {
+            Some((_, b)) if b <= v => best,
+            _ => Some((i, v)),
+        })
+        .map(|(i, _)| i)
+}
+
+pub(crate) fn compare_routes_in(
+    net: &NetModel,
+    routes: &[String],
+    travel_times: Option<&HashMap<String, f64>>,
+) -> RouteComparison {
+    // Turn direction and signal control per edge pair; any lane's connection
+    // will do, they share the junction
+    let mut links: HashMap<(&str, &str), (&str, bool)> = HashMap::new();
+    for c in &net.connections {
+        let link = links.entry((c.from.as_str(), c.to.as_str())).or_insert((c.dir.as_str(), false));
+        link.1 |= c.tl.is_some();
+    }
+
+    let routes: Vec<RouteMetrics> = routes
+        .iter()
+        .map(|r| route_metrics(net, r.split_whitespace().map(String::from).collect(), travel_times, &links))
+        .collect();
+    let ends = |r: &RouteMetrics| (r.edges.first().cloned(), r.edges.last().cloned());
+    let same_od = routes.windows(2).all(|w| ends(&w[0]) == ends(&w[1]));
+
+    let best = BestRoutes {
+        length: best_by(&routes, |r| Some(r.length)),
+        free_flow_time: best_by(&routes, |r| Some(r.free_flow_time)),
+        congested_time: best_by(&routes, |r| r.congested_time),
+        turns: best_by(&routes, |r| Some(r.turns as f64)),
+        signalized_junctions: best_by(&routes, |r| Some(r.signalized_junctions as f64)),
+        elevation_gain: best_by(&routes, |r| Some(r.elevation_gain)),
+    };
+    RouteComparison { routes, same_od, best }
+}
+
+// Compare alternative routes, each a space-separated edge list as in a
+// <route edges="..."> attribute. `edge_data_xml` (an edgeData output with
+// traveltime) adds congested travel times.
+#[wasm_bindgen(unchecked_return_type = "RouteComparison")]
+pub fn compare_routes(net_xml: &str, routes: Vec<String>, edge_data_xml: Option<String>) -> Result<JsValue, JsValue> {
+    let doc = parse_xml(net_xml)?;
+    let net = NetModel::from_root(doc.root_element());
+    let data_doc = edge_data_xml.as_deref().map(parse_xml).transpose()?;
+    let travel_times = data_doc.as_ref().map(|d| read_travel_times(d.root_element()));
+
+    let comparison = compare_routes_in(&net, &routes, travel_times.as_ref());
+    if !comparison.same_od {
+        console_log!("compare_routes: routes do not share origin and destination edges");
+    }
+    console_log!("Compared {} routes", comparison.routes.len());
+
+    to_js(&comparison)
+}
@@ -0,0 +1,97 @@
+// Vehicle demand as planned in route files: vehicles, trips and flows with
+// the edges they are meant to drive. Route references are resolved across all
+// files passed in, as SUMO does when loading several route files.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tsify::Tsify;
+
+use crate::attr_f64;
+use crate::scenario::flow_count;
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct PlannedVehicle {
+    pub id: String,
+    // "vehicle", "trip" or "flow"
+    pub kind: String,
+    #[serde(rename = "vType")]
+    pub v_type: Option<String>,
+    // Seconds; the begin for flows, None for triggered departures
+    pub depart: Option<f64>,
+    // Vehicles inserted: 1, or the flow's count
+    pub count: u64,
+    // Planned edges; for trips only the given from / via / to edges, which
+    // SUMO routes at insertion
+    pub edges: Vec<String>,
+    // Whether `edges` is a complete route rather than trip endpoints
+    pub routed: bool,
+    // Public transport line
+    pub line: Option<String>,
+}
+
+fn split_edges(edges: &str) -> Vec<String> {
+    edges.split_whitespace().map(String::from).collect()
+}
+
+// Named routes, and distributions resolved to their most probable member
+fn read_named_routes<'a>(roots: &[roxmltree::Node<'a, '_>]) -> HashMap<&'a str, Vec<String>> {
+    let mut named = HashMap::new();
+    for root in roots {
+        for node in root.children().filter(|n| n.is_element()) {
+            match node.tag_name().name() {
+                "route" => {
+                    if let (Some(id), Some(edges)) = (node.attribute("id"), node.attribute("edges")) {
+                        named.insert(id, split_edges(edges));
+                    }
+                }
+                "routeDistribution" => {
+                    let Some(id) = node.attribute("id") else { continue };
+                    let likeliest = node
+                        .children()
+                        .filter(|n| n.tag_name().name() == "route")
+                        .max_by(|a, b| {
+                            let p = |n: &roxmltree::Node| attr_f64(*n, "probability").unwrap_or(1.0);
+                            p(a).total_cmp(&p(b))
+                        });
+                    if let Some(edges) = likeliest.and_then(|r| r.attribute("edges")) {
+                        named.insert(id, split_edges(edges));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    named
+}
+
+pub(crate) fn read_planned_vehicles(roots: &[roxmltree::Node]) -> Vec<PlannedVehicle> {
+    let named = read_named_routes(roots);
+    let mut vehicles = Vec::new();
+    for root in roots {
+        for node in root.children().filter(|n| matches!(n.tag_name().name(), "vehicle" | "trip" | "flow")) {
+            let kind = node.tag_name().name();
+            let inline = node.children().find(|n| n.tag_name().name() == "route").and_then(|r| r.attribute("edges"));
+            let (edges, routed) = match (inline, node.attribute("route")) {
+                (Some(edges), _) => (split_edges(edges), true),
+                (None, Some(route)) => (named.get(route).cloned().unwrap_or_default(), true),
+                (None, None) => {
+                    let mut edges: Vec<String> = node.attribute("from").map(String::from).into_iter().collect();
+                    edges.extend(node.attribute("via").map(split_edges).unwrap_or_default());
+                    edges.extend(node.attribute("to").map(String::from));
+                    (edges, false)
+                }
+            };
+            let is_flow = kind == "flow";
+            vehicles.push(PlannedVehicle {
+                id: node.attribute("id").unwrap_or("").to_string(),
+                kind: kind.to_string(),
+                v_type: node.attribute("type").map(String::from),
+                depart: attr_f64(node, if is_flow { "begin" } else { "depart" }).or(is_flow.then_some(0.0)),
+                count: if is_flow { flow_count(node) } else { 1 },
+                edges,
+                routed,
+                line: node.attribute("line").map(String::from),
+            });
+        }
+    }
+    vehicles
+}
@@ -0,0 +1,205 @@
+// The routable topology as a plain node / edge / turn graph, for routing
+// engines outside this crate (OSRM-style edge-expanded graphs, or a hand-written
+// Dijkstra in JS). Edges and turns refer to nodes and edges by array index,
+// so the structure survives JSON.stringify unchanged:
+//
+//   nodes[i]  junction, with its position
+//   edges[i]  directed road from nodes[from] to nodes[to], with its free-flow
+//             traversal time in seconds
+//   turns[i]  allowed move from edges[from] onto edges[to] at nodes[via],
+//             costing `penalty` seconds on top of the edge durations
+//
+// A route's cost is the sum of its edge durations and turn penalties.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::graph::EdgeGraph;
+use crate::net::NetModel;
+use crate::{parse_options, parse_xml, to_js};
+
+// Bumped when the layout below changes incompatibly
+const FORMAT_VERSION: u32 = 1;
+
+// Seconds
+const DEFAULT_RIGHT_PENALTY: f64 = 2.0;
+const DEFAULT_LEFT_PENALTY: f64 = 6.0;
+const DEFAULT_UTURN_PENALTY: f64 = 20.0;
+const DEFAULT_SIGNAL_PENALTY: f64 = 10.0;
+const DEFAULT_YIELD_PENALTY: f64 = 3.0;
+
+#[derive(Deserialize, Default, Tsify)]
+#[serde(default)]
+pub struct RoutingGraphOptions {
+    // Vehicle class whose lanes form the graph (default "passenger")
+    #[serde(rename = "vClass")]
+    pub v_class: Option<String>,
+    // Seconds added per turn (defaults: right 2, left 6, U-turn 20)
+    pub right: Option<f64>,
+    pub left: Option<f64>,
+    pub uturn: Option<f64>,
+    // Seconds added for a turn through a traffic light (default 10)
+    pub signal: Option<f64>,
+    // Seconds added for a turn that has to yield (minor link, default 3)
+    #[serde(rename = "yield")]
+    pub yield_penalty: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct RoutingNode {
+    pub id: String,
+    // [lat, lng]
+    pub position: Vec<f64>,
+    pub signalized: bool,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct RoutingEdge {
+    pub id: String,
+    // Node indices
+    pub from: usize,
+    pub to: usize,
+    // Meters
+    pub length: f64,
+    // m/s, the fastest lane usable by the vehicle class
+    pub speed: f64,
+    // Free-flow seconds
+    pub duration: f64,
+    // Lanes usable by the vehicle class
+    pub lanes: usize,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct RoutingTurn {
+    // Edge indices
+    pub from: usize,
+    pub to: usize,
+    // Node index
+    pub via: usize,
+    // SUMO link direction: s, r, R, l, L, t
+    pub dir: String,
+    // Seconds
+    pub penalty: f64,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct RoutingGraph {
+    pub version: u32,
+    #[serde(rename = "vClass")]
+    pub v_class: String,
+    pub nodes: Vec<RoutingNode>,
+    pub edges: Vec<RoutingEdge>,
+    pub turns: Vec<RoutingTurn>,
+}
+
+fn turn_penalty(dir: &str, state: &str, signalized: bool, options: &RoutingGraphOptions) -> f64 {
+    let turn = match dir {
+        "r" | "R" => options.right.unwrap_or(DEFAULT_RIGHT_PENALTY),
+        "l" | "L" => options.left.unwrap_or(DEFAULT_LEFT_PENALTY),
+        "t" => options.uturn.unwrap_or(DEFAULT_UTURN_PENALTY),
+        _ => 0.0,
+    };
+    let control = if signalized {
+        options.signal.unwrap_or(DEFAULT_SIGNAL_PENALTY)
+    } else if state.starts_with(|c: char| c.is_ascii_lowercase()) {
+        options.yield_penalty.unwrap_or(DEFAULT_YIELD_PENALTY)
+    } else {
+        0.0
+    };
+    turn + control
+}
+
+pub(crate) fn routing_graph(net: &NetModel, options: &RoutingGraphOptions) -> RoutingGraph {
+    let v_class = options.v_class.as_deref().unwrap_or("passenger");
+    let graph = EdgeGraph::new(net, v_class);
+
+    let mut nodes: Vec<RoutingNode> = Vec::new();
+    let mut node_index: HashMap<&str, usize> = HashMap::new();
+    let mut node = |id: &str| -> Option<usize> {
+        let junction = net.junction(id)?;
+        Some(*node_index.entry(junction.id.as_str()).or_insert_with(|| {
+            nodes.push(RoutingNode {
+                id: junction.id.clone(),
+                position: vec![junction.y, junction.x],
+                signalized: junction.junction_type.contains("traffic_light"),
+            });
+            nodes.len() - 1
+        }))
+    };
+
+    // Edges keep the EdgeGraph order; those without both junctions are dropped
+    let mut edges = Vec::new();
+    let mut edge_index = vec![None; graph.len()];
+    for (i, id) in graph.ids.iter().enumerate() {
+        let Some(edge) = net.edge(id) else { continue };
+        let (Some(from), Some(to)) = (node(edge.from.as_deref().unwrap_or("")), node(edge.to.as_deref().unwrap_or("")))
+        else {
+            continue;
+        };
+        let usable = edge.lanes.iter().filter(|l| l.permits(v_class));
+        let length = edge.length();
+        edge_index[i] = Some(edges.len());
+        edges.push(RoutingEdge {
+            id: id.to_string(),
+            from,
+            to,
+            length,
+            speed: if graph.costs[i] > 0.0 { length / graph.costs[i] } else { 0.0 },
+            duration: graph.costs[i],
+            lanes: usable.count(),
+        });
+    }
+
+    // One turn per edge pair: the cheapest of its lane connections
+    let mut turns: Vec<RoutingTurn> = Vec::new();
+    let mut turn_index: HashMap<(usize, usize), usize> = HashMap::new();
+    for c in &net.connections {
+        let lookup = |id: &str| graph.index.get(id).and_then(|i| edge_index[*i]);
+        let (Some(from), Some(to)) = (lookup(&c.from), lookup(&c.to)) else { continue };
+        let via = edges[from].to;
+        let penalty = turn_penalty(&c.dir, &c.state, c.tl.is_some(), options);
+        match turn_index.get(&(from, to)) {
+            Some(&t) if turns[t].penalty <= penalty => {}
+            Some(&t) => {
+                turns[t].penalty = penalty;
+                turns[t].dir = c.dir.clone();
+            }
+            None => {
+                turn_index.insert((from, to), turns.len());
+                turns.push(RoutingTurn { from, to, via, dir: c.dir.clone(), penalty });
+            }
+        }
+    }
+
+    RoutingGraph {
+        version: FORMAT_VERSION,
+        v_class: v_class.to_string(),
+        nodes,
+        edges,
+        turns,
+    }
+}
+
+// Node / edge / turn-penalty graph of the network for external routing
+// engines; see the top of this file for the layout. JSON.stringify the
+// result to save it.
+#[wasm_bindgen(unchecked_return_type = "RoutingGraph")]
+pub fn export_routing_graph(
+    net_xml: &str,
+    #[wasm_bindgen(unchecked_param_type = "RoutingGraphOptions | undefined")] options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let options: RoutingGraphOptions = parse_options(options)?;
+    let doc = parse_xml(net_xml)?;
+    let net = NetModel::from_root(doc.root_element());
+    let graph = routing_graph(&net, &options);
+
+    console_log!(
+        "Exported routing graph: {} nodes, {} edges, {} turns",
+        graph.nodes.len(),
+        graph.edges.len(),
+        graph.turns.len()
+    );
+
+    to_js(&graph)
+}
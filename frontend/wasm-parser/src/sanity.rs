@@ -0,0 +1,211 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::net::NetModel;
+use crate::spatial::SegmentGrid;
+use crate::{attr_f64, parse_xml, to_js};
+
+const DEFAULT_MAX_DISTANCE: f64 = 10.0;
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct SanityIssue {
+    // "unknownEdge", "unknownLane" or "offNetwork"
+    pub kind: String,
+    pub id: String,
+    // Number of records affected
+    pub count: usize,
+    pub detail: String,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct SanityReport {
+    #[serde(rename = "outputType")]
+    pub output_type: String,
+    // Number of records cross-checked
+    pub checked: usize,
+    pub issues: Vec<SanityIssue>,
+}
+
+// Issues keyed by (kind, id) so repeated records collapse into one entry
+#[derive(Default)]
+struct IssueLog {
+    entries: BTreeMap<(&'static str, String), (usize, String)>,
+}
+
+impl IssueLog {
+    fn add(&mut self, kind: &'static str, id: &str, detail: impl FnOnce() -> String) {
+        self.entries
+            .entry((kind, id.to_string()))
+            .and_modify(|e| e.0 += 1)
+            .or_insert_with(|| (1, detail()));
+    }
+
+    fn into_issues(self) -> Vec<SanityIssue> {
+        self.entries
+            .into_iter()
+            .map(|((kind, id), (count, detail))| SanityIssue {
+                kind: kind.to_string(),
+                id,
+                count,
+                detail,
+            })
+            .collect()
+    }
+}
+
+struct NetIds<'a> {
+    edges: HashSet<&'a str>,
+    lanes: HashSet<&'a str>,
+}
+
+impl<'a> NetIds<'a> {
+    fn new(net: &'a NetModel) -> NetIds<'a> {
+        NetIds {
+            edges: net.edges.iter().map(|e| e.id.as_str()).collect(),
+            lanes: net
+                .edges
+                .iter()
+                .flat_map(|e| e.lanes.iter().map(|l| l.id.as_str()))
+                .collect(),
+        }
+    }
+
+    fn check_lane(&self, log: &mut IssueLog, lane: &str, context: &str) {
+        if !self.lanes.contains(lane) {
+            log.add("unknownLane", lane, || format!("{} refers to a lane missing from the network", context));
+        }
+    }
+}
+
+// edgedata / lanedata (<meandata>)
+fn check_meandata(root: roxmltree::Node, ids: &NetIds, log: &mut IssueLog) -> usize {
+    let mut checked = 0;
+    for interval in root.children().filter(|n| n.tag_name().name() == "interval") {
+        for item in interval.children().filter(|n| n.is_element()) {
+            let Some(id) = item.attribute("id") else { continue };
+            checked += 1;
+            match item.tag_name().name() {
+                "edge" => {
+                    if !ids.edges.contains(id) {
+                        log.add("unknownEdge", id, || "edgedata reports an edge missing from the network".to_string());
+                    }
+                    for lane in item.children().filter(|n| n.tag_name().name() == "lane") {
+                        if let Some(lane_id) = lane.attribute("id") {
+                            ids.check_lane(log, lane_id, "lanedata");
+                        }
+                    }
+                }
+                "lane" => ids.check_lane(log, id, "lanedata"),
+                _ => {}
+            }
+        }
+    }
+    checked
+}
+
+// Detector definitions from an additional file
+fn check_detectors(root: roxmltree::Node, ids: &NetIds, log: &mut IssueLog) -> usize {
+    const DETECTOR_TAGS: [&str; 6] = [
+        "inductionLoop",
+        "e1Detector",
+        "instantInductionLoop",
+        "laneAreaDetector",
+        "e2Detector",
+        "entryExitDetector",
+    ];
+    let mut checked = 0;
+    for det in root.descendants().filter(|n| DETECTOR_TAGS.contains(&n.tag_name().name())) {
+        let det_id = det.attribute("id").unwrap_or("?");
+        checked += 1;
+        let mut lanes: Vec<&str> = Vec::new();
+        if let Some(lane) = det.attribute("lane") {
+            lanes.push(lane);
+        }
+        if let Some(list) = det.attribute("lanes") {
+            lanes.extend(list.split_whitespace());
+        }
+        // E3 entry/exit points are child elements
+        lanes.extend(det.children().filter_map(|c| c.attribute("lane")));
+        for lane in lanes {
+            ids.check_lane(log, lane, &format!("detector '{}'", det_id));
+        }
+    }
+    checked
+}
+
+fn check_fcd(root: roxmltree::Node, net: &NetModel, ids: &NetIds, max_distance: f64, log: &mut IssueLog) -> usize {
+    let mut grid = SegmentGrid::new(max_distance.max(1.0) * 4.0);
+    for (i, lane) in net.edges.iter().flat_map(|e| &e.lanes).enumerate() {
+        grid.insert_polyline(i, &lane.shape);
+    }
+
+    let mut checked = 0;
+    for timestep in root.children().filter(|n| n.tag_name().name() == "timestep") {
+        let time = timestep.attribute("time").unwrap_or("?");
+        for v in timestep.children().filter(|n| n.is_element()) {
+            let id = v.attribute("id").unwrap_or("?");
+            if let Some(lane) = v.attribute("lane") {
+                ids.check_lane(log, lane, &format!("FCD record of '{}'", id));
+            }
+            let (Some(x), Some(y)) = (attr_f64(v, "x"), attr_f64(v, "y")) else { continue };
+            checked += 1;
+            if grid.nearest((x, y), max_distance).is_none() {
+                log.add("offNetwork", id, || {
+                    format!("position {:.1},{:.1} at t={} is more than {} m from any lane", x, y, time, max_distance)
+                });
+            }
+        }
+    }
+    checked
+}
+
+fn check_tripinfo(root: roxmltree::Node, ids: &NetIds, log: &mut IssueLog) -> usize {
+    let mut checked = 0;
+    for trip in root.children().filter(|n| n.tag_name().name() == "tripinfo") {
+        checked += 1;
+        let id = trip.attribute("id").unwrap_or("?");
+        for attr in ["departLane", "arrivalLane"] {
+            if let Some(lane) = trip.attribute(attr).filter(|l| !l.is_empty()) {
+                ids.check_lane(log, lane, &format!("{} of trip '{}'", attr, id));
+            }
+        }
+    }
+    checked
+}
+
+// Cross-check a simulation output (or detector definitions) against the
+// network it supposedly came from; the file type is detected from its root.
+#[wasm_bindgen(unchecked_return_type = "SanityReport")]
+pub fn check_simulation_output(
+    net_xml: &str,
+    output_xml: &str,
+    max_distance: Option<f64>,
+) -> Result<JsValue, JsValue> {
+    let net_doc = parse_xml(net_xml)?;
+    let net = NetModel::from_root(net_doc.root_element());
+    let ids = NetIds::new(&net);
+
+    let out_doc = parse_xml(output_xml)?;
+    let root = out_doc.root_element();
+    let output_type = root.tag_name().name().to_string();
+    let mut log = IssueLog::default();
+
+    let checked = match output_type.as_str() {
+        "meandata" => check_meandata(root, &ids, &mut log),
+        "additional" => check_detectors(root, &ids, &mut log),
+        "fcd-export" => check_fcd(root, &net, &ids, max_distance.unwrap_or(DEFAULT_MAX_DISTANCE), &mut log),
+        "tripinfos" => check_tripinfo(root, &ids, &mut log),
+        other => return Err(JsValue::from_str(&format!("Unsupported output type: <{}>", other))),
+    };
+
+    let issues = log.into_issues();
+    console_log!("Sanity check of {}: {} records, {} issues", output_type, checked, issues.len());
+
+    to_js(&SanityReport {
+        output_type,
+        checked,
+        issues,
+    })
+}
@@ -0,0 +1,178 @@
+// Two-phase loading: a quick pass over a .net.xml that reads attributes only
+// (no shapes) for what the map needs first, bounds, counts, traffic lights
+// and edge ids, and geometry built later for just the edges asked for.
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::geometry;
+use crate::projection::{Crs, GeoReference};
+use crate::{
+    junction_position, parse_bounds, parse_options, parse_xml, to_js, traffic_light, Bounds, NetAccumulator, Subset,
+    TrafficLight,
+};
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct NetOverview {
+    pub bounds: Option<Bounds>,
+    pub crs: Crs,
+    #[serde(rename = "edgeCount")]
+    pub edge_count: usize,
+    #[serde(rename = "internalEdgeCount")]
+    pub internal_edge_count: usize,
+    #[serde(rename = "laneCount")]
+    pub lane_count: usize,
+    #[serde(rename = "junctionCount")]
+    pub junction_count: usize,
+    #[serde(rename = "connectionCount")]
+    pub connection_count: usize,
+    pub tls: Vec<TrafficLight>,
+    // Normal edges in document order
+    #[serde(rename = "edgeIds")]
+    pub edge_ids: Vec<String>,
+}
+
+// Either list: edges by id, or those whose junctions lie in or whose
+// junction-to-junction line crosses `bbox` (x = lng, y = lat)
+#[derive(Deserialize, Default, Tsify)]
+#[serde(default)]
+pub struct GeometrySelection {
+    pub edges: Option<Vec<String>>,
+    pub bbox: Option<Bounds>,
+}
+
+// Keeps a copy of the XML text until freed
+#[wasm_bindgen]
+pub struct NetworkScan {
+    xml: String,
+    overview: NetOverview,
+    // Normal edges: from and to junction ids
+    edge_ends: Vec<(Option<String>, Option<String>)>,
+    junction_positions: HashMap<String, (f64, f64)>,
+}
+
+#[wasm_bindgen]
+impl NetworkScan {
+    #[wasm_bindgen(getter, unchecked_return_type = "NetOverview")]
+    pub fn overview(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.overview)
+    }
+
+    // Render output (as parse_sumo_net_xml) for the selected edges, their
+    // junctions and the internal lanes across those junctions
+    #[wasm_bindgen(unchecked_return_type = "ParsedNetwork")]
+    pub fn load_geometry(
+        &self,
+        #[wasm_bindgen(unchecked_param_type = "GeometrySelection")] selection: JsValue,
+    ) -> Result<JsValue, JsValue> {
+        let selection: GeometrySelection = parse_options(selection)?;
+        let subset = self.subset(&selection)?;
+        console_log!("Loading geometry of {} of {} edges", subset.edges.len(), self.overview.edge_count);
+
+        let doc = parse_xml(&self.xml)?;
+        let mut acc = NetAccumulator::new();
+        acc.restrict(subset);
+        acc.add_document(doc.root_element());
+        to_js(&acc.finish())
+    }
+}
+
+impl NetworkScan {
+    fn subset(&self, selection: &GeometrySelection) -> Result<Subset, JsValue> {
+        let position = |j: &Option<String>| j.as_ref().and_then(|j| self.junction_positions.get(j)).copied();
+        let chosen: Vec<usize> = match (&selection.edges, &selection.bbox) {
+            (Some(ids), _) => {
+                let ids: HashSet<&str> = ids.iter().map(String::as_str).collect();
+                (0..self.edge_ends.len()).filter(|i| ids.contains(self.overview.edge_ids[*i].as_str())).collect()
+            }
+            (None, Some(b)) => {
+                let min = (b.min_x.min(b.max_x), b.min_y.min(b.max_y));
+                let max = (b.min_x.max(b.max_x), b.min_y.max(b.max_y));
+                let inside = |p: (f64, f64)| p.0 >= min.0 && p.0 <= max.0 && p.1 >= min.1 && p.1 <= max.1;
+                (0..self.edge_ends.len())
+                    .filter(|i| {
+                        let (from, to) = &self.edge_ends[*i];
+                        match (position(from), position(to)) {
+                            (Some(a), Some(b)) => geometry::segment_intersects_box(a, b, min, max),
+                            (Some(p), None) | (None, Some(p)) => inside(p),
+                            (None, None) => false,
+                        }
+                    })
+                    .collect()
+            }
+            (None, None) => return Err(JsValue::from_str("Geometry selection needs edges or a bbox")),
+        };
+
+        let mut subset = Subset {
+            edges: HashSet::new(),
+            junctions: HashSet::new(),
+        };
+        for i in chosen {
+            subset.edges.insert(self.overview.edge_ids[i].clone());
+            let (from, to) = &self.edge_ends[i];
+            subset.junctions.extend(from.iter().chain(to).cloned());
+        }
+        Ok(subset)
+    }
+}
+
+pub(crate) fn scan_net(xml_text: &str) -> Result<NetworkScan, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let mut overview = NetOverview {
+        bounds: None,
+        crs: Crs::Network,
+        edge_count: 0,
+        internal_edge_count: 0,
+        lane_count: 0,
+        junction_count: 0,
+        connection_count: 0,
+        tls: Vec::new(),
+        edge_ids: Vec::new(),
+    };
+    let mut edge_ends = Vec::new();
+    let mut junction_positions = HashMap::new();
+
+    for node in doc.root_element().children().filter(|n| n.is_element()) {
+        match node.tag_name().name() {
+            "location" if overview.bounds.is_none() => {
+                overview.bounds = parse_bounds(node);
+                overview.crs = GeoReference::from_location(node).native_crs();
+            }
+            "edge" => {
+                overview.lane_count += node.children().filter(|n| n.has_tag_name("lane")).count();
+                if node.attribute("function") == Some("internal") {
+                    overview.internal_edge_count += 1;
+                    continue;
+                }
+                overview.edge_count += 1;
+                overview.edge_ids.push(node.attribute("id").unwrap_or("").to_string());
+                edge_ends.push((node.attribute("from").map(String::from), node.attribute("to").map(String::from)));
+            }
+            "junction" if node.attribute("type") != Some("internal") => {
+                overview.junction_count += 1;
+                let position = junction_position(node);
+                if let (Some(id), Some(p)) = (node.attribute("id"), position) {
+                    junction_positions.insert(id.to_string(), p);
+                }
+                overview.tls.extend(traffic_light(node, position));
+            }
+            "connection" => overview.connection_count += 1,
+            _ => {}
+        }
+    }
+
+    console_log!(
+        "Scanned {} edges, {} junctions, {} traffic lights",
+        overview.edge_count,
+        overview.junction_count,
+        overview.tls.len()
+    );
+
+    Ok(NetworkScan {
+        xml: xml_text.to_string(),
+        overview,
+        edge_ends,
+        junction_positions,
+    })
+}
@@ -0,0 +1,440 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::hash::Fnv64;
+use crate::{attr_f64, parse_bounds, parse_xml, to_js, Bounds};
+
+#[derive(Serialize, Deserialize, Tsify, Clone)]
+pub struct ScenarioFile {
+    pub name: String,
+    // Root element of the document: "net", "routes", "tripinfos", ...
+    pub kind: String,
+    pub size: usize,
+    // FNV-1a 64 of the raw text, hex; identifies identical uploads
+    pub hash: String,
+    #[serde(rename = "sumoVersion")]
+    pub sumo_version: Option<String>,
+    // Timestamp from the "generated on" header comment, as written by SUMO
+    pub generated: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct ScenarioMetadata {
+    pub files: Vec<ScenarioFile>,
+    // Network bounds in network coordinates (convBoundary)
+    pub bbox: Option<Bounds>,
+    // Bounds in the original projection, lon/lat for geo-referenced networks
+    #[serde(rename = "origBbox")]
+    pub orig_bbox: Option<Bounds>,
+    // Earliest and latest "generated on" timestamps of the files
+    #[serde(rename = "generatedFrom")]
+    pub generated_from: Option<String>,
+    #[serde(rename = "generatedTo")]
+    pub generated_to: Option<String>,
+    // Simulated time span in seconds
+    #[serde(rename = "simBegin")]
+    pub sim_begin: Option<f64>,
+    #[serde(rename = "simEnd")]
+    pub sim_end: Option<f64>,
+    // Vehicles defined in route files (flows expanded)
+    #[serde(rename = "vehicleCount")]
+    pub vehicle_count: u64,
+    #[serde(rename = "sumoVersions")]
+    pub sumo_versions: Vec<String>,
+}
+
+// What a view shows besides the time: the layer state a bookmark restores
+#[derive(Serialize, Deserialize, Tsify, Clone, Default)]
+pub struct LayerState {
+    // Visible layer ids
+    #[serde(default)]
+    pub layers: Vec<String>,
+    // Output intervals joined into the view, as [begin, end] seconds
+    #[serde(default)]
+    pub intervals: Vec<[f64; 2]>,
+    // Active filters by name
+    #[serde(default)]
+    pub filters: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Tsify, Clone)]
+pub struct Bookmark {
+    pub name: String,
+    // Simulation time in seconds
+    pub time: f64,
+    pub state: LayerState,
+    // FNV-1a 64 of the canonical layer state, hex
+    #[serde(rename = "stateHash")]
+    pub state_hash: String,
+    // Hash of the scenario files the bookmark was made on
+    #[serde(rename = "dataHash")]
+    pub data_hash: String,
+}
+
+impl LayerState {
+    // Order-insensitive form, so equal views hash equally
+    fn canonical(mut self) -> LayerState {
+        self.layers.sort();
+        self.layers.dedup();
+        self.intervals.sort_by(|a, b| a[0].total_cmp(&b[0]).then(a[1].total_cmp(&b[1])));
+        self.intervals.dedup();
+        self
+    }
+
+    fn hash(&self) -> String {
+        let mut h = Fnv64::new();
+        for layer in &self.layers {
+            h.write(layer.as_bytes());
+            h.write(&[0]);
+        }
+        h.write(&[1]);
+        for [begin, end] in &self.intervals {
+            h.write(&begin.to_bits().to_le_bytes());
+            h.write(&end.to_bits().to_le_bytes());
+        }
+        h.write(&[1]);
+        for (name, value) in &self.filters {
+            h.write(name.as_bytes());
+            h.write(&[0]);
+            h.write(value.as_bytes());
+            h.write(&[0]);
+        }
+        format!("{:016x}", h.finish())
+    }
+}
+
+// "generated on 2024-01-15 10:20:30 by Eclipse SUMO netconvert Version 1.19.0"
+fn read_header(doc: &roxmltree::Document) -> (Option<String>, Option<String>) {
+    let Some(text) = doc
+        .root()
+        .children()
+        .filter_map(|n| n.text().filter(|_| n.is_comment()))
+        .find(|t| t.contains("generated on"))
+    else {
+        return (None, None);
+    };
+    let generated = text
+        .split_once("generated on")
+        .map(|(_, rest)| rest.split(" by ").next().unwrap_or(rest).trim().to_string())
+        .filter(|s| !s.is_empty());
+    let version = text
+        .split_once("Version ")
+        .and_then(|(_, rest)| rest.split_whitespace().next())
+        .map(String::from);
+    (generated, version)
+}
+
+// SUMO's end of a flow without `end` (24 h)
+pub(crate) const DEFAULT_FLOW_END: f64 = 86_400.0;
+
+// Seconds between a flow's begin and end
+pub(crate) fn flow_duration(flow: roxmltree::Node) -> f64 {
+    attr_f64(flow, "end").unwrap_or(DEFAULT_FLOW_END) - attr_f64(flow, "begin").unwrap_or(0.0)
+}
+
+// Vehicles a <flow> (or persons a <personFlow>) inserts: `number`, or
+// derived from its period or rate. Random flows (`probability`, or a period
+// of "exp(rate)") count their expected number.
+pub(crate) fn flow_count(flow: roxmltree::Node) -> u64 {
+    if let Some(n) = flow.attribute("number").and_then(|s| s.parse::<u64>().ok()) {
+        return n;
+    }
+    let duration = flow_duration(flow);
+    if duration <= 0.0 {
+        return 0;
+    }
+    let random_rate = flow
+        .attribute("period")
+        .and_then(|p| p.trim().strip_prefix("exp(")?.strip_suffix(')')?.trim().parse::<f64>().ok());
+    let per_hour = ["vehsPerHour", "personsPerHour", "perHour"].into_iter().find_map(|a| attr_f64(flow, a));
+    let count = if let Some(period) = attr_f64(flow, "period").filter(|p| *p > 0.0) {
+        duration / period
+    } else if let Some(rate) = per_hour {
+        duration * rate / 3600.0
+    } else if let Some(rate) = random_rate.or_else(|| attr_f64(flow, "probability")) {
+        // Per-second insertion probability (or rate): the expected count
+        duration * rate.max(0.0)
+    } else {
+        0.0
+    };
+    count.ceil() as u64
+}
+
+// Files of one scenario, reduced to what the catalog entry needs as they are
+// added, so the (large) texts do not stay in memory.
+#[wasm_bindgen]
+pub struct ScenarioSession {
+    files: Vec<ScenarioFile>,
+    bbox: Option<Bounds>,
+    orig_bbox: Option<Bounds>,
+    sim_begin: Option<f64>,
+    sim_end: Option<f64>,
+    vehicle_count: u64,
+    bookmarks: Vec<Bookmark>,
+}
+
+#[wasm_bindgen]
+impl ScenarioSession {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> ScenarioSession {
+        ScenarioSession {
+            files: Vec::new(),
+            bbox: None,
+            orig_bbox: None,
+            sim_begin: None,
+            sim_end: None,
+            vehicle_count: 0,
+            bookmarks: Vec::new(),
+        }
+    }
+
+    // Add a scenario file (network, routes, sumocfg or simulation output);
+    // returns its detected kind
+    pub fn add_file(&mut self, name: &str, xml_text: &str) -> Result<String, JsValue> {
+        if self.files.iter().any(|f| f.name == name) {
+            return Err(JsValue::from_str(&format!("File '{}' already added", name)));
+        }
+        let doc = parse_xml(xml_text)?;
+        let root = doc.root_element();
+        let kind = root.tag_name().name().to_string();
+        let (generated, sumo_version) = read_header(&doc);
+
+        let mut h = Fnv64::new();
+        h.write(xml_text.as_bytes());
+
+        match kind.as_str() {
+            "net" => {
+                if let Some(location) = root.children().find(|n| n.tag_name().name() == "location") {
+                    self.bbox = parse_bounds(location);
+                    self.orig_bbox = location.attribute("origBoundary").and_then(|b| {
+                        let v: Vec<f64> = b.split(',').filter_map(|s| s.trim().parse().ok()).collect();
+                        (v.len() == 4).then(|| Bounds {
+                            min_x: v[0],
+                            min_y: v[1],
+                            max_x: v[2],
+                            max_y: v[3],
+                        })
+                    });
+                }
+            }
+            "routes" => {
+                for node in root.children().filter(|n| n.is_element()) {
+                    match node.tag_name().name() {
+                        "vehicle" | "trip" => {
+                            self.vehicle_count += 1;
+                            if let Some(t) = attr_f64(node, "depart") {
+                                self.extend_time(t);
+                            }
+                        }
+                        "flow" => {
+                            self.vehicle_count += flow_count(node);
+                            for attr in ["begin", "end"] {
+                                if let Some(t) = attr_f64(node, attr) {
+                                    self.extend_time(t);
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            "configuration" | "sumoConfiguration" => {
+                // <time><begin value="0"/><end value="3600"/></time>
+                for node in root.descendants().filter(|n| matches!(n.tag_name().name(), "begin" | "end")) {
+                    if let Some(t) = attr_f64(node, "value") {
+                        self.extend_time(t);
+                    }
+                }
+            }
+            "summary" => {
+                for step in root.children().filter(|n| n.tag_name().name() == "step") {
+                    if let Some(t) = attr_f64(step, "time") {
+                        self.extend_time(t);
+                    }
+                }
+            }
+            "tripinfos" => {
+                for trip in root.children().filter(|n| n.tag_name().name() == "tripinfo") {
+                    for attr in ["depart", "arrival"] {
+                        if let Some(t) = attr_f64(trip, attr).filter(|t| *t >= 0.0) {
+                            self.extend_time(t);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        self.files.push(ScenarioFile {
+            name: name.to_string(),
+            kind: kind.clone(),
+            size: xml_text.len(),
+            hash: format!("{:016x}", h.finish()),
+            sumo_version,
+            generated,
+        });
+        Ok(kind)
+    }
+
+    // Catalog entry for everything added so far
+    #[wasm_bindgen(unchecked_return_type = "ScenarioMetadata")]
+    pub fn metadata(&self) -> Result<JsValue, JsValue> {
+        // SUMO timestamps are "YYYY-MM-DD hh:mm:ss", so text order is time order
+        let generated: BTreeSet<&str> = self.files.iter().filter_map(|f| f.generated.as_deref()).collect();
+        let versions: BTreeSet<String> = self.files.iter().filter_map(|f| f.sumo_version.clone()).collect();
+
+        to_js(&ScenarioMetadata {
+            files: self.files.clone(),
+            bbox: self.bbox.clone(),
+            orig_bbox: self.orig_bbox.clone(),
+            generated_from: generated.first().map(|s| s.to_string()),
+            generated_to: generated.last().map(|s| s.to_string()),
+            sim_begin: self.sim_begin,
+            sim_end: self.sim_end,
+            vehicle_count: self.vehicle_count,
+            sumo_versions: versions.into_iter().collect(),
+        })
+    }
+
+    // Bookmark the current view; replaces a bookmark of the same name.
+    // Returns the state hash.
+    pub fn add_bookmark(
+        &mut self,
+        name: &str,
+        time: f64,
+        #[wasm_bindgen(unchecked_param_type = "LayerState")] state: JsValue,
+    ) -> Result<String, JsValue> {
+        let state: LayerState = serde_wasm_bindgen::from_value(state)
+            .map_err(|e| JsValue::from_str(&format!("Invalid layer state: {}", e)))?;
+        let state = state.canonical();
+        let bookmark = Bookmark {
+            name: name.to_string(),
+            time,
+            state_hash: state.hash(),
+            state,
+            data_hash: self.data_hash(),
+        };
+        let hash = bookmark.state_hash.clone();
+        self.bookmarks.retain(|b| b.name != name);
+        self.bookmarks.push(bookmark);
+        Ok(hash)
+    }
+
+    // All bookmarks, e.g. to persist them
+    #[wasm_bindgen(unchecked_return_type = "Bookmark[]")]
+    pub fn bookmarks(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.bookmarks)
+    }
+
+    // Re-add persisted bookmarks. Those made on other data or whose state no
+    // longer matches its hash are skipped; returns how many were kept.
+    pub fn import_bookmarks(
+        &mut self,
+        #[wasm_bindgen(unchecked_param_type = "Bookmark[]")] bookmarks: JsValue,
+    ) -> Result<usize, JsValue> {
+        let bookmarks: Vec<Bookmark> = serde_wasm_bindgen::from_value(bookmarks)
+            .map_err(|e| JsValue::from_str(&format!("Invalid bookmarks: {}", e)))?;
+        let data_hash = self.data_hash();
+        let mut kept = 0;
+        for mut b in bookmarks {
+            b.state = b.state.canonical();
+            if b.data_hash != data_hash || b.state.hash() != b.state_hash {
+                console_log!("Skipping bookmark '{}': made on different data", b.name);
+                continue;
+            }
+            self.bookmarks.retain(|existing| existing.name != b.name);
+            self.bookmarks.push(b);
+            kept += 1;
+        }
+        Ok(kept)
+    }
+
+    // The bookmark to restore, if it was made on the files now loaded
+    #[wasm_bindgen(unchecked_return_type = "Bookmark")]
+    pub fn resolve_bookmark(&self, name: &str) -> Result<JsValue, JsValue> {
+        let bookmark = self
+            .bookmarks
+            .iter()
+            .find(|b| b.name == name)
+            .ok_or_else(|| JsValue::from_str(&format!("No bookmark '{}'", name)))?;
+        if bookmark.data_hash != self.data_hash() {
+            return Err(JsValue::from_str(&format!(
+                "Bookmark '{}' was made on different scenario files",
+                name
+            )));
+        }
+        to_js(bookmark)
+    }
+
+    pub fn remove_bookmark(&mut self, name: &str) -> bool {
+        let before = self.bookmarks.len();
+        self.bookmarks.retain(|b| b.name != name);
+        self.bookmarks.len() != before
+    }
+}
+
+impl ScenarioSession {
+    // File contents in the session, independent of names and upload order
+    fn data_hash(&self) -> String {
+        let hashes: BTreeSet<&str> = self.files.iter().map(|f| f.hash.as_str()).collect();
+        let mut h = Fnv64::new();
+        for hash in hashes {
+            h.write(hash.as_bytes());
+        }
+        format!("{:016x}", h.finish())
+    }
+
+    fn extend_time(&mut self, t: f64) {
+        self.sim_begin = Some(self.sim_begin.map_or(t, |b| b.min(t)));
+        self.sim_end = Some(self.sim_end.map_or(t, |e| e.max(t)));
+    }
+}
+
+impl Default for ScenarioSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(flow: &str) -> u64 {
+        let doc = roxmltree::Document::parse(flow).unwrap();
+        flow_count(doc.root_element())
+    }
+
+    #[test]
+    fn flow_count_prefers_number() {
+        assert_eq!(count(r#"<flow id="f" number="7" period="1"/>"#), 7);
+    }
+
+    #[test]
+    fn flow_count_from_period_and_rate() {
+        assert_eq!(count(r#"<flow id="f" begin="0" end="100" period="10"/>"#), 10);
+        assert_eq!(count(r#"<flow id="f" begin="0" end="1800" vehsPerHour="100"/>"#), 50);
+        assert_eq!(count(r#"<personFlow id="f" begin="0" end="3600" personsPerHour="30"/>"#), 30);
+        assert_eq!(count(r#"<flow id="f" begin="0" end="3600" perHour="12"/>"#), 12);
+    }
+
+    #[test]
+    fn flow_count_defaults_end_to_a_day() {
+        assert_eq!(count(r#"<flow id="f" vehsPerHour="10"/>"#), 240);
+        assert_eq!(count(r#"<flow id="f" begin="82800" period="60"/>"#), 60);
+    }
+
+    #[test]
+    fn flow_count_expects_random_flows() {
+        assert_eq!(count(r#"<flow id="f" begin="0" end="1000" probability="0.25"/>"#), 250);
+        assert_eq!(count(r#"<flow id="f" begin="0" end="1000" period="exp(0.1)"/>"#), 100);
+    }
+
+    #[test]
+    fn flow_count_is_zero_for_empty_intervals() {
+        assert_eq!(count(r#"<flow id="f" begin="100" end="50" period="1"/>"#), 0);
+        assert_eq!(count(r#"<flow id="f" begin="0" end="100"/>"#), 0);
+    }
+}
@@ -0,0 +1,211 @@
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::fingerprint;
+use crate::geometry;
+use crate::net::NetModel;
+use crate::{parse_xml, to_js, NetAccumulator, ParsedNetwork};
+
+struct SessionNetwork {
+    label: String,
+    model: NetModel,
+    parsed: ParsedNetwork,
+    // Translation into the session frame (the first network's coordinates)
+    offset: (f64, f64),
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct FeatureMatch {
+    pub id: String,
+    pub kind: String,
+    // Distance between the two versions after alignment, in meters
+    pub displacement: f64,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct Correspondence {
+    pub matched: Vec<FeatureMatch>,
+    #[serde(rename = "onlyInA")]
+    pub only_in_a: Vec<String>,
+    #[serde(rename = "onlyInB")]
+    pub only_in_b: Vec<String>,
+}
+
+impl ParsedNetwork {
+    fn translate(&mut self, dx: f64, dy: f64) {
+        self.map_coords(|x, y| (x + dx, y + dy));
+    }
+}
+
+// Several networks (e.g. baseline and plan) aligned into one coordinate frame
+// through their <location> projections, for side-by-side comparison.
+#[wasm_bindgen]
+pub struct NetworkSession {
+    networks: Vec<SessionNetwork>,
+}
+
+#[wasm_bindgen]
+impl NetworkSession {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> NetworkSession {
+        NetworkSession { networks: Vec::new() }
+    }
+
+    // Parse and add a network; the first one added defines the session frame
+    pub fn add_network(&mut self, label: &str, xml_text: &str) -> Result<(), JsValue> {
+        if self.find(label).is_some() {
+            return Err(JsValue::from_str(&format!("Network '{}' already loaded", label)));
+        }
+
+        let doc = parse_xml(xml_text)?;
+        let root = doc.root_element();
+        let model = NetModel::from_root(root);
+        let mut acc = NetAccumulator::new();
+        acc.add_document(root);
+        let mut parsed = acc.finish();
+
+        let offset = match (self.networks.first(), &model.location) {
+            (None, _) => (0.0, 0.0),
+            (Some(base), Some(loc)) => match &base.model.location {
+                Some(base_loc) => loc.offset_to(base_loc).ok_or_else(|| {
+                    JsValue::from_str(&format!(
+                        "Cannot align '{}': projection differs from '{}'",
+                        label, base.label
+                    ))
+                })?,
+                None => (0.0, 0.0),
+            },
+            (Some(_), None) => (0.0, 0.0),
+        };
+        parsed.translate(offset.0, offset.1);
+        parsed.shrink_to_fit();
+
+        self.networks.push(SessionNetwork {
+            label: label.to_string(),
+            model,
+            parsed,
+            offset,
+        });
+        Ok(())
+    }
+
+    // Drop a network and its memory. The session frame stays that of the first
+    // network added, even if it is the one removed.
+    pub fn remove_network(&mut self, label: &str) -> bool {
+        let before = self.networks.len();
+        self.networks.retain(|n| n.label != label);
+        self.networks.shrink_to_fit();
+        self.networks.len() != before
+    }
+
+    pub fn labels(&self) -> Vec<String> {
+        self.networks.iter().map(|n| n.label.clone()).collect()
+    }
+
+    // [dx, dy] applied to the network's coordinates to reach the session frame
+    pub fn offset(&self, label: &str) -> Result<Vec<f64>, JsValue> {
+        let n = self.get(label)?;
+        Ok(vec![n.offset.0, n.offset.1])
+    }
+
+    // Render output of one network, already in the session frame
+    #[wasm_bindgen(unchecked_return_type = "ParsedNetwork")]
+    pub fn network(&self, label: &str) -> Result<JsValue, JsValue> {
+        to_js(&self.get(label)?.parsed)
+    }
+
+    // Edges and junctions present in both networks, with how far each moved
+    #[wasm_bindgen(unchecked_return_type = "Correspondence")]
+    pub fn correspondence(&self, label_a: &str, label_b: &str) -> Result<JsValue, JsValue> {
+        let a = self.get(label_a)?;
+        let b = self.get(label_b)?;
+        let shift = |n: &SessionNetwork, p: (f64, f64)| (p.0 + n.offset.0, p.1 + n.offset.1);
+
+        let mut matched = Vec::new();
+        let mut only_in_a = Vec::new();
+
+        for edge in a.model.edges.iter().filter(|e| !e.is_internal()) {
+            let Some(other) = b.model.edge(&edge.id) else {
+                only_in_a.push(edge.id.clone());
+                continue;
+            };
+            let ends = |n: &SessionNetwork, shape: &[(f64, f64)]| {
+                Some((shift(n, *shape.first()?), shift(n, *shape.last()?)))
+            };
+            let displacement = match (
+                edge.lanes.first().and_then(|l| ends(a, &l.shape)),
+                other.lanes.first().and_then(|l| ends(b, &l.shape)),
+            ) {
+                (Some((a0, a1)), Some((b0, b1))) => {
+                    geometry::distance(a0, b0).max(geometry::distance(a1, b1))
+                }
+                _ => 0.0,
+            };
+            matched.push(FeatureMatch {
+                id: edge.id.clone(),
+                kind: "edge".to_string(),
+                displacement,
+            });
+        }
+
+        for junction in a.model.junctions.iter().filter(|j| !j.id.starts_with(':')) {
+            let Some(other) = b.model.junction(&junction.id) else {
+                only_in_a.push(junction.id.clone());
+                continue;
+            };
+            matched.push(FeatureMatch {
+                id: junction.id.clone(),
+                kind: "junction".to_string(),
+                displacement: geometry::distance(
+                    shift(a, (junction.x, junction.y)),
+                    shift(b, (other.x, other.y)),
+                ),
+            });
+        }
+
+        let only_in_b = b
+            .model
+            .edges
+            .iter()
+            .filter(|e| !e.is_internal() && a.model.edge(&e.id).is_none())
+            .map(|e| e.id.clone())
+            .chain(
+                b.model
+                    .junctions
+                    .iter()
+                    .filter(|j| !j.id.starts_with(':') && a.model.junction(&j.id).is_none())
+                    .map(|j| j.id.clone()),
+            )
+            .collect();
+
+        to_js(&Correspondence {
+            matched,
+            only_in_a,
+            only_in_b,
+        })
+    }
+
+    // Edge pairs across two builds, matched by geometry even when ids changed
+    #[wasm_bindgen(unchecked_return_type = "FingerprintMatch[]")]
+    pub fn match_edges(&self, label_a: &str, label_b: &str) -> Result<JsValue, JsValue> {
+        let a = self.get(label_a)?;
+        let b = self.get(label_b)?;
+        to_js(&fingerprint::match_edges(&a.model, &b.model))
+    }
+
+    fn find(&self, label: &str) -> Option<&SessionNetwork> {
+        self.networks.iter().find(|n| n.label == label)
+    }
+
+    fn get(&self, label: &str) -> Result<&SessionNetwork, JsValue> {
+        self.find(label)
+            .ok_or_else(|| JsValue::from_str(&format!("No network named '{}'", label)))
+    }
+}
+
+impl Default for NetworkSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
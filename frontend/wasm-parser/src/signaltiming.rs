@@ -0,0 +1,281 @@
+// Signal timing diagrams: for every traffic light, the color of each link
+// index over a time window as bands, ready for a time-space diagram. Bands
+// come from the tlLogic program, or from a TLS switch output when given
+// (see tlsoutput.rs), which records the green times actually shown by
+// actuated and coordinated controllers.
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::net::{NetModel, TlLogicModel};
+use crate::tlsoutput::{read_tls_switches, TlsSwitch};
+use crate::{parse_options, parse_xml, to_js};
+
+// Longest window drawn from a program, one day; bounds the band count
+const MAX_WINDOW: f64 = 86_400.0;
+
+#[derive(Deserialize, Default, Tsify)]
+#[serde(default)]
+pub struct SignalTimingOptions {
+    // Seconds; default 0, or the first switch of the switch output
+    pub begin: Option<f64>,
+    // Seconds; default one cycle after `begin`, or the last switch
+    pub end: Option<f64>,
+    // Traffic light ids to include; all when missing
+    pub tls: Option<Vec<String>>,
+    // Program per traffic light; default the first one in the network
+    #[serde(rename = "programId")]
+    pub program_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Tsify, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum SignalColor {
+    // 'G', priority green
+    Green,
+    // 'g', green that has to yield
+    GreenMinor,
+    // 'y', 'Y'
+    Yellow,
+    // 'u', red+yellow before green
+    RedYellow,
+    // 'r', 'R', 's'
+    Red,
+    // 'o', 'O', switched off or blinking
+    Off,
+}
+
+impl SignalColor {
+    fn from_state(c: char) -> SignalColor {
+        match c {
+            'G' => SignalColor::Green,
+            'g' => SignalColor::GreenMinor,
+            'y' | 'Y' => SignalColor::Yellow,
+            'u' => SignalColor::RedYellow,
+            'o' | 'O' => SignalColor::Off,
+            _ => SignalColor::Red,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct SignalBand {
+    pub begin: f64,
+    pub end: f64,
+    pub color: SignalColor,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct LinkTimeline {
+    #[serde(rename = "linkIndex")]
+    pub link_index: usize,
+    // The first connection with this link index, when the network has one
+    #[serde(rename = "fromLane")]
+    pub from_lane: Option<String>,
+    #[serde(rename = "toLane")]
+    pub to_lane: Option<String>,
+    pub dir: Option<String>,
+    // Consecutive, covering the window
+    pub bands: Vec<SignalBand>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct SignalTimeline {
+    pub tl: String,
+    #[serde(rename = "programId")]
+    pub program_id: String,
+    // Seconds, the sum of the phase durations
+    pub cycle: f64,
+    pub offset: f64,
+    pub begin: f64,
+    pub end: f64,
+    // "program", or "switches" when taken from a switch output
+    pub source: String,
+    pub links: Vec<LinkTimeline>,
+}
+
+// Appends, merging with the previous band when the color is the same
+fn push_band(bands: &mut Vec<SignalBand>, begin: f64, end: f64, color: SignalColor) {
+    if end <= begin {
+        return;
+    }
+    match bands.last_mut() {
+        Some(last) if last.color == color && last.end == begin => last.end = end,
+        _ => bands.push(SignalBand { begin, end, color }),
+    }
+}
+
+// Every link's bands from `begin` to `end` by running the program, which is
+// at the start of its first phase at `offset` and every cycle from there
+fn program_bands(logic: &TlLogicModel, links: usize, begin: f64, end: f64) -> Vec<Vec<SignalBand>> {
+    let phases: Vec<(f64, Vec<char>)> = logic
+        .phases
+        .iter()
+        .filter_map(|p| Some((p.duration.parse::<f64>().ok().filter(|d| *d > 0.0)?, p.state.chars().collect())))
+        .collect();
+    let cycle: f64 = phases.iter().map(|p| p.0).sum();
+    let mut bands: Vec<Vec<SignalBand>> = (0..links).map(|_| Vec::new()).collect();
+    if phases.is_empty() || cycle <= 0.0 {
+        return bands;
+    }
+
+    let offset = logic.offset.as_deref().and_then(|o| o.parse::<f64>().ok()).unwrap_or(0.0);
+    let mut into_cycle = (begin - offset).rem_euclid(cycle);
+    let mut phase = 0;
+    while phase + 1 < phases.len() && into_cycle >= phases[phase].0 {
+        into_cycle -= phases[phase].0;
+        phase += 1;
+    }
+    let mut t = begin;
+    while t < end {
+        let (duration, state) = &phases[phase];
+        let until = (t + duration - into_cycle).min(end);
+        for (link, bands) in bands.iter_mut().enumerate() {
+            push_band(bands, t, until, SignalColor::from_state(state.get(link).copied().unwrap_or('o')));
+        }
+        t = until;
+        into_cycle = 0.0;
+        phase = (phase + 1) % phases.len();
+    }
+    bands
+}
+
+// Green where the switch output has a green period, red in between
+fn switch_bands(switches: &[&TlsSwitch], from_lane: &str, to_lane: &str, begin: f64, end: f64) -> Vec<SignalBand> {
+    let mut greens: Vec<(f64, f64)> = switches
+        .iter()
+        .filter(|s| s.from_lane == from_lane && s.to_lane == to_lane)
+        .map(|s| (s.begin.max(begin), s.end.min(end)))
+        .filter(|(b, e)| e > b)
+        .collect();
+    greens.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut bands = Vec::new();
+    let mut t = begin;
+    for (b, e) in greens {
+        push_band(&mut bands, t, b.max(t), SignalColor::Red);
+        push_band(&mut bands, b.max(t), e, SignalColor::Green);
+        t = t.max(e);
+    }
+    push_band(&mut bands, t, end, SignalColor::Red);
+    bands
+}
+
+pub(crate) fn signal_timelines(
+    net: &NetModel,
+    switches: Option<&[TlsSwitch]>,
+    options: &SignalTimingOptions,
+) -> Vec<SignalTimeline> {
+    // First program per traffic light, or the requested one
+    let mut logics: BTreeMap<&str, &TlLogicModel> = BTreeMap::new();
+    for logic in &net.tl_logics {
+        let wanted = options.tls.as_ref().is_none_or(|ids| ids.contains(&logic.id));
+        let program = options.program_id.as_ref().is_none_or(|p| *p == logic.program_id);
+        if wanted && program {
+            logics.entry(logic.id.as_str()).or_insert(logic);
+        }
+    }
+
+    // Link index -> (from lane, to lane, dir) per traffic light
+    let mut links: HashMap<&str, BTreeMap<usize, (String, String, &str)>> = HashMap::new();
+    for c in &net.connections {
+        if let (Some(tl), Some(index)) = (&c.tl, c.link_index) {
+            let lanes = (format!("{}_{}", c.from, c.from_lane), format!("{}_{}", c.to, c.to_lane));
+            links.entry(tl.as_str()).or_default().entry(index).or_insert((lanes.0, lanes.1, c.dir.as_str()));
+        }
+    }
+
+    let mut by_tl: HashMap<&str, Vec<&TlsSwitch>> = HashMap::new();
+    for s in switches.unwrap_or(&[]) {
+        by_tl.entry(s.tl.as_str()).or_default().push(s);
+    }
+
+    logics
+        .into_iter()
+        .map(|(id, logic)| {
+            let cycle: f64 = logic.phases.iter().filter_map(|p| p.duration.parse::<f64>().ok()).sum();
+            let offset = logic.offset.as_deref().and_then(|o| o.parse::<f64>().ok()).unwrap_or(0.0);
+            let tl_links = links.remove(id).unwrap_or_default();
+            let tl_switches = by_tl.get(id).filter(|s| !s.is_empty());
+            let link_count = logic
+                .phases
+                .iter()
+                .map(|p| p.state.chars().count())
+                .max()
+                .unwrap_or(0)
+                .max(tl_links.keys().next_back().map_or(0, |i| i + 1));
+
+            let begin = options
+                .begin
+                .or_else(|| tl_switches.map(|s| s.iter().map(|s| s.begin).fold(f64::INFINITY, f64::min)))
+                .unwrap_or(0.0);
+            let end = options
+                .end
+                .or_else(|| tl_switches.map(|s| s.iter().map(|s| s.end).fold(f64::NEG_INFINITY, f64::max)))
+                .unwrap_or(begin + cycle)
+                .clamp(begin, begin + MAX_WINDOW);
+
+            let bands = match tl_switches {
+                Some(s) => (0..link_count)
+                    .map(|i| match tl_links.get(&i) {
+                        Some((from, to, _)) => switch_bands(s, from, to, begin, end),
+                        None => Vec::new(),
+                    })
+                    .collect(),
+                None => program_bands(logic, link_count, begin, end),
+            };
+            let links = bands
+                .into_iter()
+                .enumerate()
+                .map(|(i, bands)| {
+                    let link = tl_links.get(&i);
+                    LinkTimeline {
+                        link_index: i,
+                        from_lane: link.map(|l| l.0.clone()),
+                        to_lane: link.map(|l| l.1.clone()),
+                        dir: link.map(|l| l.2.to_string()),
+                        bands,
+                    }
+                })
+                .collect();
+
+            SignalTimeline {
+                tl: id.to_string(),
+                program_id: logic.program_id.clone(),
+                cycle,
+                offset,
+                begin,
+                end,
+                source: if tl_switches.is_some() { "switches" } else { "program" }.to_string(),
+                links,
+            }
+        })
+        .collect()
+}
+
+// Per traffic light, the color bands of every link index between `begin` and
+// `end`, from the network's programs or from `switches_xml`, a TLS switch
+// output of a run on this network
+#[wasm_bindgen(unchecked_return_type = "SignalTimeline[]")]
+pub fn signal_timing_diagram(
+    net_xml: &str,
+    switches_xml: Option<String>,
+    #[wasm_bindgen(unchecked_param_type = "SignalTimingOptions | undefined")] options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let options: SignalTimingOptions = parse_options(options)?;
+    if [options.begin, options.end].into_iter().flatten().any(|t| !t.is_finite()) {
+        return Err(JsValue::from_str("begin and end must be finite"));
+    }
+    let doc = parse_xml(net_xml)?;
+    let net = NetModel::from_root(doc.root_element());
+    let switches = match &switches_xml {
+        Some(xml) => Some(read_tls_switches(parse_xml(xml)?.root_element())),
+        None => None,
+    };
+    let timelines = signal_timelines(&net, switches.as_deref(), &options);
+
+    console_log!("Built signal timelines for {} traffic lights", timelines.len());
+
+    to_js(&timelines)
+}
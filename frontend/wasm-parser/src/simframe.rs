@@ -0,0 +1,380 @@
+// Compact binary frames for live playback over a WebSocket, instead of a JSON
+// document of every vehicle every step. The encoder keeps a table of vehicle
+// id slots and the last quantized state of each, so a frame only carries ids
+// the first time they appear and small deltas after that.
+//
+// Frame layout (integers are LEB128 varints, deltas zigzag-encoded):
+//
+//   "SF" version:u8 flags:u8 time:f64le
+//   added:   n, then n × (slot, id length, id bytes)
+//   removed: n, then n × slot
+//   states:  n, then n × (slot, dx, dy, dspeed, dangle)
+//
+// x and y are in cm, speed in cm/s, angle in 0.01° (its delta wraps around
+// 360°). Each state is relative to the slot's state in the previous frame.
+// Keyframes (flag bit 0) reset the id table and carry absolute states (deltas
+// from 0); decoders that join mid-stream skip frames until the first one.
+use std::collections::{HashMap, HashSet};
+use wasm_bindgen::prelude::*;
+
+const MAGIC: &[u8; 2] = b"SF";
+const VERSION: u8 = 1;
+const FLAG_KEYFRAME: u8 = 1;
+const DEFAULT_KEYFRAME_INTERVAL: u32 = 50;
+const FULL_TURN: i64 = 36000;
+
+// Quantized (x, y, speed, angle)
+type State = [i64; 4];
+
+fn quantize(x: f64, y: f64, speed: f64, angle: f64) -> State {
+    [
+        (x * 100.0).round() as i64,
+        (y * 100.0).round() as i64,
+        (speed * 100.0).round() as i64,
+        ((angle * 100.0).round() as i64).rem_euclid(FULL_TURN),
+    ]
+}
+
+fn write_varint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn write_signed(out: &mut Vec<u8>, v: i64) {
+    write_varint(out, ((v << 1) ^ (v >> 63)) as u64);
+}
+
+// Shortest way around the circle, in -180°..180°
+fn angle_delta(from: i64, to: i64) -> i64 {
+    (to - from + FULL_TURN / 2).rem_euclid(FULL_TURN) - FULL_TURN / 2
+}
+
+// Server side: turns per-step vehicle states into frames
+#[wasm_bindgen]
+pub struct SimFrameEncoder {
+    slots: HashMap<String, u32>,
+    free: Vec<u32>,
+    next_slot: u32,
+    states: Vec<State>,
+    keyframe_interval: u32,
+    since_keyframe: Option<u32>,
+}
+
+#[wasm_bindgen]
+impl SimFrameEncoder {
+    // A keyframe every `keyframe_interval` frames (default 50), so clients
+    // joining or recovering from a dropped frame resynchronize
+    #[wasm_bindgen(constructor)]
+    pub fn new(keyframe_interval: Option<u32>) -> SimFrameEncoder {
+        SimFrameEncoder {
+            slots: HashMap::new(),
+            free: Vec::new(),
+            next_slot: 0,
+            states: Vec::new(),
+            keyframe_interval: keyframe_interval.filter(|i| *i > 0).unwrap_or(DEFAULT_KEYFRAME_INTERVAL),
+            since_keyframe: None,
+        }
+    }
+
+    // Make the next frame a keyframe, e.g. when a client connects
+    pub fn request_keyframe(&mut self) {
+        self.since_keyframe = None;
+    }
+
+    // One frame of the vehicles present at `time`; x/y in network meters,
+    // speed in m/s, angle in degrees
+    pub fn encode(
+        &mut self,
+        time: f64,
+        ids: Vec<String>,
+        x: &[f64],
+        y: &[f64],
+        speed: &[f64],
+        angle: &[f64],
+    ) -> Result<Vec<u8>, JsValue> {
+        if [x.len(), y.len(), speed.len(), angle.len()].iter().any(|l| *l != ids.len()) {
+            return Err(JsValue::from_str("ids, x, y, speed and angle must have the same length"));
+        }
+        let states: Vec<State> = (0..ids.len()).map(|i| quantize(x[i], y[i], speed[i], angle[i])).collect();
+        Ok(self.encode_frame(time, &ids, &states))
+    }
+}
+
+impl SimFrameEncoder {
+    pub(crate) fn encode_frame(&mut self, time: f64, ids: &[String], states: &[State]) -> Vec<u8> {
+        let keyframe = self.since_keyframe.is_none_or(|n| n + 1 >= self.keyframe_interval);
+        if keyframe {
+            self.slots.clear();
+            self.free.clear();
+            self.next_slot = 0;
+        }
+        self.since_keyframe = Some(if keyframe { 0 } else { self.since_keyframe.unwrap_or(0) + 1 });
+
+        let mut out = Vec::with_capacity(16 + states.len() * 8);
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.push(if keyframe { FLAG_KEYFRAME } else { 0 });
+        out.extend_from_slice(&time.to_le_bytes());
+
+        // Slots of vehicles gone since the last frame are freed first, so
+        // newcomers can reuse them
+        let present: HashMap<&str, usize> = ids.iter().enumerate().map(|(i, id)| (id.as_str(), i)).collect();
+        let mut removed: Vec<u32> = Vec::new();
+        self.slots.retain(|id, slot| {
+            let keep = present.contains_key(id.as_str());
+            if !keep {
+                removed.push(*slot);
+            }
+            keep
+        });
+        self.free.extend(&removed);
+
+        let mut added: Vec<(u32, &str)> = Vec::new();
+        let mut slots = Vec::with_capacity(ids.len());
+        for id in ids {
+            let slot = match self.slots.get(id) {
+                Some(slot) => *slot,
+                None => {
+                    let slot = self.free.pop().unwrap_or_else(|| {
+                        self.next_slot += 1;
+                        self.next_slot - 1
+                    });
+                    self.slots.insert(id.clone(), slot);
+                    added.push((slot, id));
+                    slot
+                }
+            };
+            slots.push(slot);
+        }
+        if self.states.len() < self.next_slot as usize {
+            self.states.resize(self.next_slot as usize, [0; 4]);
+        }
+
+        write_varint(&mut out, added.len() as u64);
+        for (slot, id) in &added {
+            write_varint(&mut out, *slot as u64);
+            write_varint(&mut out, id.len() as u64);
+            out.extend_from_slice(id.as_bytes());
+        }
+        write_varint(&mut out, if keyframe { 0 } else { removed.len() as u64 });
+        if !keyframe {
+            removed.iter().for_each(|slot| write_varint(&mut out, *slot as u64));
+        }
+
+        write_varint(&mut out, states.len() as u64);
+        let added: HashSet<u32> = added.iter().map(|(slot, _)| *slot).collect();
+        for (slot, state) in slots.iter().zip(states) {
+            let previous = if added.contains(slot) { [0; 4] } else { self.states[*slot as usize] };
+            write_varint(&mut out, *slot as u64);
+            for k in 0..3 {
+                write_signed(&mut out, state[k] - previous[k]);
+            }
+            write_signed(&mut out, angle_delta(previous[3], state[3]));
+            self.states[*slot as usize] = *state;
+        }
+        out
+    }
+}
+
+impl Default for SimFrameEncoder {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+// One decoded frame as parallel arrays
+#[wasm_bindgen]
+pub struct SimFrame {
+    time: f64,
+    keyframe: bool,
+    ids: Vec<String>,
+    x: Vec<f64>,
+    y: Vec<f64>,
+    speed: Vec<f32>,
+    angle: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl SimFrame {
+    #[wasm_bindgen(getter)]
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn keyframe(&self) -> bool {
+        self.keyframe
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.ids.len()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn ids(&self) -> Vec<String> {
+        self.ids.clone()
+    }
+
+    // Network meters, to cm
+    #[wasm_bindgen(getter)]
+    pub fn x(&self) -> Vec<f64> {
+        self.x.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn y(&self) -> Vec<f64> {
+        self.y.clone()
+    }
+
+    // m/s
+    #[wasm_bindgen(getter)]
+    pub fn speed(&self) -> Vec<f32> {
+        self.speed.clone()
+    }
+
+    // Degrees, 0-360
+    #[wasm_bindgen(getter)]
+    pub fn angle(&self) -> Vec<f32> {
+        self.angle.clone()
+    }
+}
+
+struct FrameReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> FrameReader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(n).filter(|e| *e <= self.bytes.len());
+        let end = end.ok_or_else(|| "Sim frame truncated".to_string())?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut v = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.take(1)?[0];
+            v |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err("Sim frame varint too long".to_string())
+    }
+
+    fn signed(&mut self) -> Result<i64, String> {
+        let v = self.varint()?;
+        Ok((v >> 1) as i64 ^ -((v & 1) as i64))
+    }
+
+    // Slots are allocated densely and each new one is announced with at
+    // least three bytes, so one beyond `known` plus the frame size is corrupt
+    fn slot(&mut self, known: usize) -> Result<usize, String> {
+        let slot = self.varint()? as usize;
+        if slot >= known + self.bytes.len() {
+            return Err(format!("Sim frame slot {} out of range", slot));
+        }
+        Ok(slot)
+    }
+}
+
+// Client side: keeps the id table and last states across frames
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct SimFrameDecoder {
+    ids: Vec<Option<String>>,
+    states: Vec<State>,
+    synced: bool,
+}
+
+#[wasm_bindgen]
+impl SimFrameDecoder {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> SimFrameDecoder {
+        SimFrameDecoder::default()
+    }
+
+    // Decodes the next frame of the stream; undefined until the first
+    // keyframe arrives
+    pub fn decode_sim_frame(&mut self, bytes: &[u8]) -> Result<Option<SimFrame>, JsValue> {
+        self.decode_frame(bytes).map_err(|e| JsValue::from_str(&e))
+    }
+}
+
+impl SimFrameDecoder {
+    pub(crate) fn decode_frame(&mut self, bytes: &[u8]) -> Result<Option<SimFrame>, String> {
+        let mut r = FrameReader { bytes, pos: 0 };
+        if r.take(2)? != MAGIC {
+            return Err("Not a sim frame".to_string());
+        }
+        let version = r.take(1)?[0];
+        if version != VERSION {
+            return Err(format!("Unsupported sim frame version {}", version));
+        }
+        let keyframe = r.take(1)?[0] & FLAG_KEYFRAME != 0;
+        let time = f64::from_le_bytes(r.take(8)?.try_into().unwrap());
+        if keyframe {
+            self.ids.clear();
+            self.states.clear();
+            self.synced = true;
+        } else if !self.synced {
+            return Ok(None);
+        }
+
+        let mut fresh = Vec::new();
+        for _ in 0..r.varint()? {
+            let slot = r.slot(self.ids.len())?;
+            let len = r.varint()? as usize;
+            let id = String::from_utf8_lossy(r.take(len)?).into_owned();
+            if self.ids.len() <= slot {
+                self.ids.resize(slot + 1, None);
+                self.states.resize(slot + 1, [0; 4]);
+            }
+            self.ids[slot] = Some(id);
+            self.states[slot] = [0; 4];
+            fresh.push(slot);
+        }
+        for _ in 0..r.varint()? {
+            let slot = r.slot(self.ids.len())?;
+            // A removed slot reused in the same frame was re-added above
+            if let Some(id) = self.ids.get_mut(slot).filter(|_| !fresh.contains(&slot)) {
+                *id = None;
+            }
+        }
+
+        let n = r.varint()? as usize;
+        let mut frame = SimFrame {
+            time,
+            keyframe,
+            ids: Vec::with_capacity(n.min(bytes.len())),
+            x: Vec::with_capacity(n.min(bytes.len())),
+            y: Vec::with_capacity(n.min(bytes.len())),
+            speed: Vec::with_capacity(n.min(bytes.len())),
+            angle: Vec::with_capacity(n.min(bytes.len())),
+        };
+        for _ in 0..n {
+            let slot = r.slot(self.ids.len())?;
+            let Some(Some(id)) = self.ids.get(slot) else {
+                return Err(format!("Sim frame references unknown slot {}", slot));
+            };
+            let state = &mut self.states[slot];
+            for v in state.iter_mut() {
+                *v = v.checked_add(r.signed()?).ok_or_else(|| format!("Sim frame delta overflows slot {}", slot))?;
+            }
+            state[3] = state[3].rem_euclid(FULL_TURN);
+            frame.ids.push(id.clone());
+            frame.x.push(state[0] as f64 / 100.0);
+            frame.y.push(state[1] as f64 / 100.0);
+            frame.speed.push(state[2] as f32 / 100.0);
+            frame.angle.push(state[3] as f32 / 100.0);
+        }
+        Ok(Some(frame))
+    }
+}
@@ -0,0 +1,84 @@
+// Save and restore a Network handle (feature "snapshot") so repeat visits skip
+// XML parsing: the render output, the topology model and the geo reference
+// after a magic number and a format version. The indexes are rebuilt on load.
+// The body is MessagePack rather than bincode or postcard because the render
+// structs skip empty fields, which only a self-describing format reads back.
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::net::{ConnectionModel, EdgeModel, JunctionModel, LocationModel, NetModel, TlLogicModel};
+use crate::projection::GeoReference;
+use crate::ParsedNetwork;
+
+const MAGIC: &[u8; 8] = b"SUMONET\0";
+
+// Bump whenever ParsedNetwork, NetModel or GeoReference change shape
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    parsed: &'a ParsedNetwork,
+    geo: &'a GeoReference,
+    lefthand: bool,
+    location: &'a Option<LocationModel>,
+    edges: &'a [EdgeModel],
+    junctions: &'a [JunctionModel],
+    connections: &'a [ConnectionModel],
+    tl_logics: &'a [TlLogicModel],
+}
+
+// NetModel without its id indexes, which NetModel::new rebuilds
+#[derive(Deserialize)]
+struct Snapshot {
+    parsed: ParsedNetwork,
+    geo: GeoReference,
+    lefthand: bool,
+    location: Option<LocationModel>,
+    edges: Vec<EdgeModel>,
+    junctions: Vec<JunctionModel>,
+    connections: Vec<ConnectionModel>,
+    tl_logics: Vec<TlLogicModel>,
+}
+
+pub(crate) fn write(parsed: &ParsedNetwork, model: &NetModel, geo: &GeoReference) -> Result<Vec<u8>, JsValue> {
+    let mut out = MAGIC.to_vec();
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    let snapshot = SnapshotRef {
+        parsed,
+        geo,
+        lefthand: model.lefthand,
+        location: &model.location,
+        edges: &model.edges,
+        junctions: &model.junctions,
+        connections: &model.connections,
+        tl_logics: &model.tl_logics,
+    };
+    rmp_serde::encode::write_named(&mut out, &snapshot)
+        .map_err(|e| JsValue::from_str(&format!("Cannot write snapshot: {}", e)))?;
+    Ok(out)
+}
+
+pub(crate) fn read(bytes: &[u8]) -> Result<(ParsedNetwork, NetModel, GeoReference), JsValue> {
+    let header = MAGIC.len() + 4;
+    if bytes.len() < header || !bytes.starts_with(MAGIC) {
+        return Err(JsValue::from_str("Not a network snapshot"));
+    }
+    let version = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
+    if version != FORMAT_VERSION {
+        return Err(JsValue::from_str(&format!(
+            "Network snapshot has format version {}, this build reads version {}; parse the XML again",
+            version, FORMAT_VERSION
+        )));
+    }
+    let snapshot: Snapshot = rmp_serde::from_slice(&bytes[header..])
+        .map_err(|e| JsValue::from_str(&format!("Corrupt network snapshot: {}", e)))?;
+    let model = NetModel::new(
+        snapshot.lefthand,
+        snapshot.location,
+        snapshot.edges,
+        snapshot.junctions,
+        snapshot.connections,
+        snapshot.tl_logics,
+    );
+    Ok((snapshot.parsed, model, snapshot.geo))
+}
@@ -0,0 +1,239 @@
+// Space-time cube: vehicle positions counted into square or hexagonal cells
+// per time bucket, for density heatmaps and 3D (x, y, time) views. Works on
+// any position records: an FCD file's tracks or plain arrays.
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::fcd::FcdData;
+use crate::parse_options;
+
+const DEFAULT_CELL_SIZE: f64 = 100.0;
+const DEFAULT_INTERVAL: f64 = 60.0;
+
+#[derive(Serialize, Deserialize, Tsify, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum CellShape {
+    #[default]
+    Square,
+    // Pointy-top hexagons
+    Hex,
+}
+
+#[derive(Deserialize, Default, Tsify)]
+#[serde(default)]
+pub struct SpaceTimeOptions {
+    // Distance between neighboring cell centers, in position units (default
+    // 100, meters for network coordinates)
+    #[serde(rename = "cellSize")]
+    pub cell_size: Option<f64>,
+    pub shape: CellShape,
+    // Seconds per time bucket (default 60)
+    pub interval: Option<f64>,
+}
+
+// Occupied (cell, bucket) bins as parallel arrays, ordered by bucket, then
+// cell
+#[wasm_bindgen]
+pub struct SpaceTimeBins {
+    shape: CellShape,
+    cell_size: f64,
+    interval: f64,
+    // Cell column/row (square) or axial q/r (hex) pairs
+    cells: Vec<i32>,
+    centers: Vec<f64>,
+    buckets: Vec<i64>,
+    counts: Vec<u32>,
+    mean_speed: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl SpaceTimeBins {
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.counts.len()
+    }
+
+    // "square" or "hex"
+    #[wasm_bindgen(getter)]
+    pub fn shape(&self) -> String {
+        match self.shape {
+            CellShape::Square => "square",
+            CellShape::Hex => "hex",
+        }
+        .to_string()
+    }
+
+    #[wasm_bindgen(getter, js_name = cellSize)]
+    pub fn cell_size(&self) -> f64 {
+        self.cell_size
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn interval(&self) -> f64 {
+        self.interval
+    }
+
+    // Integer cell coordinates per bin: column, row for squares (origin at
+    // 0, 0), axial q, r for hexagons
+    #[wasm_bindgen(getter)]
+    pub fn cells(&self) -> Vec<i32> {
+        self.cells.clone()
+    }
+
+    // x, y of each bin's cell center, in the positions' frame
+    #[wasm_bindgen(getter)]
+    pub fn centers(&self) -> Vec<f64> {
+        self.centers.clone()
+    }
+
+    // Bucket start time (seconds) per bin
+    #[wasm_bindgen(getter)]
+    pub fn times(&self) -> Vec<f64> {
+        self.buckets.iter().map(|b| *b as f64 * self.interval).collect()
+    }
+
+    // Position records per bin
+    #[wasm_bindgen(getter)]
+    pub fn counts(&self) -> Vec<u32> {
+        self.counts.clone()
+    }
+
+    // m/s; NaN when no record in the bin has a speed
+    #[wasm_bindgen(getter, js_name = meanSpeed)]
+    pub fn mean_speed(&self) -> Vec<f32> {
+        self.mean_speed.clone()
+    }
+}
+
+struct CellGrid {
+    shape: CellShape,
+    size: f64,
+}
+
+impl CellGrid {
+    // Hexagon circumradius for the center spacing
+    fn radius(&self) -> f64 {
+        self.size / 3f64.sqrt()
+    }
+
+    fn cell_of(&self, x: f64, y: f64) -> (i32, i32) {
+        match self.shape {
+            CellShape::Square => ((x / self.size).floor() as i32, (y / self.size).floor() as i32),
+            CellShape::Hex => {
+                let r = self.radius();
+                let q = (3f64.sqrt() / 3.0 * x - y / 3.0) / r;
+                let s = 2.0 / 3.0 * y / r;
+                hex_round(q, s)
+            }
+        }
+    }
+
+    fn center(&self, (a, b): (i32, i32)) -> (f64, f64) {
+        match self.shape {
+            CellShape::Square => ((a as f64 + 0.5) * self.size, (b as f64 + 0.5) * self.size),
+            CellShape::Hex => {
+                let r = self.radius();
+                (r * 3f64.sqrt() * (a as f64 + b as f64 / 2.0), r * 1.5 * b as f64)
+            }
+        }
+    }
+}
+
+// Nearest hexagon of fractional axial coordinates, via cube rounding
+fn hex_round(q: f64, r: f64) -> (i32, i32) {
+    let s = -q - r;
+    let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+    let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+    if dq > dr && dq > ds {
+        rq = -rr - rs;
+    } else if dr > ds {
+        rr = -rq - rs;
+    }
+    (rq as i32, rr as i32)
+}
+
+#[derive(Default)]
+struct Bin {
+    count: u32,
+    speed_sum: f64,
+    // Records with a speed
+    with_speed: u32,
+}
+
+// Records are (x, y, time, speed); speed may be NaN
+pub(crate) fn bin_records(
+    records: impl Iterator<Item = (f64, f64, f64, f64)>,
+    options: &SpaceTimeOptions,
+) -> SpaceTimeBins {
+    let grid = CellGrid {
+        shape: options.shape,
+        size: options.cell_size.filter(|s| *s > 0.0).unwrap_or(DEFAULT_CELL_SIZE),
+    };
+    let interval = options.interval.filter(|i| *i > 0.0).unwrap_or(DEFAULT_INTERVAL);
+
+    let mut bins: BTreeMap<(i64, (i32, i32)), Bin> = BTreeMap::new();
+    for (x, y, time, speed) in records {
+        if !(x.is_finite() && y.is_finite() && time.is_finite()) {
+            continue;
+        }
+        let bin = bins.entry(((time / interval).floor() as i64, grid.cell_of(x, y))).or_default();
+        bin.count += 1;
+        if speed.is_finite() {
+            bin.speed_sum += speed;
+            bin.with_speed += 1;
+        }
+    }
+
+    let mut out = SpaceTimeBins {
+        shape: grid.shape,
+        cell_size: grid.size,
+        interval,
+        cells: Vec::with_capacity(bins.len() * 2),
+        centers: Vec::with_capacity(bins.len() * 2),
+        buckets: Vec::with_capacity(bins.len()),
+        counts: Vec::with_capacity(bins.len()),
+        mean_speed: Vec::with_capacity(bins.len()),
+    };
+    for ((bucket, cell), bin) in bins {
+        let center = grid.center(cell);
+        out.cells.extend([cell.0, cell.1]);
+        out.centers.extend([center.0, center.1]);
+        out.buckets.push(bucket);
+        out.counts.push(bin.count);
+        out.mean_speed.push(if bin.with_speed > 0 { (bin.speed_sum / bin.with_speed as f64) as f32 } else { f32::NAN });
+    }
+    out
+}
+
+#[wasm_bindgen]
+impl FcdData {
+    // Every FCD record binned into space-time cells
+    pub fn bin_space_time(
+        &self,
+        #[wasm_bindgen(unchecked_param_type = "SpaceTimeOptions | undefined")] options: JsValue,
+    ) -> Result<SpaceTimeBins, JsValue> {
+        let options: SpaceTimeOptions = parse_options(options)?;
+        let bins = bin_records(self.records(), &options);
+        console_debug!("Binned FCD into {} space-time cells", bins.length());
+        Ok(bins)
+    }
+}
+
+// Position records from any source as parallel arrays; `speed` may be empty
+#[wasm_bindgen]
+pub fn bin_positions(
+    x: &[f64],
+    y: &[f64],
+    time: &[f64],
+    speed: &[f64],
+    #[wasm_bindgen(unchecked_param_type = "SpaceTimeOptions | undefined")] options: JsValue,
+) -> Result<SpaceTimeBins, JsValue> {
+    if y.len() != x.len() || time.len() != x.len() || !(speed.is_empty() || speed.len() == x.len()) {
+        return Err(JsValue::from_str("x, y, time and speed must have the same length"));
+    }
+    let options: SpaceTimeOptions = parse_options(options)?;
+    let records = (0..x.len()).map(|i| (x[i], y[i], time[i], speed.get(i).copied().unwrap_or(f64::NAN)));
+    Ok(bin_records(records, &options))
+}
@@ -0,0 +1,165 @@
+// Uniform-grid index over polyline segments, shared by every query that has to
+// find geometry near a point or inside a box
+use std::collections::HashMap;
+
+use crate::geometry;
+use crate::point_to_segment_distance_sq;
+
+// Segments spanning more cells than this are kept out of the grid and checked
+// on every query, so stray far-away coordinates cannot exhaust memory
+const MAX_CELLS_PER_SEGMENT: i64 = 1024;
+
+pub(crate) struct Segment {
+    pub a: (f64, f64),
+    pub b: (f64, f64),
+    // Caller-defined id of the polyline the segment belongs to
+    pub owner: usize,
+}
+
+pub(crate) struct SegmentGrid {
+    cell_size: f64,
+    cells: HashMap<(i64, i64), Vec<usize>>,
+    oversized: Vec<usize>,
+    segments: Vec<Segment>,
+}
+
+impl SegmentGrid {
+    pub fn new(cell_size: f64) -> SegmentGrid {
+        SegmentGrid {
+            cell_size,
+            cells: HashMap::new(),
+            oversized: Vec::new(),
+            segments: Vec::new(),
+        }
+    }
+
+    fn cell_of(&self, p: (f64, f64)) -> (i64, i64) {
+        (
+            (p.0 / self.cell_size).floor() as i64,
+            (p.1 / self.cell_size).floor() as i64,
+        )
+    }
+
+    pub fn insert_polyline(&mut self, owner: usize, points: &[(f64, f64)]) {
+        for w in points.windows(2) {
+            let idx = self.segments.len();
+            self.segments.push(Segment { a: w[0], b: w[1], owner });
+            let (c0x, c0y) = self.cell_of((w[0].0.min(w[1].0), w[0].1.min(w[1].1)));
+            let (c1x, c1y) = self.cell_of((w[0].0.max(w[1].0), w[0].1.max(w[1].1)));
+            if cell_span((c0x, c0y), (c1x, c1y)) > MAX_CELLS_PER_SEGMENT {
+                self.oversized.push(idx);
+                continue;
+            }
+            for cx in c0x..=c1x {
+                for cy in c0y..=c1y {
+                    self.cells.entry((cx, cy)).or_default().push(idx);
+                }
+            }
+        }
+    }
+
+    // A single point, stored as a zero-length segment
+    pub fn insert_point(&mut self, owner: usize, p: (f64, f64)) {
+        let idx = self.segments.len();
+        self.segments.push(Segment { a: p, b: p, owner });
+        let cell = self.cell_of(p);
+        self.cells.entry(cell).or_default().push(idx);
+    }
+
+    // Release the spare capacity left by incremental inserts
+    pub fn shrink_to_fit(&mut self) {
+        for cell in self.cells.values_mut() {
+            cell.shrink_to_fit();
+        }
+        self.cells.shrink_to_fit();
+        self.oversized.shrink_to_fit();
+        self.segments.shrink_to_fit();
+    }
+
+    // Segment indices whose cells overlap the box; may contain duplicates.
+    // Boxes covering more cells than are populated scan every segment instead.
+    fn candidates(&self, min: (f64, f64), max: (f64, f64)) -> Box<dyn Iterator<Item = usize> + '_> {
+        if self.scans_all(min, max) {
+            return Box::new(0..self.segments.len());
+        }
+        let (c0x, c0y) = self.cell_of(min);
+        let (c1x, c1y) = self.cell_of(max);
+        Box::new(
+            (c0x..=c1x)
+                .flat_map(move |cx| (c0y..=c1y).map(move |cy| (cx, cy)))
+                .filter_map(move |c| self.cells.get(&c))
+                .flatten()
+                .chain(&self.oversized)
+                .copied(),
+        )
+    }
+
+    fn scans_all(&self, min: (f64, f64), max: (f64, f64)) -> bool {
+        cell_span(self.cell_of(min), self.cell_of(max)) > self.cells.len() as i64
+    }
+
+    // Closest segment within `max_distance` of `p`, as (segment, distance)
+    pub fn nearest(&self, p: (f64, f64), max_distance: f64) -> Option<(&Segment, f64)> {
+        let min = (p.0 - max_distance, p.1 - max_distance);
+        let max = (p.0 + max_distance, p.1 + max_distance);
+        self.candidates(min, max)
+            .map(|i| (i, point_to_segment_distance_sq(p, self.segments[i].a, self.segments[i].b)))
+            .filter(|(_, d)| *d <= max_distance * max_distance)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, d)| (&self.segments[i], d.sqrt()))
+    }
+
+    // The k owners closest to `p`, nearest first, as (owner, distance). The
+    // search box doubles until it holds k owners or covers the whole grid, so
+    // there is no distance cap.
+    pub fn k_nearest(&self, p: (f64, f64), k: usize) -> Vec<(usize, f64)> {
+        if k == 0 || self.segments.is_empty() {
+            return Vec::new();
+        }
+        let mut radius = self.cell_size;
+        loop {
+            let min = (p.0 - radius, p.1 - radius);
+            let max = (p.0 + radius, p.1 + radius);
+            // A radius that overflowed (or a non-finite `p`) can never cover
+            // the grid by cells, so fall back to scanning everything
+            let exhaustive = !radius.is_finite() || self.scans_all(min, max);
+            // Owners farther than the radius may be beaten by ones outside the box
+            let mut best: HashMap<usize, f64> = HashMap::new();
+            for i in self.candidates(min, max) {
+                let s = &self.segments[i];
+                let d = point_to_segment_distance_sq(p, s.a, s.b);
+                if exhaustive || d <= radius * radius {
+                    let entry = best.entry(s.owner).or_insert(d);
+                    *entry = entry.min(d);
+                }
+            }
+            if exhaustive || best.len() >= k {
+                let mut nearest: Vec<(usize, f64)> = best.into_iter().map(|(o, d)| (o, d.sqrt())).collect();
+                nearest.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+                nearest.truncate(k);
+                return nearest;
+            }
+            radius *= 2.0;
+        }
+    }
+
+    // Owners with at least one segment inside the box, sorted and deduplicated
+    pub fn owners_in_box(&self, min: (f64, f64), max: (f64, f64)) -> Vec<usize> {
+        let mut owners: Vec<usize> = self
+            .candidates(min, max)
+            .map(|i| &self.segments[i])
+            .filter(|s| geometry::segment_intersects_box(s.a, s.b, min, max))
+            .map(|s| s.owner)
+            .collect();
+        owners.sort_unstable();
+        owners.dedup();
+        owners
+    }
+}
+
+// Number of grid cells in the box spanned by two cell coordinates
+fn cell_span(c0: (i64, i64), c1: (i64, i64)) -> i64 {
+    c1.0.saturating_sub(c0.0)
+        .saturating_add(1)
+        .saturating_mul(c1.1.saturating_sub(c0.1).saturating_add(1))
+}
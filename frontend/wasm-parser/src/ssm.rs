@@ -0,0 +1,188 @@
+// Conflicts from an SSM device output (surrogate safety measures,
+// --device.ssm.file): the worst time-to-collision, deceleration rate to avoid
+// a crash and post-encroachment time of every encounter, placed where they
+// occurred and graded, for mapping near-miss hotspots.
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::{attr_f64, parse_options, parse_xml, to_js};
+
+// Grading thresholds common in the traffic safety literature: TTC in s, PET
+// in s, DRAC in m/s²
+const HIGH_TTC: f64 = 1.5;
+const HIGH_PET: f64 = 1.0;
+const HIGH_DRAC: f64 = 3.35;
+const MEDIUM_TTC: f64 = 3.0;
+const MEDIUM_PET: f64 = 2.0;
+const MEDIUM_DRAC: f64 = 1.5;
+
+#[derive(Deserialize, Default, Tsify)]
+#[serde(default)]
+pub struct SsmOptions {
+    // Include each encounter's trajectories and measure time series
+    pub trajectories: bool,
+}
+
+#[derive(Serialize, Deserialize, Tsify, Clone, Copy, PartialEq, PartialOrd)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+// The extreme value of one measure over an encounter
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct SsmMeasure {
+    pub time: f64,
+    pub value: f64,
+    // [lat, lng] of the conflict point, when SUMO could determine one
+    pub position: Option<Vec<f64>>,
+    // SUMO encounter type code at that time (following, merging, crossing, ...)
+    #[serde(rename = "encounterType")]
+    pub encounter_type: Option<u32>,
+    // Ego speed, m/s
+    pub speed: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct SsmTrajectory {
+    pub time: Vec<f64>,
+    #[serde(rename = "encounterType")]
+    pub encounter_type: Vec<Option<u32>>,
+    // [lat, lng] per time
+    pub ego: Vec<Option<Vec<f64>>>,
+    pub foe: Vec<Option<Vec<f64>>>,
+    #[serde(rename = "conflictPoint")]
+    pub conflict_point: Vec<Option<Vec<f64>>>,
+    pub ttc: Vec<Option<f64>>,
+    pub drac: Vec<Option<f64>>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct SsmConflict {
+    pub ego: String,
+    pub foe: String,
+    pub begin: f64,
+    pub end: f64,
+    #[serde(rename = "minTtc")]
+    pub min_ttc: Option<SsmMeasure>,
+    #[serde(rename = "maxDrac")]
+    pub max_drac: Option<SsmMeasure>,
+    pub pet: Option<SsmMeasure>,
+    // [lat, lng] to map the conflict at: where its most severe measure was
+    // taken, else the first conflict point of the encounter
+    pub position: Option<Vec<f64>>,
+    pub severity: Severity,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trajectory: Option<SsmTrajectory>,
+}
+
+fn value_f64(s: &str) -> Option<f64> {
+    s.parse().ok().filter(|v: &f64| v.is_finite())
+}
+
+// "x,y" (or "x,y,z") to [lat, lng]; "NA" and malformed values to None
+fn value_point(s: &str) -> Option<Vec<f64>> {
+    let mut parts = s.split(',');
+    let x = value_f64(parts.next()?)?;
+    let y = value_f64(parts.next()?)?;
+    Some(vec![y, x])
+}
+
+// Whitespace separated values of a <... values="..."/> child
+fn span<'a>(conflict: roxmltree::Node<'a, 'a>, name: &str) -> impl Iterator<Item = &'a str> {
+    conflict
+        .children()
+        .find(|n| n.has_tag_name(name))
+        .and_then(|n| n.attribute("values"))
+        .unwrap_or("")
+        .split_whitespace()
+}
+
+fn read_measure(conflict: roxmltree::Node, name: &str) -> Option<SsmMeasure> {
+    let node = conflict.children().find(|n| n.has_tag_name(name))?;
+    Some(SsmMeasure {
+        time: attr_f64(node, "time")?,
+        value: node.attribute("value").and_then(value_f64)?,
+        position: node.attribute("position").and_then(value_point),
+        encounter_type: node.attribute("type").and_then(|t| t.parse().ok()),
+        speed: node.attribute("speed").and_then(value_f64),
+    })
+}
+
+fn read_trajectory(conflict: roxmltree::Node) -> SsmTrajectory {
+    SsmTrajectory {
+        time: span(conflict, "timeSpan").filter_map(value_f64).collect(),
+        encounter_type: span(conflict, "typeSpan").map(|t| t.parse().ok()).collect(),
+        ego: span(conflict, "egoPosition").map(value_point).collect(),
+        foe: span(conflict, "foePosition").map(value_point).collect(),
+        conflict_point: span(conflict, "conflictPoint").map(value_point).collect(),
+        ttc: span(conflict, "TTCSpan").map(value_f64).collect(),
+        drac: span(conflict, "DRACSpan").map(value_f64).collect(),
+    }
+}
+
+fn grade(ttc: Option<f64>, drac: Option<f64>, pet: Option<f64>) -> Severity {
+    let below = |v: Option<f64>, limit| v.is_some_and(|v| v < limit);
+    let above = |v: Option<f64>, limit| v.is_some_and(|v| v >= limit);
+    if below(ttc, HIGH_TTC) || below(pet, HIGH_PET) || above(drac, HIGH_DRAC) {
+        Severity::High
+    } else if below(ttc, MEDIUM_TTC) || below(pet, MEDIUM_PET) || above(drac, MEDIUM_DRAC) {
+        Severity::Medium
+    } else {
+        Severity::Low
+    }
+}
+
+pub(crate) fn read_conflicts(root: roxmltree::Node, trajectories: bool) -> Vec<SsmConflict> {
+    root.children()
+        .filter(|n| n.has_tag_name("conflict"))
+        .filter_map(|c| {
+            let min_ttc = read_measure(c, "minTTC");
+            let max_drac = read_measure(c, "maxDRAC");
+            let pet = read_measure(c, "PET");
+            let severity = grade(
+                min_ttc.as_ref().map(|m| m.value),
+                max_drac.as_ref().map(|m| m.value),
+                pet.as_ref().map(|m| m.value),
+            );
+            // TTC first: it is recorded for most encounter types
+            let position = [&min_ttc, &pet, &max_drac]
+                .into_iter()
+                .flatten()
+                .find_map(|m| m.position.clone())
+                .or_else(|| span(c, "conflictPoint").find_map(value_point));
+            Some(SsmConflict {
+                ego: c.attribute("ego")?.to_string(),
+                foe: c.attribute("foe")?.to_string(),
+                begin: attr_f64(c, "begin")?,
+                end: attr_f64(c, "end")?,
+                min_ttc,
+                max_drac,
+                pet,
+                position,
+                severity,
+                trajectory: trajectories.then(|| read_trajectory(c)),
+            })
+        })
+        .collect()
+}
+
+// Conflicts in file order; positions are in the frame SUMO wrote them in
+// (network coordinates, or lon/lat with device.ssm.geo)
+#[wasm_bindgen(unchecked_return_type = "SsmConflict[]")]
+pub fn parse_ssm_output(
+    xml_text: &str,
+    #[wasm_bindgen(unchecked_param_type = "SsmOptions | undefined")] options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let options: SsmOptions = parse_options(options)?;
+    let doc = parse_xml(xml_text)?;
+    let conflicts = read_conflicts(doc.root_element(), options.trajectories);
+
+    let high = conflicts.iter().filter(|c| c.severity == Severity::High).count();
+    console_log!("Parsed {} SSM conflicts, {} of high severity", conflicts.len(), high);
+
+    to_js(&conflicts)
+}
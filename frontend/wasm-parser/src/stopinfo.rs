@@ -0,0 +1,126 @@
+// Stops as served, from a stop output (--stop-output): one <stopinfo> per
+// vehicle stop, and dwell times, headways and passenger counts per stop.
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::headway::headways;
+use crate::stats::Stats;
+use crate::{attr_f64, parse_xml, to_js};
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct StopRecord {
+    // Vehicle
+    pub id: String,
+    #[serde(rename = "vType")]
+    pub v_type: Option<String>,
+    // busStop or trainStop; None for stops on a plain lane position
+    #[serde(rename = "busStop")]
+    pub bus_stop: Option<String>,
+    #[serde(rename = "parkingArea")]
+    pub parking_area: Option<String>,
+    pub lane: Option<String>,
+    pub pos: Option<f64>,
+    pub started: f64,
+    // None when the stop had not ended by the end of the simulation
+    pub ended: Option<f64>,
+    // Seconds departed after the stop's `until`, when it has one
+    pub delay: Option<f64>,
+    // Seconds arrived after the stop's `arrival`, when it has one
+    #[serde(rename = "arrivalDelay")]
+    pub arrival_delay: Option<f64>,
+    pub line: Option<String>,
+    #[serde(rename = "loadedPersons")]
+    pub loaded_persons: u32,
+    #[serde(rename = "unloadedPersons")]
+    pub unloaded_persons: u32,
+}
+
+impl StopRecord {
+    fn dwell(&self) -> Option<f64> {
+        self.ended.map(|e| e - self.started)
+    }
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct StopDwell {
+    #[serde(rename = "busStop")]
+    pub bus_stop: String,
+    pub visits: usize,
+    // Seconds from arrival to departure
+    pub dwell: Stats,
+    // Seconds between consecutive arrivals, any line
+    pub headway: Stats,
+    pub delay: Stats,
+    pub boarded: u64,
+    pub alighted: u64,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct StopOutput {
+    pub records: Vec<StopRecord>,
+    // Per busStop / trainStop, by id
+    pub stops: Vec<StopDwell>,
+}
+
+pub(crate) fn read_stop_records(root: roxmltree::Node) -> Vec<StopRecord> {
+    let count = |n: roxmltree::Node, name| n.attribute(name).and_then(|v| v.parse().ok()).unwrap_or(0);
+    root.children()
+        .filter(|n| n.tag_name().name() == "stopinfo")
+        .filter_map(|s| {
+            Some(StopRecord {
+                id: s.attribute("id")?.to_string(),
+                v_type: s.attribute("type").map(String::from),
+                bus_stop: s.attribute("busStop").or(s.attribute("trainStop")).map(String::from),
+                parking_area: s.attribute("parkingArea").map(String::from),
+                lane: s.attribute("lane").map(String::from),
+                pos: attr_f64(s, "pos"),
+                started: attr_f64(s, "started")?,
+                ended: attr_f64(s, "ended").filter(|e| *e >= 0.0),
+                delay: attr_f64(s, "delay"),
+                arrival_delay: attr_f64(s, "arrivalDelay"),
+                line: s.attribute("line").map(String::from),
+                loaded_persons: count(s, "loadedPersons"),
+                unloaded_persons: count(s, "unloadedPersons"),
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn summarize_stops(records: &[StopRecord]) -> Vec<StopDwell> {
+    let mut by_stop: BTreeMap<&str, Vec<&StopRecord>> = BTreeMap::new();
+    for r in records {
+        if let Some(stop) = &r.bus_stop {
+            by_stop.entry(stop).or_default().push(r);
+        }
+    }
+    by_stop
+        .into_iter()
+        .map(|(stop, visits)| {
+            let dwell: Vec<f64> = visits.iter().filter_map(|r| r.dwell()).collect();
+            let delay: Vec<f64> = visits.iter().filter_map(|r| r.delay).collect();
+            let mut arrivals: Vec<f64> = visits.iter().map(|r| r.started).collect();
+            StopDwell {
+                bus_stop: stop.to_string(),
+                visits: visits.len(),
+                dwell: Stats::from_values(&dwell),
+                headway: Stats::from_values(&headways(&mut arrivals)),
+                delay: Stats::from_values(&delay),
+                boarded: visits.iter().map(|r| r.loaded_persons as u64).sum(),
+                alighted: visits.iter().map(|r| r.unloaded_persons as u64).sum(),
+            }
+        })
+        .collect()
+}
+
+#[wasm_bindgen(unchecked_return_type = "StopOutput")]
+pub fn parse_stop_output(xml_text: &str) -> Result<JsValue, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let records = read_stop_records(doc.root_element());
+    let stops = summarize_stops(&records);
+
+    console_log!("Parsed {} stop records at {} stops", records.len(), stops.len());
+
+    to_js(&StopOutput { records, stops })
+}
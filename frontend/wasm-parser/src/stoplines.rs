@@ -0,0 +1,102 @@
+// Stop lines and signal heads of traffic-light controlled connections. Like
+// sumo-gui, the stop line across a lane's end is split between the lane's
+// signal links, right turns on the right, and each link's head sits in the
+// middle of its part, to be colored by state[linkIndex] of the running phase.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::geometry;
+use crate::markings::DEFAULT_LANE_WIDTH;
+use crate::net::{ConnectionModel, NetModel};
+use crate::{parse_xml, to_js};
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct SignalHead {
+    // Traffic light (tlLogic) id
+    pub tl: String,
+    #[serde(rename = "linkIndex")]
+    pub link_index: usize,
+    #[serde(rename = "fromLane")]
+    pub from_lane: String,
+    #[serde(rename = "toLane")]
+    pub to_lane: String,
+    // SUMO direction: "s", "r", "l", "t", "R", "L" (partly right / left)
+    pub dir: String,
+    // This link's part of the stop line, [lat, lng] from right to left
+    #[serde(rename = "stopLine")]
+    pub stop_line: Vec<Vec<f64>>,
+    // Head anchor [lat, lng], the middle of the stop line part
+    pub position: Vec<f64>,
+    // Compass bearing of the lane at its end, degrees clockwise from north
+    pub bearing: f64,
+}
+
+// Rightmost movements first
+fn dir_rank(dir: &str) -> u8 {
+    match dir {
+        "r" => 0,
+        "R" => 1,
+        "s" => 2,
+        "L" => 3,
+        "l" => 4,
+        _ => 5,
+    }
+}
+
+pub(crate) fn signal_heads(net: &NetModel) -> Vec<SignalHead> {
+    let mut by_lane: HashMap<String, Vec<&ConnectionModel>> = HashMap::new();
+    for c in net.connections.iter().filter(|c| c.tl.is_some() && c.link_index.is_some()) {
+        by_lane.entry(format!("{}_{}", c.from, c.from_lane)).or_default().push(c);
+    }
+    let mut lanes: Vec<_> = by_lane.into_iter().collect();
+    lanes.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut heads = Vec::new();
+    for (lane_id, mut links) in lanes {
+        let Some(lane) = net.lane(&lane_id) else { continue };
+        let n = lane.shape.len();
+        if n < 2 {
+            continue;
+        }
+        let (a, b) = (lane.shape[n - 2], lane.shape[n - 1]);
+        let Some((dx, dy)) = geometry::unit_direction(a, b) else { continue };
+        let width = lane.width.unwrap_or(DEFAULT_LANE_WIDTH);
+        // Left of the direction of travel
+        let (lx, ly) = (-dy, dx);
+        let right = (b.0 - lx * width / 2.0, b.1 - ly * width / 2.0);
+        let across = |t: f64| (right.0 + lx * width * t, right.1 + ly * width * t);
+
+        links.sort_by_key(|c| (dir_rank(&c.dir), c.to_lane));
+        let k = links.len() as f64;
+        for (i, c) in links.into_iter().enumerate() {
+            let i = i as f64;
+            let (start, end, middle) = (across(i / k), across((i + 1.0) / k), across((i + 0.5) / k));
+            heads.push(SignalHead {
+                tl: c.tl.clone().unwrap_or_default(),
+                link_index: c.link_index.unwrap_or_default(),
+                from_lane: lane_id.clone(),
+                to_lane: format!("{}_{}", c.to, c.to_lane),
+                dir: c.dir.clone(),
+                stop_line: vec![vec![start.1, start.0], vec![end.1, end.0]],
+                position: vec![middle.1, middle.0],
+                bearing: geometry::compass_bearing(dx, dy),
+            });
+        }
+    }
+    heads
+}
+
+// Stop line parts and signal head positions of every link controlled by a
+// traffic light, in network coordinates
+#[wasm_bindgen(unchecked_return_type = "SignalHead[]")]
+pub fn extract_signal_heads(xml_text: &str) -> Result<JsValue, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let net = NetModel::from_root(doc.root_element());
+    let heads = signal_heads(&net);
+
+    console_log!("Placed {} signal heads", heads.len());
+
+    to_js(&heads)
+}
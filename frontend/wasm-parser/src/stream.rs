@@ -0,0 +1,243 @@
+use wasm_bindgen::prelude::*;
+
+use crate::{to_js, NetAccumulator, ParsedNetwork};
+
+// Incremental scanner over an XML byte stream that hands out each complete
+// top-level element (a child of the root) as soon as its closing tag is in
+// the buffer. Only the bytes of the element still open are kept.
+#[derive(Default)]
+pub(crate) struct ElementScanner {
+    buffer: Vec<u8>,
+    // Scan cursor into `buffer`
+    pos: usize,
+    depth: usize,
+    // Start of the currently open top-level element, if any
+    element_start: Option<usize>,
+    root_seen: bool,
+    root_closed: bool,
+}
+
+fn find(haystack: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|i| from + i)
+}
+
+// Index of the `>` closing a tag, ignoring any inside quoted attribute values
+fn find_tag_end(buf: &[u8], from: usize) -> Option<usize> {
+    let mut quote: Option<u8> = None;
+    for (i, &b) in buf.iter().enumerate().skip(from) {
+        match quote {
+            Some(q) if b == q => quote = None,
+            Some(_) => {}
+            None if b == b'"' || b == b'\'' => quote = Some(b),
+            None if b == b'>' => return Some(i),
+            None => {}
+        }
+    }
+    None
+}
+
+// Parses one top-level element on its own
+pub(crate) fn parse_fragment(bytes: &[u8]) -> Result<roxmltree::Document<'_>, String> {
+    let fragment = std::str::from_utf8(bytes).map_err(|e| format!("Invalid UTF-8 in stream: {}", e))?;
+    roxmltree::Document::parse(fragment).map_err(|e| format!("XML parse error: {}", e))
+}
+
+impl ElementScanner {
+    pub fn feed(
+        &mut self,
+        chunk: &[u8],
+        on_element: impl FnMut(&[u8]) -> Result<(), String>,
+    ) -> Result<(), String> {
+        self.buffer.extend_from_slice(chunk);
+        self.drain_complete(on_element)?;
+
+        // Drop everything before the first byte still needed
+        let keep_from = self.element_start.unwrap_or(self.pos);
+        if keep_from > 0 {
+            self.buffer.drain(..keep_from);
+            self.pos -= keep_from;
+            if let Some(start) = self.element_start.as_mut() {
+                *start -= keep_from;
+            }
+        }
+        Ok(())
+    }
+
+    // Checks the stream held one complete document
+    pub fn finish(&mut self, on_element: impl FnMut(&[u8]) -> Result<(), String>) -> Result<(), String> {
+        self.drain_complete(on_element)?;
+        if !self.root_seen {
+            return Err("XML parse error: no root element".to_string());
+        }
+        if !self.root_closed {
+            return Err("XML parse error: stream ended inside an element".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn shrink(&mut self) {
+        self.buffer.shrink_to_fit();
+    }
+
+    fn drain_complete(&mut self, mut on_element: impl FnMut(&[u8]) -> Result<(), String>) -> Result<(), String> {
+        let buf = &self.buffer;
+        while !self.root_closed {
+            let Some(lt) = buf[self.pos..].iter().position(|&b| b == b'<').map(|i| self.pos + i) else {
+                self.pos = buf.len();
+                break;
+            };
+            let rest = &buf[lt..];
+
+            // Markup that never opens or closes an element: skip once complete
+            let skip_to = if rest.starts_with(b"<!--") {
+                Some(find(buf, lt + 4, b"-->").map(|i| i + 3))
+            } else if rest.starts_with(b"<?") {
+                Some(find(buf, lt + 2, b"?>").map(|i| i + 2))
+            } else if rest.starts_with(b"<![CDATA[") {
+                Some(find(buf, lt + 9, b"]]>").map(|i| i + 3))
+            } else if rest.starts_with(b"<!") {
+                Some(find_tag_end(buf, lt + 2).map(|i| i + 1))
+            } else {
+                None
+            };
+            if let Some(end) = skip_to {
+                match end {
+                    Some(end) => {
+                        self.pos = end;
+                        continue;
+                    }
+                    None => {
+                        self.pos = lt;
+                        break;
+                    }
+                }
+            }
+
+            let Some(gt) = find_tag_end(buf, lt + 1) else {
+                self.pos = lt;
+                break;
+            };
+            let closing = buf.get(lt + 1) == Some(&b'/');
+            let self_closing = !closing && buf[gt - 1] == b'/';
+
+            if closing {
+                if self.depth == 0 {
+                    return Err("XML parse error: unexpected closing tag".to_string());
+                }
+                self.depth -= 1;
+                if self.depth == 1 {
+                    if let Some(start) = self.element_start.take() {
+                        on_element(&buf[start..=gt])?;
+                    }
+                } else if self.depth == 0 {
+                    self.root_closed = true;
+                }
+            } else if self.depth == 0 {
+                self.root_seen = true;
+                if self_closing {
+                    self.root_closed = true;
+                } else {
+                    self.depth = 1;
+                }
+            } else if self.depth == 1 && self_closing {
+                on_element(&buf[lt..=gt])?;
+            } else if !self_closing {
+                if self.depth == 1 {
+                    self.element_start = Some(lt);
+                }
+                self.depth += 1;
+            }
+
+            self.pos = gt + 1;
+        }
+        Ok(())
+    }
+}
+
+// Push-style net.xml parser. Bytes are fed as they arrive from a fetch stream;
+// each top-level element (<edge>, <junction>, ...) is parsed as soon as its
+// closing tag is in the buffer, so parsing overlaps the download.
+#[wasm_bindgen]
+pub struct NetParser {
+    scanner: ElementScanner,
+    elements: usize,
+    acc: NetAccumulator,
+}
+
+fn process_element(bytes: &[u8], acc: &mut NetAccumulator) -> Result<(), String> {
+    let doc = parse_fragment(bytes)?;
+    for node in doc.root_element().descendants() {
+        acc.add_element(node);
+    }
+    Ok(())
+}
+
+#[wasm_bindgen]
+impl NetParser {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> NetParser {
+        NetParser {
+            scanner: ElementScanner::default(),
+            elements: 0,
+            acc: NetAccumulator::new(),
+        }
+    }
+
+    // Number of top-level elements parsed so far
+    #[wasm_bindgen(getter)]
+    pub fn elements(&self) -> usize {
+        self.elements
+    }
+
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<(), JsValue> {
+        self.feed_bytes(chunk).map_err(|e| JsValue::from_str(&e))
+    }
+
+    // Release the read buffer's spare capacity, which grows to the largest
+    // chunk fed; useful between downloads when a parser is kept alive
+    pub fn shrink(&mut self) {
+        self.scanner.shrink();
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "ParsedNetwork")]
+    pub fn finish(self) -> Result<JsValue, JsValue> {
+        let parsed = self.finish_parsed().map_err(|e| JsValue::from_str(&e))?;
+        to_js(&parsed)
+    }
+}
+
+// Error handling stays in plain strings below the wasm boundary, so the
+// parser also runs natively (fuzz targets)
+impl NetParser {
+    pub(crate) fn feed_bytes(&mut self, chunk: &[u8]) -> Result<(), String> {
+        let (acc, elements) = (&mut self.acc, &mut self.elements);
+        self.scanner.feed(chunk, |bytes| {
+            process_element(bytes, acc)?;
+            *elements += 1;
+            Ok(())
+        })
+    }
+
+    pub(crate) fn finish_parsed(mut self) -> Result<ParsedNetwork, String> {
+        let (acc, elements) = (&mut self.acc, &mut self.elements);
+        self.scanner.finish(|bytes| {
+            process_element(bytes, acc)?;
+            *elements += 1;
+            Ok(())
+        })?;
+
+        console_log!("Streamed {} top-level elements", self.elements);
+
+        Ok(self.acc.finish())
+    }
+}
+
+impl Default for NetParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,114 @@
+// Streets as continuous polylines: runs of edges that only continue into each
+// other (one way in, one way out, same name) merged into one feature, so a
+// street layer has far fewer features and a label can follow a whole street.
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::net::{EdgeModel, NetModel};
+use crate::{parse_xml, to_js};
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct Street {
+    // Id of the first edge
+    pub id: String,
+    pub name: Option<String>,
+    // Edge ids in driving order
+    pub edges: Vec<String>,
+    // [lat, lng] in network coordinates, junctions bridged by straight lines
+    pub points: Vec<Vec<f64>>,
+    // Meters, the sum of the edge lengths
+    pub length: f64,
+}
+
+// The edge's own geometry when stored, else its middle lane's
+fn centerline(edge: &EdgeModel) -> &[(f64, f64)] {
+    if edge.shape.len() >= 2 {
+        return &edge.shape;
+    }
+    edge.lanes.get(edge.lanes.len() / 2).map_or(&[], |l| &l.shape)
+}
+
+fn edge_length(edge: &EdgeModel) -> f64 {
+    edge.lane(0).and_then(|l| l.length).unwrap_or_else(|| edge.length())
+}
+
+pub(crate) fn merge_edges(net: &NetModel) -> Vec<Street> {
+    // Distinct successors and predecessors among normal edges; turnarounds
+    // don't make a junction a fork
+    let mut outgoing: HashMap<&str, HashSet<&str>> = HashMap::new();
+    let mut incoming: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for c in &net.connections {
+        if c.from.starts_with(':') || c.to.starts_with(':') || c.dir == "t" {
+            continue;
+        }
+        outgoing.entry(c.from.as_str()).or_default().insert(c.to.as_str());
+        incoming.entry(c.to.as_str()).or_default().insert(c.from.as_str());
+    }
+
+    let edges: Vec<&EdgeModel> = net.edges.iter().filter(|e| e.is_normal()).collect();
+    let mut next: HashMap<&str, &str> = HashMap::new();
+    for edge in &edges {
+        let Some(to) = outgoing.get(edge.id.as_str()).filter(|s| s.len() == 1).and_then(|s| s.iter().next()) else {
+            continue;
+        };
+        let Some(successor) = net.edge(to) else { continue };
+        let single_in = incoming.get(to).is_some_and(|s| s.len() == 1);
+        // Dead ends whose only way on is the opposite direction
+        let reverse = successor.from == edge.to && successor.to == edge.from;
+        if single_in && !reverse && successor.id != edge.id && successor.name == edge.name {
+            next.insert(edge.id.as_str(), successor.id.as_str());
+        }
+    }
+
+    // Chains start at edges nothing merges into; closed loops at their
+    // smallest id
+    let merged_into: HashSet<&str> = next.values().copied().collect();
+    let mut order: Vec<&str> = edges.iter().map(|e| e.id.as_str()).collect();
+    order.sort_unstable();
+    let starts = order.iter().filter(|id| !merged_into.contains(*id)).chain(&order);
+
+    let mut visited: HashSet<&str> = HashSet::new();
+    let mut streets = Vec::new();
+    for &start in starts {
+        if visited.contains(start) {
+            continue;
+        }
+        let mut street = Street {
+            id: start.to_string(),
+            name: net.edge(start).and_then(|e| e.name.clone()),
+            edges: Vec::new(),
+            points: Vec::new(),
+            length: 0.0,
+        };
+        let mut current = Some(start);
+        while let Some(id) = current.filter(|id| visited.insert(*id)) {
+            let Some(edge) = net.edge(id) else { break };
+            for &(x, y) in centerline(edge) {
+                if street.points.last().is_none_or(|p| p[0] != y || p[1] != x) {
+                    street.points.push(vec![y, x]);
+                }
+            }
+            street.edges.push(id.to_string());
+            street.length += edge_length(edge);
+            current = next.get(id).copied();
+        }
+        streets.push(street);
+    }
+    streets
+}
+
+// Normal edges merged into streets wherever an edge has a single successor
+// that has no other predecessor and carries the same name
+#[wasm_bindgen(unchecked_return_type = "Street[]")]
+pub fn merge_streets(xml_text: &str) -> Result<JsValue, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let net = NetModel::from_root(doc.root_element());
+    let streets = merge_edges(&net);
+
+    let edge_count: usize = streets.iter().map(|s| s.edges.len()).sum();
+    console_log!("Merged {} edges into {} streets", edge_count, streets.len());
+
+    to_js(&streets)
+}
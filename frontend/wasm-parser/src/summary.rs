@@ -0,0 +1,136 @@
+use wasm_bindgen::prelude::*;
+
+use crate::{attr_f64, parse_xml};
+
+// Per-step series from --summary-output, one parallel array per attribute
+// so the KPI charts can consume them without reshaping.
+#[wasm_bindgen]
+pub struct SummarySeries {
+    time: Vec<f64>,
+    loaded: Vec<u32>,
+    inserted: Vec<u32>,
+    running: Vec<u32>,
+    waiting: Vec<u32>,
+    ended: Vec<u32>,
+    halting: Vec<u32>,
+    teleports: Vec<u32>,
+    collisions: Vec<u32>,
+    mean_waiting_time: Vec<f64>,
+    mean_travel_time: Vec<f64>,
+    mean_speed: Vec<f64>,
+}
+
+#[wasm_bindgen]
+impl SummarySeries {
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.time.len()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn time(&self) -> Vec<f64> {
+        self.time.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn loaded(&self) -> Vec<u32> {
+        self.loaded.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn inserted(&self) -> Vec<u32> {
+        self.inserted.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn running(&self) -> Vec<u32> {
+        self.running.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn waiting(&self) -> Vec<u32> {
+        self.waiting.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn ended(&self) -> Vec<u32> {
+        self.ended.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn halting(&self) -> Vec<u32> {
+        self.halting.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn teleports(&self) -> Vec<u32> {
+        self.teleports.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn collisions(&self) -> Vec<u32> {
+        self.collisions.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name = meanWaitingTime)]
+    pub fn mean_waiting_time(&self) -> Vec<f64> {
+        self.mean_waiting_time.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name = meanTravelTime)]
+    pub fn mean_travel_time(&self) -> Vec<f64> {
+        self.mean_travel_time.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name = meanSpeed)]
+    pub fn mean_speed(&self) -> Vec<f64> {
+        self.mean_speed.clone()
+    }
+}
+
+pub(crate) fn read_summary(root: roxmltree::Node) -> SummarySeries {
+    let steps: Vec<_> = root
+        .children()
+        .filter(|n| n.tag_name().name() == "step")
+        .filter(|n| attr_f64(*n, "time").is_some())
+        .collect();
+
+    let count = |name: &str| -> Vec<u32> {
+        steps
+            .iter()
+            .map(|s| s.attribute(name).and_then(|v| v.parse::<u32>().ok()).unwrap_or(0))
+            .collect()
+    };
+    // SUMO writes -1 for means that are undefined at this step
+    let mean = |name: &str| -> Vec<f64> {
+        steps
+            .iter()
+            .map(|s| attr_f64(*s, name).filter(|v| *v >= 0.0).unwrap_or(f64::NAN))
+            .collect()
+    };
+
+    SummarySeries {
+        time: steps.iter().filter_map(|s| attr_f64(*s, "time")).collect(),
+        loaded: count("loaded"),
+        inserted: count("inserted"),
+        running: count("running"),
+        waiting: count("waiting"),
+        ended: count("ended"),
+        halting: count("halting"),
+        teleports: count("teleports"),
+        collisions: count("collisions"),
+        mean_waiting_time: mean("meanWaitingTime"),
+        mean_travel_time: mean("meanTravelTime"),
+        mean_speed: mean("meanSpeed"),
+    }
+}
+
+#[wasm_bindgen]
+pub fn parse_summary_output(xml_text: &str) -> Result<SummarySeries, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let series = read_summary(doc.root_element());
+
+    console_log!("Parsed {} summary steps", series.time.len());
+
+    Ok(series)
+}
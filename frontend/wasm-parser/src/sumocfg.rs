@@ -0,0 +1,286 @@
+// SUMO configuration files (.sumocfg) and loading a whole project from one:
+// the JS side hands over file contents by name, the loader resolves the
+// configuration's references against them and returns network, routes and
+// additionals linked in one object.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::heatmap::lane_edge;
+use crate::net::NetModel;
+use crate::persons::{self, PersonPlan};
+use crate::routes::{self, PlannedVehicle};
+use crate::{attr_f64, parse_xml, to_js, NetAccumulator, ParsedNetwork};
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct OutputSetting {
+    // Option name, e.g. "tripinfo-output"
+    pub option: String,
+    pub file: String,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct SumoConfig {
+    #[serde(rename = "netFile")]
+    pub net_file: Option<String>,
+    #[serde(rename = "routeFiles")]
+    pub route_files: Vec<String>,
+    #[serde(rename = "additionalFiles")]
+    pub additional_files: Vec<String>,
+    // Seconds
+    pub begin: Option<f64>,
+    pub end: Option<f64>,
+    #[serde(rename = "stepLength")]
+    pub step_length: Option<f64>,
+    // Every *-output option that names a file
+    pub outputs: Vec<OutputSetting>,
+}
+
+// Options may sit in any section (<input>, <output>, ...) or at top level
+fn option_node<'a, 'input>(root: roxmltree::Node<'a, 'input>, name: &str) -> Option<roxmltree::Node<'a, 'input>> {
+    root.descendants().find(|n| n.tag_name().name() == name)
+}
+
+fn option<'a>(root: roxmltree::Node<'a, '_>, name: &str) -> Option<&'a str> {
+    option_node(root, name).and_then(|n| n.attribute("value")).filter(|v| !v.trim().is_empty())
+}
+
+// File lists are comma separated; SUMO also accepts spaces
+fn file_list(value: Option<&str>) -> Vec<String> {
+    value
+        .unwrap_or("")
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+pub(crate) fn read_config(root: roxmltree::Node) -> SumoConfig {
+    let number = |name| option_node(root, name).and_then(|n| attr_f64(n, "value"));
+    SumoConfig {
+        net_file: option(root, "net-file").map(|s| s.trim().to_string()),
+        route_files: file_list(option(root, "route-files")),
+        additional_files: file_list(option(root, "additional-files")),
+        begin: number("begin"),
+        end: number("end"),
+        step_length: number("step-length"),
+        outputs: root
+            .descendants()
+            .filter(|n| n.tag_name().name().ends_with("-output"))
+            .filter_map(|n| {
+                Some(OutputSetting {
+                    option: n.tag_name().name().to_string(),
+                    file: n.attribute("value").filter(|v| !v.trim().is_empty())?.trim().to_string(),
+                })
+            })
+            .collect(),
+    }
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct RouteFile {
+    pub name: String,
+    pub vehicles: Vec<PlannedVehicle>,
+    pub persons: Vec<PersonPlan>,
+}
+
+// A top-level element of an additional file, tied to the network where it
+// names a lane or edge
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct AdditionalElement {
+    // Element name: "busStop", "inductionLoop", "taz", ...
+    pub kind: String,
+    pub id: String,
+    pub lane: Option<String>,
+    pub edge: Option<String>,
+    // The lane / edge exists in the network
+    pub linked: bool,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct AdditionalFile {
+    pub name: String,
+    pub elements: Vec<AdditionalElement>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct LinkedProject {
+    pub config: SumoConfig,
+    pub network: Option<ParsedNetwork>,
+    pub routes: Vec<RouteFile>,
+    pub additionals: Vec<AdditionalFile>,
+    // Referenced files the loader was not given
+    pub missing: Vec<String>,
+}
+
+fn read_additional_elements(root: roxmltree::Node, net: Option<&NetModel>) -> Vec<AdditionalElement> {
+    root.children()
+        .filter(|n| n.is_element())
+        .filter_map(|n| {
+            let lane = n.attribute("lane").map(String::from);
+            let edge = n.attribute("edge").map(String::from).or_else(|| lane.as_deref().map(|l| lane_edge(l).to_string()));
+            let linked = match (net, &lane, &edge) {
+                (Some(net), Some(lane), _) => net.lane(lane).is_some(),
+                (Some(net), None, Some(edge)) => net.edge(edge).is_some(),
+                _ => false,
+            };
+            Some(AdditionalElement {
+                kind: n.tag_name().name().to_string(),
+                id: n.attribute("id")?.to_string(),
+                lane,
+                edge,
+                linked,
+            })
+        })
+        .collect()
+}
+
+// "../net/city.net.xml" and "city.net.xml" name the same upload
+fn base_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+// File contents supplied by the JS side, looked up by the names a
+// configuration uses
+#[wasm_bindgen]
+pub struct ProjectLoader {
+    files: HashMap<String, String>,
+}
+
+#[wasm_bindgen]
+impl ProjectLoader {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> ProjectLoader {
+        ProjectLoader { files: HashMap::new() }
+    }
+
+    // Replaces an earlier file of the same name
+    pub fn add_file(&mut self, name: &str, text: String) {
+        self.files.insert(name.to_string(), text);
+    }
+
+    // Referenced input files not added yet, so the caller can fetch them
+    // before `load`
+    pub fn missing(&self, config_xml: &str) -> Result<Vec<String>, JsValue> {
+        let doc = parse_xml(config_xml)?;
+        let config = read_config(doc.root_element());
+        Ok(Self::inputs(&config).filter(|f| self.get(f).is_none()).map(String::from).collect())
+    }
+
+    // Network, route files and additional files of a configuration, parsed
+    // and cross-referenced: route references resolve across route files,
+    // person stages and additional elements against the network and stops
+    #[wasm_bindgen(unchecked_return_type = "LinkedProject")]
+    pub fn load(&self, config_xml: &str) -> Result<JsValue, JsValue> {
+        let doc = parse_xml(config_xml)?;
+        let config = read_config(doc.root_element());
+        let missing: Vec<String> = Self::inputs(&config).filter(|f| self.get(f).is_none()).map(String::from).collect();
+
+        let net_doc = config.net_file.as_deref().and_then(|f| self.get(f)).map(parse_xml).transpose()?;
+        let network = net_doc.as_ref().map(|d| {
+            let mut acc = NetAccumulator::new();
+            acc.add_document(d.root_element());
+            acc.finish()
+        });
+        let net = net_doc.as_ref().map(|d| NetModel::from_root(d.root_element()));
+
+        let parse_all = |names: &[String]| -> Result<Vec<(String, roxmltree::Document)>, JsValue> {
+            names
+                .iter()
+                .filter_map(|name| Some((name.clone(), self.get(name)?)))
+                .map(|(name, text)| Ok((name, parse_xml(text)?)))
+                .collect()
+        };
+        let route_docs = parse_all(&config.route_files)?;
+        let additional_docs = parse_all(&config.additional_files)?;
+
+        // Stops may be defined in either kind of file
+        let mut stops = HashMap::new();
+        for (_, d) in route_docs.iter().chain(&additional_docs) {
+            persons::read_stop_edges(d.root_element(), &mut stops);
+        }
+
+        let roots: Vec<roxmltree::Node> = route_docs.iter().map(|(_, d)| d.root_element()).collect();
+        let mut vehicles = routes::read_planned_vehicles(&roots);
+        let routes = route_docs
+            .iter()
+            .map(|(name, d)| {
+                let root = d.root_element();
+                let own = root.children().filter(|n| matches!(n.tag_name().name(), "vehicle" | "trip" | "flow")).count();
+                RouteFile {
+                    name: name.clone(),
+                    vehicles: vehicles.drain(..own).collect(),
+                    persons: persons::read_person_plans(root, &stops, net.as_ref()),
+                }
+            })
+            .collect();
+        let additionals = additional_docs
+            .iter()
+            .map(|(name, d)| AdditionalFile {
+                name: name.clone(),
+                elements: read_additional_elements(d.root_element(), net.as_ref()),
+            })
+            .collect();
+
+        console_log!(
+            "Loaded project: network {}, {} route and {} additional files, {} missing",
+            if network.is_some() { "found" } else { "missing" },
+            config.route_files.len(),
+            config.additional_files.len(),
+            missing.len()
+        );
+
+        to_js(&LinkedProject {
+            config,
+            network,
+            routes,
+            additionals,
+            missing,
+        })
+    }
+}
+
+impl ProjectLoader {
+    fn inputs(config: &SumoConfig) -> impl Iterator<Item = &str> {
+        config
+            .net_file
+            .iter()
+            .chain(&config.route_files)
+            .chain(&config.additional_files)
+            .map(String::as_str)
+    }
+
+    // Exact name first, then by file name alone
+    fn get(&self, path: &str) -> Option<&str> {
+        self.files
+            .get(path)
+            .or_else(|| {
+                let name = base_name(path);
+                self.files.iter().find(|(k, _)| base_name(k) == name).map(|(_, v)| v)
+            })
+            .map(String::as_str)
+    }
+}
+
+impl Default for ProjectLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Inputs, time span and outputs named by a .sumocfg
+#[wasm_bindgen(unchecked_return_type = "SumoConfig")]
+pub fn parse_sumocfg(xml_text: &str) -> Result<JsValue, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let config = read_config(doc.root_element());
+
+    console_log!(
+        "Parsed sumocfg: {} route files, {} additional files, {} outputs",
+        config.route_files.len(),
+        config.additional_files.len(),
+        config.outputs.len()
+    );
+
+    to_js(&config)
+}
@@ -0,0 +1,148 @@
+// Standalone SVG of the rendered network for reports and print: junction
+// polygons filled, lanes stroked in the path_buffers palette. Coordinates stay
+// in network units (y flipped), so the viewBox is the drawn area and the
+// pixel size only sets the default scale.
+use serde::Deserialize;
+use std::fmt::Write;
+use tsify::Tsify;
+
+use crate::deckgl::{self, is_path};
+use crate::markings::DEFAULT_LANE_WIDTH;
+use crate::plain::escape;
+use crate::{Bounds, Junction, Lane};
+
+const DEFAULT_PIXEL_WIDTH: f64 = 1000.0;
+const DEFAULT_JUNCTION_FILL: &str = "#d0d0d0";
+
+#[derive(Deserialize, Default, Tsify)]
+#[serde(default)]
+pub struct SvgOptions {
+    // "speed" (default), "id" or "type", as for path_buffers
+    #[serde(rename = "colorBy")]
+    pub color_by: Option<String>,
+    // Stroke widths in meters (default 3.2, and half that for internal lanes)
+    #[serde(rename = "laneWidth")]
+    pub lane_width: Option<f64>,
+    #[serde(rename = "internalLaneWidth")]
+    pub internal_lane_width: Option<f64>,
+    // Any CSS color (default light grey); "none" leaves junctions out
+    #[serde(rename = "junctionFill")]
+    pub junction_fill: Option<String>,
+    // CSS color of a full-size background rectangle; transparent when absent
+    pub background: Option<String>,
+    // Area drawn, x = lng and y = lat in the render frame (default the
+    // network bounds)
+    #[serde(rename = "viewBox")]
+    pub view_box: Option<Bounds>,
+    // Image width in pixels (default 1000); the height keeps the aspect ratio
+    pub width: Option<f64>,
+}
+
+// Render frame [lat, lng] to SVG user space: origin at the top left of the
+// view box, y down
+struct Frame {
+    min_x: f64,
+    max_y: f64,
+    digits: usize,
+}
+
+impl Frame {
+    fn point(&self, out: &mut String, p: &[f64]) {
+        let _ = write!(out, "{:.*},{:.*}", self.digits, p[1] - self.min_x, self.digits, self.max_y - p[0]);
+    }
+}
+
+pub(crate) fn overlaps(points: &[Vec<f64>], b: &Bounds) -> bool {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+    for p in points {
+        (min_x, max_x) = (min_x.min(p[1]), max_x.max(p[1]));
+        (min_y, max_y) = (min_y.min(p[0]), max_y.max(p[0]));
+    }
+    min_x <= b.max_x && max_x >= b.min_x && min_y <= b.max_y && max_y >= b.min_y
+}
+
+fn css([r, g, b, _]: [u8; 4]) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+// `scale`: meters per network unit, for the stroke widths. `bounds` is the
+// fallback view box.
+pub(crate) fn write_svg(
+    lanes: &[Lane],
+    junctions: &[Junction],
+    bounds: &Bounds,
+    scale: f64,
+    options: &SvgOptions,
+) -> Result<String, String> {
+    let color_of = deckgl::lane_color(options.color_by.as_deref().unwrap_or("speed"))?;
+    let view = options.view_box.as_ref().unwrap_or(bounds);
+    let (w, h) = (view.max_x - view.min_x, view.max_y - view.min_y);
+    if !(w > 0.0 && h > 0.0) {
+        return Err("SVG view box is empty".to_string());
+    }
+    let frame = Frame {
+        min_x: view.min_x,
+        max_y: view.max_y,
+        // Centimeters, or about one centimeter in degrees
+        digits: if scale > 1.0 { 7 } else { 2 },
+    };
+    let pixel_width = options.width.filter(|w| *w > 0.0).unwrap_or(DEFAULT_PIXEL_WIDTH);
+    let lane_width = options.lane_width.unwrap_or(DEFAULT_LANE_WIDTH);
+    let internal_width = options.internal_lane_width.unwrap_or(lane_width / 2.0);
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" viewBox=\"0 0 {:.*} {:.*}\">",
+        pixel_width,
+        pixel_width * h / w,
+        frame.digits,
+        w,
+        frame.digits,
+        h
+    );
+    if let Some(background) = &options.background {
+        let _ = writeln!(out, "<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>", escape(background));
+    }
+
+    let fill = options.junction_fill.as_deref().unwrap_or(DEFAULT_JUNCTION_FILL);
+    if fill != "none" {
+        let _ = writeln!(out, "<g fill=\"{}\" stroke=\"none\">", escape(fill));
+        for junction in junctions.iter().filter(|j| j.polygon.len() >= 3 && overlaps(&j.polygon, view)) {
+            out.push_str("<polygon points=\"");
+            for (i, p) in junction.polygon.iter().enumerate() {
+                if i > 0 {
+                    out.push(' ');
+                }
+                frame.point(&mut out, p);
+            }
+            out.push_str("\"/>\n");
+        }
+        out.push_str("</g>\n");
+    }
+
+    // Internal lanes below the regular ones
+    for (internal, width) in [(true, internal_width), (false, lane_width)] {
+        let _ = writeln!(
+            out,
+            "<g fill=\"none\" stroke-width=\"{:.*}\" stroke-linecap=\"round\" stroke-linejoin=\"round\">",
+            frame.digits,
+            width / scale
+        );
+        for lane in lanes.iter().filter(|l| l.is_internal == internal && is_path(l) && overlaps(&l.points, view)) {
+            let _ = write!(out, "<path stroke=\"{}\" d=\"M", css(color_of(lane)));
+            for (i, p) in lane.points.iter().enumerate() {
+                out.push_str(match i {
+                    0 => "",
+                    1 => " L",
+                    _ => " ",
+                });
+                frame.point(&mut out, p);
+            }
+            out.push_str("\"/>\n");
+        }
+        out.push_str("</g>\n");
+    }
+    out.push_str("</svg>\n");
+    Ok(out)
+}
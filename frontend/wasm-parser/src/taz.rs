@@ -0,0 +1,183 @@
+// Traffic assignment zones (<taz>): edge membership from the `edges`
+// attribute and <tazSource>/<tazSink> children, plus the optional shape and
+// center. Shared by the OD, parking and zone outputs.
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::geometry;
+use crate::net::NetModel;
+use crate::{parse_point_string, parse_xml, to_js};
+
+pub(crate) struct TazModel {
+    pub id: String,
+    pub edges: Vec<String>,
+    pub shape: Vec<(f64, f64)>,
+    pub center: Option<(f64, f64)>,
+    pub color: Option<String>,
+}
+
+pub(crate) fn read_tazs(root: roxmltree::Node) -> Vec<TazModel> {
+    root.descendants()
+        .filter(|n| n.tag_name().name() == "taz")
+        .filter_map(|t| {
+            let sources = t
+                .children()
+                .filter(|n| matches!(n.tag_name().name(), "tazSource" | "tazSink"))
+                .filter_map(|n| n.attribute("id"));
+            let mut edges: Vec<String> =
+                t.attribute("edges").unwrap_or("").split_whitespace().chain(sources).map(String::from).collect();
+            // An edge is usually both a source and a sink
+            let mut seen = HashSet::new();
+            edges.retain(|e| seen.insert(e.clone()));
+            Some(TazModel {
+                id: t.attribute("id")?.to_string(),
+                edges,
+                shape: t.attribute("shape").map(parse_point_string).unwrap_or_default(),
+                center: t.attribute("center").map(parse_point_string).and_then(|p| p.first().copied()),
+                color: t.attribute("color").map(String::from),
+            })
+        })
+        .collect()
+}
+
+// Area-weighted centroid, the vertex mean for degenerate rings
+fn polygon_centroid(ring: &[(f64, f64)]) -> Option<(f64, f64)> {
+    let (mut area, mut cx, mut cy) = (0.0, 0.0, 0.0);
+    for (a, b) in ring.iter().zip(ring.iter().cycle().skip(1)) {
+        let cross = a.0 * b.1 - b.0 * a.1;
+        area += cross;
+        cx += (a.0 + b.0) * cross;
+        cy += (a.1 + b.1) * cross;
+    }
+    if area.abs() > f64::EPSILON {
+        return Some((cx / (3.0 * area), cy / (3.0 * area)));
+    }
+    mean_point(ring)
+}
+
+fn mean_point(points: &[(f64, f64)]) -> Option<(f64, f64)> {
+    if points.is_empty() {
+        return None;
+    }
+    let n = points.len() as f64;
+    Some((points.iter().map(|p| p.0).sum::<f64>() / n, points.iter().map(|p| p.1).sum::<f64>() / n))
+}
+
+fn edge_midpoint(net: &NetModel, id: &str) -> Option<(f64, f64)> {
+    let edge = net.edge(id)?;
+    let lane = edge.lane(0).or_else(|| edge.lanes.first())?;
+    geometry::point_at(&lane.shape, edge.length() / 2.0)
+}
+
+impl TazModel {
+    // From `center`, else the shape, else the midpoints of the member edges
+    // (needs the network)
+    pub fn centroid(&self, net: Option<&NetModel>) -> Option<(f64, f64)> {
+        if let Some(center) = self.center {
+            return Some(center);
+        }
+        if let Some(c) = polygon_centroid(&self.shape) {
+            return Some(c);
+        }
+        let net = net?;
+        let midpoints: Vec<(f64, f64)> = self.edges.iter().filter_map(|e| edge_midpoint(net, e)).collect();
+        mean_point(&midpoints)
+    }
+
+    // The shape, else the convex hull of the member edges' lanes (needs the
+    // network)
+    pub fn polygon(&self, net: Option<&NetModel>) -> Vec<(f64, f64)> {
+        if !self.shape.is_empty() {
+            return self.shape.clone();
+        }
+        let Some(net) = net else { return Vec::new() };
+        let points: Vec<(f64, f64)> = self
+            .edges
+            .iter()
+            .filter_map(|e| net.edge(e))
+            .flat_map(|e| e.lanes.iter().flat_map(|l| l.shape.iter().copied()))
+            .collect();
+        geometry::convex_hull(&points)
+    }
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct TazZone {
+    pub id: String,
+    pub color: Option<String>,
+    // Member edges, as listed
+    pub edges: Vec<String>,
+    // [lat, lng] ring: the TAZ shape, else the hull of its edges
+    pub polygon: Vec<Vec<f64>>,
+    // [lat, lng]
+    pub center: Option<Vec<f64>>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct EdgeZone {
+    pub edge: String,
+    pub taz: String,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct TazZones {
+    pub zones: Vec<TazZone>,
+    // Zone of every network edge that has one: the first TAZ listing it,
+    // else the first TAZ shape containing its midpoint
+    #[serde(rename = "edgeZones")]
+    pub edge_zones: Vec<EdgeZone>,
+}
+
+pub(crate) fn assign_zones(tazs: &[TazModel], net: &NetModel) -> Vec<EdgeZone> {
+    let mut listed: HashMap<&str, &str> = HashMap::new();
+    for taz in tazs {
+        for edge in &taz.edges {
+            listed.entry(edge.as_str()).or_insert(taz.id.as_str());
+        }
+    }
+    net.edges
+        .iter()
+        .filter(|e| e.is_normal())
+        .filter_map(|e| {
+            let taz = listed.get(e.id.as_str()).copied().or_else(|| {
+                let mid = edge_midpoint(net, &e.id)?;
+                tazs.iter()
+                    .find(|t| t.shape.len() >= 3 && geometry::point_in_polygon(mid, &t.shape))
+                    .map(|t| t.id.as_str())
+            })?;
+            Some(EdgeZone {
+                edge: e.id.clone(),
+                taz: taz.to_string(),
+            })
+        })
+        .collect()
+}
+
+// Zone polygons for choropleths and, with the network, the TAZ of each edge
+// so edge-level results can be aggregated per zone
+#[wasm_bindgen(unchecked_return_type = "TazZones")]
+pub fn parse_taz_zones(taz_xml: &str, net_xml: Option<String>) -> Result<JsValue, JsValue> {
+    let doc = parse_xml(taz_xml)?;
+    let tazs = read_tazs(doc.root_element());
+    let net_doc = net_xml.as_deref().map(parse_xml).transpose()?;
+    let net = net_doc.as_ref().map(|d| NetModel::from_root(d.root_element()));
+
+    let ring = |points: Vec<(f64, f64)>| points.into_iter().map(|(x, y)| vec![y, x]).collect();
+    let zones: Vec<TazZone> = tazs
+        .iter()
+        .map(|t| TazZone {
+            id: t.id.clone(),
+            color: t.color.clone(),
+            edges: t.edges.clone(),
+            polygon: ring(t.polygon(net.as_ref())),
+            center: t.centroid(net.as_ref()).map(|(x, y)| vec![y, x]),
+        })
+        .collect();
+    let edge_zones = net.as_ref().map(|n| assign_zones(&tazs, n)).unwrap_or_default();
+
+    console_log!("Parsed {} TAZ, {} edges assigned", zones.len(), edge_zones.len());
+
+    to_js(&TazZones { zones, edge_zones })
+}
@@ -0,0 +1,74 @@
+// Rayon thread pool backed by Web Workers, for the `parallel` feature. This
+// follows wasm-bindgen-rayon: each worker instantiates this module on the
+// shared memory and runs one rayon thread, handed over through a channel in
+// that memory. Call `initThreadPool(n)` once before parsing; until then (or
+// on hosts that are not cross-origin isolated) rayon runs everything on the
+// calling thread.
+use crossbeam_channel::{bounded, Receiver, Sender};
+use rayon::{ThreadBuilder, ThreadPoolBuilder};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen(module = "/src/workerHelpers.js")]
+extern "C" {
+    #[wasm_bindgen(js_name = startWorkers)]
+    fn start_workers(module: JsValue, memory: JsValue, builder: PoolBuilder) -> js_sys::Promise;
+}
+
+#[wasm_bindgen]
+pub struct PoolBuilder {
+    num_threads: usize,
+    sender: Sender<ThreadBuilder>,
+    receiver: Receiver<ThreadBuilder>,
+}
+
+#[wasm_bindgen]
+impl PoolBuilder {
+    fn new(num_threads: usize) -> PoolBuilder {
+        let (sender, receiver) = bounded(num_threads);
+        PoolBuilder {
+            num_threads,
+            sender,
+            receiver,
+        }
+    }
+
+    #[wasm_bindgen(js_name = numThreads)]
+    pub fn num_threads(&self) -> usize {
+        self.num_threads
+    }
+
+    // Address the workers pass back to `wbg_rayon_start_worker`
+    pub fn receiver(&self) -> *const Receiver<ThreadBuilder> {
+        &self.receiver
+    }
+
+    // Called once every worker is ready to receive its thread
+    pub fn build(&mut self) {
+        let sender = self.sender.clone();
+        ThreadPoolBuilder::new()
+            .num_threads(self.num_threads)
+            .spawn_handler(move |thread| {
+                sender.send(thread).unwrap_throw();
+                Ok(())
+            })
+            .build_global()
+            .unwrap_throw();
+    }
+}
+
+// Resolves once `num_threads` workers (e.g. navigator.hardwareConcurrency)
+// have joined the pool
+#[wasm_bindgen(js_name = initThreadPool)]
+pub fn init_thread_pool(num_threads: usize) -> js_sys::Promise {
+    start_workers(wasm_bindgen::module(), wasm_bindgen::memory(), PoolBuilder::new(num_threads))
+}
+
+// Worker entry point: blocks running one rayon thread
+#[wasm_bindgen]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn wbg_rayon_start_worker(receiver: *const Receiver<ThreadBuilder>) {
+    // The pointer comes from `PoolBuilder::receiver`; every worker takes its
+    // thread during `build`, before JS can release the builder
+    let receiver = unsafe { &*receiver };
+    receiver.recv().unwrap_throw().run()
+}
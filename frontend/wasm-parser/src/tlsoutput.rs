@@ -0,0 +1,161 @@
+// Traffic light outputs of a run, for replaying the signals actually shown in
+// sync with FCD playback: states over time (SaveTLSStates, or
+// SaveTLSSwitchStates with only the changes) and green periods per link
+// (SaveTLSSwitchTimes).
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::{attr_f64, parse_xml, to_js};
+
+// One traffic light's states as parallel arrays, in time order. A state holds
+// from its time until the next one.
+#[wasm_bindgen]
+#[derive(Clone, Default)]
+pub struct TlsStateSeries {
+    time: Vec<f64>,
+    // Phase index; -1 when the record has none
+    phase: Vec<i32>,
+    state: Vec<String>,
+    program_id: Vec<String>,
+}
+
+#[wasm_bindgen]
+impl TlsStateSeries {
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.time.len()
+    }
+
+    // Seconds
+    #[wasm_bindgen(getter)]
+    pub fn time(&self) -> Vec<f64> {
+        self.time.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn phase(&self) -> Vec<i32> {
+        self.phase.clone()
+    }
+
+    // Signal state strings, one character per link index
+    #[wasm_bindgen(getter)]
+    pub fn state(&self) -> Vec<String> {
+        self.state.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name = programId)]
+    pub fn program_id(&self) -> Vec<String> {
+        self.program_id.clone()
+    }
+}
+
+impl TlsStateSeries {
+    // Index of the record in effect at `time`
+    fn index_at(&self, time: f64) -> Option<usize> {
+        self.time.partition_point(|t| *t <= time).checked_sub(1)
+    }
+}
+
+// Every traffic light of a TLS state output
+#[wasm_bindgen]
+pub struct TlsStates {
+    series: BTreeMap<String, TlsStateSeries>,
+}
+
+#[wasm_bindgen]
+impl TlsStates {
+    pub fn ids(&self) -> Vec<String> {
+        self.series.keys().cloned().collect()
+    }
+
+    pub fn series(&self, tl_id: &str) -> Option<TlsStateSeries> {
+        self.series.get(tl_id).cloned()
+    }
+
+    // The state shown at `time`; None before the first record
+    pub fn state_at(&self, tl_id: &str, time: f64) -> Option<String> {
+        let series = self.series.get(tl_id)?;
+        Some(series.state[series.index_at(time)?].clone())
+    }
+
+    // States of all traffic lights at `time`, by id, for one playback frame
+    #[wasm_bindgen(unchecked_return_type = "Record<string, string>")]
+    pub fn states_at(&self, time: f64) -> Result<JsValue, JsValue> {
+        let states: BTreeMap<&str, &str> = self
+            .series
+            .iter()
+            .filter_map(|(id, s)| Some((id.as_str(), s.state[s.index_at(time)?].as_str())))
+            .collect();
+        to_js(&states)
+    }
+}
+
+// One green period of a link
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct TlsSwitch {
+    pub tl: String,
+    #[serde(rename = "programId")]
+    pub program_id: Option<String>,
+    #[serde(rename = "fromLane")]
+    pub from_lane: String,
+    #[serde(rename = "toLane")]
+    pub to_lane: String,
+    pub begin: f64,
+    pub end: f64,
+}
+
+pub(crate) fn read_tls_states(root: roxmltree::Node) -> BTreeMap<String, TlsStateSeries> {
+    let mut series: BTreeMap<String, TlsStateSeries> = BTreeMap::new();
+    for record in root.children().filter(|n| n.has_tag_name("tlsState")) {
+        let (Some(id), Some(time), Some(state)) =
+            (record.attribute("id"), attr_f64(record, "time"), record.attribute("state"))
+        else {
+            continue;
+        };
+        let s = series.entry(id.to_string()).or_default();
+        s.time.push(time);
+        s.phase.push(record.attribute("phase").and_then(|p| p.parse().ok()).unwrap_or(-1));
+        s.state.push(state.to_string());
+        s.program_id.push(record.attribute("programID").unwrap_or("").to_string());
+    }
+    series
+}
+
+pub(crate) fn read_tls_switches(root: roxmltree::Node) -> Vec<TlsSwitch> {
+    root.children()
+        .filter(|n| n.has_tag_name("tlsSwitch"))
+        .filter_map(|s| {
+            Some(TlsSwitch {
+                tl: s.attribute("id")?.to_string(),
+                program_id: s.attribute("programID").map(String::from),
+                from_lane: s.attribute("fromLane")?.to_string(),
+                to_lane: s.attribute("toLane")?.to_string(),
+                begin: attr_f64(s, "begin")?,
+                end: attr_f64(s, "end")?,
+            })
+        })
+        .collect()
+}
+
+#[wasm_bindgen]
+pub fn parse_tls_states_output(xml_text: &str) -> Result<TlsStates, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let series = read_tls_states(doc.root_element());
+
+    console_log!("Parsed TLS states of {} traffic lights", series.len());
+
+    Ok(TlsStates { series })
+}
+
+// Green periods in file order
+#[wasm_bindgen(unchecked_return_type = "TlsSwitch[]")]
+pub fn parse_tls_switches_output(xml_text: &str) -> Result<JsValue, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let switches = read_tls_switches(doc.root_element());
+
+    console_log!("Parsed {} TLS green periods", switches.len());
+
+    to_js(&switches)
+}
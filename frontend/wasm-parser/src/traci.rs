@@ -0,0 +1,535 @@
+// TraCI wire format, for a browser client talking to a live SUMO instance
+// through a WebSocket-to-TCP proxy. TraciMessage builds one outgoing message
+// (4-byte length, then length-prefixed commands); TraciDecoder splits the
+// incoming byte stream back into messages and decodes their status,
+// variable and subscription responses. Commands are addressed by their
+// numeric ids (e.g. 0xa4 get vehicle variable, 0xd4 subscribe vehicle
+// variable), as listed in SUMO's TraCI constants.
+//
+// Only variable subscriptions are decoded; context subscription results and
+// other unknown responses are skipped and their ids listed in `unparsed`.
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::to_js;
+
+const CMD_GETVERSION: u8 = 0x00;
+const CMD_SIMSTEP: u8 = 0x02;
+const CMD_SETORDER: u8 = 0x03;
+const CMD_CLOSE: u8 = 0x7f;
+
+const TYPE_LONLAT: u8 = 0x00;
+const TYPE_POSITION2D: u8 = 0x01;
+const TYPE_LONLATALT: u8 = 0x02;
+const TYPE_POSITION3D: u8 = 0x03;
+const TYPE_ROADMAP: u8 = 0x04;
+const TYPE_BOUNDINGBOX: u8 = 0x05;
+const TYPE_POLYGON: u8 = 0x06;
+const TYPE_UBYTE: u8 = 0x07;
+const TYPE_BYTE: u8 = 0x08;
+const TYPE_INTEGER: u8 = 0x09;
+const TYPE_DOUBLE: u8 = 0x0b;
+const TYPE_STRING: u8 = 0x0c;
+const TYPE_STRINGLIST: u8 = 0x0e;
+const TYPE_COMPOUND: u8 = 0x0f;
+const TYPE_DOUBLELIST: u8 = 0x10;
+const TYPE_COLOR: u8 = 0x11;
+
+// A typed TraCI value, e.g. { type: "double", value: 13.9 }
+#[derive(Serialize, Deserialize, Tsify, Clone, Debug, PartialEq)]
+#[serde(tag = "type", content = "value", rename_all = "camelCase")]
+pub enum TraciValue {
+    Ubyte(u8),
+    Byte(i8),
+    Integer(i32),
+    Double(f64),
+    String(String),
+    StringList(Vec<String>),
+    DoubleList(Vec<f64>),
+    Compound(Vec<TraciValue>),
+    // [x, y] / [x, y, z] in network coordinates
+    Position2d([f64; 2]),
+    Position3d([f64; 3]),
+    // [lon, lat] / [lon, lat, alt]
+    LonLat([f64; 2]),
+    LonLatAlt([f64; 3]),
+    Roadmap { edge: String, pos: f64, lane: u8 },
+    // [xmin, ymin, xmax, ymax]
+    BoundingBox([f64; 4]),
+    Polygon(Vec<[f64; 2]>),
+    // [r, g, b, a]
+    Color([u8; 4]),
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct TraciStatus {
+    pub command: u8,
+    // 0 OK, 1 not implemented, 0xff error
+    pub result: u8,
+    pub ok: bool,
+    pub description: String,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct TraciVariable {
+    // Response id (the get command id + 0x10)
+    pub command: u8,
+    pub variable: u8,
+    #[serde(rename = "objectId")]
+    pub object_id: String,
+    pub value: TraciValue,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct SubscribedValue {
+    pub variable: u8,
+    // When false, `value` is SUMO's error message
+    pub ok: bool,
+    pub value: TraciValue,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct TraciSubscription {
+    // Response id (the subscribe command id + 0x10)
+    pub command: u8,
+    #[serde(rename = "objectId")]
+    pub object_id: String,
+    pub values: Vec<SubscribedValue>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct TraciVersion {
+    pub api: i32,
+    pub identifier: String,
+}
+
+#[derive(Serialize, Deserialize, Tsify, Default)]
+pub struct TraciResponse {
+    pub statuses: Vec<TraciStatus>,
+    pub variables: Vec<TraciVariable>,
+    pub subscriptions: Vec<TraciSubscription>,
+    pub version: Option<TraciVersion>,
+    // Ids of responses not decoded
+    pub unparsed: Vec<u8>,
+}
+
+// Big-endian writes, as all of TraCI
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn ubyte(&mut self, v: u8) {
+        self.0.push(v);
+    }
+
+    fn int(&mut self, v: i32) {
+        self.0.extend_from_slice(&v.to_be_bytes());
+    }
+
+    fn double(&mut self, v: f64) {
+        self.0.extend_from_slice(&v.to_be_bytes());
+    }
+
+    fn string(&mut self, v: &str) {
+        self.int(v.len() as i32);
+        self.0.extend_from_slice(v.as_bytes());
+    }
+
+    fn value(&mut self, value: &TraciValue) {
+        match value {
+            TraciValue::Ubyte(v) => {
+                self.ubyte(TYPE_UBYTE);
+                self.ubyte(*v);
+            }
+            TraciValue::Byte(v) => {
+                self.ubyte(TYPE_BYTE);
+                self.ubyte(*v as u8);
+            }
+            TraciValue::Integer(v) => {
+                self.ubyte(TYPE_INTEGER);
+                self.int(*v);
+            }
+            TraciValue::Double(v) => {
+                self.ubyte(TYPE_DOUBLE);
+                self.double(*v);
+            }
+            TraciValue::String(v) => {
+                self.ubyte(TYPE_STRING);
+                self.string(v);
+            }
+            TraciValue::StringList(list) => {
+                self.ubyte(TYPE_STRINGLIST);
+                self.int(list.len() as i32);
+                list.iter().for_each(|s| self.string(s));
+            }
+            TraciValue::DoubleList(list) => {
+                self.ubyte(TYPE_DOUBLELIST);
+                self.int(list.len() as i32);
+                list.iter().for_each(|v| self.double(*v));
+            }
+            TraciValue::Compound(items) => {
+                self.ubyte(TYPE_COMPOUND);
+                self.int(items.len() as i32);
+                items.iter().for_each(|v| self.value(v));
+            }
+            TraciValue::Position2d(p) => self.doubles(TYPE_POSITION2D, p),
+            TraciValue::Position3d(p) => self.doubles(TYPE_POSITION3D, p),
+            TraciValue::LonLat(p) => self.doubles(TYPE_LONLAT, p),
+            TraciValue::LonLatAlt(p) => self.doubles(TYPE_LONLATALT, p),
+            TraciValue::BoundingBox(b) => self.doubles(TYPE_BOUNDINGBOX, b),
+            TraciValue::Roadmap { edge, pos, lane } => {
+                self.ubyte(TYPE_ROADMAP);
+                self.string(edge);
+                self.double(*pos);
+                self.ubyte(*lane);
+            }
+            TraciValue::Polygon(points) => {
+                self.ubyte(TYPE_POLYGON);
+                // Counts above 255 are written as 0 and an int
+                match u8::try_from(points.len()) {
+                    Ok(n) if n > 0 => self.ubyte(n),
+                    _ => {
+                        self.ubyte(0);
+                        self.int(points.len() as i32);
+                    }
+                }
+                points.iter().flatten().for_each(|v| self.double(*v));
+            }
+            TraciValue::Color(c) => {
+                self.ubyte(TYPE_COLOR);
+                self.0.extend_from_slice(c);
+            }
+        }
+    }
+
+    fn doubles(&mut self, type_id: u8, values: &[f64]) {
+        self.ubyte(type_id);
+        values.iter().for_each(|v| self.double(*v));
+    }
+}
+
+// Big-endian reads over one message; running past the end is an error
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Reader<'a> {
+        Reader { bytes, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(n).filter(|e| *e <= self.bytes.len());
+        let end = end.ok_or_else(|| "TraCI message truncated".to_string())?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn ubyte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn int(&mut self) -> Result<i32, String> {
+        Ok(i32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn count(&mut self) -> Result<usize, String> {
+        usize::try_from(self.int()?).map_err(|_| "Negative count in TraCI message".to_string())
+    }
+
+    fn double(&mut self) -> Result<f64, String> {
+        Ok(f64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.count()?;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+
+    fn doubles<const N: usize>(&mut self) -> Result<[f64; N], String> {
+        let mut values = [0.0; N];
+        for v in &mut values {
+            *v = self.double()?;
+        }
+        Ok(values)
+    }
+
+    fn value(&mut self) -> Result<TraciValue, String> {
+        let type_id = self.ubyte()?;
+        Ok(match type_id {
+            TYPE_UBYTE => TraciValue::Ubyte(self.ubyte()?),
+            TYPE_BYTE => TraciValue::Byte(self.ubyte()? as i8),
+            TYPE_INTEGER => TraciValue::Integer(self.int()?),
+            TYPE_DOUBLE => TraciValue::Double(self.double()?),
+            TYPE_STRING => TraciValue::String(self.string()?),
+            TYPE_STRINGLIST => {
+                let n = self.count()?;
+                TraciValue::StringList((0..n).map(|_| self.string()).collect::<Result<_, _>>()?)
+            }
+            TYPE_DOUBLELIST => {
+                let n = self.count()?;
+                TraciValue::DoubleList((0..n).map(|_| self.double()).collect::<Result<_, _>>()?)
+            }
+            TYPE_COMPOUND => {
+                let n = self.count()?;
+                TraciValue::Compound((0..n).map(|_| self.value()).collect::<Result<_, _>>()?)
+            }
+            TYPE_POSITION2D => TraciValue::Position2d(self.doubles()?),
+            TYPE_POSITION3D => TraciValue::Position3d(self.doubles()?),
+            TYPE_LONLAT => TraciValue::LonLat(self.doubles()?),
+            TYPE_LONLATALT => TraciValue::LonLatAlt(self.doubles()?),
+            TYPE_BOUNDINGBOX => TraciValue::BoundingBox(self.doubles()?),
+            TYPE_ROADMAP => TraciValue::Roadmap {
+                edge: self.string()?,
+                pos: self.double()?,
+                lane: self.ubyte()?,
+            },
+            TYPE_POLYGON => {
+                let n = match self.ubyte()? {
+                    0 => self.count()?,
+                    n => n as usize,
+                };
+                TraciValue::Polygon((0..n).map(|_| self.doubles()).collect::<Result<_, _>>()?)
+            }
+            TYPE_COLOR => TraciValue::Color(self.take(4)?.try_into().unwrap()),
+            other => return Err(format!("Unknown TraCI type 0x{:02x}", other)),
+        })
+    }
+
+    // One command: (id, reader over its content)
+    fn command(&mut self) -> Result<(u8, Reader<'a>), String> {
+        let content = match self.ubyte()? {
+            // Extended length: 0, then an int counting all of the command
+            0 => self.count()?.checked_sub(6),
+            len => (len as usize).checked_sub(2),
+        };
+        let content = content.ok_or_else(|| "Invalid TraCI command length".to_string())?;
+        let id = self.ubyte()?;
+        Ok((id, Reader::new(self.take(content)?)))
+    }
+}
+
+pub(crate) fn decode_message(message: &[u8]) -> Result<TraciResponse, String> {
+    let mut reader = Reader::new(message);
+    let length = reader.count()?;
+    if length != message.len() {
+        return Err(format!("TraCI message length {} does not match {} bytes", length, message.len()));
+    }
+
+    let mut response = TraciResponse::default();
+    let mut version_status_seen = false;
+    while !reader.is_empty() {
+        let (id, mut body) = reader.command()?;
+        match id {
+            // getVersion answers with a status and then a response of the same id
+            CMD_GETVERSION if version_status_seen => {
+                response.version = Some(TraciVersion { api: body.int()?, identifier: body.string()? });
+            }
+            0xb0..=0xbf => response.variables.push(TraciVariable {
+                command: id,
+                variable: body.ubyte()?,
+                object_id: body.string()?,
+                value: body.value()?,
+            }),
+            0xe0..=0xef => {
+                let object_id = body.string()?;
+                let n = body.ubyte()?;
+                let values = (0..n)
+                    .map(|_| {
+                        Ok(SubscribedValue {
+                            variable: body.ubyte()?,
+                            ok: body.ubyte()? == 0,
+                            value: body.value()?,
+                        })
+                    })
+                    .collect::<Result<_, String>>()?;
+                response.subscriptions.push(TraciSubscription { command: id, object_id, values });
+            }
+            CMD_GETVERSION | CMD_SIMSTEP | CMD_SETORDER | CMD_CLOSE | 0x80..=0x8f | 0xa0..=0xaf | 0xc0..=0xdf => {
+                version_status_seen |= id == CMD_GETVERSION;
+                let result = body.ubyte()?;
+                response.statuses.push(TraciStatus { command: id, result, ok: result == 0, description: body.string()? });
+                // A successful step is followed by the count of the
+                // subscription results that come next as commands
+                if id == CMD_SIMSTEP && result == 0 {
+                    reader.int()?;
+                }
+            }
+            _ => response.unparsed.push(id),
+        }
+    }
+    Ok(response)
+}
+
+// One outgoing TraCI message; add commands, then finish() for the bytes to send
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct TraciMessage {
+    commands: Vec<u8>,
+    count: usize,
+}
+
+#[wasm_bindgen]
+impl TraciMessage {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> TraciMessage {
+        TraciMessage::default()
+    }
+
+    // Number of commands added
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.count
+    }
+
+    pub fn get_version(&mut self) {
+        self.push(CMD_GETVERSION, Writer::default());
+    }
+
+    // Client order when several clients share the simulation
+    pub fn set_order(&mut self, order: i32) {
+        let mut w = Writer::default();
+        w.int(order);
+        self.push(CMD_SETORDER, w);
+    }
+
+    // Advance to `time` seconds (0: a single step)
+    pub fn simulation_step(&mut self, time: f64) {
+        let mut w = Writer::default();
+        w.double(time);
+        self.push(CMD_SIMSTEP, w);
+    }
+
+    pub fn close(&mut self) {
+        self.push(CMD_CLOSE, Writer::default());
+    }
+
+    // `parameter` for variables that take one (e.g. a parameter key)
+    pub fn get_variable(
+        &mut self,
+        command: u8,
+        variable: u8,
+        object_id: &str,
+        #[wasm_bindgen(unchecked_param_type = "TraciValue | undefined")] parameter: JsValue,
+    ) -> Result<(), JsValue> {
+        let mut w = Writer::default();
+        w.ubyte(variable);
+        w.string(object_id);
+        if !parameter.is_undefined() && !parameter.is_null() {
+            w.value(&from_js(parameter)?);
+        }
+        self.push(command, w);
+        Ok(())
+    }
+
+    pub fn set_variable(
+        &mut self,
+        command: u8,
+        variable: u8,
+        object_id: &str,
+        #[wasm_bindgen(unchecked_param_type = "TraciValue")] value: JsValue,
+    ) -> Result<(), JsValue> {
+        let mut w = Writer::default();
+        w.ubyte(variable);
+        w.string(object_id);
+        w.value(&from_js(value)?);
+        self.push(command, w);
+        Ok(())
+    }
+
+    // Results for `variables` of `object_id` arrive with every simulation
+    // step between `begin` and `end` (seconds)
+    pub fn subscribe(&mut self, command: u8, begin: f64, end: f64, object_id: &str, variables: Vec<u8>) {
+        let mut w = Writer::default();
+        w.double(begin);
+        w.double(end);
+        w.string(object_id);
+        w.ubyte(variables.len().min(u8::MAX as usize) as u8);
+        variables.iter().take(u8::MAX as usize).for_each(|v| w.ubyte(*v));
+        self.push(command, w);
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        let mut message = Writer::default();
+        message.int((self.commands.len() + 4) as i32);
+        message.0.extend(self.commands);
+        message.0
+    }
+}
+
+impl TraciMessage {
+    fn push(&mut self, id: u8, content: Writer) {
+        let len = content.0.len() + 2;
+        if len <= u8::MAX as usize {
+            self.commands.push(len as u8);
+        } else {
+            self.commands.push(0);
+            self.commands.extend_from_slice(&((len + 4) as i32).to_be_bytes());
+        }
+        self.commands.push(id);
+        self.commands.extend(content.0);
+        self.count += 1;
+    }
+}
+
+fn from_js(value: JsValue) -> Result<TraciValue, JsValue> {
+    serde_wasm_bindgen::from_value(value).map_err(|e| JsValue::from_str(&format!("Invalid TraCI value: {}", e)))
+}
+
+// Splits received bytes into TraCI messages; chunks may end anywhere, as
+// proxies forward the TCP stream without regard to message boundaries
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct TraciDecoder {
+    buffer: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl TraciDecoder {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> TraciDecoder {
+        TraciDecoder::default()
+    }
+
+    pub fn feed(&mut self, chunk: &[u8]) {
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    // The next complete message, or undefined until more bytes arrive
+    #[wasm_bindgen(unchecked_return_type = "TraciResponse | undefined")]
+    pub fn next(&mut self) -> Result<JsValue, JsValue> {
+        match self.next_message().map_err(|e| JsValue::from_str(&e))? {
+            Some(response) => to_js(&response),
+            None => Ok(JsValue::UNDEFINED),
+        }
+    }
+}
+
+impl TraciDecoder {
+    pub(crate) fn next_message(&mut self) -> Result<Option<TraciResponse>, String> {
+        let Some(header) = self.buffer.get(..4) else { return Ok(None) };
+        let length = i32::from_be_bytes(header.try_into().unwrap());
+        let Some(length) = usize::try_from(length).ok().filter(|l| *l >= 4) else {
+            return Err(format!("Invalid TraCI message length {}", length));
+        };
+        if self.buffer.len() < length {
+            return Ok(None);
+        }
+        let message: Vec<u8> = self.buffer.drain(..length).collect();
+        decode_message(&message).map(Some)
+    }
+}
+
+// Decodes one complete TraCI message (including its length prefix)
+#[wasm_bindgen(unchecked_return_type = "TraciResponse")]
+pub fn decode_traci_message(bytes: &[u8]) -> Result<JsValue, JsValue> {
+    let response = decode_message(bytes).map_err(|e| JsValue::from_str(&e))?;
+    to_js(&response)
+}
@@ -0,0 +1,328 @@
+// Transit stop KPIs for the accessibility report: how far apart consecutive
+// stops of each public transport line are, and how much of the network lies
+// within walking distance of any stop.
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::geometry;
+use crate::heatmap::lane_edge;
+use crate::net::NetModel;
+use crate::persons::WalkGraph;
+use crate::{attr_f64, parse_xml, to_js};
+
+const DEFAULT_WALK_DISTANCE: f64 = 400.0;
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct LineSpacing {
+    pub line: String,
+    // Stop ids in service order
+    pub stops: Vec<String>,
+    // Meters between consecutive stops, along the line's route when it has one
+    // and as the crow flies otherwise
+    pub spacing: Vec<f64>,
+    #[serde(rename = "meanSpacing")]
+    pub mean_spacing: Option<f64>,
+    #[serde(rename = "minSpacing")]
+    pub min_spacing: Option<f64>,
+    #[serde(rename = "maxSpacing")]
+    pub max_spacing: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct TransitCoverage {
+    pub lines: Vec<LineSpacing>,
+    // Mean over every stop-to-stop gap of every line
+    #[serde(rename = "meanSpacing")]
+    pub mean_spacing: Option<f64>,
+    #[serde(rename = "walkDistance")]
+    pub walk_distance: f64,
+    // Normal edges with an end within walking distance of a stop
+    #[serde(rename = "coveredEdges")]
+    pub covered_edges: usize,
+    #[serde(rename = "totalEdges")]
+    pub total_edges: usize,
+    // Covered share of the edges, by count and by length (0..1)
+    #[serde(rename = "edgeShare")]
+    pub edge_share: f64,
+    #[serde(rename = "lengthShare")]
+    pub length_share: f64,
+}
+
+// A stopping place, located at the middle of its extent on the lane
+struct StopPlace {
+    edge: String,
+    pos: f64,
+    point: Option<(f64, f64)>,
+}
+
+fn read_stop_places(root: roxmltree::Node, net: &NetModel, stops: &mut HashMap<String, StopPlace>) {
+    for stop in root
+        .descendants()
+        .filter(|n| matches!(n.tag_name().name(), "busStop" | "trainStop"))
+    {
+        let (Some(id), Some(lane_id)) = (stop.attribute("id"), stop.attribute("lane")) else {
+            continue;
+        };
+        let lane = net.lane(lane_id);
+        let length = lane.map_or(0.0, |l| geometry::polyline_length(&l.shape));
+        // Negative positions count from the lane end
+        let at = |name, default: f64| {
+            let v = attr_f64(stop, name).unwrap_or(default);
+            if v < 0.0 { length + v } else { v }
+        };
+        let pos = (at("startPos", 0.0) + at("endPos", length)) / 2.0;
+        stops.insert(
+            id.to_string(),
+            StopPlace {
+                edge: lane_edge(lane_id).to_string(),
+                pos,
+                point: lane.and_then(|l| geometry::point_at(&l.shape, pos)),
+            },
+        );
+    }
+}
+
+// A line's stops in order and, when known, the edges it drives along
+struct LineRun {
+    line: String,
+    stops: Vec<String>,
+    route: Vec<String>,
+}
+
+// <busStop id> children of a ptLine, <stop busStop> children of a route or
+// vehicle
+fn stop_children(node: roxmltree::Node) -> Vec<String> {
+    node.children()
+        .filter_map(|n| match n.tag_name().name() {
+            "busStop" => n.attribute("id"),
+            "stop" => n.attribute("busStop").or(n.attribute("trainStop")),
+            _ => None,
+        })
+        .map(String::from)
+        .collect()
+}
+
+fn route_edges(route: roxmltree::Node) -> Vec<String> {
+    route.attribute("edges").unwrap_or("").split_whitespace().map(String::from).collect()
+}
+
+// Lines from netconvert's <ptLine> output or from route files (vehicles and
+// flows with a `line` attribute and stops); the first run of each line wins
+fn read_lines(root: roxmltree::Node) -> Vec<LineRun> {
+    let routes: HashMap<&str, roxmltree::Node> = root
+        .children()
+        .filter(|n| n.tag_name().name() == "route")
+        .filter_map(|r| Some((r.attribute("id")?, r)))
+        .collect();
+
+    let mut lines: Vec<LineRun> = Vec::new();
+    for node in root.descendants() {
+        let run = match node.tag_name().name() {
+            "ptLine" => LineRun {
+                line: node.attribute("line").or(node.attribute("id")).unwrap_or("").to_string(),
+                stops: stop_children(node),
+                route: node.children().find(|n| n.tag_name().name() == "route").map(route_edges).unwrap_or_default(),
+            },
+            "vehicle" | "flow" | "trip" => {
+                let Some(line) = node.attribute("line") else { continue };
+                let route = node
+                    .children()
+                    .find(|n| n.tag_name().name() == "route")
+                    .or_else(|| routes.get(node.attribute("route")?).copied());
+                LineRun {
+                    line: line.to_string(),
+                    // Stops on a shared route come before the vehicle's own
+                    stops: route.into_iter().flat_map(stop_children).chain(stop_children(node)).collect(),
+                    route: route.map(route_edges).unwrap_or_default(),
+                }
+            }
+            _ => continue,
+        };
+        if run.stops.len() >= 2 && !lines.iter().any(|l| l.line == run.line) {
+            lines.push(run);
+        }
+    }
+    lines
+}
+
+fn line_spacing(run: &LineRun, net: &NetModel, stops: &HashMap<String, StopPlace>) -> LineSpacing {
+    // Offset of each route edge's start along the route
+    let mut starts = Vec::with_capacity(run.route.len());
+    let mut total = 0.0;
+    for edge in &run.route {
+        starts.push(total);
+        total += net.edge(edge).map_or(0.0, |e| e.length());
+    }
+
+    // Stops are matched to route edges in order, so loops visiting an edge
+    // twice still measure forward
+    let mut cursor = 0;
+    let offsets: Vec<Option<f64>> = run
+        .stops
+        .iter()
+        .map(|id| {
+            let stop = stops.get(id)?;
+            let i = cursor + run.route[cursor..].iter().position(|e| *e == stop.edge)?;
+            cursor = i;
+            Some(starts[i] + stop.pos)
+        })
+        .collect();
+
+    let spacing: Vec<f64> = run
+        .stops
+        .windows(2)
+        .zip(offsets.windows(2))
+        .filter_map(|(ids, along)| match (along[0], along[1]) {
+            (Some(a), Some(b)) if b >= a => Some(b - a),
+            _ => Some(geometry::distance(stops.get(&ids[0])?.point?, stops.get(&ids[1])?.point?)),
+        })
+        .collect();
+
+    LineSpacing {
+        line: run.line.clone(),
+        stops: run.stops.clone(),
+        mean_spacing: (!spacing.is_empty()).then(|| spacing.iter().sum::<f64>() / spacing.len() as f64),
+        min_spacing: spacing.iter().copied().reduce(f64::min),
+        max_spacing: spacing.iter().copied().reduce(f64::max),
+        spacing,
+    }
+}
+
+fn transit_coverage(
+    net: &NetModel,
+    stops: &HashMap<String, StopPlace>,
+    lines: &[LineRun],
+    walk_distance: f64,
+) -> TransitCoverage {
+    let lines: Vec<LineSpacing> = lines.iter().map(|run| line_spacing(run, net, stops)).collect();
+    let gaps: Vec<f64> = lines.iter().flat_map(|l| l.spacing.iter().copied()).collect();
+
+    let seeds: Vec<(&str, f64)> = stops.values().map(|s| (s.edge.as_str(), s.pos)).collect();
+    let reach = WalkGraph::new(net).reach(&seeds);
+    let stop_edges: HashSet<&str> = stops.values().map(|s| s.edge.as_str()).collect();
+    let near = |id: &Option<String>| {
+        id.as_deref()
+            .and_then(|j| net.junction_index.get(j))
+            .is_some_and(|&j| reach[j] <= walk_distance)
+    };
+
+    let (mut covered_edges, mut total_edges, mut covered_length, mut total_length) = (0, 0, 0.0, 0.0);
+    for edge in net.edges.iter().filter(|e| e.is_normal()) {
+        let length = edge.length();
+        total_edges += 1;
+        total_length += length;
+        if near(&edge.from) || near(&edge.to) || stop_edges.contains(edge.id.as_str()) {
+            covered_edges += 1;
+            covered_length += length;
+        }
+    }
+
+    TransitCoverage {
+        mean_spacing: (!gaps.is_empty()).then(|| gaps.iter().sum::<f64>() / gaps.len() as f64),
+        lines,
+        walk_distance,
+        covered_edges,
+        total_edges,
+        edge_share: if total_edges > 0 { covered_edges as f64 / total_edges as f64 } else { 0.0 },
+        length_share: if total_length > 0.0 { covered_length / total_length } else { 0.0 },
+    }
+}
+
+// Stop spacing per line and walking-distance coverage. `stops_xml` holds the
+// busStop / trainStop definitions, `lines_xml` the ptLines or a route file
+// with line vehicles (both may be the same document). Coverage walks the
+// pedestrian graph up to `walk_distance` meters (default 400) from any stop.
+#[wasm_bindgen(unchecked_return_type = "TransitCoverage")]
+pub fn analyze_transit_coverage(
+    net_xml: &str,
+    stops_xml: &str,
+    lines_xml: Option<String>,
+    walk_distance: Option<f64>,
+) -> Result<JsValue, JsValue> {
+    let doc = parse_xml(net_xml)?;
+    let net = NetModel::from_root(doc.root_element());
+    let stops_doc = parse_xml(stops_xml)?;
+    let lines_doc = lines_xml.as_deref().map(parse_xml).transpose()?;
+
+    let mut stops = HashMap::new();
+    read_stop_places(stops_doc.root_element(), &net, &mut stops);
+    let mut lines = read_lines(stops_doc.root_element());
+    if let Some(d) = &lines_doc {
+        read_stop_places(d.root_element(), &net, &mut stops);
+        for run in read_lines(d.root_element()) {
+            if !lines.iter().any(|l| l.line == run.line) {
+                lines.push(run);
+            }
+        }
+    }
+
+    let coverage = transit_coverage(&net, &stops, &lines, walk_distance.unwrap_or(DEFAULT_WALK_DISTANCE));
+
+    console_log!(
+        "Transit coverage: {} lines, {:.1}% of edges within {} m of a stop",
+        coverage.lines.len(),
+        coverage.edge_share * 100.0,
+        coverage.walk_distance
+    );
+
+    to_js(&coverage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a and b chained, c far away; the crossing and walking area have no ends
+    const NET: &str = r#"<net>
+        <edge id="a" from="j0" to="j1"><lane id="a_0" index="0" shape="0,0 100,0"/></edge>
+        <edge id="b" from="j1" to="j2"><lane id="b_0" index="0" shape="100,0 200,0"/></edge>
+        <edge id="c" from="j3" to="j4"><lane id="c_0" index="0" shape="5000,0 5100,0"/></edge>
+        <edge id=":j1_c0" function="crossing" crossingEdges="b"><lane id=":j1_c0_0" index="0" shape="105,-5 105,5"/></edge>
+        <edge id=":j1_w0" function="walkingarea"><lane id=":j1_w0_0" index="0" shape="95,5 105,5"/></edge>
+        <junction id="j0" type="priority" x="0" y="0"/>
+        <junction id="j1" type="priority" x="100" y="0"/>
+        <junction id="j2" type="priority" x="200" y="0"/>
+        <junction id="j3" type="priority" x="5000" y="0"/>
+        <junction id="j4" type="priority" x="5100" y="0"/>
+    </net>"#;
+
+    const STOPS: &str = r#"<additional>
+        <busStop id="s1" lane="a_0" startPos="40" endPos="60"/>
+        <busStop id="s2" lane="b_0" startPos="70" endPos="90"/>
+        <busStop id="s3" lane="c_0" startPos="0" endPos="20"/>
+        <ptLine id="l1" line="1">
+            <busStop id="s1"/><busStop id="s2"/>
+            <route edges="a b"/>
+        </ptLine>
+        <ptLine id="l2" line="2"><busStop id="s1"/><busStop id="s3"/></ptLine>
+    </additional>"#;
+
+    fn coverage(walk_distance: f64) -> TransitCoverage {
+        let net_doc = roxmltree::Document::parse(NET).unwrap();
+        let net = NetModel::from_root(net_doc.root_element());
+        let doc = roxmltree::Document::parse(STOPS).unwrap();
+        let mut stops = HashMap::new();
+        read_stop_places(doc.root_element(), &net, &mut stops);
+        transit_coverage(&net, &stops, &read_lines(doc.root_element()), walk_distance)
+    }
+
+    #[test]
+    fn spacing_follows_the_route_or_the_crow_flies() {
+        let coverage = coverage(DEFAULT_WALK_DISTANCE);
+        assert_eq!(coverage.lines[0].spacing, [130.0]);
+        assert_eq!(coverage.lines[1].spacing, [4960.0]);
+        assert_eq!(coverage.mean_spacing, Some(2545.0));
+    }
+
+    #[test]
+    fn coverage_counts_only_normal_edges() {
+        let coverage = coverage(10.0);
+        assert_eq!(coverage.total_edges, 3);
+        // Every edge holds a stop
+        assert_eq!(coverage.covered_edges, 3);
+        assert_eq!(coverage.edge_share, 1.0);
+        assert_eq!(coverage.length_share, 1.0);
+    }
+}
@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::stats::Stats;
+use crate::{attr_f64, parse_xml, to_js};
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct TripEmissions {
+    #[serde(rename = "CO")]
+    pub co: f64,
+    #[serde(rename = "CO2")]
+    pub co2: f64,
+    #[serde(rename = "HC")]
+    pub hc: f64,
+    #[serde(rename = "PMx")]
+    pub pmx: f64,
+    #[serde(rename = "NOx")]
+    pub nox: f64,
+    pub fuel: f64,
+    pub electricity: f64,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct TripInfo {
+    pub id: String,
+    #[serde(rename = "vType")]
+    pub v_type: Option<String>,
+    pub depart: f64,
+    pub arrival: f64,
+    pub duration: f64,
+    #[serde(rename = "departLane")]
+    pub depart_lane: Option<String>,
+    #[serde(rename = "arrivalLane")]
+    pub arrival_lane: Option<String>,
+    #[serde(rename = "routeLength")]
+    pub route_length: f64,
+    #[serde(rename = "waitingTime")]
+    pub waiting_time: f64,
+    #[serde(rename = "timeLoss")]
+    pub time_loss: f64,
+    pub emissions: Option<TripEmissions>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct TripInfoSummary {
+    pub count: usize,
+    pub duration: Stats,
+    #[serde(rename = "routeLength")]
+    pub route_length: Stats,
+    #[serde(rename = "waitingTime")]
+    pub waiting_time: Stats,
+    #[serde(rename = "timeLoss")]
+    pub time_loss: Stats,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct TripInfoOutput {
+    pub trips: Vec<TripInfo>,
+    pub summary: TripInfoSummary,
+}
+
+pub(crate) fn read_trips(root: roxmltree::Node) -> Vec<TripInfo> {
+    root.children()
+        .filter(|n| n.tag_name().name() == "tripinfo")
+        .filter_map(|t| {
+            let emissions = t
+                .children()
+                .find(|n| n.tag_name().name() == "emissions")
+                .map(|e| TripEmissions {
+                    co: attr_f64(e, "CO_abs").unwrap_or(0.0),
+                    co2: attr_f64(e, "CO2_abs").unwrap_or(0.0),
+                    hc: attr_f64(e, "HC_abs").unwrap_or(0.0),
+                    pmx: attr_f64(e, "PMx_abs").unwrap_or(0.0),
+                    nox: attr_f64(e, "NOx_abs").unwrap_or(0.0),
+                    fuel: attr_f64(e, "fuel_abs").unwrap_or(0.0),
+                    electricity: attr_f64(e, "electricity_abs").unwrap_or(0.0),
+                });
+
+            Some(TripInfo {
+                id: t.attribute("id")?.to_string(),
+                v_type: t.attribute("vType").map(String::from),
+                depart: attr_f64(t, "depart")?,
+                arrival: attr_f64(t, "arrival").unwrap_or(-1.0),
+                duration: attr_f64(t, "duration").unwrap_or(0.0),
+                depart_lane: t.attribute("departLane").map(String::from),
+                arrival_lane: t.attribute("arrivalLane").map(String::from),
+                route_length: attr_f64(t, "routeLength").unwrap_or(0.0),
+                waiting_time: attr_f64(t, "waitingTime").unwrap_or(0.0),
+                time_loss: attr_f64(t, "timeLoss").unwrap_or(0.0),
+                emissions,
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn summarize_trips(trips: &[TripInfo]) -> TripInfoSummary {
+    // Unfinished trips (written with --tripinfo-output.write-unfinished) skew the stats
+    let finished: Vec<&TripInfo> = trips.iter().filter(|t| t.arrival >= 0.0).collect();
+    let column = |f: fn(&TripInfo) -> f64| -> Vec<f64> { finished.iter().map(|t| f(t)).collect() };
+
+    TripInfoSummary {
+        count: finished.len(),
+        duration: Stats::from_values(&column(|t| t.duration)),
+        route_length: Stats::from_values(&column(|t| t.route_length)),
+        waiting_time: Stats::from_values(&column(|t| t.waiting_time)),
+        time_loss: Stats::from_values(&column(|t| t.time_loss)),
+    }
+}
+
+#[wasm_bindgen(unchecked_return_type = "TripInfoOutput")]
+pub fn parse_tripinfo_output(xml_text: &str) -> Result<JsValue, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let trips = read_trips(doc.root_element());
+    let summary = summarize_trips(&trips);
+
+    console_log!("Parsed {} tripinfo records", trips.len());
+
+    to_js(&TripInfoOutput { trips, summary })
+}
@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+const MS_TO_KMH: f64 = 3.6;
+const MS_TO_MPH: f64 = 2.236_936_292;
+
+// Posted limits come in steps of 5; SUMO speeds are exact conversions
+// (13.89 m/s), so snapping recovers the signposted value
+fn round_to_limit(value: f64) -> f64 {
+    (value / 5.0).round() * 5.0
+}
+
+#[derive(Serialize, Deserialize, Tsify, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SpeedClass {
+    // up to 20 km/h
+    Walking,
+    // up to 30 km/h
+    Residential,
+    // up to 50 km/h
+    Urban,
+    // up to 70 km/h
+    Arterial,
+    // up to 90 km/h
+    Expressway,
+    // above 90 km/h
+    Motorway,
+}
+
+impl SpeedClass {
+    pub(crate) fn from_kmh(kmh: f64) -> SpeedClass {
+        match kmh {
+            v if v <= 20.0 => SpeedClass::Walking,
+            v if v <= 30.0 => SpeedClass::Residential,
+            v if v <= 50.0 => SpeedClass::Urban,
+            v if v <= 70.0 => SpeedClass::Arterial,
+            v if v <= 90.0 => SpeedClass::Expressway,
+            _ => SpeedClass::Motorway,
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            SpeedClass::Walking => "walking",
+            SpeedClass::Residential => "residential",
+            SpeedClass::Urban => "urban",
+            SpeedClass::Arterial => "arterial",
+            SpeedClass::Expressway => "expressway",
+            SpeedClass::Motorway => "motorway",
+        }
+    }
+}
+
+// m/s to the nearest signposted km/h value
+#[wasm_bindgen]
+pub fn speed_limit_kmh(speed_ms: f64) -> f64 {
+    round_to_limit(speed_ms * MS_TO_KMH)
+}
+
+// m/s to the nearest signposted mph value
+#[wasm_bindgen]
+pub fn speed_limit_mph(speed_ms: f64) -> f64 {
+    round_to_limit(speed_ms * MS_TO_MPH)
+}
+
+// Limit bucket name for a speed in m/s
+#[wasm_bindgen]
+pub fn speed_class(speed_ms: f64) -> String {
+    SpeedClass::from_kmh(speed_limit_kmh(speed_ms)).name().to_string()
+}
@@ -0,0 +1,105 @@
+// Routes as driven, from a vehroute output (--vehroute-output). Vehicles that
+// were rerouted carry a <routeDistribution> with every route they held; the
+// one marked `last` is the route they finished on.
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::{attr_f64, parse_xml, to_js};
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct ReplacedRoute {
+    pub edges: Vec<String>,
+    // Edge the vehicle was on when the route was replaced
+    #[serde(rename = "replacedOnEdge")]
+    pub replaced_on_edge: Option<String>,
+    #[serde(rename = "replacedAtTime")]
+    pub replaced_at_time: Option<f64>,
+    // Rerouting device or TraCI call that replaced it
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct RealizedRoute {
+    pub id: String,
+    #[serde(rename = "vType")]
+    pub v_type: Option<String>,
+    pub depart: Option<f64>,
+    // None for vehicles still running at the end
+    // (--vehroute-output.write-unfinished)
+    pub arrival: Option<f64>,
+    pub edges: Vec<String>,
+    // Seconds at which each edge was left, with
+    // --vehroute-output.exit-times
+    #[serde(rename = "exitTimes")]
+    pub exit_times: Option<Vec<f64>>,
+    // Meters, with --vehroute-output.route-length
+    #[serde(rename = "routeLength")]
+    pub route_length: Option<f64>,
+    // Routes held earlier, oldest first
+    pub replaced: Vec<ReplacedRoute>,
+}
+
+fn split_edges(edges: &str) -> Vec<String> {
+    edges.split_whitespace().map(String::from).collect()
+}
+
+fn read_vehicle(vehicle: roxmltree::Node) -> Option<RealizedRoute> {
+    let mut routes: Vec<roxmltree::Node> = vehicle.children().filter(|n| n.tag_name().name() == "route").collect();
+    let mut current = routes.len().checked_sub(1);
+    if let Some(distribution) = vehicle.children().find(|n| n.tag_name().name() == "routeDistribution") {
+        routes = distribution.children().filter(|n| n.tag_name().name() == "route").collect();
+        current = distribution
+            .attribute("last")
+            .and_then(|l| l.parse().ok())
+            .filter(|l| *l < routes.len())
+            .or(routes.len().checked_sub(1));
+    }
+    let route = routes.get(current?)?;
+
+    let exit_times = route
+        .attribute("exitTimes")
+        .and_then(|t| t.split_whitespace().map(|t| t.parse().ok()).collect::<Option<Vec<f64>>>());
+    let replaced = routes
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| Some(*i) != current)
+        .map(|(_, r)| ReplacedRoute {
+            edges: r.attribute("edges").map(split_edges).unwrap_or_default(),
+            replaced_on_edge: r.attribute("replacedOnEdge").map(String::from),
+            replaced_at_time: attr_f64(*r, "replacedAtTime"),
+            reason: r.attribute("reason").map(String::from),
+        })
+        .collect();
+    Some(RealizedRoute {
+        id: vehicle.attribute("id")?.to_string(),
+        v_type: vehicle.attribute("type").map(String::from),
+        depart: attr_f64(vehicle, "depart"),
+        arrival: attr_f64(vehicle, "arrival").filter(|a| *a >= 0.0),
+        edges: route.attribute("edges").map(split_edges).unwrap_or_default(),
+        exit_times,
+        route_length: attr_f64(vehicle, "routeLength"),
+        replaced,
+    })
+}
+
+pub(crate) fn read_realized_routes(root: roxmltree::Node) -> Vec<RealizedRoute> {
+    root.children()
+        .filter(|n| n.tag_name().name() == "vehicle")
+        .filter_map(read_vehicle)
+        .collect()
+}
+
+#[wasm_bindgen(unchecked_return_type = "RealizedRoute[]")]
+pub fn parse_vehroute_output(xml_text: &str) -> Result<JsValue, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let routes = read_realized_routes(doc.root_element());
+
+    console_log!(
+        "Parsed {} vehroute records ({} rerouted)",
+        routes.len(),
+        routes.iter().filter(|r| !r.replaced.is_empty()).count()
+    );
+
+    to_js(&routes)
+}
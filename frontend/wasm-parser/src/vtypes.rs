@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::color::parse_sumo_color;
+use crate::{attr_f64, parse_xml, to_js};
+
+#[derive(Serialize, Deserialize, Tsify)]
+pub struct VehicleType {
+    pub id: String,
+    #[serde(rename = "vClass")]
+    pub v_class: String,
+    pub length: f64,
+    pub width: f64,
+    pub height: f64,
+    #[serde(rename = "minGap")]
+    pub min_gap: f64,
+    #[serde(rename = "maxSpeed")]
+    pub max_speed: f64,
+    pub accel: f64,
+    pub decel: f64,
+    #[serde(rename = "emissionClass")]
+    pub emission_class: Option<String>,
+    // RGBA, 0-255
+    pub color: Option<[u8; 4]>,
+    #[serde(rename = "guiShape")]
+    pub gui_shape: String,
+    // Distribution this type belongs to, if declared inside a <vTypeDistribution>
+    pub distribution: Option<String>,
+}
+
+// SUMO's per-vClass defaults: (length, width, height, minGap, maxSpeed, accel, decel, guiShape)
+fn class_defaults(v_class: &str) -> (f64, f64, f64, f64, f64, f64, f64, &'static str) {
+    match v_class {
+        "pedestrian" => (0.215, 0.478, 1.719, 0.25, 10.44, 1.5, 2.0, "pedestrian"),
+        "bicycle" => (1.6, 0.65, 1.7, 0.5, 13.89, 1.2, 3.0, "bicycle"),
+        "moped" => (2.1, 0.8, 1.7, 2.5, 12.5, 1.1, 7.0, "moped"),
+        "motorcycle" => (2.2, 0.9, 1.5, 2.5, 55.56, 6.0, 10.0, "motorcycle"),
+        "bus" => (12.0, 2.5, 3.4, 2.5, 27.78, 1.2, 4.0, "bus"),
+        "coach" => (14.0, 2.6, 4.0, 2.5, 33.33, 2.0, 4.0, "bus/coach"),
+        "trolleybus" => (12.0, 2.5, 3.4, 2.5, 27.78, 1.2, 4.0, "bus/trolley"),
+        "truck" => (7.1, 2.4, 2.4, 2.5, 36.11, 1.3, 4.0, "truck"),
+        "trailer" => (16.5, 2.55, 4.0, 2.5, 36.11, 1.1, 4.0, "truck/semitrailer"),
+        "delivery" => (6.5, 2.16, 2.86, 2.5, 55.56, 2.6, 4.5, "delivery"),
+        "taxi" => (5.0, 1.8, 1.5, 2.5, 55.56, 2.6, 4.5, "passenger"),
+        "emergency" => (6.5, 2.16, 2.86, 2.5, 55.56, 2.6, 4.5, "emergency"),
+        "tram" => (22.0, 2.4, 3.2, 2.5, 22.22, 1.0, 3.0, "rail/railcar"),
+        "rail_urban" | "subway" => (36.5, 3.0, 3.6, 2.5, 27.78, 1.0, 3.0, "rail/railcar"),
+        "rail" => (67.5, 2.84, 3.75, 2.5, 44.44, 0.25, 1.3, "rail"),
+        _ => (5.0, 1.8, 1.5, 2.5, 55.56, 2.6, 4.5, "passenger"),
+    }
+}
+
+pub(crate) fn read_vehicle_types(root: roxmltree::Node) -> BTreeMap<String, VehicleType> {
+    root.descendants()
+        .filter(|n| n.tag_name().name() == "vType")
+        .filter_map(|t| {
+            let id = t.attribute("id")?.to_string();
+            let v_class = t.attribute("vClass").unwrap_or("passenger").to_string();
+            let (length, width, height, min_gap, max_speed, accel, decel, gui_shape) = class_defaults(&v_class);
+            let distribution = t
+                .parent_element()
+                .filter(|p| p.tag_name().name() == "vTypeDistribution")
+                .and_then(|p| p.attribute("id"))
+                .map(String::from);
+
+            let vt = VehicleType {
+                id: id.clone(),
+                length: attr_f64(t, "length").unwrap_or(length),
+                width: attr_f64(t, "width").unwrap_or(width),
+                height: attr_f64(t, "height").unwrap_or(height),
+                min_gap: attr_f64(t, "minGap").unwrap_or(min_gap),
+                max_speed: attr_f64(t, "maxSpeed").unwrap_or(max_speed),
+                accel: attr_f64(t, "accel").unwrap_or(accel),
+                decel: attr_f64(t, "decel").unwrap_or(decel),
+                emission_class: t.attribute("emissionClass").map(String::from),
+                color: t.attribute("color").and_then(parse_sumo_color),
+                gui_shape: t.attribute("guiShape").unwrap_or(gui_shape).to_string(),
+                v_class,
+                distribution,
+            };
+            Some((id, vt))
+        })
+        .collect()
+}
+
+// vType definitions from a route or additional file, keyed by id, with
+// SUMO's vClass defaults filled in for unspecified attributes
+#[wasm_bindgen(unchecked_return_type = "Map<string, VehicleType>")]
+pub fn parse_vehicle_types(xml_text: &str) -> Result<JsValue, JsValue> {
+    let doc = parse_xml(xml_text)?;
+    let types = read_vehicle_types(doc.root_element());
+
+    console_log!("Parsed {} vehicle types", types.len());
+
+    to_js(&types)
+}
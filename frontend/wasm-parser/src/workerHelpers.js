@@ -0,0 +1,45 @@
+// Worker side of src/threads.rs, loaded as a wasm-bindgen snippet. The same
+// file runs as the module script of every pool worker.
+
+function waitForMsgType(target, type) {
+  return new Promise((resolve) => {
+    target.addEventListener('message', function onMsg({ data }) {
+      if (data?.type !== type) return;
+      target.removeEventListener('message', onMsg);
+      resolve(data);
+    });
+  });
+}
+
+// In a worker: instantiate the module on the shared memory, then block
+// running one rayon thread
+waitForMsgType(self, 'wasm_bindgen_worker_init').then(async ({ init, receiver }) => {
+  // Snippets live in pkg/snippets/<crate>/src/, so this is the package entry
+  const pkg = await import('../../..');
+  await pkg.default(init);
+  postMessage({ type: 'wasm_bindgen_worker_ready' });
+  pkg.wbg_rayon_start_worker(receiver);
+});
+
+let workers;
+
+export async function startWorkers(module, memory, builder) {
+  if (builder.numThreads() === 0) {
+    throw new Error('initThreadPool needs at least one thread');
+  }
+  const workerInit = {
+    type: 'wasm_bindgen_worker_init',
+    init: { module_or_path: module, memory },
+    receiver: builder.receiver(),
+  };
+  // Kept alive for the lifetime of the page
+  workers = await Promise.all(
+    Array.from({ length: builder.numThreads() }, async () => {
+      const worker = new Worker(new URL('./workerHelpers.js', import.meta.url), { type: 'module' });
+      worker.postMessage(workerInit);
+      await waitForMsgType(worker, 'wasm_bindgen_worker_ready');
+      return worker;
+    }),
+  );
+  builder.build();
+}